@@ -93,16 +93,26 @@ pub enum PalaceCryptError {
 /// let data = b"Hello, Palace!";
 /// let crc = crc32(data, 0);
 /// ```
-pub fn crc32(input: &[u8], seed: u32) -> u32 {
+///
+/// There is no lookup table to initialize, and the function is `const`, so
+/// checksums of fixed data can be computed at compile time:
+///
+/// ```
+/// const EMPTY: u32 = thepalace::algo::crc32(b"", 0);
+/// assert_eq!(EMPTY, 0xD9216290);
+/// ```
+pub const fn crc32(input: &[u8], seed: u32) -> u32 {
     let mut crc = if seed == 0 { CRC_MAGIC } else { seed };
 
-    for &byte in input {
+    // `for` loops aren't allowed in const fns
+    let mut i = 0;
+    while i < input.len() {
         // Rotate left by 1 bit with carry (high bit becomes low bit)
-        let carry = if crc & 0x80000000 != 0 { 1 } else { 0 };
-        crc = (crc << 1) | carry;
+        crc = crc.rotate_left(1);
 
         // XOR with current byte
-        crc ^= byte as u32;
+        crc ^= input[i] as u32;
+        i += 1;
     }
 
     crc
@@ -131,16 +141,20 @@ pub fn crc32(input: &[u8], seed: u32) -> u32 {
 /// let crc2 = pseudo_crc32(2);
 /// assert_ne!(crc1, crc2);
 /// ```
-pub fn pseudo_crc32(counter: u32) -> u32 {
+pub const fn pseudo_crc32(counter: u32) -> u32 {
     let mut crc = 0xA95ADE76u32;
     let ctr_bytes = counter.to_be_bytes();
 
-    for &byte in &ctr_bytes {
+    let mut i = 0;
+    while i < ctr_bytes.len() {
+        let byte = ctr_bytes[i];
+
         // Rotate left by 1 bit
-        crc = crc.wrapping_shl(1) | (crc >> 31);
+        crc = crc.rotate_left(1);
 
         // XOR with byte and mask
         crc = (crc ^ (byte as u32)) ^ CRC_MASK[byte as usize];
+        i += 1;
     }
 
     crc
@@ -293,6 +307,28 @@ mod tests {
         assert_ne!(crc1, crc2);
     }
 
+    #[test]
+    fn test_crc32_reference_values() {
+        // Evaluated at compile time
+        const EMPTY: u32 = crc32(b"", 0);
+        const PALACE: u32 = crc32(b"Hello, Palace!", 0);
+        assert_eq!(EMPTY, 0xD9216290);
+        assert_eq!(PALACE, 0x58A948C7);
+
+        assert_eq!(crc32(b"a", 0), 0xB242C540);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog", 0),
+            0xF5AAA9BC
+        );
+        assert_eq!(crc32(b"Test", 0x12345678), 0x23456427);
+
+        const PSEUDO: u32 = pseudo_crc32(42);
+        assert_eq!(PSEUDO, 0x752056D3);
+        assert_eq!(pseudo_crc32(0), 0x5905F923);
+        assert_eq!(pseudo_crc32(1), 0xC4E0BCC2);
+        assert_eq!(pseudo_crc32(0xDEADBEEF), 0xECD0C8AE);
+    }
+
     #[test]
     fn test_pseudo_crc32_different_counters() {
         let crc1 = pseudo_crc32(1);
@@ -0,0 +1,30 @@
+//! Errors from asset storage.
+
+use std::io;
+
+use thiserror::Error;
+
+/// Error loading or storing an asset
+///
+/// The variants tell a client what to do next: [`NotFound`](Self::NotFound)
+/// means the asset should be requested from the server, while
+/// [`CrcMismatch`](Self::CrcMismatch) means the stored copy is corrupt and
+/// should be deleted.
+#[derive(Error, Debug)]
+pub enum AssetError {
+    /// No asset with this CRC is stored
+    #[error("Asset {crc:08X} not found")]
+    NotFound { crc: u32 },
+
+    /// Stored data doesn't match the CRC it was requested by
+    #[error("Asset CRC mismatch: expected {expected:08X}, got {actual:08X}")]
+    CrcMismatch { expected: u32, actual: u32 },
+
+    /// Data isn't in the expected format
+    #[error("Bad asset format: {0}")]
+    BadFormat(String),
+
+    /// Underlying filesystem error
+    #[error("Asset I/O error: {0}")]
+    Io(#[from] io::Error),
+}
@@ -7,7 +7,10 @@
 //!
 //! - Props: `assets/props/{CRC32_HEX}.prop`
 //! - Backgrounds: `assets/backgrounds/{CRC32_HEX}.{png,jpg}`
-//! - Other assets as needed
+//! - User databases: `assets/users/{CRC32_HEX}.{user,iusr}`
+//!
+//! See [`AssetStore`] for the implementation. Its operations fail with
+//! [`AssetError`], which separates missing assets from corrupt ones.
 //!
 //! ## Prop Formats
 //!
@@ -21,7 +24,11 @@
 //!
 //! All props are typically 44x44 pixels and include a 12-byte header with metadata.
 
+pub mod error;
+pub mod store;
+
+pub use error::AssetError;
+pub use store::{AssetStore, ImageFormat};
+
 // TODO: Implement asset management
-// - Asset storage and retrieval
 // - Asset upload/download protocol
-// - CRC32-based asset identification
@@ -0,0 +1,316 @@
+//! Filesystem storage for assets, keyed by CRC32.
+//!
+//! [`AssetStore`] routes each asset to a subdirectory of its root based on the
+//! asset type, so props, user databases and room backgrounds never share a
+//! directory:
+//!
+//! - `AssetType::Prop` → `props/{CRC32_HEX}.prop`
+//! - `AssetType::Userbase` / `AssetType::IpUserbase` → `users/{CRC32_HEX}.user` / `.iusr`
+//! - Backgrounds → `backgrounds/{CRC32_HEX}.{png,jpg}`
+//!
+//! Backgrounds aren't protocol assets (they are fetched by name or over HTTP),
+//! so they have their own [`AssetStore::store_background`] entry point.
+//!
+//! Loads check the data against the CRC it was requested by, so a corrupt
+//! file shows up as [`AssetError::CrcMismatch`] rather than as a bad asset.
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::assets::AssetError;
+use crate::{AssetType, crc32};
+
+/// Subdirectory for room background images
+const BACKGROUNDS_DIR: &str = "backgrounds";
+
+/// Image format of a room background
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    /// Portable Network Graphics
+    Png,
+    /// JPEG / JFIF
+    Jpeg,
+}
+
+impl ImageFormat {
+    /// All supported formats, in lookup order
+    pub const ALL: [ImageFormat; 2] = [ImageFormat::Png, ImageFormat::Jpeg];
+
+    /// Detect the format from the file's magic bytes
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else {
+            None
+        }
+    }
+
+    /// File extension used on disk
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// CRC32-addressed asset storage rooted at a directory
+#[derive(Debug, Clone)]
+pub struct AssetStore {
+    root: PathBuf,
+}
+
+impl AssetStore {
+    /// Create a store rooted at `root` (e.g. `assets/`)
+    ///
+    /// Directories are created lazily on the first write.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Get the root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get the directory holding assets of the given type
+    pub fn dir_for(&self, asset_type: AssetType) -> PathBuf {
+        let subdir = match asset_type {
+            AssetType::Prop => "props",
+            AssetType::Userbase | AssetType::IpUserbase => "users",
+        };
+        self.root.join(subdir)
+    }
+
+    /// Get the path an asset is stored at
+    pub fn asset_path(&self, asset_type: AssetType, crc: u32) -> PathBuf {
+        let extension = match asset_type {
+            AssetType::Prop => "prop",
+            AssetType::Userbase => "user",
+            AssetType::IpUserbase => "iusr",
+        };
+        self.dir_for(asset_type)
+            .join(format!("{:08X}.{}", crc, extension))
+    }
+
+    /// Store an asset, returning the path written
+    pub fn store(
+        &self,
+        asset_type: AssetType,
+        crc: u32,
+        data: &[u8],
+    ) -> Result<PathBuf, AssetError> {
+        let path = self.asset_path(asset_type, crc);
+        write_file(&path, data)?;
+        Ok(path)
+    }
+
+    /// Load an asset's raw bytes
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AssetError::NotFound`] if nothing is stored under `crc`, and
+    /// [`AssetError::CrcMismatch`] if the stored data doesn't checksum to
+    /// `crc` (a CRC of 0 is "don't care" and isn't checked).
+    pub fn load(&self, asset_type: AssetType, crc: u32) -> Result<Vec<u8>, AssetError> {
+        let data = fs::read(self.asset_path(asset_type, crc)).map_err(|e| not_found(e, crc))?;
+        check_crc(&data, crc)?;
+        Ok(data)
+    }
+
+    /// Get the path a background with the given CRC and format is stored at
+    pub fn background_path(&self, crc: u32, format: ImageFormat) -> PathBuf {
+        self.root
+            .join(BACKGROUNDS_DIR)
+            .join(format!("{:08X}.{}", crc, format.extension()))
+    }
+
+    /// Store a room background, returning its CRC32
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AssetError::BadFormat`] if the data isn't a PNG or JPEG, or
+    /// if its magic bytes don't match `format`.
+    pub fn store_background(&self, data: &[u8], format: ImageFormat) -> Result<u32, AssetError> {
+        match ImageFormat::detect(data) {
+            Some(detected) if detected == format => {}
+            Some(detected) => {
+                return Err(AssetError::BadFormat(format!(
+                    "background declared as {:?} but is {:?}",
+                    format, detected
+                )));
+            }
+            None => {
+                return Err(AssetError::BadFormat(
+                    "background is not a PNG or JPEG image".to_string(),
+                ));
+            }
+        }
+
+        let crc = crc32(data, 0);
+        write_file(&self.background_path(crc, format), data)?;
+        Ok(crc)
+    }
+
+    /// Load a room background by CRC32, returning its bytes and format
+    ///
+    /// Fails like [`AssetStore::load`] when the background is missing or
+    /// corrupt.
+    pub fn load_background(&self, crc: u32) -> Result<(Vec<u8>, ImageFormat), AssetError> {
+        for format in ImageFormat::ALL {
+            match fs::read(self.background_path(crc, format)) {
+                Ok(data) => {
+                    check_crc(&data, crc)?;
+                    return Ok((data, format));
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(AssetError::NotFound { crc })
+    }
+}
+
+/// Turn a missing file into [`AssetError::NotFound`]
+fn not_found(err: io::Error, crc: u32) -> AssetError {
+    if err.kind() == ErrorKind::NotFound {
+        AssetError::NotFound { crc }
+    } else {
+        AssetError::Io(err)
+    }
+}
+
+/// Check data against the CRC it was stored under, unless that CRC is 0
+fn check_crc(data: &[u8], expected: u32) -> Result<(), AssetError> {
+    let actual = crc32(data, 0);
+    if expected != 0 && actual != expected {
+        return Err(AssetError::CrcMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+/// Write a file, creating its parent directory if needed
+fn write_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Temporary store root, removed on drop
+    struct TempRoot(PathBuf);
+
+    impl TempRoot {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "thepalace-asset-store-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(ImageFormat::detect(PNG), Some(ImageFormat::Png));
+        assert_eq!(
+            ImageFormat::detect(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some(ImageFormat::Jpeg)
+        );
+        assert_eq!(ImageFormat::detect(b"GIF89a"), None);
+        assert_eq!(ImageFormat::detect(&[]), None);
+    }
+
+    #[test]
+    fn test_store_routes_by_type() {
+        let root = TempRoot::new("routes");
+        let store = AssetStore::new(&root.0);
+
+        let crc = store.store_background(PNG, ImageFormat::Png).unwrap();
+        let background = store.background_path(crc, ImageFormat::Png);
+        assert!(background.starts_with(root.0.join("backgrounds")));
+        assert!(background.exists());
+        assert_eq!(
+            store.load_background(crc).unwrap(),
+            (PNG.to_vec(), ImageFormat::Png)
+        );
+
+        let prop = [1u8, 2, 3, 4];
+        let crc = crc32(&prop, 0);
+        let path = store.store(AssetType::Prop, crc, &prop).unwrap();
+        assert_eq!(path, root.0.join("props").join(format!("{:08X}.prop", crc)));
+        assert_eq!(store.load(AssetType::Prop, crc).unwrap(), prop);
+
+        assert_eq!(store.dir_for(AssetType::Userbase), root.0.join("users"));
+    }
+
+    #[test]
+    fn test_store_background_rejects_bad_format() {
+        let root = TempRoot::new("reject");
+        let store = AssetStore::new(&root.0);
+
+        let err = store
+            .store_background(b"GIF89a....", ImageFormat::Png)
+            .unwrap_err();
+        assert!(matches!(err, AssetError::BadFormat(_)));
+
+        let err = store.store_background(PNG, ImageFormat::Jpeg).unwrap_err();
+        assert!(matches!(err, AssetError::BadFormat(_)));
+
+        assert!(matches!(
+            store.load_background(0x1234),
+            Err(AssetError::NotFound { crc: 0x1234 })
+        ));
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let root = TempRoot::new("errors");
+        let store = AssetStore::new(&root.0);
+
+        assert!(matches!(
+            store.load(AssetType::Prop, 0xDEADBEEF),
+            Err(AssetError::NotFound { crc: 0xDEADBEEF })
+        ));
+
+        // Stored under the wrong CRC, e.g. a truncated download
+        let prop = [1u8, 2, 3, 4];
+        let actual = crc32(&prop, 0);
+        store.store(AssetType::Prop, 0xDEADBEEF, &prop).unwrap();
+        match store.load(AssetType::Prop, 0xDEADBEEF) {
+            Err(AssetError::CrcMismatch {
+                expected: 0xDEADBEEF,
+                actual: got,
+            }) => assert_eq!(got, actual),
+            other => panic!("expected CrcMismatch, got {:?}", other),
+        }
+
+        // A don't-care CRC loads whatever is there
+        store.store(AssetType::Prop, 0, &prop).unwrap();
+        assert_eq!(store.load(AssetType::Prop, 0).unwrap(), prop);
+
+        // Anything other than a missing file is passed through
+        fs::create_dir_all(store.asset_path(AssetType::Userbase, 1)).unwrap();
+        assert!(matches!(
+            store.load(AssetType::Userbase, 1),
+            Err(AssetError::Io(_))
+        ));
+    }
+}
@@ -9,9 +9,20 @@
 //!
 //! All multi-byte integers in the Palace Protocol use **big-endian** byte order (network byte order),
 //! as the protocol originated on classic Macintosh systems.
+//!
+//! Text fields are decoded as MacRoman, except chat, which newer clients send
+//! as UTF-8; [`decode_chat_text`] tells the two apart.
+//!
+//! [`hex_dump`] renders raw bytes in the classic offset/hex/ASCII layout for debugging.
+//!
+//! Payload parsers read through the `*_checked` getters on [`BufExt`], which
+//! return `InvalidData` on short input where the plain `Buf` getters panic.
 
 use bytes::{Buf, BufMut};
-use std::io::{self, ErrorKind};
+use std::io::{self, ErrorKind, Write};
+
+/// Bytes shown per hex dump line
+const HEX_DUMP_WIDTH: usize = 16;
 
 /// Extension trait for reading Palace Protocol data types from buffers.
 pub trait BufExt: Buf {
@@ -134,21 +145,132 @@ pub trait BufExt: Buf {
     ///
     /// Returns `UnexpectedEof` if no null terminator is found before the buffer ends.
     fn get_cstring(&mut self) -> io::Result<String> {
-        let mut bytes = Vec::new();
+        get_cstring_bytes(self).map(|bytes| macroman_to_string(&bytes))
+    }
 
-        while self.has_remaining() {
-            let byte = self.get_u8();
-            if byte == 0 {
-                // Found null terminator
-                return Ok(macroman_to_string(&bytes));
-            }
-            bytes.push(byte);
+    /// Read a null-terminated chat string from the buffer.
+    ///
+    /// Like [`get_cstring`](Self::get_cstring), but the text may be UTF-8
+    /// or MacRoman; see [`decode_chat_text`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `UnexpectedEof` if no null terminator is found before the buffer ends.
+    fn get_chat_cstring(&mut self) -> io::Result<String> {
+        get_cstring_bytes(self).map(|bytes| decode_chat_text(&bytes))
+    }
+
+    /// Read a `u8`, or fail with `InvalidData` if the buffer is empty
+    fn get_u8_checked(&mut self) -> io::Result<u8> {
+        ensure_remaining(self, 1)?;
+        Ok(self.get_u8())
+    }
+
+    /// Read a big-endian `u16`, or fail with `InvalidData` on short input
+    fn get_u16_checked(&mut self) -> io::Result<u16> {
+        ensure_remaining(self, 2)?;
+        Ok(self.get_u16())
+    }
+
+    /// Read a big-endian `i16`, or fail with `InvalidData` on short input
+    fn get_i16_checked(&mut self) -> io::Result<i16> {
+        ensure_remaining(self, 2)?;
+        Ok(self.get_i16())
+    }
+
+    /// Read a big-endian `u32`, or fail with `InvalidData` on short input
+    fn get_u32_checked(&mut self) -> io::Result<u32> {
+        ensure_remaining(self, 4)?;
+        Ok(self.get_u32())
+    }
+
+    /// Read a big-endian `i32`, or fail with `InvalidData` on short input
+    fn get_i32_checked(&mut self) -> io::Result<i32> {
+        ensure_remaining(self, 4)?;
+        Ok(self.get_i32())
+    }
+
+    /// Read a little-endian `u16`, or fail with `InvalidData` on short input
+    ///
+    /// Only for data stored by little-endian clients, such as some prop headers.
+    fn get_u16_le_checked(&mut self) -> io::Result<u16> {
+        ensure_remaining(self, 2)?;
+        Ok(self.get_u16_le())
+    }
+
+    /// Read a little-endian `i16`, or fail with `InvalidData` on short input
+    ///
+    /// Only for data stored by little-endian clients, such as some prop headers.
+    fn get_i16_le_checked(&mut self) -> io::Result<i16> {
+        ensure_remaining(self, 2)?;
+        Ok(self.get_i16_le())
+    }
+
+    /// Fill `dst`, or fail with `InvalidData` if fewer bytes remain
+    fn copy_to_slice_checked(&mut self, dst: &mut [u8]) -> io::Result<()> {
+        ensure_remaining(self, dst.len())?;
+        self.copy_to_slice(dst);
+        Ok(())
+    }
+
+    /// Take the next `len` bytes, or fail with `InvalidData` if fewer remain
+    fn copy_to_bytes_checked(&mut self, len: usize) -> io::Result<bytes::Bytes> {
+        ensure_remaining(self, len)?;
+        Ok(self.copy_to_bytes(len))
+    }
+
+    /// Skip `count` bytes, or fail with `InvalidData` if fewer remain
+    fn advance_checked(&mut self, count: usize) -> io::Result<()> {
+        ensure_remaining(self, count)?;
+        self.advance(count);
+        Ok(())
+    }
+}
+
+/// Fail with `InvalidData` unless at least `needed` bytes remain
+fn ensure_remaining<B: Buf + ?Sized>(buf: &B, needed: usize) -> io::Result<()> {
+    if buf.remaining() < needed {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "truncated data: needed {} bytes, only {} remain",
+                needed,
+                buf.remaining()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Read raw bytes up to and including a null terminator, which is dropped
+fn get_cstring_bytes<B: Buf + ?Sized>(buf: &mut B) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    while buf.has_remaining() {
+        let byte = buf.get_u8();
+        if byte == 0 {
+            // Found null terminator
+            return Ok(bytes);
         }
+        bytes.push(byte);
+    }
+
+    Err(io::Error::new(
+        ErrorKind::UnexpectedEof,
+        "CString not null-terminated",
+    ))
+}
 
-        Err(io::Error::new(
-            ErrorKind::UnexpectedEof,
-            "CString not null-terminated",
-        ))
+/// Decode chat text from a client of either era.
+///
+/// Classic clients send MacRoman; newer ones send UTF-8. Text that is valid
+/// UTF-8 is taken as UTF-8, anything else is transcoded from MacRoman. Pure
+/// ASCII reads the same either way. MacRoman text whose high bytes happen to
+/// form valid UTF-8 sequences is rare enough in practice to ignore.
+pub fn decode_chat_text(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => macroman_to_string(bytes),
     }
 }
 
@@ -481,6 +603,52 @@ pub trait BufMutExt: BufMut {
 impl<T: Buf> BufExt for T {}
 impl<T: BufMut> BufMutExt for T {}
 
+/// Format bytes as a hex dump, 16 bytes per line.
+///
+/// Each line has the offset, the bytes in hex (split into two groups of 8)
+/// and the printable ASCII characters, with other bytes shown as `.`:
+///
+/// ```text
+/// 00000000  48 65 6c 6c 6f 00 01 02  03 04 05 06 07 08 09 0a  |Hello...........|
+/// ```
+pub fn hex_dump(data: &[u8]) -> String {
+    let mut out = Vec::new();
+    hex_dump_to(&mut out, data).expect("writing to a Vec can't fail");
+    String::from_utf8(out).expect("hex dump output is ASCII")
+}
+
+/// Write a hex dump of `data` to `writer`, one line at a time.
+///
+/// Uses the same format as [`hex_dump`].
+pub fn hex_dump_to(writer: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    for (line, chunk) in data.chunks(HEX_DUMP_WIDTH).enumerate() {
+        write!(writer, "{:08x} ", line * HEX_DUMP_WIDTH)?;
+
+        for i in 0..HEX_DUMP_WIDTH {
+            if i % 8 == 0 {
+                write!(writer, " ")?;
+            }
+            match chunk.get(i) {
+                Some(byte) => write!(writer, "{:02x} ", byte)?,
+                None => write!(writer, "   ")?,
+            }
+        }
+
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        writeln!(writer, " |{}|", ascii)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -685,4 +853,41 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
     }
+
+    #[test]
+    fn test_decode_chat_text() {
+        // UTF-8 emoji (U+1F600)
+        assert_eq!(
+            decode_chat_text(&[b'h', b'i', 0xF0, 0x9F, 0x98, 0x80]),
+            "hi\u{1F600}"
+        );
+        // MacRoman 0x8E is 'é'; alone it isn't valid UTF-8
+        assert_eq!(decode_chat_text(b"caf\x8E"), "café");
+        assert_eq!(decode_chat_text(b"plain ascii"), "plain ascii");
+
+        let mut reader = Bytes::from_static(b"caf\xC3\xA9\0rest");
+        assert_eq!(reader.get_chat_cstring().unwrap(), "café");
+        assert_eq!(reader.remaining(), 4);
+    }
+
+    #[test]
+    fn test_hex_dump_partial_line() {
+        let data = b"Hello, Palace!\x00\x01\x7f\xff\x10 ";
+        assert_eq!(data.len(), 20);
+
+        let dump = hex_dump(data);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "00000000  48 65 6c 6c 6f 2c 20 50  61 6c 61 63 65 21 00 01  |Hello, Palace!..|",
+                "00000010  7f ff 10 20                                       |... |",
+            ]
+        );
+
+        let mut streamed = Vec::new();
+        hex_dump_to(&mut streamed, data).unwrap();
+        assert_eq!(streamed, dump.as_bytes());
+        assert_eq!(hex_dump(&[]), "");
+    }
 }
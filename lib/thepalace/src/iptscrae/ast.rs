@@ -2,7 +2,7 @@
 //!
 //! The AST represents the structure of parsed Iptscrae code before execution.
 
-use crate::iptscrae::events::EventType;
+use crate::iptscrae::events::{EventMask, EventType};
 use crate::iptscrae::token::SourcePos;
 use crate::iptscrae::value::Value;
 
@@ -16,6 +16,198 @@ impl Script {
     pub const fn new(handlers: Vec<EventHandler>) -> Self {
         Self { handlers }
     }
+
+    /// Get the events this script has handlers for
+    ///
+    /// This is the hotspot record's `script_event_mask` for the script.
+    pub fn event_mask(&self) -> EventMask {
+        self.handlers
+            .iter()
+            .fold(EventMask::empty(), |mask, handler| {
+                mask | handler.event.to_mask()
+            })
+    }
+
+    /// Serialize the script back to Iptscrae source
+    ///
+    /// Re-parsing the output yields the same AST apart from source positions.
+    /// Each handler's leading comments are written above it; comments
+    /// elsewhere were dropped by the parser. Nodes the parser never produces
+    /// (array literals, NaN and infinite floats, logical and unary operators)
+    /// are written as builtin expressions that compute the same value.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        for handler in &self.handlers {
+            for comment in &handler.comments {
+                out.push('#');
+                out.push_str(comment);
+                out.push('\n');
+            }
+            out.push_str("ON ");
+            out.push_str(handler.event.keyword());
+            out.push(' ');
+            write_block(&mut out, &handler.body, 0);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Write a braced block at `indent`
+///
+/// Runs of plain expressions share a line; assignments and control flow end
+/// the line, so `"hi" SAY` and `0 count =` read the way they were written.
+fn write_block(out: &mut String, block: &Block, indent: usize) {
+    out.push_str("{\n");
+    let mut line_open = false;
+    for statement in &block.statements {
+        if line_open {
+            out.push(' ');
+        } else {
+            push_indent(out, indent + 1);
+        }
+        write_statement(out, statement, indent + 1);
+        line_open = matches!(
+            statement,
+            Statement::Expr(expr) if !matches!(expr, Expr::Block(_))
+        );
+        if !line_open {
+            out.push('\n');
+        }
+    }
+    if line_open {
+        out.push('\n');
+    }
+    push_indent(out, indent);
+    out.push('}');
+}
+
+fn write_statement(out: &mut String, statement: &Statement, indent: usize) {
+    match statement {
+        Statement::Expr(expr) => write_expr(out, expr, indent),
+        Statement::Assign { name, .. } => {
+            out.push_str(name);
+            out.push_str(" =");
+        }
+        Statement::If {
+            then_block,
+            else_block,
+            ..
+        } => {
+            // The condition is left on the stack by the preceding statement
+            out.push_str("IF ");
+            write_block(out, then_block, indent);
+            if let Some(else_block) = else_block {
+                out.push_str(" ELSE ");
+                write_block(out, else_block, indent);
+            }
+        }
+        Statement::While { body, .. } => {
+            out.push_str("WHILE ");
+            write_block(out, body, indent);
+        }
+        Statement::Break { .. } => out.push_str("BREAK"),
+        Statement::Case { arms, default, .. } => {
+            out.push_str("CASE {\n");
+            for arm in arms {
+                push_indent(out, indent + 1);
+                write_value(out, &arm.label);
+                out.push(' ');
+                write_block(out, &arm.body, indent + 1);
+                out.push('\n');
+            }
+            if let Some(default) = default {
+                push_indent(out, indent + 1);
+                out.push_str("DEFAULT ");
+                write_block(out, default, indent + 1);
+                out.push('\n');
+            }
+            push_indent(out, indent);
+            out.push('}');
+        }
+    }
+}
+
+fn write_expr(out: &mut String, expr: &Expr, indent: usize) {
+    match expr {
+        Expr::Literal { value, .. } => write_value(out, value),
+        Expr::Variable { name, .. } | Expr::Call { name, .. } => out.push_str(name),
+        Expr::BinOp { op, .. } => out.push_str(match op {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Eq => "==",
+            BinOp::NotEq => "!=",
+            BinOp::Less => "<",
+            BinOp::Greater => ">",
+            BinOp::LessEq => "<=",
+            BinOp::GreaterEq => ">=",
+            BinOp::And => "AND",
+            BinOp::Or => "OR",
+            BinOp::Xor => "XOR",
+            BinOp::Concat => "&",
+        }),
+        Expr::UnaryOp { op, .. } => out.push_str(match op {
+            UnaryOp::Neg => "0 SWAP -",
+            UnaryOp::Not => "NOT",
+        }),
+        Expr::Block(block) => write_block(out, block, indent),
+    }
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Integer(n) => out.push_str(&n.to_string()),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => {
+            // No array literal syntax; push the elements individually
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_value(out, item);
+            }
+        }
+        // No literal lexes to these; POW is the one builtin that yields them
+        Value::Float(n) if n.is_nan() => out.push_str("-1.0 0.5 POW"),
+        Value::Float(n) if n.is_infinite() => out.push_str(if *n > 0.0 {
+            "10.0 400.0 POW"
+        } else {
+            "-10.0 401.0 POW"
+        }),
+        Value::Float(n) => {
+            // Keep the decimal point so the literal lexes back as a float
+            let text = n.to_string();
+            out.push_str(&text);
+            if !text.contains('.') {
+                out.push_str(".0");
+            }
+        }
+    }
+}
+
+/// Write a double-quoted string literal, escaping what the lexer unescapes
+pub(crate) fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+    for _ in 0..indent {
+        out.push_str("  ");
+    }
 }
 
 /// Event handler (ON eventname { statements })
@@ -24,11 +216,24 @@ pub struct EventHandler {
     pub event: EventType,
     pub body: Block,
     pub pos: SourcePos,
+    /// Comment lines above the handler, without the leading `#`
+    pub comments: Vec<String>,
 }
 
 impl EventHandler {
     pub const fn new(event: EventType, body: Block, pos: SourcePos) -> Self {
-        Self { event, body, pos }
+        Self {
+            event,
+            body,
+            pos,
+            comments: Vec::new(),
+        }
+    }
+
+    /// Attach leading comment lines (without the `#`)
+    pub fn with_comments(mut self, comments: Vec<String>) -> Self {
+        self.comments = comments;
+        self
     }
 }
 
@@ -70,6 +275,30 @@ pub enum Statement {
 
     /// Break from loop
     Break { pos: SourcePos },
+
+    /// Multi-way branch (value CASE { label { ... } ... DEFAULT { ... } })
+    ///
+    /// Runs the first arm whose label matches the popped value, or the
+    /// default block if none does. Arms never fall through into each other.
+    Case {
+        arms: Vec<CaseArm>,
+        default: Option<Block>,
+        pos: SourcePos,
+    },
+}
+
+/// One labeled arm of a CASE statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseArm {
+    pub label: Value,
+    pub body: Block,
+    pub pos: SourcePos,
+}
+
+impl CaseArm {
+    pub const fn new(label: Value, body: Block, pos: SourcePos) -> Self {
+        Self { label, body, pos }
+    }
 }
 
 /// Expression
@@ -101,7 +330,9 @@ pub enum BinOp {
     Add,
     Sub,
     Mul,
+    /// Integer division truncates toward zero (`-7 3 /` is -2); see FLOORDIV
     Div,
+    /// Remainder takes the sign of the dividend (`-7 3 %` is -1); see FLOORMOD
     Mod,
 
     // Comparison
@@ -154,6 +385,119 @@ mod tests {
         assert_eq!(script.handlers[0], handler);
     }
 
+    #[test]
+    fn test_script_to_source_roundtrip() {
+        use crate::iptscrae::{Lexer, Parser};
+
+        let source = r#"
+            ON SELECT {
+                0 count =
+                count 3 < WHILE { count 1 + count = count 3 < }
+                "say \"hi\"\n" SAY
+                count 3 >= IF { "three" SAY } ELSE { BREAK }
+                count 3 == IF { "equal" SAY }
+                1.0 0.25 + SINRAD
+            }
+            ON ENTER { { "inline" SAY } }
+        "#;
+        let parse = |src: &str| {
+            Parser::new(Lexer::new(src).tokenize().unwrap())
+                .parse()
+                .unwrap()
+        };
+
+        let script = parse(source);
+        let emitted = script.to_source();
+        let reparsed = parse(&emitted);
+
+        assert_eq!(reparsed.handlers.len(), 2);
+        assert_eq!(reparsed.to_source(), emitted);
+        assert!(emitted.contains(r#""say \"hi\"\n" SAY"#));
+        assert!(emitted.contains("1.0 0.25 +"));
+        assert!(emitted.contains("count 3 == IF"));
+    }
+
+    #[test]
+    fn test_leading_comments_preserved() {
+        use crate::iptscrae::{Lexer, Parser};
+
+        let parse = |src: &str| {
+            Parser::new(Lexer::new(src).tokenize().unwrap())
+                .parse()
+                .unwrap()
+        };
+        let source = r#"
+            # Greets whoever clicks
+            #   the spot
+            ON SELECT { "hi" SAY } # trailing
+
+            # Not attached: a blank line follows
+
+            ON ENTER { }
+        "#;
+        let script = parse(source);
+        assert_eq!(
+            script.handlers[0].comments,
+            [" Greets whoever clicks", "   the spot"]
+        );
+        assert!(script.handlers[1].comments.is_empty());
+
+        let emitted = script.to_source();
+        assert!(emitted.starts_with("# Greets whoever clicks\n#   the spot\nON SELECT {\n"));
+        assert!(!emitted.contains("trailing"));
+        assert_eq!(parse(&emitted).to_source(), emitted);
+    }
+
+    #[test]
+    fn test_non_finite_float_to_source() {
+        use crate::iptscrae::{Lexer, Parser, ScriptContext, SecurityLevel, Vm};
+
+        let pos = SourcePos::new(1, 1);
+        let literal = |n: f64| {
+            Statement::Expr(Expr::Literal {
+                value: Value::Float(n),
+                pos,
+            })
+        };
+        let body = Block::new(vec![
+            literal(f64::NAN),
+            literal(f64::INFINITY),
+            literal(f64::NEG_INFINITY),
+        ]);
+        let script = Script::new(vec![EventHandler::new(EventType::Select, body, pos)]);
+
+        let emitted = script.to_source();
+        let reparsed = Parser::new(Lexer::new(&emitted).tokenize().unwrap())
+            .parse()
+            .unwrap();
+        let mut actions = ();
+        let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+        let mut vm = Vm::new();
+        vm.execute_handler(&reparsed, EventType::Select, &mut context)
+            .unwrap();
+        assert_eq!(vm.pop("test").unwrap(), Value::Float(f64::NEG_INFINITY));
+        assert_eq!(vm.pop("test").unwrap(), Value::Float(f64::INFINITY));
+        assert!(matches!(vm.pop("test").unwrap(), Value::Float(n) if n.is_nan()));
+    }
+
+    #[test]
+    fn test_case_to_source_roundtrip() {
+        use crate::iptscrae::{Lexer, Parser};
+
+        let parse = |src: &str| {
+            Parser::new(Lexer::new(src).tokenize().unwrap())
+                .parse()
+                .unwrap()
+        };
+        let script =
+            parse(r#"ON SELECT { x CASE { 1 { "one" SAY } "two" { } DEFAULT { "?" SAY } } }"#);
+        let emitted = script.to_source();
+
+        assert!(emitted.contains("x CASE {\n    1 {\n"));
+        assert!(emitted.contains("    DEFAULT {\n"));
+        assert_eq!(parse(&emitted).to_source(), emitted);
+    }
+
     #[test]
     fn test_binop_precedence() {
         assert!(BinOp::Mul.precedence() > BinOp::Add.precedence());
@@ -14,8 +14,9 @@ pub fn execute_array_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
                     message: "ARRAY size must be non-negative".to_string(),
                 });
             }
+            vm.check_array_size(size as usize)?;
             let arr = vec![Value::Integer(0); size as usize];
-            vm.push(Value::Array(arr));
+            vm.push(Value::array(arr))?;
             Ok(())
         }
         "GET" => {
@@ -29,7 +30,7 @@ pub fn execute_array_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
                         message: format!("Array index {} out of bounds", index),
                     });
                 }
-                vm.push(arr[index as usize].clone());
+                vm.push(arr[index as usize].clone())?;
             } else {
                 return Err(VmError::TypeError {
                     message: "GET requires an array".to_string(),
@@ -50,7 +51,7 @@ pub fn execute_array_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
                     });
                 }
                 arr[index as usize] = value;
-                vm.push(array);
+                vm.push(array)?;
             } else {
                 return Err(VmError::TypeError {
                     message: "PUT requires an array".to_string(),
@@ -58,15 +59,31 @@ pub fn execute_array_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
             }
             Ok(())
         }
+        "APPEND" => {
+            // APPEND: array value -> array (with value added at the end)
+            let value = vm.pop("APPEND value")?;
+            let mut array = vm.pop("APPEND array")?;
+
+            if let Some(arr) = array.as_array_mut() {
+                vm.check_array_size(arr.len() + 1)?;
+                arr.push(value);
+                vm.push(array)?;
+            } else {
+                return Err(VmError::TypeError {
+                    message: "APPEND requires an array".to_string(),
+                });
+            }
+            Ok(())
+        }
         "LENGTH" => {
             // LENGTH: array -> length (also works on strings)
             let value = vm.pop("LENGTH")?;
             let length = match value {
                 Value::Array(ref arr) => arr.len() as i32,
                 Value::String(ref s) => s.len() as i32,
-                Value::Integer(_) => 0,
+                Value::Integer(_) | Value::Float(_) => 0,
             };
-            vm.push(Value::Integer(length));
+            vm.push(Value::Integer(length))?;
             Ok(())
         }
         _ => Err(VmError::UndefinedFunction {
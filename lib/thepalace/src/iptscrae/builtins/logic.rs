@@ -10,27 +10,60 @@ pub fn execute_logic_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
             // AND: a b -> (a AND b)
             let right = vm.pop("AND right")?.to_bool();
             let left = vm.pop("AND left")?.to_bool();
-            vm.push(Value::Integer(if left && right { 1 } else { 0 }));
+            vm.push(Value::Integer(if left && right { 1 } else { 0 }))?;
             Ok(())
         }
         "OR" => {
             // OR: a b -> (a OR b)
             let right = vm.pop("OR right")?.to_bool();
             let left = vm.pop("OR left")?.to_bool();
-            vm.push(Value::Integer(if left || right { 1 } else { 0 }));
+            vm.push(Value::Integer(if left || right { 1 } else { 0 }))?;
             Ok(())
         }
         "XOR" => {
             // XOR: a b -> (a XOR b)
             let right = vm.pop("XOR right")?.to_bool();
             let left = vm.pop("XOR left")?.to_bool();
-            vm.push(Value::Integer(if left != right { 1 } else { 0 }));
+            vm.push(Value::Integer(if left != right { 1 } else { 0 }))?;
             Ok(())
         }
         "NOT" => {
             // NOT: a -> (NOT a)
             let value = vm.pop("NOT")?.to_bool();
-            vm.push(Value::Integer(if value { 0 } else { 1 }));
+            vm.push(Value::Integer(if value { 0 } else { 1 }))?;
+            Ok(())
+        }
+        "BITAND" | "BITOR" | "BITXOR" => {
+            // a b -> bitwise combination of the integer bit patterns
+            let right = vm.pop(name)?.to_integer();
+            let left = vm.pop(name)?.to_integer();
+            let result = match name {
+                "BITAND" => left & right,
+                "BITOR" => left | right,
+                _ => left ^ right,
+            };
+            vm.push(Value::Integer(result))?;
+            Ok(())
+        }
+        "BITNOT" => {
+            // a -> every bit flipped
+            let value = vm.pop("BITNOT")?.to_integer();
+            vm.push(Value::Integer(!value))?;
+            Ok(())
+        }
+        "SHL" | "SHR" => {
+            // a n -> a shifted by n bits; SHR is logical (zero-filling), and
+            // counts outside 0..32 shift every bit out
+            let count = vm.pop(name)?.to_integer();
+            let bits = vm.pop(name)?.to_integer() as u32;
+            let shifted = u32::try_from(count).ok().and_then(|count| {
+                if name == "SHL" {
+                    bits.checked_shl(count)
+                } else {
+                    bits.checked_shr(count)
+                }
+            });
+            vm.push(Value::Integer(shifted.unwrap_or(0) as i32))?;
             Ok(())
         }
         _ => Err(VmError::UndefinedFunction {
@@ -11,8 +11,15 @@ pub fn execute_math_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
             let degrees = vm.pop($name)?.to_integer();
             let radians = (degrees as f64).to_radians();
             let result = (radians.$func() * 1000.0) as i32;
-            vm.push(Value::Integer(result));
-            Ok(())
+            vm.push(Value::Integer(result))
+        }};
+    }
+
+    // Macro for float functions (SINRAD, DEG2RAD, ...): float in, float out
+    macro_rules! float_builtin {
+        ($name:expr, $func:ident) => {{
+            let value = vm.pop($name)?.to_float();
+            vm.push(Value::Float(value.$func()))
         }};
     }
 
@@ -21,11 +28,11 @@ pub fn execute_math_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
             // RANDOM takes max value from stack, returns random 0..max
             let max = vm.pop("RANDOM")?.to_integer();
             if max <= 0 {
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
             } else {
                 // Simple pseudo-random using instruction count as seed
                 let random_val = (vm.instruction_count() as i32 * 1103515245 + 12345) % max;
-                vm.push(Value::Integer(random_val.abs()));
+                vm.push(Value::Integer(random_val.abs()))?;
             }
             Ok(())
         }
@@ -36,14 +43,119 @@ pub fn execute_math_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
             } else {
                 0
             };
-            vm.push(Value::Integer(result));
+            vm.push(Value::Integer(result))?;
+            Ok(())
+        }
+        "ABS" => {
+            let value = vm.pop("ABS")?;
+            let result = match value {
+                Value::Float(n) => Value::Float(n.abs()),
+                // |i32::MIN| doesn't fit, so saturate
+                _ => Value::Integer(value.to_integer().saturating_abs()),
+            };
+            vm.push(result)?;
+            Ok(())
+        }
+        "MIN" | "MAX" => {
+            // a b -> smaller/larger; floats if either operand is a float
+            let right = vm.pop(name)?;
+            let left = vm.pop(name)?;
+            let min = name == "MIN";
+            let result = if left.is_float() || right.is_float() {
+                let (l, r) = (left.to_float(), right.to_float());
+                Value::Float(if min { l.min(r) } else { l.max(r) })
+            } else {
+                let (l, r) = (left.to_integer(), right.to_integer());
+                Value::Integer(if min { l.min(r) } else { l.max(r) })
+            };
+            vm.push(result)?;
+            Ok(())
+        }
+        "CLAMP" => {
+            // n lo hi -> n limited to lo..=hi; if lo > hi the result is lo
+            let hi = vm.pop("CLAMP")?;
+            let lo = vm.pop("CLAMP")?;
+            let value = vm.pop("CLAMP")?;
+            let result = if value.is_float() || lo.is_float() || hi.is_float() {
+                Value::Float(value.to_float().min(hi.to_float()).max(lo.to_float()))
+            } else {
+                Value::Integer(value.to_integer().min(hi.to_integer()).max(lo.to_integer()))
+            };
+            vm.push(result)?;
+            Ok(())
+        }
+        "FLOORDIV" | "FLOORMOD" => {
+            // a b -> floored quotient/modulo. Unlike `/` and `%`, which truncate
+            // toward zero, these round the quotient down, so the modulo takes
+            // the sign of the divisor: -7 3 FLOORDIV is -3, -7 3 FLOORMOD is 2
+            let right = vm.pop(name)?;
+            let left = vm.pop(name)?;
+            let div = name == "FLOORDIV";
+            let result = if left.is_float() || right.is_float() {
+                let (l, r) = (left.to_float(), right.to_float());
+                if r == 0.0 {
+                    return Err(VmError::DivisionByZero);
+                }
+                let quotient = (l / r).floor();
+                Value::Float(if div { quotient } else { l - r * quotient })
+            } else {
+                let (l, r) = (left.to_integer(), right.to_integer());
+                if r == 0 {
+                    return Err(VmError::DivisionByZero);
+                }
+                let (quotient, remainder) = floor_div_mod(l, r);
+                Value::Integer(if div { quotient } else { remainder })
+            };
+            vm.push(result)?;
             Ok(())
         }
         "SINE" => trig_builtin!("SINE", sin),
         "COSINE" => trig_builtin!("COSINE", cos),
         "TANGENT" => trig_builtin!("TANGENT", tan),
+        "SINRAD" => float_builtin!("SINRAD", sin),
+        "COSRAD" => float_builtin!("COSRAD", cos),
+        "TANRAD" => float_builtin!("TANRAD", tan),
+        "DEG2RAD" => float_builtin!("DEG2RAD", to_radians),
+        "RAD2DEG" => float_builtin!("RAD2DEG", to_degrees),
+        "EXP" => float_builtin!("EXP", exp),
+        "POW" => {
+            // base exp -> base^exp
+            let exponent = vm.pop("POW")?.to_float();
+            let base = vm.pop("POW")?.to_float();
+            vm.push(Value::Float(base.powf(exponent)))?;
+            Ok(())
+        }
+        "LOG" | "LOG10" => {
+            let value = vm.pop(name)?.to_float();
+            // Refuse rather than push NaN or -inf into the script
+            if value <= 0.0 {
+                return Err(VmError::TypeError {
+                    message: format!("{} of non-positive number {}", name, value),
+                });
+            }
+            let result = if name == "LOG" {
+                value.ln()
+            } else {
+                value.log10()
+            };
+            vm.push(Value::Float(result))?;
+            Ok(())
+        }
         _ => Err(VmError::UndefinedFunction {
             name: name.to_string(),
         }),
     }
 }
+
+/// Floored integer division and modulo (`r` must be non-zero)
+///
+/// `i32::MIN / -1` wraps rather than panicking.
+fn floor_div_mod(l: i32, r: i32) -> (i32, i32) {
+    let quotient = l.wrapping_div(r);
+    let remainder = l.wrapping_rem(r);
+    if remainder != 0 && (remainder < 0) != (r < 0) {
+        (quotient - 1, remainder + r)
+    } else {
+        (quotient, remainder)
+    }
+}
@@ -10,9 +10,361 @@ mod logic;
 mod array;
 mod palace;
 
+use crate::iptscrae::context::ScriptContext;
+use crate::iptscrae::vm::{Vm, VmError};
+
 pub use stack::execute_stack_builtin;
 pub use string::execute_string_builtin;
 pub use math::execute_math_builtin;
 pub use logic::execute_logic_builtin;
 pub use array::execute_array_builtin;
 pub use palace::execute_palace_builtin;
+
+/// Builtin category, matching the module that implements it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Stack,
+    String,
+    Math,
+    Logic,
+    Array,
+    /// Messaging, props, users, navigation, rooms, graphics and system
+    Palace,
+}
+
+/// Name and stack effect of a builtin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinInfo {
+    /// Uppercase name as dispatched
+    pub name: &'static str,
+    /// Values popped; the minimum for variadic builtins
+    pub pops: u8,
+    /// Values pushed; not meaningful for variadic builtins
+    pub pushes: u8,
+    /// Whether the stack effect depends on runtime values (e.g. MACRO)
+    pub variadic: bool,
+    pub category: Category,
+}
+
+impl BuiltinInfo {
+    /// Stack effect for static analysis
+    pub(crate) const fn stack_effect(&self) -> StackEffect {
+        if self.variadic {
+            StackEffect::Variadic {
+                pops: self.pops as usize,
+            }
+        } else {
+            StackEffect::Fixed {
+                pops: self.pops as usize,
+                pushes: self.pushes as usize,
+            }
+        }
+    }
+}
+
+const fn fixed(name: &'static str, category: Category, pops: u8, pushes: u8) -> BuiltinInfo {
+    BuiltinInfo {
+        name,
+        pops,
+        pushes,
+        variadic: false,
+        category,
+    }
+}
+
+const fn variadic(name: &'static str, category: Category, pops: u8) -> BuiltinInfo {
+    BuiltinInfo {
+        name,
+        pops,
+        pushes: 0,
+        variadic: true,
+        category,
+    }
+}
+
+/// Every builtin the VM dispatches, grouped by category
+static REGISTRY: &[BuiltinInfo] = {
+    use Category::{Array, Logic, Math, Palace, Stack, String};
+    &[
+        // Stack
+        fixed("DUP", Stack, 1, 2),
+        fixed("DROP", Stack, 1, 0),
+        fixed("POP", Stack, 1, 0),
+        fixed("SWAP", Stack, 2, 2),
+        fixed("OVER", Stack, 2, 3),
+        fixed("ROT", Stack, 3, 3),
+        fixed("PICK", Stack, 1, 1),
+        fixed("VARTYPE", Stack, 1, 1),
+        fixed("STACKDEPTH", Stack, 0, 1),
+        variadic("CLEARSTACK", Stack, 0),
+        fixed("TOPTYPE", Stack, 1, 2),
+
+        // String
+        fixed("ITOA", String, 1, 1),
+        fixed("ATOI", String, 1, 1),
+        fixed("ISNUM", String, 1, 1),
+        fixed("STRLEN", String, 1, 1),
+        fixed("UPPERCASE", String, 1, 1),
+        fixed("LOWERCASE", String, 1, 1),
+        fixed("TONUM", String, 1, 2),
+        fixed("SUBSTR", String, 2, 1),
+        fixed("STRINDEX", String, 2, 1),
+        fixed("SUBSTRING", String, 3, 1),
+
+        // Math
+        fixed("RANDOM", Math, 1, 1),
+        fixed("SQUAREROOT", Math, 1, 1),
+        fixed("SINE", Math, 1, 1),
+        fixed("COSINE", Math, 1, 1),
+        fixed("TANGENT", Math, 1, 1),
+        fixed("SINRAD", Math, 1, 1),
+        fixed("COSRAD", Math, 1, 1),
+        fixed("TANRAD", Math, 1, 1),
+        fixed("DEG2RAD", Math, 1, 1),
+        fixed("RAD2DEG", Math, 1, 1),
+        fixed("ABS", Math, 1, 1),
+        fixed("EXP", Math, 1, 1),
+        fixed("LOG", Math, 1, 1),
+        fixed("LOG10", Math, 1, 1),
+        fixed("MIN", Math, 2, 1),
+        fixed("MAX", Math, 2, 1),
+        fixed("POW", Math, 2, 1),
+        fixed("FLOORDIV", Math, 2, 1),
+        fixed("FLOORMOD", Math, 2, 1),
+        fixed("CLAMP", Math, 3, 1),
+
+        // Logic
+        fixed("AND", Logic, 2, 1),
+        fixed("OR", Logic, 2, 1),
+        fixed("XOR", Logic, 2, 1),
+        fixed("BITAND", Logic, 2, 1),
+        fixed("BITOR", Logic, 2, 1),
+        fixed("BITXOR", Logic, 2, 1),
+        fixed("SHL", Logic, 2, 1),
+        fixed("SHR", Logic, 2, 1),
+        fixed("NOT", Logic, 1, 1),
+        fixed("BITNOT", Logic, 1, 1),
+
+        // Array
+        fixed("ARRAY", Array, 1, 1),
+        fixed("LENGTH", Array, 1, 1),
+        fixed("GET", Array, 2, 1),
+        fixed("APPEND", Array, 2, 1),
+        fixed("PUT", Array, 3, 1),
+
+        // Messaging
+        fixed("SAY", Palace, 1, 0),
+        fixed("CHAT", Palace, 1, 0),
+        fixed("LOCALMSG", Palace, 1, 0),
+        fixed("ROOMMSG", Palace, 1, 0),
+        fixed("GLOBALMSG", Palace, 1, 0),
+        fixed("STATUSMSG", Palace, 1, 0),
+        fixed("SUSRMSG", Palace, 1, 0),
+        fixed("LOGMSG", Palace, 1, 0),
+        fixed("PRIVATEMSG", Palace, 2, 0),
+        fixed("SAYAT", Palace, 3, 0),
+        fixed("WHOCHAT", Palace, 0, 1),
+        fixed("CHATSTR", Palace, 0, 1),
+        fixed("INCHATSTR", Palace, 0, 1),
+
+        // Props
+        variadic("GETPROPS", Palace, 0),
+        variadic("SETPROPS", Palace, 1),
+        fixed("NAKED", Palace, 0, 0),
+        fixed("DROPPROP", Palace, 0, 0),
+        fixed("CLEARLOOSEPROPS", Palace, 0, 0),
+        fixed("DONPROP", Palace, 2, 0),
+        fixed("DOFFPROP", Palace, 1, 0),
+        fixed("REMOVEPROP", Palace, 1, 0),
+        fixed("SHOWLOOSEPROPS", Palace, 1, 0),
+        fixed("USERPROP", Palace, 1, 2),
+        fixed("NBRUSERPROPS", Palace, 0, 1),
+        fixed("TOPPROP", Palace, 0, 2),
+        fixed("HASPROP", Palace, 1, 1),
+        fixed("ADDLOOSEPROP", Palace, 3, 0),
+
+        // User
+        fixed("USERNAME", Palace, 0, 1),
+        fixed("WHOME", Palace, 0, 1),
+        fixed("USERID", Palace, 0, 1),
+        fixed("WHOTARGET", Palace, 0, 1),
+        fixed("ISGOD", Palace, 0, 1),
+        fixed("ISWIZARD", Palace, 0, 1),
+        fixed("ISGUEST", Palace, 0, 1),
+        fixed("ME", Palace, 0, 1),
+        fixed("WHONAME", Palace, 1, 1),
+        fixed("SETFACE", Palace, 1, 0),
+        fixed("SETCOLOR", Palace, 1, 0),
+        fixed("WHOPOS", Palace, 1, 2),
+        fixed("MOUSEPOS", Palace, 0, 2),
+
+        // Navigation
+        fixed("GOTOROOM", Palace, 1, 0),
+        fixed("GOTOURL", Palace, 1, 0),
+        fixed("KILLUSER", Palace, 1, 0),
+        fixed("LAUNCHAPP", Palace, 1, 0),
+        fixed("MOVE", Palace, 2, 0),
+        fixed("GOTOURLFRAME", Palace, 2, 0),
+        fixed("NETGOTO", Palace, 2, 0),
+        fixed("SETLOC", Palace, 2, 0),
+        fixed("DEST", Palace, 1, 1),
+
+        // Room
+        fixed("ROOMNAME", Palace, 0, 1),
+        fixed("ROOMID", Palace, 0, 1),
+        fixed("NBRROOMUSERS", Palace, 0, 1),
+        fixed("DOORIDX", Palace, 0, 1),
+        fixed("NBRDOORS", Palace, 0, 1),
+        fixed("SPOTIDX", Palace, 0, 1),
+        fixed("NBRSPOTS", Palace, 0, 1),
+        fixed("LOCK", Palace, 1, 0),
+        fixed("UNLOCK", Palace, 1, 0),
+        fixed("DIMROOM", Palace, 1, 0),
+        fixed("ROOMUSER", Palace, 1, 1),
+        fixed("ISLOCKED", Palace, 1, 1),
+        fixed("SPOTNAME", Palace, 1, 1),
+        fixed("SPOTDEST", Palace, 1, 1),
+        fixed("INSPOT", Palace, 1, 1),
+        fixed("GETSPOTSTATE", Palace, 1, 1),
+        fixed("SETSPOTSTATE", Palace, 2, 0),
+        fixed("SETSPOTSTATELOCAL", Palace, 2, 0),
+        fixed("SETPICLOC", Palace, 2, 0),
+
+        // Graphics
+        fixed("POSX", Palace, 0, 1),
+        fixed("POSY", Palace, 0, 1),
+        fixed("SETPOS", Palace, 2, 0),
+        fixed("LINETO", Palace, 2, 0),
+        fixed("PENTO", Palace, 2, 0),
+        fixed("LINE", Palace, 4, 0),
+        fixed("PENPOS", Palace, 0, 2),
+        fixed("PENSIZE", Palace, 1, 0),
+        fixed("PENCOLOR", Palace, 1, 0),
+        fixed("PENFRONT", Palace, 0, 0),
+        fixed("PENBACK", Palace, 0, 0),
+        fixed("PAINTCLEAR", Palace, 0, 0),
+        fixed("PAINTUNDO", Palace, 0, 0),
+
+        // System
+        variadic("MACRO", Palace, 1),
+        fixed("DELAY", Palace, 1, 0),
+        fixed("SOUND", Palace, 1, 0),
+        fixed("MIDIPLAY", Palace, 1, 0),
+        fixed("SERVERNAME", Palace, 0, 1),
+        fixed("CLIENTTYPE", Palace, 0, 1),
+        fixed("IPTVERSION", Palace, 0, 1),
+        fixed("DATETIME", Palace, 0, 1),
+        fixed("TIMESTAMP", Palace, 0, 1),
+        fixed("TICKS", Palace, 0, 1),
+        fixed("ID", Palace, 0, 1),
+        fixed("GLOBAL", Palace, 1, 1),
+        fixed("MIDISTOP", Palace, 0, 0),
+        fixed("BEEP", Palace, 0, 0),
+        fixed("DUMPSTACK", Palace, 0, 0),
+    ]
+};
+
+/// List every builtin with its stack effect and category
+///
+/// For tooling such as autocomplete and documentation; the validator uses
+/// it through [`stack_effect`].
+pub fn registry() -> &'static [BuiltinInfo] {
+    REGISTRY
+}
+
+/// Stack effect of a builtin, used for static analysis of scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StackEffect {
+    /// Pops and pushes a fixed number of values
+    Fixed { pops: usize, pushes: usize },
+    /// Pops at least `pops` values; the rest depends on runtime values
+    Variadic { pops: usize },
+}
+
+/// Look up the stack effect of a builtin by (uppercase) name.
+///
+/// Returns `None` for names no builtin module dispatches.
+pub(crate) fn stack_effect(name: &str) -> Option<StackEffect> {
+    REGISTRY
+        .iter()
+        .find(|info| info.name == name)
+        .map(BuiltinInfo::stack_effect)
+}
+
+/// Run a builtin by (uppercase) name
+///
+/// The registry is the single list of dispatched names: a name it does not
+/// contain is undefined even if a category module would handle it.
+pub(crate) fn execute_builtin(
+    vm: &mut Vm,
+    name: &str,
+    context: Option<&mut ScriptContext>,
+) -> Result<(), VmError> {
+    let Some(info) = REGISTRY.iter().find(|info| info.name == name) else {
+        return Err(VmError::UndefinedFunction {
+            name: name.to_string(),
+        });
+    };
+    match info.category {
+        Category::Stack => execute_stack_builtin(vm, name),
+        Category::String => execute_string_builtin(vm, name),
+        Category::Math => execute_math_builtin(vm, name),
+        Category::Logic => execute_logic_builtin(vm, name),
+        Category::Array => execute_array_builtin(vm, name),
+        Category::Palace => execute_palace_builtin(vm, name, context),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iptscrae::value::Value;
+    use std::collections::HashSet;
+
+    fn info(name: &str) -> BuiltinInfo {
+        *registry().iter().find(|info| info.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_registry_arity() {
+        let say = info("SAY");
+        assert_eq!((say.pops, say.pushes, say.variadic), (1, 0, false));
+        assert_eq!(say.category, Category::Palace);
+
+        let dup = info("DUP");
+        assert_eq!((dup.pops, dup.pushes, dup.variadic), (1, 2, false));
+        assert_eq!(dup.category, Category::Stack);
+
+        assert!(info("MACRO").variadic);
+        assert_eq!(stack_effect("DUP"), Some(StackEffect::Fixed { pops: 1, pushes: 2 }));
+        assert_eq!(stack_effect("NOSUCH"), None);
+
+        let names: HashSet<_> = registry().iter().map(|info| info.name).collect();
+        assert_eq!(names.len(), registry().len(), "duplicate registry entry");
+    }
+
+    #[test]
+    fn test_registry_covers_dispatch() {
+        // Every registry entry is handled by its category's module
+        for info in registry() {
+            let mut vm = Vm::new();
+            for _ in 0..info.pops.max(4) {
+                vm.push(Value::Integer(1)).unwrap();
+            }
+            let result = vm.execute_builtin_with_context(info.name, None);
+            assert!(
+                !matches!(result, Err(VmError::UndefinedFunction { .. })),
+                "{} is not dispatched as {:?}",
+                info.name,
+                info.category
+            );
+        }
+
+        // Dispatch goes through the registry, so unlisted names are undefined
+        let mut vm = Vm::new();
+        assert!(matches!(
+            vm.execute_builtin_with_context("NOSUCH", None),
+            Err(VmError::UndefinedFunction { .. })
+        ));
+    }
+}
@@ -13,17 +13,17 @@ pub fn execute_graphics_builtin(
     match name {
         "POSX" => {
             if let Some(ctx) = context {
-                vm.push(Value::Integer(ctx.user_pos_x as i32));
+                vm.push(Value::Integer(ctx.user_pos_x as i32))?;
             } else {
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
         "POSY" => {
             if let Some(ctx) = context {
-                vm.push(Value::Integer(ctx.user_pos_y as i32));
+                vm.push(Value::Integer(ctx.user_pos_y as i32))?;
             } else {
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
@@ -53,8 +53,8 @@ pub fn execute_graphics_builtin(
         }
         "PENPOS" => {
             // Get pen position - push X and Y
-            vm.push(Value::Integer(0));
-            vm.push(Value::Integer(0));
+            vm.push(Value::Integer(0))?;
+            vm.push(Value::Integer(0))?;
             Ok(())
         }
         "PENTO" => {
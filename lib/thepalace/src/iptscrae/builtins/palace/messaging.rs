@@ -12,64 +12,75 @@ pub fn execute_messaging_builtin(
 ) -> Result<(), VmError> {
     match name {
         "SAY" => {
-            let message = vm.pop("SAY")?;
+            let message = vm.pop("SAY")?.to_string();
+            vm.check_output(&message)?;
             if let Some(ctx) = context {
-                ctx.actions.say(&message.to_string());
+                ctx.actions.say(&message);
             } else {
                 // Fallback for tests
-                vm.push_output(message.to_string());
+                vm.push_output(message);
             }
             Ok(())
         }
         "CHAT" => {
-            let message = vm.pop("CHAT")?;
+            let message = vm.pop("CHAT")?.to_string();
+            vm.check_output(&message)?;
             if let Some(ctx) = context {
-                ctx.actions.chat(&message.to_string());
+                ctx.actions.chat(&message);
             } else {
                 // Fallback for tests
-                vm.push_output(message.to_string());
+                vm.push_output(message);
             }
             Ok(())
         }
         "LOCALMSG" => {
-            let message = vm.pop("LOCALMSG")?;
+            let message = vm.pop("LOCALMSG")?.to_string();
+            vm.check_output(&message)?;
             if let Some(ctx) = context {
-                ctx.actions.local_msg(&message.to_string());
+                ctx.actions.local_msg(&message);
             }
             Ok(())
         }
         "ROOMMSG" => {
-            let message = vm.pop("ROOMMSG")?;
+            let message = vm.pop("ROOMMSG")?.to_string();
+            vm.check_output(&message)?;
             if let Some(ctx) = context {
-                ctx.actions.room_msg(&message.to_string());
+                ctx.actions.room_msg(&message);
             }
             Ok(())
         }
         "PRIVATEMSG" => {
-            let message = vm.pop("PRIVATEMSG")?;
+            let message = vm.pop("PRIVATEMSG")?.to_string();
             let user_id = vm.pop("PRIVATEMSG user_id")?.to_integer();
+            vm.check_output(&message)?;
             if let Some(ctx) = context {
-                ctx.actions.private_msg(user_id, &message.to_string());
+                ctx.actions.private_msg(user_id, &message);
             }
             Ok(())
         }
         "WHOCHAT" => {
-            // Get user ID from last chat message - would need event data
-            if let Some(ctx) = context {
-                if let Some(Value::Integer(user_id)) = ctx.event_data.get("chat_user_id") {
-                    vm.push(Value::Integer(*user_id));
-                } else {
-                    vm.push(Value::Integer(ctx.user_id));
-                }
-            } else {
-                vm.push(Value::Integer(0));
-            }
+            // Get user ID from last chat message, defaulting to the current user
+            vm.push_from_context_or(
+                context.as_deref(),
+                |ctx| Value::Integer(ctx.chat_user().unwrap_or(ctx.user_id)),
+                || Value::Integer(0),
+            )?;
+            Ok(())
+        }
+        "CHATSTR" | "INCHATSTR" => {
+            // Text of the chat that triggered INCHAT/OUTCHAT
+            vm.push_from_context_or(
+                context.as_deref(),
+                |ctx| Value::String(ctx.chat_text.clone().unwrap_or_default()),
+                || Value::String(String::new()),
+            )?;
             Ok(())
         }
         "SAYAT" => {
             let y = vm.pop("SAYAT y")?.to_integer();
             let x = vm.pop("SAYAT x")?.to_integer();
             let message = vm.pop("SAYAT message")?.to_string();
+            vm.check_output(&message)?;
             if let Some(ctx) = context {
                 // SAYAT displays text at a specific position
                 // Format as special message with coordinates
@@ -80,6 +91,7 @@ pub fn execute_messaging_builtin(
         }
         "GLOBALMSG" => {
             let message = vm.pop("GLOBALMSG")?.to_string();
+            vm.check_output(&message)?;
             if let Some(ctx) = context {
                 ctx.actions.global_msg(&message);
             }
@@ -87,6 +99,7 @@ pub fn execute_messaging_builtin(
         }
         "STATUSMSG" => {
             let message = vm.pop("STATUSMSG")?.to_string();
+            vm.check_output(&message)?;
             if let Some(ctx) = context {
                 ctx.actions.status_msg(&message);
             }
@@ -94,6 +107,7 @@ pub fn execute_messaging_builtin(
         }
         "SUSRMSG" => {
             let message = vm.pop("SUSRMSG")?.to_string();
+            vm.check_output(&message)?;
             if let Some(ctx) = context {
                 if !matches!(ctx.security_level, SecurityLevel::Admin) {
                     return Err(VmError::TypeError {
@@ -14,13 +14,13 @@ use crate::iptscrae::vm::{Vm, VmError};
 /// Execute Palace-specific builtin functions.
 ///
 /// This function dispatches to category-specific modules:
-/// - messaging: SAY, CHAT, LOCALMSG, ROOMMSG, PRIVATEMSG, etc.
+/// - messaging: SAY, CHAT, LOCALMSG, ROOMMSG, PRIVATEMSG, CHATSTR, etc.
 /// - props: GETPROPS, SETPROPS, NAKED, DONPROP, etc.
 /// - user: USERNAME, WHOME, SETFACE, SETCOLOR, etc.
 /// - navigation: GOTOROOM, GOTOURL, NETGOTO, etc.
 /// - room: ROOMNAME, ROOMID, NBRDOORS, LOCK, UNLOCK, etc.
 /// - graphics: PENCOLOR, LINE, LINETO, PAINTCLEAR, etc.
-/// - system: DELAY, BEEP, SOUND, TICKS, DATETIME, etc.
+/// - system: DELAY, BEEP, SOUND, TICKS, DATETIME, TIMESTAMP, etc.
 pub fn execute_palace_builtin(
     vm: &mut Vm,
     name: &str,
@@ -72,7 +72,7 @@ pub fn execute_navigation_builtin(
             // Get destination room ID for a door - would need room data
             let _door_id = vm.pop("DEST")?.to_integer();
             // For now, return 0
-            vm.push(Value::Integer(0));
+            vm.push(Value::Integer(0))?;
             Ok(())
         }
         "SETLOC" => {
@@ -15,15 +15,15 @@ pub fn execute_props_builtin(
             if let Some(ctx) = context {
                 // Push number of props first, then each prop as crc:id
                 let num_props = ctx.user_props.len() as i32;
-                vm.push(Value::Integer(num_props));
+                vm.push(Value::Integer(num_props))?;
 
                 // Push each prop's CRC and ID
                 for prop in &ctx.user_props {
-                    vm.push(Value::Integer(prop.crc as i32));
-                    vm.push(Value::Integer(prop.id));
+                    vm.push(Value::Integer(prop.crc as i32))?;
+                    vm.push(Value::Integer(prop.id))?;
                 }
             } else {
-                vm.push(Value::Integer(0)); // No props
+                vm.push(Value::Integer(0))?; // No props
             }
             Ok(())
         }
@@ -115,24 +115,24 @@ pub fn execute_props_builtin(
             if let Some(ctx) = context {
                 if index >= 0 && (index as usize) < ctx.user_props.len() {
                     let prop = &ctx.user_props[index as usize];
-                    vm.push(Value::Integer(prop.crc as i32));
-                    vm.push(Value::Integer(prop.id));
+                    vm.push(Value::Integer(prop.crc as i32))?;
+                    vm.push(Value::Integer(prop.id))?;
                 } else {
-                    vm.push(Value::Integer(0));
-                    vm.push(Value::Integer(0));
+                    vm.push(Value::Integer(0))?;
+                    vm.push(Value::Integer(0))?;
                 }
             } else {
-                vm.push(Value::Integer(0));
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
         "NBRUSERPROPS" => {
             // Get number of props user is wearing
             if let Some(ctx) = context {
-                vm.push(Value::Integer(ctx.user_props.len() as i32));
+                vm.push(Value::Integer(ctx.user_props.len() as i32))?;
             } else {
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
@@ -140,15 +140,15 @@ pub fn execute_props_builtin(
             // Get the top (last) prop: -> crc id
             if let Some(ctx) = context {
                 if let Some(prop) = ctx.user_props.last() {
-                    vm.push(Value::Integer(prop.crc as i32));
-                    vm.push(Value::Integer(prop.id));
+                    vm.push(Value::Integer(prop.crc as i32))?;
+                    vm.push(Value::Integer(prop.id))?;
                 } else {
-                    vm.push(Value::Integer(0));
-                    vm.push(Value::Integer(0));
+                    vm.push(Value::Integer(0))?;
+                    vm.push(Value::Integer(0))?;
                 }
             } else {
-                vm.push(Value::Integer(0));
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
@@ -157,9 +157,9 @@ pub fn execute_props_builtin(
             let id = vm.pop("HASPROP")?.to_integer();
             if let Some(ctx) = context {
                 let has_prop = ctx.user_props.iter().any(|p| p.id == id);
-                vm.push(Value::Integer(if has_prop { 1 } else { 0 }));
+                vm.push(Value::Integer(if has_prop { 1 } else { 0 }))?;
             } else {
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
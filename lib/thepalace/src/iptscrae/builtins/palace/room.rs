@@ -16,7 +16,7 @@ pub fn execute_room_builtin(
                 context.as_deref(),
                 |ctx| Value::String(ctx.room_name.clone()),
                 || Value::String(String::new()),
-            );
+            )?;
             Ok(())
         }
         "ROOMID" => {
@@ -24,7 +24,7 @@ pub fn execute_room_builtin(
                 context.as_deref(),
                 |ctx| Value::Integer(ctx.room_id as i32),
                 || Value::Integer(0),
-            );
+            )?;
             Ok(())
         }
         "LOCK" => {
@@ -42,7 +42,7 @@ pub fn execute_room_builtin(
         "NBRROOMUSERS" => {
             // Number of users in current room - would need room state
             // For now, return 1 (just the current user)
-            vm.push(Value::Integer(1));
+            vm.push(Value::Integer(1))?;
             Ok(())
         }
         "ROOMUSER" => {
@@ -50,85 +50,77 @@ pub fn execute_room_builtin(
             let index = vm.pop("ROOMUSER")?.to_integer();
             if let Some(ctx) = context {
                 if index == 0 {
-                    vm.push(Value::Integer(ctx.user_id));
+                    vm.push(Value::Integer(ctx.user_id))?;
                 } else {
-                    vm.push(Value::Integer(0));
+                    vm.push(Value::Integer(0))?;
                 }
             } else {
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
         "DOORIDX" => {
-            // Get current door index - would need event data
-            if let Some(ctx) = context {
-                if let Some(Value::Integer(door_id)) = ctx.event_data.get("door_id") {
-                    vm.push(Value::Integer(*door_id));
-                } else {
-                    vm.push(Value::Integer(-1));
-                }
-            } else {
-                vm.push(Value::Integer(-1));
-            }
+            // Get current door index from event data
+            vm.push_from_context_or(
+                context.as_deref(),
+                |ctx| Value::Integer(ctx.door_id().unwrap_or(-1)),
+                || Value::Integer(-1),
+            )?;
             Ok(())
         }
         "NBRDOORS" => {
             // Get number of doors in room - would need room data
             // For now, return 0
-            vm.push(Value::Integer(0));
+            vm.push(Value::Integer(0))?;
             Ok(())
         }
         "ISLOCKED" => {
             // Check if door is locked - would need room state
             let _door_id = vm.pop("ISLOCKED")?.to_integer();
             // For now, return 0 (unlocked)
-            vm.push(Value::Integer(0));
+            vm.push(Value::Integer(0))?;
             Ok(())
         }
         "SPOTIDX" => {
-            // Get current spot index - would need event data
-            if let Some(ctx) = context {
-                if let Some(Value::Integer(spot_id)) = ctx.event_data.get("spot_id") {
-                    vm.push(Value::Integer(*spot_id));
-                } else {
-                    vm.push(Value::Integer(-1));
-                }
-            } else {
-                vm.push(Value::Integer(-1));
-            }
+            // Get current spot index from event data
+            vm.push_from_context_or(
+                context.as_deref(),
+                |ctx| Value::Integer(ctx.spot_id().unwrap_or(-1)),
+                || Value::Integer(-1),
+            )?;
             Ok(())
         }
         "NBRSPOTS" => {
             // Get number of spots in room - would need room data
             // For now, return 0
-            vm.push(Value::Integer(0));
+            vm.push(Value::Integer(0))?;
             Ok(())
         }
         "SPOTNAME" => {
             // Get name of spot by ID - would need room data
             let _spot_id = vm.pop("SPOTNAME")?.to_integer();
-            vm.push(Value::String(String::new()));
+            vm.push(Value::String(String::new()))?;
             Ok(())
         }
         "SPOTDEST" => {
             // Get destination for spot - would need room data
             let _spot_id = vm.pop("SPOTDEST")?.to_integer();
             // Returns room_id
-            vm.push(Value::Integer(0));
+            vm.push(Value::Integer(0))?;
             Ok(())
         }
         "INSPOT" => {
             // Check if user is in a specific spot - would need position/spot data
             let _spot_id = vm.pop("INSPOT")?.to_integer();
             // For now, return 0 (not in spot)
-            vm.push(Value::Integer(0));
+            vm.push(Value::Integer(0))?;
             Ok(())
         }
         "GETSPOTSTATE" => {
             // Get state of a spot
             let _spot_id = vm.pop("GETSPOTSTATE")?.to_integer();
             // For now, return 0
-            vm.push(Value::Integer(0));
+            vm.push(Value::Integer(0))?;
             Ok(())
         }
         "SETSPOTSTATE" => {
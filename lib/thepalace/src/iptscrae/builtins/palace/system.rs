@@ -1,5 +1,6 @@
 //! System builtin functions for Palace.
 
+use crate::iptscrae::clock::format_datetime;
 use crate::iptscrae::context::ScriptContext;
 use crate::iptscrae::value::Value;
 use crate::iptscrae::vm::{Vm, VmError};
@@ -12,48 +13,66 @@ pub fn execute_system_builtin(
 ) -> Result<(), VmError> {
     match name {
         "MACRO" => {
-            // Execute a macro (prop script) - stub for now
-            let _macro_id = vm.pop("MACRO")?.to_integer();
-            // Would need to look up and execute the macro script
+            // Execute a macro (prop script), sharing the stack and variables
+            // Unknown macro IDs are ignored
+            let macro_id = vm.pop("MACRO")?.to_integer();
+            if let Some(ctx) = context
+                && let Some(script) = ctx.macros.get(&macro_id).cloned()
+            {
+                vm.run_macro(&script, ctx)?;
+            }
+            Ok(())
+        }
+        "DUMPSTACK" => {
+            // Log the stack, bottom first, without changing it
+            let dump = format!(
+                "Stack ({}): {}",
+                vm.stack().len(),
+                Value::array(vm.stack().to_vec())
+            );
+            if let Some(ctx) = context {
+                ctx.actions.log_msg(&dump);
+            } else {
+                // Fallback for tests
+                vm.push_output(dump);
+            }
             Ok(())
         }
         "SERVERNAME" => {
             if let Some(ctx) = context {
-                vm.push(Value::String(ctx.server_name.clone()));
+                vm.push(Value::String(ctx.server_name.clone()))?;
             } else {
-                vm.push(Value::String("localhost".to_string()));
+                vm.push(Value::String("localhost".to_string()))?;
             }
             Ok(())
         }
         "CLIENTTYPE" => {
             // Return client type identifier
-            vm.push(Value::String("Palace".to_string()));
+            vm.push(Value::String("Palace".to_string()))?;
             Ok(())
         }
         "IPTVERSION" => {
             // Return Iptscrae version
-            vm.push(Value::Integer(1));
+            vm.push(Value::Integer(1))?;
             Ok(())
         }
         "DATETIME" => {
             // Return current datetime as string
-            // Format: "MM/DD/YYYY HH:MM:SS"
-            use std::time::SystemTime;
-            let now = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            vm.push(Value::String(format!("{}", now)));
+            // Format: "MM/DD/YYYY HH:MM:SS", always in UTC (the VM has no time zone)
+            vm.push(Value::String(format_datetime(epoch_secs(vm))))?;
+            Ok(())
+        }
+        "TIMESTAMP" => {
+            // Return current time as seconds since the Unix epoch
+            // Truncated to the VM's 32-bit integer type
+            let secs = epoch_secs(vm);
+            vm.push(Value::Integer(secs as i32))?;
             Ok(())
         }
         "TICKS" => {
-            // Return ticks (milliseconds since start)
-            use std::time::SystemTime;
-            let ticks = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as i32;
-            vm.push(Value::Integer(ticks));
+            // Return ticks (milliseconds) from the VM clock
+            let ticks = vm.clock().ticks() as i32;
+            vm.push(Value::Integer(ticks))?;
             Ok(())
         }
         "DELAY" => {
@@ -66,18 +85,18 @@ pub fn execute_system_builtin(
             let var_name = vm.pop("GLOBAL")?.to_string();
             // For now, treat as regular variable
             if let Some(value) = vm.get_variable(&var_name) {
-                vm.push(value.clone());
+                vm.push(value.clone())?;
             } else {
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
         "ID" => {
             // Alias for ME
             if let Some(ctx) = context {
-                vm.push(Value::Integer(ctx.user_id));
+                vm.push(Value::Integer(ctx.user_id))?;
             } else {
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
@@ -112,3 +131,12 @@ pub fn execute_system_builtin(
         }),
     }
 }
+
+/// Seconds since the Unix epoch according to the VM clock (0 if the clock is before it)
+fn epoch_secs(vm: &Vm) -> u64 {
+    vm.clock()
+        .now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -1,6 +1,6 @@
 //! User builtin functions for Palace.
 
-use crate::iptscrae::context::{ScriptContext, SecurityLevel};
+use crate::iptscrae::context::ScriptContext;
 use crate::iptscrae::value::Value;
 use crate::iptscrae::vm::{Vm, VmError};
 
@@ -16,7 +16,7 @@ pub fn execute_user_builtin(
                 context.as_deref(),
                 |ctx| Value::String(ctx.user_name.clone()),
                 || Value::String("Guest".to_string()),
-            );
+            )?;
             Ok(())
         }
         "WHOME" => {
@@ -24,7 +24,7 @@ pub fn execute_user_builtin(
                 context.as_deref(),
                 |ctx| Value::Integer(ctx.user_id),
                 || Value::Integer(0),
-            );
+            )?;
             Ok(())
         }
         "WHONAME" => {
@@ -33,12 +33,12 @@ pub fn execute_user_builtin(
                 // Look up username by ID (would need context support)
                 // For now, just return user's own name if ID matches
                 if user_id == ctx.user_id {
-                    vm.push(Value::String(ctx.user_name.clone()));
+                    vm.push(Value::String(ctx.user_name.clone()))?;
                 } else {
-                    vm.push(Value::String(format!("User{}", user_id)));
+                    vm.push(Value::String(format!("User{}", user_id)))?;
                 }
             } else {
-                vm.push(Value::String("Guest".to_string()));
+                vm.push(Value::String("Guest".to_string()))?;
             }
             Ok(())
         }
@@ -57,80 +57,77 @@ pub fn execute_user_builtin(
             // For now, return current user's position if ID matches
             if let Some(ctx) = context {
                 if user_id == ctx.user_id {
-                    vm.push(Value::Integer(ctx.user_pos_x as i32));
-                    vm.push(Value::Integer(ctx.user_pos_y as i32));
+                    vm.push(Value::Integer(ctx.user_pos_x as i32))?;
+                    vm.push(Value::Integer(ctx.user_pos_y as i32))?;
                 } else {
                     // Would need to look up other user's position
-                    vm.push(Value::Integer(0));
-                    vm.push(Value::Integer(0));
+                    vm.push(Value::Integer(0))?;
+                    vm.push(Value::Integer(0))?;
                 }
             } else {
-                vm.push(Value::Integer(0));
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
         "USERID" => {
             // USERID is an alias for WHOME
             if let Some(ctx) = context {
-                vm.push(Value::Integer(ctx.user_id));
+                vm.push(Value::Integer(ctx.user_id))?;
             } else {
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
         "WHOTARGET" => {
-            // Get targeted user ID - would need event data
-            if let Some(ctx) = context {
-                if let Some(Value::Integer(user_id)) = ctx.event_data.get("target_user_id") {
-                    vm.push(Value::Integer(*user_id));
-                } else {
-                    vm.push(Value::Integer(0));
-                }
-            } else {
-                vm.push(Value::Integer(0));
-            }
+            // Get targeted user ID from event data
+            vm.push_from_context_or(
+                context.as_deref(),
+                |ctx| Value::Integer(ctx.target_user().unwrap_or(0)),
+                || Value::Integer(0),
+            )?;
             Ok(())
         }
         "ISGOD" => {
-            // Check if current user has god/wizard privileges
-            if let Some(ctx) = context {
-                let is_god = matches!(ctx.security_level, SecurityLevel::Admin);
-                vm.push(Value::Integer(if is_god { 1 } else { 0 }));
-            } else {
-                vm.push(Value::Integer(0));
-            }
+            // Check if current user has god privileges
+            vm.push_from_context_or(
+                context.as_deref(),
+                |ctx| Value::Integer(ctx.is_god() as i32),
+                || Value::Integer(0),
+            )?;
             Ok(())
         }
         "ISWIZARD" => {
-            // Alias for ISGOD
-            if let Some(ctx) = context {
-                let is_wizard = matches!(ctx.security_level, SecurityLevel::Admin);
-                vm.push(Value::Integer(if is_wizard { 1 } else { 0 }));
-            } else {
-                vm.push(Value::Integer(0));
-            }
+            // Check if current user has wizard (or god) privileges
+            vm.push_from_context_or(
+                context.as_deref(),
+                |ctx| Value::Integer(ctx.is_wizard() as i32),
+                || Value::Integer(0),
+            )?;
             Ok(())
         }
         "ISGUEST" => {
-            // Check if user is a guest (would need user flags)
-            // For now, return 0 (not a guest)
-            vm.push(Value::Integer(0));
+            // Check the guest bit of the user flags
+            vm.push_from_context_or(
+                context.as_deref(),
+                |ctx| Value::Integer(ctx.is_guest() as i32),
+                || Value::Integer(0),
+            )?;
             Ok(())
         }
         "MOUSEPOS" => {
             // Get mouse position - would need UI state
             // Push X and Y coordinates
-            vm.push(Value::Integer(0));
-            vm.push(Value::Integer(0));
+            vm.push(Value::Integer(0))?;
+            vm.push(Value::Integer(0))?;
             Ok(())
         }
         "ME" => {
             // Return current user ID
             if let Some(ctx) = context {
-                vm.push(Value::Integer(ctx.user_id));
+                vm.push(Value::Integer(ctx.user_id))?;
             } else {
-                vm.push(Value::Integer(0));
+                vm.push(Value::Integer(0))?;
             }
             Ok(())
         }
@@ -8,7 +8,7 @@ pub fn execute_stack_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
     match name {
         "DUP" => {
             let value = vm.peek("DUP")?;
-            vm.push(value);
+            vm.push(value)?;
             Ok(())
         }
         "DROP" => {
@@ -18,8 +18,8 @@ pub fn execute_stack_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
         "SWAP" => {
             let a = vm.pop("SWAP first")?;
             let b = vm.pop("SWAP second")?;
-            vm.push(a);
-            vm.push(b);
+            vm.push(a)?;
+            vm.push(b)?;
             Ok(())
         }
         "OVER" => {
@@ -30,7 +30,7 @@ pub fn execute_stack_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
                 });
             }
             let value = vm.stack_get(vm.stack_len() - 2).clone();
-            vm.push(value);
+            vm.push(value)?;
             Ok(())
         }
         "ROT" => {
@@ -43,9 +43,9 @@ pub fn execute_stack_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
             let c = vm.pop("ROT")?;
             let b = vm.pop("ROT")?;
             let a = vm.pop("ROT")?;
-            vm.push(b);
-            vm.push(c);
-            vm.push(a);
+            vm.push(b)?;
+            vm.push(c)?;
+            vm.push(a)?;
             Ok(())
         }
         "PICK" => {
@@ -63,7 +63,7 @@ pub fn execute_stack_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
                 });
             }
             let value = vm.stack_get(vm.stack_len() - 1 - index).clone();
-            vm.push(value);
+            vm.push(value)?;
             Ok(())
         }
         "POP" => {
@@ -71,8 +71,12 @@ pub fn execute_stack_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
             vm.pop("POP")?;
             Ok(())
         }
+        "CLEARSTACK" => {
+            vm.clear_stack();
+            Ok(())
+        }
         "STACKDEPTH" => {
-            vm.push(Value::Integer(vm.stack_len() as i32));
+            vm.push(Value::Integer(vm.stack_len() as i32))?;
             Ok(())
         }
         "TOPTYPE" => {
@@ -81,8 +85,9 @@ pub fn execute_stack_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
                 Value::Integer(_) => 1,
                 Value::String(_) => 2,
                 Value::Array(_) => 3,
+                Value::Float(_) => 4,
             };
-            vm.push(Value::Integer(type_id));
+            vm.push(Value::Integer(type_id))?;
             Ok(())
         }
         "VARTYPE" => {
@@ -93,10 +98,11 @@ pub fn execute_stack_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
                     Value::Integer(_) => 1,
                     Value::String(_) => 2,
                     Value::Array(_) => 3,
+                    Value::Float(_) => 4,
                 };
-                vm.push(Value::Integer(type_id));
+                vm.push(Value::Integer(type_id))?;
             } else {
-                vm.push(Value::Integer(0)); // Undefined = 0
+                vm.push(Value::Integer(0))?; // Undefined = 0
             }
             Ok(())
         }
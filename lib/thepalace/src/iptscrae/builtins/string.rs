@@ -8,27 +8,41 @@ pub fn execute_string_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
     match name {
         "ITOA" => {
             let value = vm.pop("ITOA")?;
-            vm.push(Value::String(value.to_integer().to_string()));
+            vm.push(Value::String(value.to_integer().to_string()))?;
             Ok(())
         }
         "ATOI" => {
             let value = vm.pop("ATOI")?;
-            vm.push(Value::Integer(value.to_integer()));
+            vm.push(Value::Integer(value.to_integer()))?;
+            Ok(())
+        }
+        "ISNUM" => {
+            // 1 if ATOI would read a real number rather than falling back to 0
+            let value = vm.pop("ISNUM")?;
+            vm.push(Value::Integer(parse_integer(&value).is_some() as i32))?;
+            Ok(())
+        }
+        "TONUM" => {
+            // string -> number flag; flag is 1 on success, else both are 0
+            let value = vm.pop("TONUM")?;
+            let parsed = parse_integer(&value);
+            vm.push(Value::Integer(parsed.unwrap_or(0)))?;
+            vm.push(Value::Integer(parsed.is_some() as i32))?;
             Ok(())
         }
         "STRLEN" => {
             let value = vm.pop("STRLEN")?;
-            vm.push(Value::Integer(value.to_string().len() as i32));
+            vm.push(Value::Integer(value.to_string().len() as i32))?;
             Ok(())
         }
         "UPPERCASE" => {
             let value = vm.pop("UPPERCASE")?;
-            vm.push(Value::String(value.to_string().to_uppercase()));
+            vm.push(Value::String(value.to_string().to_uppercase()))?;
             Ok(())
         }
         "LOWERCASE" => {
             let value = vm.pop("LOWERCASE")?;
-            vm.push(Value::String(value.to_string().to_lowercase()));
+            vm.push(Value::String(value.to_string().to_lowercase()))?;
             Ok(())
         }
         "SUBSTR" => {
@@ -36,7 +50,7 @@ pub fn execute_string_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
             let needle = vm.pop("SUBSTR needle")?.to_string();
             let haystack = vm.pop("SUBSTR haystack")?.to_string();
             let found = if haystack.contains(&needle) { 1 } else { 0 };
-            vm.push(Value::Integer(found));
+            vm.push(Value::Integer(found))?;
             Ok(())
         }
         "SUBSTRING" => {
@@ -46,7 +60,7 @@ pub fn execute_string_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
             let string = vm.pop("SUBSTRING string")?.to_string();
 
             if start < 0 || length < 0 {
-                vm.push(Value::String(String::new()));
+                vm.push(Value::String(String::new()))?;
                 return Ok(());
             }
 
@@ -59,7 +73,7 @@ pub fn execute_string_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
                 .take(length_usize)
                 .collect::<String>();
 
-            vm.push(Value::String(result));
+            vm.push(Value::String(result))?;
             Ok(())
         }
         "STRINDEX" => {
@@ -69,7 +83,7 @@ pub fn execute_string_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
 
             let index = haystack.find(&needle).map(|i| i as i32).unwrap_or(-1);
 
-            vm.push(Value::Integer(index));
+            vm.push(Value::Integer(index))?;
             Ok(())
         }
         _ => Err(VmError::UndefinedFunction {
@@ -77,3 +91,15 @@ pub fn execute_string_builtin(vm: &mut Vm, name: &str) -> Result<(), VmError> {
         }),
     }
 }
+
+/// Read a value as an integer, or `None` where ATOI would silently give 0
+///
+/// Strings must be a plain decimal `i32` (an optional sign, no whitespace);
+/// numbers always convert, floats truncating as in ATOI.
+fn parse_integer(value: &Value) -> Option<i32> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        Value::Integer(_) | Value::Float(_) => Some(value.to_integer()),
+        Value::Array(_) => None,
+    }
+}
@@ -0,0 +1,215 @@
+//! Cache of parsed scripts keyed by source text.
+//!
+//! Hosts that keep scripts as text (e.g. hotspot scripts stored with a room)
+//! can use [`ScriptCache`] to lex and parse each distinct script once instead
+//! of on every event. Entries are keyed by a hash of the text, so editing a
+//! script simply misses the old entry; call [`ScriptCache::invalidate`] to
+//! drop the stale AST. The cache holds at most a fixed number of scripts and
+//! evicts the least recently used one when full, so stale entries that are
+//! never invalidated do not accumulate.
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use crate::iptscrae::ast::Script;
+use crate::iptscrae::lexer::Lexer;
+use crate::iptscrae::parser::{ParseError, Parser};
+
+/// Cached AST together with the text it was parsed from
+struct CacheEntry {
+    source: String,
+    script: Arc<Script>,
+    /// Value of [`Entries::clock`] when the entry was last returned
+    last_used: u64,
+}
+
+struct Entries {
+    map: HashMap<u64, CacheEntry>,
+    /// Incremented on every hit or insert to order entries by recency
+    clock: u64,
+}
+
+/// Thread-safe, size-bounded cache of parsed scripts
+pub struct ScriptCache {
+    entries: Mutex<Entries>,
+    capacity: usize,
+}
+
+impl Default for ScriptCache {
+    fn default() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl ScriptCache {
+    /// Default maximum number of cached scripts
+    pub const DEFAULT_CAPACITY: usize = 256;
+
+    /// Create an empty cache holding up to [`Self::DEFAULT_CAPACITY`] scripts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty cache holding up to `capacity` scripts (at least one)
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(Entries {
+                map: HashMap::new(),
+                clock: 0,
+            }),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Get the maximum number of cached scripts
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the parsed script for `source`, parsing it on the first request
+    ///
+    /// Scripts that fail to lex or parse are not cached.
+    pub fn get_or_parse(&self, source: &str) -> Result<Arc<Script>, ParseError> {
+        let key = hash_source(source);
+        {
+            let mut entries = self.lock();
+            entries.clock += 1;
+            let now = entries.clock;
+            if let Some(entry) = entries.map.get_mut(&key)
+                && entry.source == source
+            {
+                entry.last_used = now;
+                return Ok(Arc::clone(&entry.script));
+            }
+        }
+
+        // Parse without holding the lock; a racing parse of the same text is harmless
+        let tokens = Lexer::new(source).tokenize()?;
+        let script = Arc::new(Parser::new(tokens).parse()?);
+
+        let mut entries = self.lock();
+        if entries.map.len() >= self.capacity
+            && !entries.map.contains_key(&key)
+            && let Some(oldest) = entries
+                .map
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(&key, _)| key)
+        {
+            entries.map.remove(&oldest);
+        }
+        entries.clock += 1;
+        let now = entries.clock;
+        entries.map.insert(
+            key,
+            CacheEntry {
+                source: source.to_string(),
+                script: Arc::clone(&script),
+                last_used: now,
+            },
+        );
+        Ok(script)
+    }
+
+    /// Drop the cached AST for `source`, returning whether one was cached
+    pub fn invalidate(&self, source: &str) -> bool {
+        let mut entries = self.lock();
+        let key = hash_source(source);
+        match entries.map.get(&key) {
+            Some(entry) if entry.source == source => entries.map.remove(&key).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Drop every cached AST
+    pub fn clear(&self) {
+        self.lock().map.clear();
+    }
+
+    /// Get the number of cached scripts
+    pub fn len(&self) -> usize {
+        self.lock().map.len()
+    }
+
+    /// Check if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.lock().map.is_empty()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        // Entries are only ever inserted whole, so a poisoned map is still consistent
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl std::fmt::Debug for ScriptCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptCache")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// Hash script text for use as a cache key
+fn hash_source(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_reuses_parsed_script() {
+        let cache = ScriptCache::new();
+        let source = r#"ON SELECT { "hi" SAY }"#;
+
+        let first = cache.get_or_parse(source).unwrap();
+        let second = cache.get_or_parse(source).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.len(), 1);
+
+        // Changed text is a different entry
+        let edited = cache.get_or_parse(r#"ON SELECT { "bye" SAY }"#).unwrap();
+        assert!(!Arc::ptr_eq(&first, &edited));
+        assert_eq!(cache.len(), 2);
+
+        assert!(cache.invalidate(source));
+        assert!(!cache.invalidate(source));
+        let reparsed = cache.get_or_parse(source).unwrap();
+        assert!(!Arc::ptr_eq(&first, &reparsed));
+        assert_eq!(*first, *reparsed);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = ScriptCache::with_capacity(2);
+        let a = r#"ON SELECT { "a" SAY }"#;
+        let b = r#"ON SELECT { "b" SAY }"#;
+        let c = r#"ON SELECT { "c" SAY }"#;
+
+        let first_a = cache.get_or_parse(a).unwrap();
+        cache.get_or_parse(b).unwrap();
+        // Touch `a` so `b` is the oldest
+        cache.get_or_parse(a).unwrap();
+        cache.get_or_parse(c).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        assert!(Arc::ptr_eq(&first_a, &cache.get_or_parse(a).unwrap()));
+        assert!(!cache.invalidate(b));
+        assert!(cache.invalidate(c));
+    }
+
+    #[test]
+    fn test_cache_skips_errors() {
+        let cache = ScriptCache::new();
+        assert!(cache.get_or_parse("ON SELECT { \"unterminated }").is_err());
+        assert!(cache.get_or_parse("ON NOWHERE { }").is_err());
+        assert!(cache.is_empty());
+    }
+}
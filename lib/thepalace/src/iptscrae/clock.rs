@@ -0,0 +1,85 @@
+//! Time source for Iptscrae time builtins.
+//!
+//! The VM reads the current time through the [`Clock`] trait so hosts and
+//! tests can substitute a fixed or simulated clock.
+
+use std::time::SystemTime;
+
+/// Source of the current time for the VM
+///
+/// `Sync` so a [`Vm`](crate::iptscrae::Vm) can live in state shared across
+/// async tasks, such as a server's per-connection handler.
+pub trait Clock: Send + Sync {
+    /// Current wall-clock time
+    fn now(&self) -> SystemTime;
+
+    /// Millisecond tick counter used by TICKS
+    ///
+    /// Defaults to milliseconds since the Unix epoch as reported by [`Clock::now`].
+    fn ticks(&self) -> i64 {
+        self.now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0)
+    }
+}
+
+/// Clock backed by the operating system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Format seconds since the Unix epoch as `MM/DD/YYYY HH:MM:SS` (UTC)
+pub fn format_datetime(epoch_secs: u64) -> String {
+    let days = (epoch_secs / 86_400) as i64;
+    let secs_of_day = epoch_secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{:02}/{:02}/{:04} {:02}:{:02}:{:02}",
+        month,
+        day,
+        year,
+        secs_of_day / 3600,
+        (secs_of_day / 60) % 60,
+        secs_of_day % 60
+    )
+}
+
+/// Convert days since 1970-01-01 to a proleptic Gregorian (year, month, day)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Shift the epoch to 0000-03-01 so leap days fall at the end of each era year
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_datetime_epoch() {
+        assert_eq!(format_datetime(0), "01/01/1970 00:00:00");
+    }
+
+    #[test]
+    fn test_format_datetime_known_values() {
+        assert_eq!(format_datetime(1_700_000_000), "11/14/2023 22:13:20");
+        // Leap day
+        assert_eq!(format_datetime(951_782_400), "02/29/2000 00:00:00");
+        assert_eq!(format_datetime(1_709_251_199), "02/29/2024 23:59:59");
+    }
+}
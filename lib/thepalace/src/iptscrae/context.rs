@@ -4,11 +4,22 @@
 //! including information about the current user, room, and event, as well as callbacks
 //! for performing Palace operations like navigation and chat.
 
+use crate::iptscrae::ast::Script;
 use crate::iptscrae::events::EventType;
 use crate::iptscrae::value::Value;
+use crate::messages::flags::UserFlags;
 use crate::AssetSpec;
 use std::collections::HashMap;
 
+/// `event_data` key for the user who sent the chat (WHOCHAT).
+const CHAT_USER_KEY: &str = "chat_user_id";
+/// `event_data` key for the targeted user (WHOTARGET).
+const TARGET_USER_KEY: &str = "target_user_id";
+/// `event_data` key for the door that triggered the event (DOORIDX).
+const DOOR_ID_KEY: &str = "door_id";
+/// `event_data` key for the spot that triggered the event (SPOTIDX).
+const SPOT_ID_KEY: &str = "spot_id";
+
 /// Security level for script execution.
 ///
 /// Different security levels restrict which built-in functions scripts can call.
@@ -77,7 +88,7 @@ pub trait ScriptActions {
     /// Send a status message (STATUSMSG).
     fn status_msg(&mut self, message: &str);
 
-    /// Send a superuser message (SUSRMSG).
+    /// Send a superuser message (SUSRMSG, sent as `MessageId::Smsg`).
     fn superuser_msg(&mut self, message: &str);
 
     /// Log a message (LOGMSG).
@@ -139,6 +150,168 @@ impl ScriptActions for () {
     fn launch_app(&mut self, _url: &str) {}
 }
 
+/// One recorded call to a [`ScriptActions`] method.
+///
+/// A `Vec<ScriptAction>` is itself a `ScriptActions` that records every call,
+/// so a script's side effects can be buffered and replayed later with
+/// [`ScriptAction::apply`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptAction {
+    Say(String),
+    Chat(String),
+    LocalMsg(String),
+    RoomMsg(String),
+    PrivateMsg { user_id: i32, message: String },
+    GotoRoom(i16),
+    LockDoor(i32),
+    UnlockDoor(i32),
+    SetFace(i16),
+    SetColor(i16),
+    SetProps(Vec<AssetSpec>),
+    SetPos { x: i16, y: i16 },
+    MoveUser { dx: i16, dy: i16 },
+    GotoUrl(String),
+    GotoUrlFrame { url: String, frame: String },
+    GlobalMsg(String),
+    StatusMsg(String),
+    SuperuserMsg(String),
+    LogMsg(String),
+    SetSpotState { spot_id: i32, state: i32 },
+    AddLooseProp { prop_id: i32, x: i16, y: i16 },
+    ClearLooseProps,
+    PlaySound(i32),
+    PlayMidi(i32),
+    StopMidi,
+    Beep,
+    LaunchApp(String),
+}
+
+impl ScriptAction {
+    /// Perform the recorded call on `actions`.
+    pub fn apply(self, actions: &mut dyn ScriptActions) {
+        match self {
+            ScriptAction::Say(message) => actions.say(&message),
+            ScriptAction::Chat(message) => actions.chat(&message),
+            ScriptAction::LocalMsg(message) => actions.local_msg(&message),
+            ScriptAction::RoomMsg(message) => actions.room_msg(&message),
+            ScriptAction::PrivateMsg { user_id, message } => actions.private_msg(user_id, &message),
+            ScriptAction::GotoRoom(room_id) => actions.goto_room(room_id),
+            ScriptAction::LockDoor(door_id) => actions.lock_door(door_id),
+            ScriptAction::UnlockDoor(door_id) => actions.unlock_door(door_id),
+            ScriptAction::SetFace(face_id) => actions.set_face(face_id),
+            ScriptAction::SetColor(color) => actions.set_color(color),
+            ScriptAction::SetProps(props) => actions.set_props(props),
+            ScriptAction::SetPos { x, y } => actions.set_pos(x, y),
+            ScriptAction::MoveUser { dx, dy } => actions.move_user(dx, dy),
+            ScriptAction::GotoUrl(url) => actions.goto_url(&url),
+            ScriptAction::GotoUrlFrame { url, frame } => actions.goto_url_frame(&url, &frame),
+            ScriptAction::GlobalMsg(message) => actions.global_msg(&message),
+            ScriptAction::StatusMsg(message) => actions.status_msg(&message),
+            ScriptAction::SuperuserMsg(message) => actions.superuser_msg(&message),
+            ScriptAction::LogMsg(message) => actions.log_msg(&message),
+            ScriptAction::SetSpotState { spot_id, state } => actions.set_spot_state(spot_id, state),
+            ScriptAction::AddLooseProp { prop_id, x, y } => actions.add_loose_prop(prop_id, x, y),
+            ScriptAction::ClearLooseProps => actions.clear_loose_props(),
+            ScriptAction::PlaySound(sound_id) => actions.play_sound(sound_id),
+            ScriptAction::PlayMidi(midi_id) => actions.play_midi(midi_id),
+            ScriptAction::StopMidi => actions.stop_midi(),
+            ScriptAction::Beep => actions.beep(),
+            ScriptAction::LaunchApp(url) => actions.launch_app(&url),
+        }
+    }
+}
+
+/// Records every call instead of performing it.
+impl ScriptActions for Vec<ScriptAction> {
+    fn say(&mut self, message: &str) {
+        self.push(ScriptAction::Say(message.to_string()));
+    }
+    fn chat(&mut self, message: &str) {
+        self.push(ScriptAction::Chat(message.to_string()));
+    }
+    fn local_msg(&mut self, message: &str) {
+        self.push(ScriptAction::LocalMsg(message.to_string()));
+    }
+    fn room_msg(&mut self, message: &str) {
+        self.push(ScriptAction::RoomMsg(message.to_string()));
+    }
+    fn private_msg(&mut self, user_id: i32, message: &str) {
+        self.push(ScriptAction::PrivateMsg {
+            user_id,
+            message: message.to_string(),
+        });
+    }
+    fn goto_room(&mut self, room_id: i16) {
+        self.push(ScriptAction::GotoRoom(room_id));
+    }
+    fn lock_door(&mut self, door_id: i32) {
+        self.push(ScriptAction::LockDoor(door_id));
+    }
+    fn unlock_door(&mut self, door_id: i32) {
+        self.push(ScriptAction::UnlockDoor(door_id));
+    }
+    fn set_face(&mut self, face_id: i16) {
+        self.push(ScriptAction::SetFace(face_id));
+    }
+    fn set_color(&mut self, color: i16) {
+        self.push(ScriptAction::SetColor(color));
+    }
+    fn set_props(&mut self, props: Vec<AssetSpec>) {
+        self.push(ScriptAction::SetProps(props));
+    }
+    fn set_pos(&mut self, x: i16, y: i16) {
+        self.push(ScriptAction::SetPos { x, y });
+    }
+    fn move_user(&mut self, dx: i16, dy: i16) {
+        self.push(ScriptAction::MoveUser { dx, dy });
+    }
+    fn goto_url(&mut self, url: &str) {
+        self.push(ScriptAction::GotoUrl(url.to_string()));
+    }
+    fn goto_url_frame(&mut self, url: &str, frame: &str) {
+        self.push(ScriptAction::GotoUrlFrame {
+            url: url.to_string(),
+            frame: frame.to_string(),
+        });
+    }
+    fn global_msg(&mut self, message: &str) {
+        self.push(ScriptAction::GlobalMsg(message.to_string()));
+    }
+    fn status_msg(&mut self, message: &str) {
+        self.push(ScriptAction::StatusMsg(message.to_string()));
+    }
+    fn superuser_msg(&mut self, message: &str) {
+        self.push(ScriptAction::SuperuserMsg(message.to_string()));
+    }
+    fn log_msg(&mut self, message: &str) {
+        self.push(ScriptAction::LogMsg(message.to_string()));
+    }
+    fn set_spot_state(&mut self, spot_id: i32, state: i32) {
+        self.push(ScriptAction::SetSpotState { spot_id, state });
+    }
+    fn add_loose_prop(&mut self, prop_id: i32, x: i16, y: i16) {
+        self.push(ScriptAction::AddLooseProp { prop_id, x, y });
+    }
+    fn clear_loose_props(&mut self) {
+        self.push(ScriptAction::ClearLooseProps);
+    }
+    fn play_sound(&mut self, sound_id: i32) {
+        self.push(ScriptAction::PlaySound(sound_id));
+    }
+    fn play_midi(&mut self, midi_id: i32) {
+        self.push(ScriptAction::PlayMidi(midi_id));
+    }
+    fn stop_midi(&mut self) {
+        self.push(ScriptAction::StopMidi);
+    }
+    fn beep(&mut self) {
+        self.push(ScriptAction::Beep);
+    }
+    fn launch_app(&mut self, url: &str) {
+        self.push(ScriptAction::LaunchApp(url.to_string()));
+    }
+}
+
 /// Execution context for Iptscrae scripts.
 ///
 /// Provides information about the current user, room, and event, as well as
@@ -162,6 +335,9 @@ pub struct ScriptContext<'a> {
     /// Current user props.
     pub user_props: Vec<AssetSpec>,
 
+    /// Current user flags (as stored in `UserRec`).
+    pub user_flags: UserFlags,
+
     /// Current user position X coordinate.
     pub user_pos_x: i16,
 
@@ -183,6 +359,12 @@ pub struct ScriptContext<'a> {
     /// Optional event data (e.g., hotspot ID, user ID for INCHAT/OUTCHAT).
     pub event_data: HashMap<String, Value>,
 
+    /// Chat text that triggered an INCHAT/OUTCHAT event (read by CHATSTR).
+    pub chat_text: Option<String>,
+
+    /// Macro (prop) scripts run by MACRO, keyed by macro ID.
+    pub macros: HashMap<i32, Script>,
+
     /// Callbacks for performing Palace operations.
     pub actions: &'a mut dyn ScriptActions,
 }
@@ -197,6 +379,7 @@ impl<'a> ScriptContext<'a> {
             user_face: 0,
             user_color: 0,
             user_props: Vec::new(),
+            user_flags: UserFlags::empty(),
             user_pos_x: 0,
             user_pos_y: 0,
             room_id: 0,
@@ -204,10 +387,162 @@ impl<'a> ScriptContext<'a> {
             server_name: String::new(),
             event_type: EventType::Select,
             event_data: HashMap::new(),
+            chat_text: None,
+            macros: HashMap::new(),
             actions,
         }
     }
 
+    /// Start building a script context with fluent setters.
+    ///
+    /// Fields that aren't set keep the defaults from [`ScriptContext::new`].
+    pub fn builder(
+        security_level: SecurityLevel,
+        actions: &'a mut dyn ScriptActions,
+    ) -> ScriptContextBuilder<'a> {
+        ScriptContextBuilder {
+            context: Self::new(security_level, actions),
+        }
+    }
+
+    /// Set the user who sent the chat message (read by WHOCHAT).
+    pub fn set_chat_user(&mut self, user_id: i32) {
+        self.event_data
+            .insert(CHAT_USER_KEY.to_string(), Value::Integer(user_id));
+    }
+
+    /// Get the user who sent the chat message, if set.
+    pub fn chat_user(&self) -> Option<i32> {
+        self.event_int(CHAT_USER_KEY)
+    }
+
+    /// Set the targeted user (read by WHOTARGET).
+    pub fn set_target_user(&mut self, user_id: i32) {
+        self.event_data
+            .insert(TARGET_USER_KEY.to_string(), Value::Integer(user_id));
+    }
+
+    /// Get the targeted user, if set.
+    pub fn target_user(&self) -> Option<i32> {
+        self.event_int(TARGET_USER_KEY)
+    }
+
+    /// Set the door that triggered the event (read by DOORIDX).
+    pub fn set_door_id(&mut self, door_id: i32) {
+        self.event_data
+            .insert(DOOR_ID_KEY.to_string(), Value::Integer(door_id));
+    }
+
+    /// Get the door that triggered the event, if set.
+    pub fn door_id(&self) -> Option<i32> {
+        self.event_int(DOOR_ID_KEY)
+    }
+
+    /// Set the spot that triggered the event (read by SPOTIDX).
+    pub fn set_spot_id(&mut self, spot_id: i32) {
+        self.event_data
+            .insert(SPOT_ID_KEY.to_string(), Value::Integer(spot_id));
+    }
+
+    /// Get the spot that triggered the event, if set.
+    pub fn spot_id(&self) -> Option<i32> {
+        self.event_int(SPOT_ID_KEY)
+    }
+
+    /// Check if the current user is a guest (read by ISGUEST).
+    pub fn is_guest(&self) -> bool {
+        self.user_flags.is_guest()
+    }
+
+    /// Check if the current user is a god (read by ISGOD).
+    ///
+    /// True for admin scripts or when the user has the god flag.
+    pub fn is_god(&self) -> bool {
+        self.security_level == SecurityLevel::Admin || self.user_flags.contains(UserFlags::GOD)
+    }
+
+    /// Check if the current user is a wizard (read by ISWIZARD).
+    ///
+    /// Gods count as wizards.
+    pub fn is_wizard(&self) -> bool {
+        self.is_god() || self.user_flags.is_wizard()
+    }
+
+    /// Run `f` on a copy of this context whose actions go to `actions`.
+    ///
+    /// Whatever `f` changes in the copy's other fields is written back.
+    pub(crate) fn with_actions<R>(
+        &mut self,
+        actions: &mut dyn ScriptActions,
+        f: impl FnOnce(&mut ScriptContext<'_>) -> R,
+    ) -> R {
+        let mut redirected = ScriptContext {
+            security_level: self.security_level,
+            user_id: self.user_id,
+            user_name: std::mem::take(&mut self.user_name),
+            user_face: self.user_face,
+            user_color: self.user_color,
+            user_props: std::mem::take(&mut self.user_props),
+            user_flags: self.user_flags,
+            user_pos_x: self.user_pos_x,
+            user_pos_y: self.user_pos_y,
+            room_id: self.room_id,
+            room_name: std::mem::take(&mut self.room_name),
+            server_name: std::mem::take(&mut self.server_name),
+            event_type: self.event_type,
+            event_data: std::mem::take(&mut self.event_data),
+            chat_text: self.chat_text.take(),
+            macros: std::mem::take(&mut self.macros),
+            actions,
+        };
+        let result = f(&mut redirected);
+
+        let ScriptContext {
+            security_level,
+            user_id,
+            user_name,
+            user_face,
+            user_color,
+            user_props,
+            user_flags,
+            user_pos_x,
+            user_pos_y,
+            room_id,
+            room_name,
+            server_name,
+            event_type,
+            event_data,
+            chat_text,
+            macros,
+            actions: _,
+        } = redirected;
+        self.security_level = security_level;
+        self.user_id = user_id;
+        self.user_name = user_name;
+        self.user_face = user_face;
+        self.user_color = user_color;
+        self.user_props = user_props;
+        self.user_flags = user_flags;
+        self.user_pos_x = user_pos_x;
+        self.user_pos_y = user_pos_y;
+        self.room_id = room_id;
+        self.room_name = room_name;
+        self.server_name = server_name;
+        self.event_type = event_type;
+        self.event_data = event_data;
+        self.chat_text = chat_text;
+        self.macros = macros;
+        result
+    }
+
+    /// Look up an integer entry in `event_data`.
+    fn event_int(&self, key: &str) -> Option<i32> {
+        match self.event_data.get(key) {
+            Some(Value::Integer(n)) => Some(*n),
+            _ => None,
+        }
+    }
+
     /// Check if a function is allowed at the current security level.
     pub fn is_function_allowed(&self, function_name: &str) -> bool {
         match self.security_level {
@@ -220,6 +555,105 @@ impl<'a> ScriptContext<'a> {
     }
 }
 
+/// Builder for [`ScriptContext`].
+///
+/// Created with [`ScriptContext::builder`]; finish with [`build`](Self::build).
+pub struct ScriptContextBuilder<'a> {
+    context: ScriptContext<'a>,
+}
+
+impl<'a> ScriptContextBuilder<'a> {
+    /// Set the current user ID.
+    pub fn user_id(mut self, user_id: i32) -> Self {
+        self.context.user_id = user_id;
+        self
+    }
+
+    /// Set the current user name.
+    pub fn user_name(mut self, user_name: impl Into<String>) -> Self {
+        self.context.user_name = user_name.into();
+        self
+    }
+
+    /// Set the current user face (avatar) ID.
+    pub fn user_face(mut self, user_face: i16) -> Self {
+        self.context.user_face = user_face;
+        self
+    }
+
+    /// Set the current user color.
+    pub fn user_color(mut self, user_color: i16) -> Self {
+        self.context.user_color = user_color;
+        self
+    }
+
+    /// Set the current user props.
+    pub fn user_props(mut self, user_props: Vec<AssetSpec>) -> Self {
+        self.context.user_props = user_props;
+        self
+    }
+
+    /// Set the current user flags.
+    pub fn user_flags(mut self, user_flags: UserFlags) -> Self {
+        self.context.user_flags = user_flags;
+        self
+    }
+
+    /// Set the current user position.
+    pub fn user_pos(mut self, x: i16, y: i16) -> Self {
+        self.context.user_pos_x = x;
+        self.context.user_pos_y = y;
+        self
+    }
+
+    /// Set the current room ID.
+    pub fn room_id(mut self, room_id: i16) -> Self {
+        self.context.room_id = room_id;
+        self
+    }
+
+    /// Set the current room name.
+    pub fn room_name(mut self, room_name: impl Into<String>) -> Self {
+        self.context.room_name = room_name.into();
+        self
+    }
+
+    /// Set the server name.
+    pub fn server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.context.server_name = server_name.into();
+        self
+    }
+
+    /// Set the event type that triggered the script.
+    pub fn event_type(mut self, event_type: EventType) -> Self {
+        self.context.event_type = event_type;
+        self
+    }
+
+    /// Add one entry of event data.
+    pub fn event_data(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.context.event_data.insert(key.into(), value);
+        self
+    }
+
+    /// Set the chat text for an INCHAT/OUTCHAT event.
+    pub fn chat_text(mut self, chat_text: impl Into<String>) -> Self {
+        self.context.chat_text = Some(chat_text.into());
+        self
+    }
+
+    /// Register one macro script under a macro ID.
+    pub fn macro_script(mut self, macro_id: i32, script: Script) -> Self {
+        self.context.macros.insert(macro_id, script);
+        self
+    }
+
+    /// Finish building the context.
+    pub fn build(self) -> ScriptContext<'a> {
+        self.context
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -239,6 +673,33 @@ mod tests {
         assert!(cyborg_ctx.is_function_allowed("WHONAME"));
     }
 
+    #[test]
+    fn test_recorded_actions_replay() {
+        let mut recorded: Vec<ScriptAction> = Vec::new();
+        recorded.say("hi");
+        recorded.private_msg(3, "psst");
+        recorded.set_pos(10, 20);
+        recorded.beep();
+        assert_eq!(
+            recorded,
+            vec![
+                ScriptAction::Say("hi".to_string()),
+                ScriptAction::PrivateMsg {
+                    user_id: 3,
+                    message: "psst".to_string()
+                },
+                ScriptAction::SetPos { x: 10, y: 20 },
+                ScriptAction::Beep,
+            ]
+        );
+
+        let mut replayed: Vec<ScriptAction> = Vec::new();
+        for action in recorded.clone() {
+            action.apply(&mut replayed);
+        }
+        assert_eq!(replayed, recorded);
+    }
+
     #[test]
     fn test_context_creation() {
         let mut actions = ();
@@ -249,6 +710,41 @@ mod tests {
         assert_eq!(ctx.room_id, 0);
     }
 
+    #[test]
+    fn test_context_builder() {
+        let mut actions = ();
+        let props = vec![AssetSpec::new(100, 0x1234)];
+        let ctx = ScriptContext::builder(SecurityLevel::Cyborg, &mut actions)
+            .user_id(7)
+            .user_name("Alice")
+            .user_face(3)
+            .user_color(12)
+            .user_props(props.clone())
+            .user_pos(100, 200)
+            .room_id(42)
+            .room_name("Lobby")
+            .server_name("Test Palace")
+            .event_type(EventType::InChat)
+            .event_data("message", Value::String("hi".to_string()))
+            .build();
+
+        assert_eq!(ctx.security_level, SecurityLevel::Cyborg);
+        assert_eq!(ctx.user_id, 7);
+        assert_eq!(ctx.user_name, "Alice");
+        assert_eq!(ctx.user_face, 3);
+        assert_eq!(ctx.user_color, 12);
+        assert_eq!(ctx.user_props, props);
+        assert_eq!((ctx.user_pos_x, ctx.user_pos_y), (100, 200));
+        assert_eq!(ctx.room_id, 42);
+        assert_eq!(ctx.room_name, "Lobby");
+        assert_eq!(ctx.server_name, "Test Palace");
+        assert_eq!(ctx.event_type, EventType::InChat);
+        assert_eq!(
+            ctx.event_data.get("message"),
+            Some(&Value::String("hi".to_string()))
+        );
+    }
+
     #[test]
     fn test_event_data() {
         let mut actions = ();
@@ -25,7 +25,10 @@ pub enum EventType {
     Enter,
     Leave,
     OutChat,
+    /// Fired once per session, after the user has logged on and before the
+    /// first ENTER; cyborgs use it to set up their globals
     SignOn,
+    /// Fired once per session when the user disconnects, after the last LEAVE
     SignOff,
     Macro0,
     Macro1,
@@ -40,6 +43,35 @@ pub enum EventType {
 }
 
 impl EventType {
+    /// Every event type, in event mask bit order
+    pub const ALL: [EventType; 25] = [
+        EventType::Select,
+        EventType::Lock,
+        EventType::Unlock,
+        EventType::Hide,
+        EventType::Show,
+        EventType::Startup,
+        EventType::Alarm,
+        EventType::Custom,
+        EventType::InChat,
+        EventType::PropChange,
+        EventType::Enter,
+        EventType::Leave,
+        EventType::OutChat,
+        EventType::SignOn,
+        EventType::SignOff,
+        EventType::Macro0,
+        EventType::Macro1,
+        EventType::Macro2,
+        EventType::Macro3,
+        EventType::Macro4,
+        EventType::Macro5,
+        EventType::Macro6,
+        EventType::Macro7,
+        EventType::Macro8,
+        EventType::Macro9,
+    ];
+
     /// Convert event type to event mask
     pub const fn to_mask(self) -> EventMask {
         match self {
@@ -133,6 +165,30 @@ impl EventType {
             EventType::Macro9 => "MACRO9",
         }
     }
+
+    /// Get the keyword that names this event in scripts (`ON SELECT`)
+    ///
+    /// Every event has exactly one keyword, so this round-trips through
+    /// [`EventType::from_keyword`].
+    pub const fn keyword(self) -> &'static str {
+        self.name()
+    }
+
+    /// Look up an event by its script keyword (case-insensitive)
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        Self::from_name(keyword)
+    }
+}
+
+impl EventMask {
+    /// Get the script keywords of the events in this mask, in bit order
+    pub fn keywords(&self) -> Vec<&'static str> {
+        EventType::ALL
+            .iter()
+            .filter(|event| self.contains(event.to_mask()))
+            .map(|event| event.keyword())
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -192,6 +248,24 @@ mod tests {
         assert_eq!(EventType::Macro5.name(), "MACRO5");
     }
 
+    #[test]
+    fn test_event_keyword_roundtrip() {
+        let mut keywords = std::collections::HashSet::new();
+        let mut union = EventMask::empty();
+        for event in EventType::ALL {
+            assert_eq!(EventType::from_keyword(event.keyword()), Some(event));
+            assert!(keywords.insert(event.keyword()), "duplicate keyword");
+            assert!(!union.intersects(event.to_mask()), "shared mask bit");
+            union |= event.to_mask();
+        }
+        assert_eq!(union, EventMask::all());
+
+        let mask = EventMask::LEAVE | EventMask::SELECT | EventMask::MACRO9;
+        assert_eq!(mask.keywords(), ["SELECT", "LEAVE", "MACRO9"]);
+        assert_eq!(EventMask::all().keywords().len(), EventType::ALL.len());
+        assert!(EventMask::empty().keywords().is_empty());
+    }
+
     #[test]
     fn test_event_mask_default() {
         let mask = EventMask::default();
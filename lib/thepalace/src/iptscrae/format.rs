@@ -0,0 +1,64 @@
+//! Canonical formatting for Iptscrae source.
+//!
+//! [`format_source`] parses a script and writes it back out with
+//! [`Script::to_source`], so every script comes out with the same layout no
+//! matter how it was typed:
+//! - Each `ON` handler starts on its own line
+//! - `IF`, `ELSE`, `WHILE` and `CASE` blocks are indented two spaces per level
+//! - Runs of plain expressions share a line, which ends at an assignment or
+//!   control-flow block
+//!
+//! Comment lines directly above a handler are kept; other comments are not.
+
+use crate::iptscrae::ast::Script;
+use crate::iptscrae::lexer::Lexer;
+use crate::iptscrae::parser::{ParseError, Parser};
+
+/// Reformat Iptscrae source into the canonical layout
+///
+/// Formatting is idempotent: formatting the output again returns it
+/// unchanged.
+pub fn format_source(src: &str) -> Result<String, ParseError> {
+    let tokens = Lexer::new(src).tokenize()?;
+    let script: Script = Parser::new(tokens).parse()?;
+    Ok(script.to_source())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_messy_script() {
+        let messy = concat!(
+            "on   select{0 n =\n\n   n 3 <   WHILE{\"tick\"   SAY n 1 + n = n 3 <}\n",
+            " n 3 >= IF {\"done\" SAY} ELSE{ BREAK }}   ON ENTER{\"hi\" SAY}",
+        );
+        let expected = "\
+ON SELECT {
+  0 n =
+  n 3 < WHILE {
+    \"tick\" SAY n 1 + n =
+    n 3 <
+  }
+  n 3 >= IF {
+    \"done\" SAY
+  } ELSE {
+    BREAK
+  }
+}
+ON ENTER {
+  \"hi\" SAY
+}
+";
+        let formatted = format_source(messy).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_rejects_invalid_source() {
+        assert!(format_source("ON SELECT { \"unterminated }").is_err());
+        assert!(format_source("ON SELECT { 1 IF }").is_err());
+    }
+}
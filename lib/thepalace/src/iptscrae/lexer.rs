@@ -3,8 +3,10 @@
 //! The lexer tokenizes Iptscrae source code into a sequence of tokens.
 //! It handles:
 //! - Comments (# to end of line)
-//! - String literals ("...")
-//! - Integer literals
+//! - String literals ("..."), with `\n`, `\r`, `\t`, `\0`, `\\`, `\"` and
+//!   `\xHH` escapes
+//! - Integer literals, decimal or hex (`0x1F` / `$1F`), and float literals
+//!   (`3.14`); either may be negative (`-5`)
 //! - Identifiers and keywords
 //! - Operators and delimiters
 
@@ -27,6 +29,13 @@ pub enum LexError {
         line: usize,
         column: usize,
     },
+    /// Backslash escape cut off by the end of input, or `\x` without two
+    /// hex digits; the position is the backslash's
+    InvalidEscape {
+        text: String,
+        line: usize,
+        column: usize,
+    },
 }
 
 impl std::fmt::Display for LexError {
@@ -49,6 +58,13 @@ impl std::fmt::Display for LexError {
                     text, line, column
                 )
             }
+            LexError::InvalidEscape { text, line, column } => {
+                write!(
+                    f,
+                    "Invalid escape '{}' at line {}, column {}",
+                    text, line, column
+                )
+            }
         }
     }
 }
@@ -126,7 +142,7 @@ impl Lexer {
         }
 
         // Numbers
-        if ch.is_ascii_digit() || (ch == '-' && self.peek().is_some_and(|c| c.is_ascii_digit())) {
+        if ch.is_ascii_digit() || ch == '$' || (ch == '-' && self.starts_negative_literal()) {
             return self.lex_number();
         }
 
@@ -163,7 +179,12 @@ impl Lexer {
             }
             '=' => {
                 self.advance();
-                Token::new(TokenKind::Equals, pos)
+                if self.current_char() == '=' {
+                    self.advance();
+                    Token::new(TokenKind::EqEq, pos)
+                } else {
+                    Token::new(TokenKind::Equals, pos)
+                }
             }
             '!' => {
                 self.advance();
@@ -251,24 +272,7 @@ impl Lexer {
         while !self.is_eof() && self.current_char() != '"' {
             let ch = self.current_char();
             if ch == '\\' {
-                self.advance();
-                if self.is_eof() {
-                    return Err(LexError::UnterminatedString {
-                        line: pos.line,
-                        column: pos.column,
-                    });
-                }
-                // Handle escape sequences
-                let escaped = match self.current_char() {
-                    'n' => '\n',
-                    'r' => '\r',
-                    't' => '\t',
-                    '\\' => '\\',
-                    '"' => '"',
-                    c => c, // Unknown escape, just use the character
-                };
-                string.push(escaped);
-                self.advance();
+                string.push(self.lex_escape()?);
             } else {
                 string.push(ch);
                 self.advance();
@@ -286,6 +290,45 @@ impl Lexer {
         Ok(Token::new(TokenKind::String(string), pos))
     }
 
+    /// Lex a backslash escape inside a string literal
+    fn lex_escape(&mut self) -> Result<char, LexError> {
+        let pos = self.current_pos();
+        let invalid = |text: String| LexError::InvalidEscape {
+            text,
+            line: pos.line,
+            column: pos.column,
+        };
+        self.advance(); // Skip backslash
+
+        if self.is_eof() {
+            return Err(invalid("\\".to_string()));
+        }
+        let escaped = match self.current_char() {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            'x' => {
+                let digits: String = self.source[self.position + 1..]
+                    .iter()
+                    .take(2)
+                    .take_while(|c| c.is_ascii_hexdigit())
+                    .collect();
+                if digits.len() != 2 {
+                    return Err(invalid(format!("\\x{}", digits)));
+                }
+                self.advance();
+                self.advance();
+                u8::from_str_radix(&digits, 16).unwrap() as char
+            }
+            c => c, // Unknown escape, just use the character
+        };
+        self.advance();
+        Ok(escaped)
+    }
+
     /// Lex a number (integer only)
     fn lex_number(&mut self) -> Result<Token, LexError> {
         let pos = self.current_pos();
@@ -297,12 +340,37 @@ impl Lexer {
             self.advance();
         }
 
+        if self.current_char() == '$'
+            || (self.current_char() == '0' && matches!(self.peek(), Some('x' | 'X')))
+        {
+            return self.lex_hex(number, pos);
+        }
+
         // Collect digits
         while !self.is_eof() && self.current_char().is_ascii_digit() {
             number.push(self.current_char());
             self.advance();
         }
 
+        // A decimal point with a digit after it makes a float
+        if self.current_char() == '.' && self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            number.push('.');
+            self.advance();
+            while !self.is_eof() && self.current_char().is_ascii_digit() {
+                number.push(self.current_char());
+                self.advance();
+            }
+            // Literals too large for an f64 would parse as infinity
+            return match number.parse::<f64>() {
+                Ok(x) if x.is_finite() => Ok(Token::new(TokenKind::Float(x), pos)),
+                _ => Err(LexError::InvalidNumber {
+                    text: number,
+                    line: pos.line,
+                    column: pos.column,
+                }),
+            };
+        }
+
         // Parse the number
         match number.parse::<i32>() {
             Ok(n) => Ok(Token::new(TokenKind::Integer(n), pos)),
@@ -314,6 +382,43 @@ impl Lexer {
         }
     }
 
+    /// Lex a hex integer (`0x1F` or `$1F`) after any minus sign in `text`
+    ///
+    /// All 32 bits may be set, so flag masks like `0xFFFFFFFF` read as -1.
+    fn lex_hex(&mut self, mut text: String, pos: SourcePos) -> Result<Token, LexError> {
+        let prefix_len = if self.current_char() == '$' { 1 } else { 2 };
+        for _ in 0..prefix_len {
+            text.push(self.current_char());
+            self.advance();
+        }
+
+        // Take the whole word so a malformed literal is reported in full
+        let digits_start = text.len();
+        while !self.is_eof()
+            && (self.current_char().is_alphanumeric() || self.current_char() == '_')
+        {
+            text.push(self.current_char());
+            self.advance();
+        }
+
+        match u32::from_str_radix(&text[digits_start..], 16) {
+            Ok(n) => {
+                let n = n as i32;
+                let n = if text.starts_with('-') {
+                    n.wrapping_neg()
+                } else {
+                    n
+                };
+                Ok(Token::new(TokenKind::Integer(n), pos))
+            }
+            Err(_) => Err(LexError::InvalidNumber {
+                text,
+                line: pos.line,
+                column: pos.column,
+            }),
+        }
+    }
+
     /// Lex an identifier or keyword
     fn lex_identifier(&mut self) -> Token {
         let pos = self.current_pos();
@@ -333,6 +438,18 @@ impl Lexer {
         Token::new(kind, pos)
     }
 
+    /// Check whether the `-` at the current position starts a negative literal
+    ///
+    /// It does when a digit follows and it isn't stuck to the end of an
+    /// operand, so `-5` and `x -5` are literals but `a-5` and `3-1` subtract.
+    fn starts_negative_literal(&self) -> bool {
+        let after_operand = self.position > 0 && {
+            let prev = self.source[self.position - 1];
+            prev.is_alphanumeric() || matches!(prev, '_' | '"' | ')' | '}')
+        };
+        !after_operand && self.peek().is_some_and(|c| c.is_ascii_digit())
+    }
+
     /// Skip whitespace characters (but not newlines)
     fn skip_whitespace(&mut self) {
         while !self.is_eof() {
@@ -396,6 +513,55 @@ mod tests {
         assert_eq!(tokens[2].kind, TokenKind::Integer(0));
     }
 
+    #[test]
+    fn test_lex_signed_and_float_literals() {
+        let mut lexer = Lexer::new("-5 3.14 -0.5 {-2}");
+        let tokens = lexer.tokenize().unwrap();
+        let kinds: Vec<_> = tokens.into_iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Integer(-5),
+                TokenKind::Float("3.14".parse().unwrap()),
+                TokenKind::Float(-0.5),
+                TokenKind::LeftBrace,
+                TokenKind::Integer(-2),
+                TokenKind::RightBrace,
+                TokenKind::Eof,
+            ]
+        );
+
+        // A number needs a digit after its decimal point
+        let err = Lexer::new("7.").tokenize().unwrap_err();
+        assert!(matches!(err, LexError::InvalidCharacter { ch: '.', .. }));
+
+        // So does one that overflows to infinity
+        let huge = format!("1{}.0", "0".repeat(400));
+        let err = Lexer::new(&huge).tokenize().unwrap_err();
+        assert!(matches!(err, LexError::InvalidNumber { .. }));
+    }
+
+    #[test]
+    fn test_lex_minus_after_operand() {
+        let mut lexer = Lexer::new("a-5 3-1 x -5");
+        let tokens = lexer.tokenize().unwrap();
+        let kinds: Vec<_> = tokens.into_iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Ident("a".to_string()),
+                TokenKind::Minus,
+                TokenKind::Integer(5),
+                TokenKind::Integer(3),
+                TokenKind::Minus,
+                TokenKind::Integer(1),
+                TokenKind::Ident("x".to_string()),
+                TokenKind::Integer(-5),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
     #[test]
     fn test_lex_strings() {
         let mut lexer = Lexer::new(r#""hello" "world" "test\"quote""#);
@@ -406,6 +572,70 @@ mod tests {
         assert_eq!(tokens[2].kind, TokenKind::String("test\"quote".to_string()));
     }
 
+    #[test]
+    fn test_lex_string_escapes() {
+        let mut lexer = Lexer::new(r#""a\nb" "\t\\\0" "\x41\x7e" "\q""#);
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(tokens[0].kind, TokenKind::String("a\nb".to_string()));
+        assert_eq!(tokens[1].kind, TokenKind::String("\t\\\0".to_string()));
+        assert_eq!(tokens[2].kind, TokenKind::String("A~".to_string()));
+        // Unknown escapes stand for the character itself
+        assert_eq!(tokens[3].kind, TokenKind::String("q".to_string()));
+
+        let err = Lexer::new("\"ok\" \"bad \\xG1\"").tokenize().unwrap_err();
+        assert_eq!(
+            err,
+            LexError::InvalidEscape {
+                text: "\\x".to_string(),
+                line: 1,
+                column: 11,
+            }
+        );
+
+        let err = Lexer::new("SAY \"trailing\\").tokenize().unwrap_err();
+        assert_eq!(
+            err,
+            LexError::InvalidEscape {
+                text: "\\".to_string(),
+                line: 1,
+                column: 14,
+            }
+        );
+    }
+
+    #[test]
+    fn test_lex_hex_integers() {
+        let mut lexer = Lexer::new("0xFF $ff 0X10 -0x10 0xFFFFFFFF 0x0");
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(tokens[0].kind, TokenKind::Integer(255));
+        assert_eq!(tokens[1].kind, TokenKind::Integer(255));
+        assert_eq!(tokens[2].kind, TokenKind::Integer(16));
+        assert_eq!(tokens[3].kind, TokenKind::Integer(-16));
+        assert_eq!(tokens[4].kind, TokenKind::Integer(-1));
+        assert_eq!(tokens[5].kind, TokenKind::Integer(0));
+
+        for (source, text, column) in [
+            ("flags 0xZZ", "0xZZ", 7),
+            ("$", "$", 1),
+            ("0x", "0x", 1),
+            ("0x100000000", "0x100000000", 1),
+        ] {
+            let err = Lexer::new(source).tokenize().unwrap_err();
+            assert_eq!(
+                err,
+                LexError::InvalidNumber {
+                    text: text.to_string(),
+                    line: 1,
+                    column,
+                },
+                "{}",
+                source
+            );
+        }
+    }
+
     #[test]
     fn test_lex_identifiers() {
         let mut lexer = Lexer::new("foo bar_baz test123");
@@ -431,7 +661,7 @@ mod tests {
 
     #[test]
     fn test_lex_operators() {
-        let mut lexer = Lexer::new("+ - * / % & = != < > <= >=");
+        let mut lexer = Lexer::new("+ - * / % & = != < > <= >= ==");
         let tokens = lexer.tokenize().unwrap();
 
         assert_eq!(tokens[0].kind, TokenKind::Plus);
@@ -446,6 +676,7 @@ mod tests {
         assert_eq!(tokens[9].kind, TokenKind::Greater);
         assert_eq!(tokens[10].kind, TokenKind::LessEq);
         assert_eq!(tokens[11].kind, TokenKind::GreaterEq);
+        assert_eq!(tokens[12].kind, TokenKind::EqEq);
     }
 
     #[test]
@@ -14,15 +14,20 @@
 //!
 //! - **Server scripts**: Full trust, no sandboxing
 //! - **Cyborg scripts**: Sandboxed with instruction limits and timeouts
-//! - Room flag RF_CyborgFreeZone (0x1000) disables cyborg scripts
+//! - Room flag RF_CyborgFreeZone (0x0010) disables cyborg scripts
 //! - Server flag SF_AllowCyborgs (0x0200) enables globally
+//! - [`resolve_script_policy`] combines these into the limits a script runs under
 
 pub mod ast;
 pub mod builtins;
+pub mod cache;
+pub mod clock;
 pub mod context;
 pub mod events;
+pub mod format;
 pub mod lexer;
 pub mod parser;
+pub mod policy;
 #[cfg(feature = "room-script")]
 pub mod room_script;
 #[cfg(feature = "room-script")]
@@ -30,20 +35,32 @@ pub mod room_script_parser;
 #[cfg(all(feature = "room-script", feature = "net", feature = "room"))]
 pub mod room_script_converter;
 pub mod token;
+pub mod validate;
 pub mod value;
 pub mod vm;
 
-pub use ast::{BinOp, Block, EventHandler, Expr, Script, Statement, UnaryOp};
-pub use context::{ScriptActions, ScriptContext, SecurityLevel};
+pub use ast::{BinOp, Block, CaseArm, EventHandler, Expr, Script, Statement, UnaryOp};
+pub use cache::ScriptCache;
+pub use clock::{Clock, SystemClock};
+pub use context::{
+    ScriptAction, ScriptActions, ScriptContext, ScriptContextBuilder, SecurityLevel,
+};
 pub use events::{EventMask, EventType};
+pub use format::format_source;
 pub use lexer::{LexError, Lexer};
 pub use parser::{ParseError, Parser};
+pub use policy::resolve_script_policy;
 #[cfg(feature = "room-script")]
-pub use room_script::{DoorDecl, PictureDecl, RoomDecl, RoomFlags, SpotDecl, StateDecl};
+pub use room_script::{
+    DirtyRoom, DoorDecl, OutlineIssue, PictureDecl, RoomDecl, RoomFlags, SpotDecl, StateDecl,
+};
 #[cfg(feature = "room-script")]
 pub use room_script_parser::RoomScriptParser;
 #[cfg(all(feature = "room-script", feature = "net", feature = "room"))]
 pub use room_script_converter::{convert_room, ConversionError};
 pub use token::{SourcePos, Token, TokenKind};
+pub use validate::ValidationWarning;
 pub use value::Value;
-pub use vm::{ExecutionLimits, Vm, VmError};
+pub use vm::{
+    ExecutionLimits, PartialRun, RunStats, UserScriptState, Vm, VmError, VmErrorAt, MAX_MACRO_DEPTH,
+};
@@ -4,26 +4,48 @@
 //! Iptscrae is a stack-based language where most expressions push values onto
 //! the stack, and operations consume values from the stack.
 
-use crate::iptscrae::ast::{BinOp, Block, EventHandler, Expr, Script, Statement};
+use crate::iptscrae::ast::{BinOp, Block, CaseArm, EventHandler, Expr, Script, Statement};
 use crate::iptscrae::events::EventType;
+use crate::iptscrae::lexer::LexError;
 use crate::iptscrae::token::{SourcePos, Token, TokenKind};
 use crate::iptscrae::value::Value;
 
 /// Parser error types
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// `expected` lists every token that would have been accepted. Tokens that
+/// carry a value (`Integer`, `String`, `Ident`, ...) stand for any token of
+/// that kind, so e.g. `Ident(String::new())` means "an identifier".
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParseError {
     UnexpectedToken {
-        expected: String,
-        found: String,
+        expected: Vec<TokenKind>,
+        found: TokenKind,
         pos: SourcePos,
     },
     UnexpectedEof {
-        expected: String,
+        expected: Vec<TokenKind>,
     },
     InvalidEventName {
         name: String,
         pos: SourcePos,
     },
+    /// Well-formed tokens with a bad value, e.g. a room without an ID
+    Invalid {
+        message: String,
+        pos: SourcePos,
+    },
+    Lex(LexError),
+}
+
+/// Render an expected-token set as "A", "A or B" or "one of A, B, C"
+fn describe_expected(expected: &[TokenKind]) -> String {
+    let names: Vec<&str> = expected.iter().map(TokenKind::name).collect();
+    match names.as_slice() {
+        [] => "nothing".to_string(),
+        [name] => name.to_string(),
+        [first, second] => format!("{} or {}", first, second),
+        _ => format!("one of {}", names.join(", ")),
+    }
 }
 
 impl std::fmt::Display for ParseError {
@@ -37,11 +59,18 @@ impl std::fmt::Display for ParseError {
                 write!(
                     f,
                     "Expected {} but found {} at line {}, column {}",
-                    expected, found, pos.line, pos.column
+                    describe_expected(expected),
+                    found,
+                    pos.line,
+                    pos.column
                 )
             }
             ParseError::UnexpectedEof { expected } => {
-                write!(f, "Unexpected end of file, expected {}", expected)
+                write!(
+                    f,
+                    "Unexpected end of file, expected {}",
+                    describe_expected(expected)
+                )
             }
             ParseError::InvalidEventName { name, pos } => {
                 write!(
@@ -50,12 +79,44 @@ impl std::fmt::Display for ParseError {
                     name, pos.line, pos.column
                 )
             }
+            ParseError::Invalid { message, pos } => {
+                write!(f, "{} at line {}, column {}", message, pos.line, pos.column)
+            }
+            ParseError::Lex(e) => e.fmt(f),
         }
     }
 }
 
 impl std::error::Error for ParseError {}
 
+impl From<LexError> for ParseError {
+    fn from(e: LexError) -> Self {
+        ParseError::Lex(e)
+    }
+}
+
+/// Tokens that can start an expression
+const EXPRESSION_START: &[TokenKind] = &[
+    TokenKind::Integer(0),
+    TokenKind::Float(0.0),
+    TokenKind::String(String::new()),
+    TokenKind::Ident(String::new()),
+    TokenKind::Plus,
+    TokenKind::Minus,
+    TokenKind::Star,
+    TokenKind::Slash,
+    TokenKind::Percent,
+    TokenKind::Ampersand,
+    TokenKind::Equals,
+    TokenKind::EqEq,
+    TokenKind::NotEquals,
+    TokenKind::Less,
+    TokenKind::Greater,
+    TokenKind::LessEq,
+    TokenKind::GreaterEq,
+    TokenKind::LeftBrace,
+];
+
 /// Parser for Iptscrae source code
 pub struct Parser {
     tokens: Vec<Token>,
@@ -72,33 +133,41 @@ impl Parser {
     }
 
     /// Parse tokens into a script
+    ///
+    /// A run of comment lines directly above a handler is kept as its
+    /// leading comments. Other comments are dropped.
     pub fn parse(&mut self) -> Result<Script, ParseError> {
         let mut handlers = Vec::new();
-
-        // Skip initial newlines
-        self.skip_newlines();
+        let mut comments = Vec::new();
 
         while !self.is_at_end() {
-            // Skip comments and newlines
-            if self.skip_ignorable() {
-                continue;
-            }
-
-            // Parse event handler
-            if self.check(&TokenKind::On) {
-                handlers.push(self.parse_event_handler()?);
-            } else if self.is_at_end() {
-                break;
-            } else {
-                let tok = self.current();
-                return Err(ParseError::UnexpectedToken {
-                    expected: "ON or end of file".to_string(),
-                    found: self.token_description(&tok.kind),
-                    pos: tok.pos,
-                });
+            match &self.current().kind {
+                TokenKind::Comment(text) => comments.push(text.clone()),
+                TokenKind::Newline => {
+                    // A blank line detaches the comments above it
+                    let prev = self.position.checked_sub(1).map(|i| &self.tokens[i].kind);
+                    if prev == Some(&TokenKind::Newline) {
+                        comments.clear();
+                    }
+                }
+                TokenKind::On => {
+                    let handler = self.parse_event_handler()?;
+                    handlers.push(handler.with_comments(std::mem::take(&mut comments)));
+                    // A comment after the closing brace belongs to neither handler
+                    if matches!(self.current().kind, TokenKind::Comment(_)) {
+                        self.advance();
+                    }
+                    continue;
+                }
+                kind => {
+                    return Err(ParseError::UnexpectedToken {
+                        expected: vec![TokenKind::On, TokenKind::Eof],
+                        found: kind.clone(),
+                        pos: self.current().pos,
+                    });
+                }
             }
-
-            self.skip_newlines();
+            self.advance();
         }
 
         Ok(Script::new(handlers))
@@ -107,15 +176,15 @@ impl Parser {
     /// Parse an event handler: ON eventname { block }
     fn parse_event_handler(&mut self) -> Result<EventHandler, ParseError> {
         let pos = self.current().pos;
-        self.consume(&TokenKind::On, "ON")?;
+        self.consume(&TokenKind::On)?;
 
         // Parse event name
         let event_name = if let TokenKind::Ident(name) = &self.current().kind {
             name.clone()
         } else {
             return Err(ParseError::UnexpectedToken {
-                expected: "event name".to_string(),
-                found: self.token_description(&self.current().kind),
+                expected: vec![TokenKind::Ident(String::new())],
+                found: self.current().kind.clone(),
                 pos: self.current().pos,
             });
         };
@@ -123,7 +192,7 @@ impl Parser {
 
         // Convert event name to EventType
         let event =
-            EventType::from_name(&event_name).ok_or_else(|| ParseError::InvalidEventName {
+            EventType::from_keyword(&event_name).ok_or_else(|| ParseError::InvalidEventName {
                 name: event_name.clone(),
                 pos,
             })?;
@@ -138,7 +207,7 @@ impl Parser {
 
     /// Parse a block: { statements }
     fn parse_block(&mut self) -> Result<Block, ParseError> {
-        self.consume(&TokenKind::LeftBrace, "{")?;
+        self.consume(&TokenKind::LeftBrace)?;
         self.skip_newlines();
 
         let mut statements = Vec::new();
@@ -154,7 +223,7 @@ impl Parser {
             self.skip_newlines();
         }
 
-        self.consume(&TokenKind::RightBrace, "}")?;
+        self.consume(&TokenKind::RightBrace)?;
         Ok(Block::new(statements))
     }
 
@@ -172,6 +241,11 @@ impl Parser {
             return self.parse_while_statement();
         }
 
+        // CASE statement
+        if self.at_block_keyword("CASE") {
+            return self.parse_case_statement();
+        }
+
         // BREAK statement
         if self.check(&TokenKind::Break) {
             self.advance();
@@ -207,7 +281,7 @@ impl Parser {
     /// Parse an IF statement
     fn parse_if_statement(&mut self) -> Result<Statement, ParseError> {
         let pos = self.current().pos;
-        self.consume(&TokenKind::If, "IF")?;
+        self.consume(&TokenKind::If)?;
 
         self.skip_newlines();
 
@@ -241,7 +315,7 @@ impl Parser {
     /// Parse a WHILE statement
     fn parse_while_statement(&mut self) -> Result<Statement, ParseError> {
         let pos = self.current().pos;
-        self.consume(&TokenKind::While, "WHILE")?;
+        self.consume(&TokenKind::While)?;
 
         self.skip_newlines();
 
@@ -259,6 +333,66 @@ impl Parser {
         })
     }
 
+    /// Parse a CASE statement: CASE { label { block } ... [DEFAULT { block }] }
+    fn parse_case_statement(&mut self) -> Result<Statement, ParseError> {
+        let pos = self.current().pos;
+        self.advance();
+
+        self.skip_newlines();
+        self.consume(&TokenKind::LeftBrace)?;
+
+        // Like IF, the value to match was pushed before CASE
+        let mut arms = Vec::new();
+        let mut default = None;
+        loop {
+            self.skip_ignorable();
+            if self.check(&TokenKind::RightBrace) || self.is_at_end() {
+                break;
+            }
+
+            if self.at_block_keyword("DEFAULT") && default.is_none() {
+                self.advance();
+                self.skip_newlines();
+                default = Some(self.parse_block()?);
+            } else {
+                let arm_pos = self.current().pos;
+                let label = self.parse_case_label()?;
+                self.skip_newlines();
+                arms.push(CaseArm::new(label, self.parse_block()?, arm_pos));
+            }
+        }
+
+        self.consume(&TokenKind::RightBrace)?;
+        Ok(Statement::Case { arms, default, pos })
+    }
+
+    /// Parse a CASE arm label: an integer (optionally negated) or string literal
+    fn parse_case_label(&mut self) -> Result<Value, ParseError> {
+        let negate = self.check(&TokenKind::Minus);
+        if negate {
+            self.advance();
+        }
+
+        let label = match &self.current().kind {
+            TokenKind::Integer(n) if negate => Value::Integer(n.wrapping_neg()),
+            TokenKind::Integer(n) => Value::Integer(*n),
+            TokenKind::String(s) if !negate => Value::String(s.clone()),
+            kind => {
+                let mut expected = vec![TokenKind::Integer(0)];
+                if !negate {
+                    expected.extend([TokenKind::String(String::new()), TokenKind::Minus]);
+                }
+                return Err(ParseError::UnexpectedToken {
+                    expected,
+                    found: kind.clone(),
+                    pos: self.current().pos,
+                });
+            }
+        };
+        self.advance();
+        Ok(label)
+    }
+
     /// Parse an expression
     fn parse_expression(&mut self) -> Result<Expr, ParseError> {
         self.parse_primary()
@@ -279,6 +413,16 @@ impl Parser {
                 })
             }
 
+            // Float literal
+            TokenKind::Float(x) => {
+                let value = *x;
+                self.advance();
+                Ok(Expr::Literal {
+                    value: Value::Float(value),
+                    pos,
+                })
+            }
+
             // String literal
             TokenKind::String(s) => {
                 let value = s.clone();
@@ -352,6 +496,13 @@ impl Parser {
                     pos,
                 })
             }
+            TokenKind::EqEq => {
+                self.advance();
+                Ok(Expr::BinOp {
+                    op: BinOp::Eq,
+                    pos,
+                })
+            }
             TokenKind::NotEquals => {
                 self.advance();
                 Ok(Expr::BinOp {
@@ -395,8 +546,8 @@ impl Parser {
             }
 
             _ => Err(ParseError::UnexpectedToken {
-                expected: "expression".to_string(),
-                found: self.token_description(&self.current().kind),
+                expected: EXPRESSION_START.to_vec(),
+                found: self.current().kind.clone(),
                 pos,
             }),
         }
@@ -411,18 +562,18 @@ impl Parser {
     }
 
     /// Consume a token of the expected kind
-    fn consume(&mut self, kind: &TokenKind, expected: &str) -> Result<(), ParseError> {
+    fn consume(&mut self, kind: &TokenKind) -> Result<(), ParseError> {
         if self.check(kind) {
             self.advance();
             Ok(())
         } else if self.is_at_end() {
             Err(ParseError::UnexpectedEof {
-                expected: expected.to_string(),
+                expected: vec![kind.clone()],
             })
         } else {
             Err(ParseError::UnexpectedToken {
-                expected: expected.to_string(),
-                found: self.token_description(&self.current().kind),
+                expected: vec![kind.clone()],
+                found: self.current().kind.clone(),
                 pos: self.current().pos,
             })
         }
@@ -446,6 +597,21 @@ impl Parser {
             || matches!(self.tokens[self.position].kind, TokenKind::Eof)
     }
 
+    /// Whether the current token is `word` (any case) with a block after it
+    ///
+    /// CASE and DEFAULT are only keywords in front of a brace, so scripts
+    /// written before CASE existed can keep using them as variable names.
+    fn at_block_keyword(&self, word: &str) -> bool {
+        let TokenKind::Ident(name) = &self.current().kind else {
+            return false;
+        };
+        name.eq_ignore_ascii_case(word)
+            && self.tokens[self.position + 1..]
+                .iter()
+                .find(|token| !matches!(token.kind, TokenKind::Newline))
+                .is_some_and(|token| token.kind == TokenKind::LeftBrace)
+    }
+
     /// Skip newlines
     fn skip_newlines(&mut self) {
         while !self.is_at_end() && matches!(self.current().kind, TokenKind::Newline) {
@@ -467,89 +633,6 @@ impl Parser {
         }
         skipped
     }
-
-    /// Get a description of a token for error messages
-    fn token_description(&self, kind: &TokenKind) -> String {
-        match kind {
-            TokenKind::Integer(n) => format!("integer {}", n),
-            TokenKind::String(s) => format!("string \"{}\"", s),
-            TokenKind::Ident(name) => format!("identifier '{}'", name),
-            TokenKind::On => "ON".to_string(),
-            TokenKind::If => "IF".to_string(),
-            TokenKind::Else => "ELSE".to_string(),
-            TokenKind::While => "WHILE".to_string(),
-            TokenKind::Do => "DO".to_string(),
-            TokenKind::Break => "BREAK".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Room => "ROOM".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::EndRoom => "ENDROOM".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Door => "DOOR".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::EndDoor => "ENDDOOR".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Spot => "SPOT".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::EndSpot => "ENDSPOT".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Script => "SCRIPT".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::EndScript => "ENDSCRIPT".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Id => "ID".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Name => "NAME".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Pict => "PICT".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Artist => "ARTIST".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Dest => "DEST".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Outline => "OUTLINE".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Picts => "PICTS".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::EndPicts => "ENDPICTS".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Picture => "PICTURE".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::EndPicture => "ENDPICTURE".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::TransColor => "TRANSCOLOR".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Private => "PRIVATE".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::NoPainting => "NOPAINTING".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::NoCyborgs => "NOCYBORGS".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::Hidden => "HIDDEN".to_string(),
-            #[cfg(feature = "room-script")]
-            TokenKind::NoGuests => "NOGUESTS".to_string(),
-            TokenKind::Plus => "+".to_string(),
-            TokenKind::Minus => "-".to_string(),
-            TokenKind::Star => "*".to_string(),
-            TokenKind::Slash => "/".to_string(),
-            TokenKind::Percent => "%".to_string(),
-            TokenKind::Ampersand => "&".to_string(),
-            TokenKind::Equals => "=".to_string(),
-            TokenKind::NotEquals => "!=".to_string(),
-            TokenKind::Less => "<".to_string(),
-            TokenKind::Greater => ">".to_string(),
-            TokenKind::LessEq => "<=".to_string(),
-            TokenKind::GreaterEq => ">=".to_string(),
-            TokenKind::LeftBrace => "{".to_string(),
-            TokenKind::RightBrace => "}".to_string(),
-            TokenKind::LeftParen => "(".to_string(),
-            TokenKind::RightParen => ")".to_string(),
-            TokenKind::Comma => ",".to_string(),
-            TokenKind::Comment(_) => "comment".to_string(),
-            TokenKind::Newline => "newline".to_string(),
-            TokenKind::Eof => "end of file".to_string(),
-        }
-    }
 }
 
 #[cfg(test)]
@@ -598,13 +681,14 @@ mod tests {
             ON SELECT {
                 42
                 "test"
+                -0.5
             }
         "#;
         let script = parse_source(source).unwrap();
         assert_eq!(script.handlers.len(), 1);
 
         let statements = &script.handlers[0].body.statements;
-        assert_eq!(statements.len(), 2);
+        assert_eq!(statements.len(), 3);
 
         if let Statement::Expr(Expr::Literal {
             value: Value::Integer(n),
@@ -625,6 +709,14 @@ mod tests {
         } else {
             panic!("Expected string literal");
         }
+
+        assert!(matches!(
+            &statements[2],
+            Statement::Expr(Expr::Literal {
+                value: Value::Float(x),
+                ..
+            }) if *x == -0.5
+        ));
     }
 
     #[test]
@@ -679,6 +771,64 @@ mod tests {
         assert!(statements.len() >= 2);
     }
 
+    #[test]
+    fn test_parse_case_statement() {
+        let source = r#"ON SELECT {
+            choice CASE {
+                1 { "one" SAY }
+                -2 { "minus two" SAY }
+                "three" { "three" SAY }
+                DEFAULT { "other" SAY }
+            }
+        }"#;
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+
+        let statements = &script.handlers[0].body.statements;
+        assert_eq!(statements.len(), 2);
+        let Statement::Case { arms, default, .. } = &statements[1] else {
+            panic!("expected CASE, got {:?}", statements[1]);
+        };
+        let labels: Vec<_> = arms.iter().map(|arm| arm.label.clone()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                Value::Integer(1),
+                Value::Integer(-2),
+                Value::String("three".to_string())
+            ]
+        );
+        assert!(default.is_some());
+
+        // Labels must be literals, and only one DEFAULT is allowed
+        for source in [
+            "ON SELECT { x CASE { y { } } }",
+            "ON SELECT { x CASE { DEFAULT { } DEFAULT { } } }",
+        ] {
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            assert!(matches!(
+                Parser::new(tokens).parse(),
+                Err(ParseError::UnexpectedToken { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_case_and_default_as_variables() {
+        let script = parse_source("ON SELECT { 5 default = default SAY }").unwrap();
+        let statements = &script.handlers[0].body.statements;
+        assert!(matches!(&statements[1], Statement::Assign { name, .. } if name == "default"));
+        assert!(matches!(
+            &statements[2],
+            Statement::Expr(Expr::Variable { name, .. }) if name == "default"
+        ));
+
+        let script = parse_source("ON SELECT { 1 case = case default CASE { 1 { } } }").unwrap();
+        let statements = &script.handlers[0].body.statements;
+        assert!(matches!(&statements[1], Statement::Assign { name, .. } if name == "case"));
+        assert!(matches!(statements.last(), Some(Statement::Case { .. })));
+    }
+
     #[test]
     fn test_parse_invalid_event() {
         let source = r#"
@@ -690,6 +840,32 @@ mod tests {
         assert!(matches!(result, Err(ParseError::InvalidEventName { .. })));
     }
 
+    #[test]
+    fn test_parse_error_display() {
+        let err = parse_source("ON ENTER { x CASE { y { } } }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected one of integer, string, - but found identifier 'y' at line 1, column 21"
+        );
+
+        let err = parse_source("ON 5").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::UnexpectedToken {
+                expected: vec![TokenKind::Ident(String::new())],
+                found: TokenKind::Integer(5),
+                pos: SourcePos::new(1, 4),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Expected identifier but found integer 5 at line 1, column 4"
+        );
+
+        let err = parse_source("ON ENTER").unwrap_err();
+        assert_eq!(err.to_string(), "Unexpected end of file, expected {");
+    }
+
     #[test]
     fn test_parse_unterminated_block() {
         let source = r#"
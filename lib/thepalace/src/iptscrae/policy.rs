@@ -0,0 +1,68 @@
+//! Deciding whether a script may run, and under which limits.
+//!
+//! Server and admin scripts always run unrestricted. Cyborg scripts need the
+//! server to allow cyborgs ([`ServerFlags::ALLOW_CYBORGS`]) and the room not
+//! to be a cyborg-free zone ([`RoomFlags::CYBORG_FREE_ZONE`]), and then run
+//! under [`ExecutionLimits::cyborg`].
+
+use crate::iptscrae::context::SecurityLevel;
+use crate::iptscrae::vm::ExecutionLimits;
+use crate::messages::flags::{RoomFlags, ServerFlags};
+
+/// Resolve the execution limits for a script, or `None` if it may not run.
+pub fn resolve_script_policy(
+    room_flags: RoomFlags,
+    server_flags: ServerFlags,
+    user_level: SecurityLevel,
+) -> Option<ExecutionLimits> {
+    match user_level {
+        SecurityLevel::Server | SecurityLevel::Admin => Some(ExecutionLimits::server()),
+        SecurityLevel::Cyborg => {
+            let allowed =
+                server_flags.contains(ServerFlags::ALLOW_CYBORGS) && room_flags.allows_cyborgs();
+            allowed.then(ExecutionLimits::cyborg)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cyborg_free_zone() {
+        let policy = resolve_script_policy(
+            RoomFlags::CYBORG_FREE_ZONE,
+            ServerFlags::ALLOW_CYBORGS,
+            SecurityLevel::Cyborg,
+        );
+        assert_eq!(policy, None);
+    }
+
+    #[test]
+    fn test_cyborgs_allowed() {
+        let policy = resolve_script_policy(
+            RoomFlags::empty(),
+            ServerFlags::ALLOW_CYBORGS,
+            SecurityLevel::Cyborg,
+        );
+        assert_eq!(policy, Some(ExecutionLimits::cyborg()));
+
+        // Not without the server flag
+        let policy = resolve_script_policy(
+            RoomFlags::empty(),
+            ServerFlags::empty(),
+            SecurityLevel::Cyborg,
+        );
+        assert_eq!(policy, None);
+    }
+
+    #[test]
+    fn test_server_scripts() {
+        for level in [SecurityLevel::Server, SecurityLevel::Admin] {
+            let policy =
+                resolve_script_policy(RoomFlags::CYBORG_FREE_ZONE, ServerFlags::empty(), level);
+            assert_eq!(policy, Some(ExecutionLimits::server()));
+        }
+    }
+}
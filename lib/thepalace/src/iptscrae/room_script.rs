@@ -31,7 +31,13 @@
 //! ENDROOM
 //! ```
 
+use std::fmt::Write;
+
+use crate::iptscrae::ast::write_string;
+use crate::iptscrae::events::EventType;
 use crate::iptscrae::Script;
+use crate::messages::{Hotspot, PictureRec};
+use crate::room::{default_spot_outline, geometry, next_hotspot_id};
 use crate::Point;
 
 /// Complete room declaration in a server script file.
@@ -57,6 +63,434 @@ pub struct RoomDecl {
     pub spots: Vec<SpotDecl>,
 }
 
+impl RoomDecl {
+    /// Serialize the room back to server script source.
+    ///
+    /// Parsing the output with [`RoomScriptParser`](crate::iptscrae::RoomScriptParser)
+    /// yields an equivalent declaration (scripts differ only in source
+    /// positions). The password is not written because the room script
+    /// syntax has no keyword for it.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        out.push_str("ROOM\n");
+        let _ = writeln!(out, "  ID {}", self.id);
+        write_string_field(&mut out, 1, "NAME", self.name.as_deref());
+        write_string_field(&mut out, 1, "PICT", self.pict.as_deref());
+        write_string_field(&mut out, 1, "ARTIST", self.artist.as_deref());
+        for (set, keyword) in [
+            (self.flags.private, "PRIVATE"),
+            (self.flags.no_painting, "NOPAINTING"),
+            (self.flags.no_cyborgs, "NOCYBORGS"),
+            (self.flags.hidden, "HIDDEN"),
+            (self.flags.no_guests, "NOGUESTS"),
+        ] {
+            if set {
+                let _ = writeln!(out, "  {}", keyword);
+            }
+        }
+        for picture in &self.pictures {
+            out.push_str(&indent_lines(&picture.to_source(), 1));
+        }
+        for door in &self.doors {
+            out.push_str(&indent_lines(&door.to_source(), 1));
+        }
+        for spot in &self.spots {
+            out.push_str(&indent_lines(&spot.to_source(), 1));
+        }
+        out.push_str("ENDROOM\n");
+        out
+    }
+
+    /// Estimate the size of the varBuf `convert_room` will build, in bytes.
+    ///
+    /// Counts every PString and record array, assuming each 4-byte aligned
+    /// array needs the full 3 bytes of padding, so the estimate is never
+    /// below the real `len_vars`. Cheap enough to run on every edit.
+    pub fn estimated_var_buf_size(&self) -> usize {
+        let mut size = [&self.name, &self.pict, &self.artist, &self.password]
+            .into_iter()
+            .map(|s| pstring_size(s.as_deref()))
+            .sum::<usize>();
+
+        size += self
+            .pictures
+            .iter()
+            .map(|pic| pstring_size(Some(&pic.name)))
+            .sum::<usize>();
+        size += aligned_array_size(self.pictures.len(), PictureRec::SIZE);
+
+        let hotspots = self
+            .doors
+            .iter()
+            .map(|door| (door.name.as_deref(), &door.outline, &door.picts))
+            .chain(
+                self.spots
+                    .iter()
+                    .map(|spot| (spot.name.as_deref(), &spot.outline, &spot.picts)),
+            );
+        for (name, outline, picts) in hotspots {
+            size += pstring_size(name);
+            size += aligned_array_size(outline.len(), POINT_SIZE);
+            size += aligned_array_size(picts.len(), STATE_REC_SIZE);
+        }
+        size += aligned_array_size(self.doors.len() + self.spots.len(), Hotspot::SIZE);
+
+        size
+    }
+
+    /// Check whether the room is sure to fit the protocol's varBuf limit.
+    ///
+    /// True guarantees `convert_room` won't fail with `VarBufTooLarge`; false
+    /// means it might, since the estimate rounds up.
+    pub fn fits_var_buf(&self) -> bool {
+        self.estimated_var_buf_size() <= i16::MAX as usize
+    }
+
+    /// Get the script of the door or spot with this ID.
+    pub fn hotspot_script(&self, spot_id: i16) -> Option<&Script> {
+        self.doors
+            .iter()
+            .find(|door| door.id == spot_id)
+            .map(|door| door.script.as_ref())
+            .or_else(|| {
+                self.spots
+                    .iter()
+                    .find(|spot| spot.id == spot_id)
+                    .map(|spot| spot.script.as_ref())
+            })
+            .flatten()
+    }
+
+    /// Get the script to fire `event` at on a door or spot.
+    ///
+    /// Returns `None` if the hotspot has no handler for the event, checked
+    /// against the script's event mask as clients do with
+    /// `script_event_mask`, so unhandled events skip the VM entirely.
+    pub fn hotspot_script_for(&self, spot_id: i16, event: EventType) -> Option<&Script> {
+        self.hotspot_script(spot_id)
+            .filter(|script| script.event_mask().contains(event.to_mask()))
+    }
+
+    /// Add a spot with a default outline, returning its ID.
+    ///
+    /// This is what a SpotNew request creates: the
+    /// [`default_spot_outline`] with no name, pictures or script. The ID
+    /// comes from [`next_hotspot_id`] over the room's doors and spots, as
+    /// the server allocates it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every positive hotspot ID is already taken.
+    pub fn add_default_hotspot(&mut self) -> i16 {
+        let used: Vec<i16> = self
+            .doors
+            .iter()
+            .map(|door| door.id)
+            .chain(self.spots.iter().map(|spot| spot.id))
+            .collect();
+        let id = next_hotspot_id(&used).expect("room has a free hotspot ID");
+
+        self.spots.push(SpotDecl {
+            id,
+            name: None,
+            outline: default_spot_outline().to_vec(),
+            picts: vec![],
+            script: None,
+        });
+        id
+    }
+
+    /// Remove the door or spot with this ID, returning whether it existed.
+    pub fn remove_hotspot(&mut self, spot_id: i16) -> bool {
+        let count = self.doors.len() + self.spots.len();
+        self.doors.retain(|door| door.id != spot_id);
+        self.spots.retain(|spot| spot.id != spot_id);
+        self.doors.len() + self.spots.len() != count
+    }
+
+    /// Move a door or spot to `loc`, returning whether it exists.
+    ///
+    /// A hotspot's location is the first point of its outline, as in
+    /// `convert_room`, so the whole outline is shifted to put that point at
+    /// `loc`.
+    pub fn move_hotspot(&mut self, spot_id: i16, loc: Point) -> bool {
+        let outline = match self.doors.iter_mut().find(|door| door.id == spot_id) {
+            Some(door) => &mut door.outline,
+            None => match self.spots.iter_mut().find(|spot| spot.id == spot_id) {
+                Some(spot) => &mut spot.outline,
+                None => return false,
+            },
+        };
+        if let Some(&first) = outline.first() {
+            let offset = loc - first;
+            for point in outline.iter_mut() {
+                *point = *point + offset;
+            }
+        }
+        true
+    }
+}
+
+/// A [`RoomDecl`] that knows whether it changed since it was last saved.
+///
+/// Mutating methods mark the room dirty and [`mark_saved`](Self::mark_saved)
+/// clears it, so a server can keep rooms in memory and write back only the
+/// changed ones in batches. Taking [`room_mut`](Self::room_mut) counts as a
+/// change, since the wrapper can't see what is done with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirtyRoom {
+    room: RoomDecl,
+    dirty: bool,
+}
+
+impl DirtyRoom {
+    /// Wrap a room as loaded from storage, i.e. clean.
+    pub fn new(room: RoomDecl) -> Self {
+        Self { room, dirty: false }
+    }
+
+    /// Get the room.
+    pub fn room(&self) -> &RoomDecl {
+        &self.room
+    }
+
+    /// Get the room for arbitrary edits, marking it dirty.
+    pub fn room_mut(&mut self) -> &mut RoomDecl {
+        self.dirty = true;
+        &mut self.room
+    }
+
+    /// Whether the room changed since it was created or last saved.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Record that the room's current state has been written back.
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+    }
+
+    /// Unwrap the room, dropping the dirty flag.
+    pub fn into_inner(self) -> RoomDecl {
+        self.room
+    }
+
+    /// See [`RoomDecl::add_default_hotspot`].
+    pub fn add_default_hotspot(&mut self) -> i16 {
+        self.dirty = true;
+        self.room.add_default_hotspot()
+    }
+
+    /// See [`RoomDecl::remove_hotspot`]; only a removal marks the room dirty.
+    pub fn remove_hotspot(&mut self, spot_id: i16) -> bool {
+        let removed = self.room.remove_hotspot(spot_id);
+        self.dirty |= removed;
+        removed
+    }
+
+    /// See [`RoomDecl::move_hotspot`]; an unknown ID leaves the room clean.
+    pub fn move_hotspot(&mut self, spot_id: i16, loc: Point) -> bool {
+        let moved = self.room.move_hotspot(spot_id, loc);
+        self.dirty |= moved;
+        moved
+    }
+
+    /// Replace the room's flags, marking it dirty if they differ.
+    pub fn set_flags(&mut self, flags: RoomFlags) {
+        if self.room.flags != flags {
+            self.room.flags = flags;
+            self.dirty = true;
+        }
+    }
+}
+
+/// Wire size of a hotspot state record
+const STATE_REC_SIZE: usize = 6;
+/// Wire size of an outline point
+const POINT_SIZE: usize = 4;
+
+/// Bytes taken by an optional PString (length byte plus text)
+fn pstring_size(s: Option<&str>) -> usize {
+    s.map_or(0, |s| 1 + s.len())
+}
+
+/// Upper bound on the bytes taken by a 4-byte aligned record array
+///
+/// Empty arrays aren't written at all, so they cost nothing.
+fn aligned_array_size(count: usize, record_size: usize) -> usize {
+    if count == 0 {
+        0
+    } else {
+        3 + count * record_size
+    }
+}
+
+/// Problem found in a door or spot outline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlineIssue {
+    /// Outline has 1 or 2 points and cannot enclose an area
+    TooFewPoints { count: usize },
+    /// Two non-adjacent edges cross (edge `i` starts at point `i`)
+    SelfIntersection {
+        first_edge: usize,
+        second_edge: usize,
+    },
+}
+
+impl OutlineIssue {
+    /// Whether the outline is unusable (as opposed to merely suspicious)
+    pub const fn is_error(&self) -> bool {
+        matches!(self, OutlineIssue::TooFewPoints { .. })
+    }
+}
+
+impl std::fmt::Display for OutlineIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutlineIssue::TooFewPoints { count } => {
+                write!(f, "Outline has {} points (need at least 3)", count)
+            }
+            OutlineIssue::SelfIntersection {
+                first_edge,
+                second_edge,
+            } => write!(
+                f,
+                "Outline edges {} and {} intersect",
+                first_edge, second_edge
+            ),
+        }
+    }
+}
+
+/// Check an outline for degenerate or self-intersecting polygons.
+///
+/// An empty outline is treated as absent and produces no issues.
+fn validate_outline(outline: &[Point]) -> Vec<OutlineIssue> {
+    let mut issues = Vec::new();
+    if (1..3).contains(&outline.len()) {
+        issues.push(OutlineIssue::TooFewPoints {
+            count: outline.len(),
+        });
+    }
+    if let Some((first_edge, second_edge)) = geometry::find_self_intersection(outline) {
+        issues.push(OutlineIssue::SelfIntersection {
+            first_edge,
+            second_edge,
+        });
+    }
+    issues
+}
+
+impl PictureDecl {
+    /// Serialize as a `PICTURE ... ENDPICTURE` block.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        out.push_str("PICTURE\n");
+        let _ = writeln!(out, "  ID {}", self.id);
+        write_string_field(&mut out, 1, "NAME", Some(&self.name));
+        if let Some(trans_color) = self.trans_color {
+            let _ = writeln!(out, "  TRANSCOLOR {}", trans_color);
+        }
+        out.push_str("ENDPICTURE\n");
+        out
+    }
+}
+
+impl DoorDecl {
+    /// Check the door outline, returning any problems found.
+    pub fn validate(&self) -> Vec<OutlineIssue> {
+        validate_outline(&self.outline)
+    }
+
+    /// Serialize as a `DOOR ... ENDDOOR` block.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        out.push_str("DOOR\n");
+        let _ = writeln!(out, "  ID {}", self.id);
+        let _ = writeln!(out, "  DEST {}", self.dest);
+        write_string_field(&mut out, 1, "NAME", self.name.as_deref());
+        write_hotspot_body(&mut out, &self.outline, &self.picts, self.script.as_ref());
+        out.push_str("ENDDOOR\n");
+        out
+    }
+}
+
+impl SpotDecl {
+    /// Check the spot outline, returning any problems found.
+    pub fn validate(&self) -> Vec<OutlineIssue> {
+        validate_outline(&self.outline)
+    }
+
+    /// Serialize as a `SPOT ... ENDSPOT` block.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        out.push_str("SPOT\n");
+        let _ = writeln!(out, "  ID {}", self.id);
+        write_string_field(&mut out, 1, "NAME", self.name.as_deref());
+        write_hotspot_body(&mut out, &self.outline, &self.picts, self.script.as_ref());
+        out.push_str("ENDSPOT\n");
+        out
+    }
+}
+
+/// Write `KEYWORD "value"` if the value is present
+fn write_string_field(out: &mut String, indent: usize, keyword: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        out.push_str(&"  ".repeat(indent));
+        out.push_str(keyword);
+        out.push(' ');
+        write_string(out, value);
+        out.push('\n');
+    }
+}
+
+/// Write the OUTLINE, PICTS and SCRIPT sections shared by doors and spots
+fn write_hotspot_body(
+    out: &mut String,
+    outline: &[Point],
+    picts: &[StateDecl],
+    script: Option<&Script>,
+) {
+    // Empty sections are omitted; the parser treats them as absent
+    if !outline.is_empty() {
+        out.push_str("  OUTLINE");
+        for point in outline {
+            let _ = write!(out, " {},{}", point.h, point.v);
+        }
+        out.push('\n');
+    }
+    if !picts.is_empty() {
+        out.push_str("  PICTS\n");
+        for state in picts {
+            let _ = writeln!(
+                out,
+                "    {},{},{}",
+                state.pic_id, state.x_offset, state.y_offset
+            );
+        }
+        out.push_str("  ENDPICTS\n");
+    }
+    if let Some(script) = script {
+        out.push_str("  SCRIPT\n");
+        out.push_str(&indent_lines(&script.to_source(), 2));
+        out.push_str("  ENDSCRIPT\n");
+    }
+}
+
+/// Indent every non-empty line by `indent` levels of two spaces
+fn indent_lines(source: &str, indent: usize) -> String {
+    let prefix = "  ".repeat(indent);
+    let mut out = String::with_capacity(source.len());
+    for line in source.lines() {
+        if !line.is_empty() {
+            out.push_str(&prefix);
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
 /// Room flags that can be set in the room declaration.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct RoomFlags {
@@ -132,6 +566,7 @@ pub struct StateDecl {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::room::{ROOM_HEIGHT, ROOM_WIDTH};
 
     #[test]
     fn test_room_decl_creation() {
@@ -151,6 +586,76 @@ mod tests {
         assert_eq!(room.name, Some("Test Room".to_string()));
     }
 
+    #[test]
+    fn test_room_to_source_roundtrip() {
+        use crate::iptscrae::RoomScriptParser;
+
+        let source = r#"
+ROOM
+  ID 100
+  NAME "Entrance \"Hall\""
+  PICT "entrance.gif"
+  PRIVATE
+  NOGUESTS
+  PICTURE
+    ID 1
+    NAME "overlay.gif"
+    TRANSCOLOR 255
+  ENDPICTURE
+  DOOR
+    ID 1
+    DEST 200
+    NAME "Exit"
+    OUTLINE 10,10 50,10 50,200 10,200
+    PICTS
+      100,0,0
+      101,-4,6
+    ENDPICTS
+    SCRIPT
+      ON SELECT { "Leaving" SAY 0 count = count 0 == IF { "zero" SAY } }
+      ON LOCK { "locked" LOCALMSG }
+    ENDSCRIPT
+  ENDDOOR
+  SPOT
+    ID 2
+    OUTLINE -10,20 30,-40
+  ENDSPOT
+ENDROOM
+"#;
+        let room = RoomScriptParser::new(source)
+            .unwrap()
+            .parse()
+            .unwrap()
+            .remove(0);
+        let emitted = room.to_source();
+        let reparsed = RoomScriptParser::new(&emitted)
+            .unwrap()
+            .parse()
+            .unwrap()
+            .remove(0);
+
+        assert_eq!(reparsed.id, room.id);
+        assert_eq!(reparsed.name.as_deref(), Some("Entrance \"Hall\""));
+        assert_eq!(reparsed.pict, room.pict);
+        assert_eq!(reparsed.flags, room.flags);
+        assert_eq!(reparsed.pictures, room.pictures);
+        assert_eq!(reparsed.spots, room.spots);
+
+        let (door, original) = (&reparsed.doors[0], &room.doors[0]);
+        assert_eq!(door.dest, 200);
+        assert_eq!(door.outline, original.outline);
+        assert_eq!(door.picts, original.picts);
+        let script = door.script.as_ref().unwrap();
+        assert_eq!(script.handlers.len(), 2);
+        // Scripts only differ by source position, which to_source ignores
+        assert_eq!(
+            script.to_source(),
+            original.script.as_ref().unwrap().to_source()
+        );
+        assert!(emitted.contains("count 0 == IF"));
+        assert_eq!(reparsed.to_source(), emitted);
+    }
+
     #[test]
     fn test_room_flags() {
         let flags = RoomFlags {
@@ -207,6 +712,50 @@ mod tests {
         assert_eq!(spot.outline.len(), 4);
     }
 
+    #[test]
+    fn test_validate_outline() {
+        let mut spot = SpotDecl {
+            id: 3,
+            name: None,
+            outline: vec![
+                Point::new(0, 0),
+                Point::new(10, 0),
+                Point::new(10, 10),
+                Point::new(0, 10),
+            ],
+            picts: vec![],
+            script: None,
+        };
+        assert_eq!(spot.validate(), vec![]);
+
+        spot.outline = vec![Point::new(0, 0), Point::new(10, 10)];
+        assert_eq!(
+            spot.validate(),
+            vec![OutlineIssue::TooFewPoints { count: 2 }]
+        );
+        assert!(spot.validate()[0].is_error());
+
+        // Bowtie: edges 0 and 2 cross in the middle
+        spot.outline = vec![
+            Point::new(0, 0),
+            Point::new(10, 10),
+            Point::new(10, 0),
+            Point::new(0, 10),
+        ];
+        let issues = spot.validate();
+        assert_eq!(
+            issues,
+            vec![OutlineIssue::SelfIntersection {
+                first_edge: 0,
+                second_edge: 2
+            }]
+        );
+        assert!(!issues[0].is_error());
+
+        spot.outline.clear();
+        assert_eq!(spot.validate(), vec![]);
+    }
+
     #[test]
     fn test_state_decl() {
         let state = StateDecl {
@@ -232,4 +781,138 @@ mod tests {
         assert_eq!(pic.name, "overlay.gif");
         assert_eq!(pic.trans_color, Some(255));
     }
+
+    #[test]
+    fn test_add_default_hotspot() {
+        let mut room = RoomDecl {
+            id: 1,
+            name: None,
+            pict: None,
+            artist: None,
+            password: None,
+            flags: RoomFlags::default(),
+            pictures: vec![],
+            doors: vec![DoorDecl {
+                id: 5,
+                dest: 2,
+                name: None,
+                outline: vec![],
+                picts: vec![],
+                script: None,
+            }],
+            spots: vec![],
+        };
+
+        let first = room.add_default_hotspot();
+        let second = room.add_default_hotspot();
+        assert_eq!((first, second), (6, 7));
+        assert_eq!(room.spots.len(), 2);
+        for spot in &room.spots {
+            assert_eq!(spot.outline.len(), 4);
+            assert!(spot.validate().is_empty());
+            assert!(spot
+                .outline
+                .iter()
+                .all(|p| (0..ROOM_WIDTH).contains(&p.h) && (0..ROOM_HEIGHT).contains(&p.v)));
+        }
+
+        // Reuses a free ID once the top one is taken
+        room.spots[1].id = i16::MAX;
+        assert_eq!(room.add_default_hotspot(), 1);
+    }
+
+    #[test]
+    fn test_dirty_room() {
+        let spot = SpotDecl {
+            id: 3,
+            name: None,
+            outline: vec![Point::new(10, 10), Point::new(30, 10), Point::new(30, 40)],
+            picts: vec![],
+            script: None,
+        };
+        let mut room = DirtyRoom::new(RoomDecl {
+            id: 1,
+            name: None,
+            pict: None,
+            artist: None,
+            password: None,
+            flags: RoomFlags::default(),
+            pictures: vec![],
+            doors: vec![],
+            spots: vec![spot],
+        });
+        assert!(!room.is_dirty());
+
+        // Misses and no-op edits leave the room clean
+        assert!(!room.remove_hotspot(9));
+        assert!(!room.move_hotspot(9, Point::new(0, 0)));
+        room.set_flags(RoomFlags::default());
+        assert!(!room.is_dirty());
+
+        assert!(room.move_hotspot(3, Point::new(100, 200)));
+        assert!(room.is_dirty());
+        assert_eq!(
+            room.room().spots[0].outline,
+            [
+                Point::new(100, 200),
+                Point::new(120, 200),
+                Point::new(120, 230)
+            ]
+        );
+        room.mark_saved();
+        assert!(!room.is_dirty());
+
+        let id = room.add_default_hotspot();
+        assert!(room.is_dirty());
+        room.mark_saved();
+        assert!(room.remove_hotspot(id));
+        assert!(room.is_dirty());
+        room.mark_saved();
+
+        room.set_flags(RoomFlags {
+            hidden: true,
+            ..RoomFlags::default()
+        });
+        assert!(room.is_dirty());
+        room.mark_saved();
+
+        room.room_mut().name = Some("Lobby".to_string());
+        assert!(room.is_dirty());
+        assert_eq!(room.into_inner().name.as_deref(), Some("Lobby"));
+    }
+
+    #[test]
+    fn test_hotspot_script_for_event() {
+        use crate::iptscrae::RoomScriptParser;
+
+        let source = r#"
+ROOM
+  ID 1
+  DOOR
+    ID 1
+    DEST 2
+  ENDDOOR
+  SPOT
+    ID 2
+    SCRIPT
+      ON SELECT { "clicked" SAY }
+    ENDSCRIPT
+  ENDSPOT
+ENDROOM
+"#;
+        let room = RoomScriptParser::new(source)
+            .unwrap()
+            .parse()
+            .unwrap()
+            .remove(0);
+
+        let script = room.hotspot_script(2).unwrap();
+        assert_eq!(room.hotspot_script_for(2, EventType::Select), Some(script));
+        assert_eq!(room.hotspot_script_for(2, EventType::Enter), None);
+
+        // Doors without scripts and unknown IDs resolve to nothing
+        assert_eq!(room.hotspot_script(1), None);
+        assert_eq!(room.hotspot_script_for(1, EventType::Select), None);
+        assert_eq!(room.hotspot_script(3), None);
+    }
 }
@@ -8,7 +8,7 @@
 
 use bytes::{BufMut, Bytes, BytesMut};
 
-use crate::iptscrae::{EventMask, RoomDecl, Script};
+use crate::iptscrae::{EventMask, OutlineIssue, RoomDecl, Script};
 use crate::messages::room::{Hotspot, PictureRec, RoomRec};
 use crate::room::{HotspotState, HotspotType};
 use crate::Point;
@@ -28,6 +28,9 @@ pub enum ConversionError {
     /// Too many points in outline (max i16::MAX)
     TooManyPoints { hotspot_id: i16, count: usize },
 
+    /// Outline has fewer than 3 points and cannot enclose an area
+    DegenerateOutline { hotspot_id: i16, count: usize },
+
     /// Too many states (max i16::MAX)
     TooManyStates { hotspot_id: i16, count: usize },
 
@@ -57,6 +60,13 @@ impl std::fmt::Display for ConversionError {
                     hotspot_id, count
                 )
             }
+            ConversionError::DegenerateOutline { hotspot_id, count } => {
+                write!(
+                    f,
+                    "Outline of hotspot {} has {} points (need at least 3)",
+                    hotspot_id, count
+                )
+            }
             ConversionError::TooManyStates { hotspot_id, count } => {
                 write!(
                     f,
@@ -199,7 +209,7 @@ impl VarBufBuilder {
         self.buf.put_i16(hotspot.group_id);
         self.buf.put_i16(hotspot.nbr_scripts);
         self.buf.put_i16(hotspot.script_rec_ofst);
-        self.buf.put_i16(hotspot.state.as_i16());
+        self.buf.put_i16(hotspot.state);
         self.buf.put_i16(hotspot.nbr_states);
         self.buf.put_i16(hotspot.state_rec_ofst);
         self.buf.put_i16(hotspot.name_ofst);
@@ -281,13 +291,7 @@ fn convert_flags(flags: &crate::iptscrae::RoomFlags) -> crate::messages::flags::
 
 /// Extract event mask from a script by collecting all event types.
 fn extract_event_mask(script: &Script) -> EventMask {
-    let mut mask = EventMask::empty();
-
-    for handler in &script.handlers {
-        mask |= handler.event.to_mask();
-    }
-
-    mask
+    script.event_mask()
 }
 
 /// Serialize a script back to Iptscrae source text.
@@ -399,6 +403,9 @@ pub fn convert_room(room: &RoomDecl) -> Result<RoomRec, ConversionError> {
 }
 
 /// Convert a DoorDecl to a Hotspot.
+///
+/// Outlines with 1 or 2 points are rejected; self-intersections are only
+/// reported by [`DoorDecl::validate`](crate::iptscrae::DoorDecl::validate).
 fn convert_door(
     door: &crate::iptscrae::DoorDecl,
     var_buf: &mut VarBufBuilder,
@@ -410,6 +417,12 @@ fn convert_door(
             count: door.outline.len(),
         });
     }
+    if door.validate().iter().any(OutlineIssue::is_error) {
+        return Err(ConversionError::DegenerateOutline {
+            hotspot_id: door.id,
+            count: door.outline.len(),
+        });
+    }
     if door.picts.len() > i16::MAX as usize {
         return Err(ConversionError::TooManyStates {
             hotspot_id: door.id,
@@ -462,7 +475,7 @@ fn convert_door(
         group_id: 0,
         nbr_scripts,
         script_rec_ofst,
-        state: HotspotState::Unlocked,
+        state: HotspotState::Unlocked.as_i16(),
         nbr_states: door.picts.len() as i16,
         state_rec_ofst,
         name_ofst,
@@ -471,6 +484,9 @@ fn convert_door(
 }
 
 /// Convert a SpotDecl to a Hotspot.
+///
+/// Outlines with 1 or 2 points are rejected; self-intersections are only
+/// reported by [`SpotDecl::validate`](crate::iptscrae::SpotDecl::validate).
 fn convert_spot(
     spot: &crate::iptscrae::SpotDecl,
     var_buf: &mut VarBufBuilder,
@@ -482,6 +498,12 @@ fn convert_spot(
             count: spot.outline.len(),
         });
     }
+    if spot.validate().iter().any(OutlineIssue::is_error) {
+        return Err(ConversionError::DegenerateOutline {
+            hotspot_id: spot.id,
+            count: spot.outline.len(),
+        });
+    }
     if spot.picts.len() > i16::MAX as usize {
         return Err(ConversionError::TooManyStates {
             hotspot_id: spot.id,
@@ -534,7 +556,7 @@ fn convert_spot(
         group_id: 0,
         nbr_scripts,
         script_rec_ofst,
-        state: HotspotState::Unlocked,
+        state: HotspotState::Unlocked.as_i16(),
         nbr_states: spot.picts.len() as i16,
         state_rec_ofst,
         name_ofst,
@@ -847,7 +869,11 @@ mod tests {
                 id: 1,
                 dest: 100,
                 name: Some("Door".to_string()),
-                outline: vec![Point { h: 0, v: 0 }, Point { h: 10, v: 10 }],
+                outline: vec![
+                    Point { h: 0, v: 0 },
+                    Point { h: 10, v: 0 },
+                    Point { h: 10, v: 10 },
+                ],
                 picts: vec![StateDecl {
                     pic_id: 50,
                     x_offset: 5,
@@ -858,7 +884,11 @@ mod tests {
             spots: vec![SpotDecl {
                 id: 2,
                 name: Some("Spot".to_string()),
-                outline: vec![Point { h: 20, v: 20 }, Point { h: 30, v: 30 }],
+                outline: vec![
+                    Point { h: 20, v: 20 },
+                    Point { h: 30, v: 20 },
+                    Point { h: 30, v: 30 },
+                ],
                 picts: vec![],
                 script: None,
             }],
@@ -883,6 +913,98 @@ mod tests {
         assert_eq!(result.password().unwrap(), "secret");
     }
 
+    #[test]
+    fn test_estimated_var_buf_size_is_upper_bound() {
+        use crate::iptscrae::RoomScriptParser;
+
+        let sources = [
+            "ROOM ID 1 ENDROOM",
+            r#"ROOM ID 2 NAME "Odd" PICT "a.gif" ENDROOM"#,
+            r#"ROOM
+                ID 3
+                NAME "Lobby"
+                PICTURE ID 1 NAME "x.gif" ENDPICTURE
+                PICTURE ID 2 NAME "longer.gif" TRANSCOLOR 3 ENDPICTURE
+                DOOR ID 1 DEST 4 NAME "Out" OUTLINE 0,0 10,0 10,10 PICTS 5,1,1 ENDPICTS ENDDOOR
+                SPOT ID 2 OUTLINE 0,0 9,0 9,9 5,12 ENDSPOT
+                SPOT ID 3 NAME "Sign" PICTS 6,0,0 7,2,2 ENDPICTS ENDSPOT
+            ENDROOM"#,
+        ];
+        for source in sources {
+            let mut rooms = RoomScriptParser::new(source).unwrap().parse().unwrap();
+            let room = rooms.remove(0);
+            let estimate = room.estimated_var_buf_size();
+            let actual = convert_room(&room).unwrap().len_vars as usize;
+            assert!(estimate >= actual, "underestimate for {}", source);
+            // Slack is at most the assumed alignment padding
+            assert!(estimate - actual <= 3 * 6, "{} vs {}", estimate, actual);
+            assert!(room.fits_var_buf());
+        }
+    }
+
+    #[test]
+    fn test_estimated_var_buf_size_flags_overflow() {
+        use crate::iptscrae::{RoomDecl, SpotDecl};
+
+        let spot = |id| SpotDecl {
+            id,
+            name: Some("s".repeat(200)),
+            outline: vec![],
+            picts: vec![],
+            script: None,
+        };
+        let room = RoomDecl {
+            id: 1,
+            name: None,
+            pict: None,
+            artist: None,
+            password: None,
+            flags: AstRoomFlags::default(),
+            pictures: vec![],
+            doors: vec![],
+            spots: (0..200).map(spot).collect(),
+        };
+
+        assert!(!room.fits_var_buf());
+        assert!(matches!(
+            convert_room(&room),
+            Err(ConversionError::VarBufTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_convert_rejects_degenerate_outline() {
+        use crate::iptscrae::{RoomDecl, SpotDecl};
+
+        let spot = SpotDecl {
+            id: 7,
+            name: None,
+            outline: vec![Point { h: 0, v: 0 }, Point { h: 10, v: 10 }],
+            picts: vec![],
+            script: None,
+        };
+        let room = RoomDecl {
+            id: 1,
+            name: None,
+            pict: None,
+            artist: None,
+            password: None,
+            flags: AstRoomFlags::default(),
+            pictures: vec![],
+            doors: vec![],
+            spots: vec![spot],
+        };
+
+        let result = convert_room(&room);
+        assert!(matches!(
+            result,
+            Err(ConversionError::DegenerateOutline {
+                hotspot_id: 7,
+                count: 2
+            })
+        ));
+    }
+
     #[test]
     fn test_extract_event_mask_empty() {
         let script = Script { handlers: vec![] };
@@ -900,16 +1022,19 @@ mod tests {
                     event: EventType::Select,
                     body: Block { statements: vec![] },
                     pos: SourcePos { line: 1, column: 1 },
+                    comments: vec![],
                 },
                 EventHandler {
                     event: EventType::Enter,
                     body: Block { statements: vec![] },
                     pos: SourcePos { line: 2, column: 1 },
+                    comments: vec![],
                 },
                 EventHandler {
                     event: EventType::Leave,
                     body: Block { statements: vec![] },
                     pos: SourcePos { line: 3, column: 1 },
+                    comments: vec![],
                 },
             ],
         };
@@ -3,11 +3,57 @@
 //! This parser handles the meta-syntax for defining rooms, doors, and spots.
 
 use crate::iptscrae::{
-    DoorDecl, LexError, Lexer, ParseError, Parser, PictureDecl, RoomDecl, RoomFlags, Script,
-    SourcePos, SpotDecl, StateDecl, Token, TokenKind,
+    DoorDecl, Lexer, ParseError, Parser, PictureDecl, RoomDecl, RoomFlags, Script, SpotDecl,
+    StateDecl, Token, TokenKind,
 };
 use crate::Point;
 
+/// Tokens allowed inside ROOM ... ENDROOM
+const ROOM_ITEMS: &[TokenKind] = &[
+    TokenKind::Id,
+    TokenKind::Name,
+    TokenKind::Pict,
+    TokenKind::Artist,
+    TokenKind::Private,
+    TokenKind::NoPainting,
+    TokenKind::NoCyborgs,
+    TokenKind::Hidden,
+    TokenKind::NoGuests,
+    TokenKind::Picture,
+    TokenKind::Door,
+    TokenKind::Spot,
+    TokenKind::EndRoom,
+];
+
+/// Tokens allowed inside PICTURE ... ENDPICTURE
+const PICTURE_ITEMS: &[TokenKind] = &[
+    TokenKind::Id,
+    TokenKind::Name,
+    TokenKind::TransColor,
+    TokenKind::EndPicture,
+];
+
+/// Tokens allowed inside DOOR ... ENDDOOR
+const DOOR_ITEMS: &[TokenKind] = &[
+    TokenKind::Id,
+    TokenKind::Dest,
+    TokenKind::Name,
+    TokenKind::Outline,
+    TokenKind::Picts,
+    TokenKind::Script,
+    TokenKind::EndDoor,
+];
+
+/// Tokens allowed inside SPOT ... ENDSPOT
+const SPOT_ITEMS: &[TokenKind] = &[
+    TokenKind::Id,
+    TokenKind::Name,
+    TokenKind::Outline,
+    TokenKind::Picts,
+    TokenKind::Script,
+    TokenKind::EndSpot,
+];
+
 /// Parser for room script files (e.g., Mansion.ipt).
 pub struct RoomScriptParser {
     tokens: Vec<Token>,
@@ -21,20 +67,7 @@ impl RoomScriptParser {
         let mut tokens = Vec::new();
 
         loop {
-            let token = lexer.next_token().map_err(|e| {
-                // Convert LexError to ParseError
-                ParseError::UnexpectedToken {
-                    expected: "valid token".to_string(),
-                    found: format!("lexer error: {:?}", e),
-                    pos: match e {
-                        LexError::UnterminatedString { line, column } => SourcePos { line, column },
-                        LexError::InvalidCharacter { line, column, .. } => {
-                            SourcePos { line, column }
-                        }
-                        LexError::InvalidNumber { line, column, .. } => SourcePos { line, column },
-                    },
-                }
-            })?;
+            let token = lexer.next_token()?;
             let is_eof = matches!(token.kind, TokenKind::Eof);
             tokens.push(token);
             if is_eof {
@@ -66,10 +99,7 @@ impl RoomScriptParser {
             if matches!(self.current().kind, TokenKind::Room) {
                 rooms.push(self.parse_room()?);
             } else {
-                return Err(self.error(format!(
-                    "Expected ROOM keyword, found {}",
-                    self.token_description(&self.current().kind)
-                )));
+                return Err(self.unexpected(&[TokenKind::Room]));
             }
 
             self.skip_newlines();
@@ -78,6 +108,59 @@ impl RoomScriptParser {
         Ok(rooms)
     }
 
+    /// Parse every room declaration, collecting errors instead of stopping.
+    ///
+    /// When a room fails to parse, the error is recorded and parsing resumes
+    /// at the next `ROOM` (after skipping to the failed room's `ENDROOM`), so
+    /// tooling can report every problem in a file in one pass. Use
+    /// [`parse`](Self::parse) to stop at the first error.
+    pub fn parse_all(&mut self) -> (Vec<RoomDecl>, Vec<ParseError>) {
+        let mut rooms = Vec::new();
+        let mut errors = Vec::new();
+
+        self.skip_newlines();
+
+        while !self.is_at_end() {
+            if matches!(self.current().kind, TokenKind::Room) {
+                match self.parse_room() {
+                    Ok(room) => rooms.push(room),
+                    Err(err) => {
+                        errors.push(err);
+                        self.recover_to_room_boundary();
+                    }
+                }
+            } else {
+                errors.push(self.unexpected(&[TokenKind::Room]));
+                self.advance();
+                self.recover_to_room_boundary();
+            }
+
+            self.skip_newlines();
+        }
+
+        (rooms, errors)
+    }
+
+    /// Skip past the current room so parsing can resume at the next one.
+    ///
+    /// Stops after an `ENDROOM`, or before a `ROOM` if the closing keyword is
+    /// missing. Does nothing if the error was raised after `ENDROOM` was consumed.
+    fn recover_to_room_boundary(&mut self) {
+        if self.pos > 0 && matches!(self.tokens[self.pos - 1].kind, TokenKind::EndRoom) {
+            return;
+        }
+        while !self.is_at_end() {
+            match self.current().kind {
+                TokenKind::EndRoom => {
+                    self.advance();
+                    return;
+                }
+                TokenKind::Room => return,
+                _ => self.advance(),
+            }
+        }
+    }
+
     /// Parse a single ROOM ... ENDROOM block.
     fn parse_room(&mut self) -> Result<RoomDecl, ParseError> {
         self.expect(TokenKind::Room)?;
@@ -159,17 +242,14 @@ impl RoomScriptParser {
                     self.advance();
                 }
                 _ => {
-                    return Err(self.error(format!(
-                        "Unexpected token in room block: {}",
-                        self.token_description(&self.current().kind)
-                    )));
+                    return Err(self.unexpected(ROOM_ITEMS));
                 }
             }
         }
 
         self.expect(TokenKind::EndRoom)?;
 
-        let id = id.ok_or_else(|| self.error("Room must have an ID".to_string()))?;
+        let id = id.ok_or_else(|| self.invalid("Room must have an ID".to_string()))?;
 
         Ok(RoomDecl {
             id,
@@ -216,18 +296,15 @@ impl RoomScriptParser {
                     self.advance();
                 }
                 _ => {
-                    return Err(self.error(format!(
-                        "Unexpected token in PICTURE block: {}",
-                        self.token_description(&self.current().kind)
-                    )));
+                    return Err(self.unexpected(PICTURE_ITEMS));
                 }
             }
         }
 
         self.expect(TokenKind::EndPicture)?;
 
-        let id = id.ok_or_else(|| self.error("PICTURE must have an ID".to_string()))?;
-        let name = name.ok_or_else(|| self.error("PICTURE must have a NAME".to_string()))?;
+        let id = id.ok_or_else(|| self.invalid("PICTURE must have an ID".to_string()))?;
+        let name = name.ok_or_else(|| self.invalid("PICTURE must have a NAME".to_string()))?;
 
         Ok(PictureDecl {
             id,
@@ -284,18 +361,15 @@ impl RoomScriptParser {
                     self.advance();
                 }
                 _ => {
-                    return Err(self.error(format!(
-                        "Unexpected token in DOOR block: {}",
-                        self.token_description(&self.current().kind)
-                    )));
+                    return Err(self.unexpected(DOOR_ITEMS));
                 }
             }
         }
 
         self.expect(TokenKind::EndDoor)?;
 
-        let id = id.ok_or_else(|| self.error("DOOR must have an ID".to_string()))?;
-        let dest = dest.ok_or_else(|| self.error("DOOR must have a DEST".to_string()))?;
+        let id = id.ok_or_else(|| self.invalid("DOOR must have an ID".to_string()))?;
+        let dest = dest.ok_or_else(|| self.invalid("DOOR must have a DEST".to_string()))?;
 
         Ok(DoorDecl {
             id,
@@ -349,17 +423,14 @@ impl RoomScriptParser {
                     self.advance();
                 }
                 _ => {
-                    return Err(self.error(format!(
-                        "Unexpected token in SPOT block: {}",
-                        self.token_description(&self.current().kind)
-                    )));
+                    return Err(self.unexpected(SPOT_ITEMS));
                 }
             }
         }
 
         self.expect(TokenKind::EndSpot)?;
 
-        let id = id.ok_or_else(|| self.error("SPOT must have an ID".to_string()))?;
+        let id = id.ok_or_else(|| self.invalid("SPOT must have an ID".to_string()))?;
 
         Ok(SpotDecl {
             id,
@@ -527,49 +598,22 @@ impl RoomScriptParser {
 
     fn expect(&mut self, kind: TokenKind) -> Result<(), ParseError> {
         if std::mem::discriminant(&self.current().kind) != std::mem::discriminant(&kind) {
-            return Err(self.error(format!(
-                "Expected {}, found {}",
-                self.token_description(&kind),
-                self.token_description(&self.current().kind)
-            )));
+            return Err(self.unexpected(&[kind]));
         }
         self.advance();
         Ok(())
     }
 
+    /// Parse an integer literal; the lexer folds a leading minus into it
     fn parse_i16(&mut self) -> Result<i16, ParseError> {
         match &self.current().kind {
             TokenKind::Integer(n) => {
                 let value = *n;
                 self.advance();
-                if value < i16::MIN as i32 || value > i16::MAX as i32 {
-                    Err(self.error(format!("Integer {} out of range for i16", value)))
-                } else {
-                    Ok(value as i16)
-                }
-            }
-            TokenKind::Minus => {
-                self.advance();
-                match &self.current().kind {
-                    TokenKind::Integer(n) => {
-                        let value = -(*n);
-                        self.advance();
-                        if value < i16::MIN as i32 || value > i16::MAX as i32 {
-                            Err(self.error(format!("Integer {} out of range for i16", value)))
-                        } else {
-                            Ok(value as i16)
-                        }
-                    }
-                    _ => Err(self.error(format!(
-                        "Expected integer after minus sign, found {}",
-                        self.token_description(&self.current().kind)
-                    ))),
-                }
+                i16::try_from(value)
+                    .map_err(|_| self.invalid(format!("Integer {} out of range for i16", value)))
             }
-            _ => Err(self.error(format!(
-                "Expected integer, found {}",
-                self.token_description(&self.current().kind)
-            ))),
+            _ => Err(self.unexpected(&[TokenKind::Integer(0)])),
         }
     }
 
@@ -580,59 +624,24 @@ impl RoomScriptParser {
                 self.advance();
                 Ok(value)
             }
-            _ => Err(self.error(format!(
-                "Expected string, found {}",
-                self.token_description(&self.current().kind)
-            ))),
+            _ => Err(self.unexpected(&[TokenKind::String(String::new())])),
         }
     }
 
-    fn error(&self, message: String) -> ParseError {
+    /// Error for the current token when one of `expected` was wanted
+    fn unexpected(&self, expected: &[TokenKind]) -> ParseError {
         ParseError::UnexpectedToken {
-            expected: message,
-            found: self.token_description(&self.current().kind),
+            expected: expected.to_vec(),
+            found: self.current().kind.clone(),
             pos: self.current().pos,
         }
     }
 
-    fn token_description(&self, kind: &TokenKind) -> String {
-        match kind {
-            TokenKind::Integer(n) => format!("integer {}", n),
-            TokenKind::String(s) => format!("string \"{}\"", s),
-            TokenKind::Ident(name) => format!("identifier '{}'", name),
-            TokenKind::On => "ON".to_string(),
-            TokenKind::If => "IF".to_string(),
-            TokenKind::Else => "ELSE".to_string(),
-            TokenKind::While => "WHILE".to_string(),
-            TokenKind::Do => "DO".to_string(),
-            TokenKind::Break => "BREAK".to_string(),
-            TokenKind::Room => "ROOM".to_string(),
-            TokenKind::EndRoom => "ENDROOM".to_string(),
-            TokenKind::Door => "DOOR".to_string(),
-            TokenKind::EndDoor => "ENDDOOR".to_string(),
-            TokenKind::Spot => "SPOT".to_string(),
-            TokenKind::EndSpot => "ENDSPOT".to_string(),
-            TokenKind::Script => "SCRIPT".to_string(),
-            TokenKind::EndScript => "ENDSCRIPT".to_string(),
-            TokenKind::Id => "ID".to_string(),
-            TokenKind::Name => "NAME".to_string(),
-            TokenKind::Pict => "PICT".to_string(),
-            TokenKind::Artist => "ARTIST".to_string(),
-            TokenKind::Dest => "DEST".to_string(),
-            TokenKind::Outline => "OUTLINE".to_string(),
-            TokenKind::Picts => "PICTS".to_string(),
-            TokenKind::EndPicts => "ENDPICTS".to_string(),
-            TokenKind::Picture => "PICTURE".to_string(),
-            TokenKind::EndPicture => "ENDPICTURE".to_string(),
-            TokenKind::TransColor => "TRANSCOLOR".to_string(),
-            TokenKind::Private => "PRIVATE".to_string(),
-            TokenKind::NoPainting => "NOPAINTING".to_string(),
-            TokenKind::NoCyborgs => "NOCYBORGS".to_string(),
-            TokenKind::Hidden => "HIDDEN".to_string(),
-            TokenKind::NoGuests => "NOGUESTS".to_string(),
-            TokenKind::Comma => ",".to_string(),
-            TokenKind::Eof => "end of file".to_string(),
-            _ => format!("{:?}", kind),
+    /// Error for a well-formed declaration with a bad value
+    fn invalid(&self, message: String) -> ParseError {
+        ParseError::Invalid {
+            message,
+            pos: self.current().pos,
         }
     }
 }
@@ -829,6 +838,90 @@ ENDROOM
         assert_eq!(script.handlers.len(), 1);
     }
 
+    #[test]
+    fn test_parse_all_recovers_from_bad_room() {
+        let source = r#"
+ROOM
+  ID 100
+  NAME "First"
+ENDROOM
+
+ROOM
+  ID 150
+  DOOR
+    ID 1
+    OUTLINE 10,10 oops
+  ENDDOOR
+ENDROOM
+
+ROOM
+  ID 200
+  NAME "Second"
+ENDROOM
+"#;
+
+        let mut parser = RoomScriptParser::new(source).unwrap();
+        let (rooms, errors) = parser.parse_all();
+
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(rooms[0].id, 100);
+        assert_eq!(rooms[1].id, 200);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            &errors[0],
+            ParseError::UnexpectedToken { pos, .. } if pos.line == 11
+        ));
+
+        // Strict parsing still stops at the first error
+        let mut parser = RoomScriptParser::new(source).unwrap();
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_all_missing_id_does_not_skip_next_room() {
+        let source = "ROOM\n  NAME \"No id\"\nENDROOM\nROOM\n  ID 5\nENDROOM\n";
+
+        let mut parser = RoomScriptParser::new(source).unwrap();
+        let (rooms, errors) = parser.parse_all();
+
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0].id, 5);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_missing_endroom_expected_tokens() {
+        let source = "ROOM\n  ID 1\n  NAME \"Lobby\"\nROOM\n  ID 2\nENDROOM\n";
+
+        let err = RoomScriptParser::new(source).unwrap().parse().unwrap_err();
+        let ParseError::UnexpectedToken {
+            expected,
+            found,
+            pos,
+        } = &err
+        else {
+            panic!("expected UnexpectedToken, got {:?}", err);
+        };
+        assert!(expected.contains(&TokenKind::EndRoom));
+        assert!(expected.contains(&TokenKind::Door));
+        assert_eq!(*found, TokenKind::Room);
+        assert_eq!(pos.line, 4);
+        let message = err.to_string();
+        assert!(message.starts_with("Expected one of ID, NAME, "));
+        assert!(message.ends_with("ENDROOM but found ROOM at line 4, column 1"));
+
+        // Running out of input reports the closing keyword too
+        let err = RoomScriptParser::new("ROOM\n  ID 1\n")
+            .unwrap()
+            .parse()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::UnexpectedToken { ref expected, found: TokenKind::Eof, .. }
+                if expected.contains(&TokenKind::EndRoom)
+        ));
+    }
+
     #[test]
     fn test_parse_negative_coordinates() {
         let source = r#"
@@ -849,4 +942,21 @@ ENDROOM
         assert_eq!(rooms[0].spots[0].outline[0], Point { h: -10, v: 20 });
         assert_eq!(rooms[0].spots[0].outline[1], Point { h: 30, v: -40 });
     }
+
+    #[test]
+    fn test_parse_i16_rejects_stray_minus() {
+        // Lexes as a minus followed by the literal -2147483648
+        let source = "ROOM\n  ID --2147483648\nENDROOM\n";
+        let err = RoomScriptParser::new(source).unwrap().parse().unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::UnexpectedToken {
+                found: TokenKind::Minus,
+                ..
+            }
+        ));
+
+        let source = "ROOM\n  ID -32769\nENDROOM\n";
+        assert!(RoomScriptParser::new(source).unwrap().parse().is_err());
+    }
 }
@@ -33,6 +33,7 @@ impl Token {
 pub enum TokenKind {
     // Literals
     Integer(i32),
+    Float(f64),
     String(String),
 
     // Identifiers (variables and function names)
@@ -104,6 +105,7 @@ pub enum TokenKind {
     Percent,   // % (MOD alternative)
     Ampersand, // & (string concatenation)
     Equals,    // =
+    EqEq,      // ==
     NotEquals, // !=
     Less,      // <
     Greater,   // >
@@ -176,6 +178,93 @@ impl TokenKind {
         }
     }
 
+    /// Name of this kind of token, without any value it carries
+    ///
+    /// Used when listing expected tokens, where e.g. any integer will do.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenKind::Integer(_) => "integer",
+            TokenKind::Float(_) => "float",
+            TokenKind::String(_) => "string",
+            TokenKind::Ident(_) => "identifier",
+            TokenKind::On => "ON",
+            TokenKind::If => "IF",
+            TokenKind::Else => "ELSE",
+            TokenKind::While => "WHILE",
+            TokenKind::Do => "DO",
+            TokenKind::Break => "BREAK",
+            #[cfg(feature = "room-script")]
+            TokenKind::Room => "ROOM",
+            #[cfg(feature = "room-script")]
+            TokenKind::EndRoom => "ENDROOM",
+            #[cfg(feature = "room-script")]
+            TokenKind::Door => "DOOR",
+            #[cfg(feature = "room-script")]
+            TokenKind::EndDoor => "ENDDOOR",
+            #[cfg(feature = "room-script")]
+            TokenKind::Spot => "SPOT",
+            #[cfg(feature = "room-script")]
+            TokenKind::EndSpot => "ENDSPOT",
+            #[cfg(feature = "room-script")]
+            TokenKind::Script => "SCRIPT",
+            #[cfg(feature = "room-script")]
+            TokenKind::EndScript => "ENDSCRIPT",
+            #[cfg(feature = "room-script")]
+            TokenKind::Id => "ID",
+            #[cfg(feature = "room-script")]
+            TokenKind::Name => "NAME",
+            #[cfg(feature = "room-script")]
+            TokenKind::Pict => "PICT",
+            #[cfg(feature = "room-script")]
+            TokenKind::Artist => "ARTIST",
+            #[cfg(feature = "room-script")]
+            TokenKind::Dest => "DEST",
+            #[cfg(feature = "room-script")]
+            TokenKind::Outline => "OUTLINE",
+            #[cfg(feature = "room-script")]
+            TokenKind::Picts => "PICTS",
+            #[cfg(feature = "room-script")]
+            TokenKind::EndPicts => "ENDPICTS",
+            #[cfg(feature = "room-script")]
+            TokenKind::Picture => "PICTURE",
+            #[cfg(feature = "room-script")]
+            TokenKind::EndPicture => "ENDPICTURE",
+            #[cfg(feature = "room-script")]
+            TokenKind::TransColor => "TRANSCOLOR",
+            #[cfg(feature = "room-script")]
+            TokenKind::Private => "PRIVATE",
+            #[cfg(feature = "room-script")]
+            TokenKind::NoPainting => "NOPAINTING",
+            #[cfg(feature = "room-script")]
+            TokenKind::NoCyborgs => "NOCYBORGS",
+            #[cfg(feature = "room-script")]
+            TokenKind::Hidden => "HIDDEN",
+            #[cfg(feature = "room-script")]
+            TokenKind::NoGuests => "NOGUESTS",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Star => "*",
+            TokenKind::Slash => "/",
+            TokenKind::Percent => "%",
+            TokenKind::Ampersand => "&",
+            TokenKind::Equals => "=",
+            TokenKind::EqEq => "==",
+            TokenKind::NotEquals => "!=",
+            TokenKind::Less => "<",
+            TokenKind::Greater => ">",
+            TokenKind::LessEq => "<=",
+            TokenKind::GreaterEq => ">=",
+            TokenKind::LeftBrace => "{",
+            TokenKind::RightBrace => "}",
+            TokenKind::LeftParen => "(",
+            TokenKind::RightParen => ")",
+            TokenKind::Comma => ",",
+            TokenKind::Comment(_) => "comment",
+            TokenKind::Newline => "newline",
+            TokenKind::Eof => "end of file",
+        }
+    }
+
     /// Try to parse identifier as keyword
     pub fn from_ident(ident: &str) -> Self {
         match ident.to_uppercase().as_str() {
@@ -238,6 +327,18 @@ impl TokenKind {
     }
 }
 
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Integer(n) => write!(f, "integer {}", n),
+            TokenKind::Float(x) => write!(f, "float {}", x),
+            TokenKind::String(s) => write!(f, "string \"{}\"", s),
+            TokenKind::Ident(name) => write!(f, "identifier '{}'", name),
+            kind => f.write_str(kind.name()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,322 @@
+//! Static validation (dry-run linting) of Iptscrae scripts.
+//!
+//! The validator walks each event handler without executing it, tracking an
+//! abstract stack depth to catch obvious mistakes before a script runs on a
+//! live event. It is a best-effort linter, not a type system: once the depth
+//! depends on runtime values (e.g. GETPROPS, mismatched IF branches) tracking
+//! stops for the rest of that block.
+
+use std::collections::HashSet;
+
+use crate::iptscrae::ast::{Block, Expr, Script, Statement};
+use crate::iptscrae::builtins::{self, StackEffect};
+use crate::iptscrae::token::SourcePos;
+
+/// Warning produced by static validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationWarning {
+    /// Operation would pop more values than the stack holds
+    StackUnderflow { operation: String, pos: SourcePos },
+    /// Variable is read but never assigned anywhere in the script
+    UndefinedVariable { name: String, pos: SourcePos },
+    /// Call to a name no builtin module provides
+    UnknownBuiltin { name: String, pos: SourcePos },
+}
+
+impl ValidationWarning {
+    /// Get the source position the warning refers to
+    pub const fn pos(&self) -> SourcePos {
+        match self {
+            ValidationWarning::StackUnderflow { pos, .. }
+            | ValidationWarning::UndefinedVariable { pos, .. }
+            | ValidationWarning::UnknownBuiltin { pos, .. } => *pos,
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationWarning::StackUnderflow { operation, pos } => write!(
+                f,
+                "Stack underflow in {} at line {}, column {}",
+                operation, pos.line, pos.column
+            ),
+            ValidationWarning::UndefinedVariable { name, pos } => write!(
+                f,
+                "Variable '{}' is never assigned (line {}, column {})",
+                name, pos.line, pos.column
+            ),
+            ValidationWarning::UnknownBuiltin { name, pos } => write!(
+                f,
+                "Unknown builtin {} at line {}, column {}",
+                name, pos.line, pos.column
+            ),
+        }
+    }
+}
+
+/// Validate a script, returning any warnings found
+///
+/// Each handler is assumed to start with an empty stack. Variables are
+/// considered defined if any handler in the script assigns them; variables
+/// set by the host via `Vm::set_variable` cannot be seen here.
+pub fn validate_script(script: &Script) -> Vec<ValidationWarning> {
+    let mut assigned = HashSet::new();
+    for handler in &script.handlers {
+        collect_assignments(&handler.body, &mut assigned);
+    }
+
+    let mut validator = Validator {
+        assigned,
+        warnings: Vec::new(),
+    };
+    for handler in &script.handlers {
+        validator.block(&handler.body, Some(0));
+    }
+    validator.warnings
+}
+
+/// Collect every variable name assigned within a block
+fn collect_assignments(block: &Block, assigned: &mut HashSet<String>) {
+    for statement in &block.statements {
+        match statement {
+            Statement::Assign { name, .. } => {
+                assigned.insert(name.clone());
+            }
+            Statement::If {
+                then_block,
+                else_block,
+                ..
+            } => {
+                collect_assignments(then_block, assigned);
+                if let Some(else_block) = else_block {
+                    collect_assignments(else_block, assigned);
+                }
+            }
+            Statement::While { body, .. } => collect_assignments(body, assigned),
+            Statement::Case { arms, default, .. } => {
+                for arm in arms {
+                    collect_assignments(&arm.body, assigned);
+                }
+                if let Some(default) = default {
+                    collect_assignments(default, assigned);
+                }
+            }
+            Statement::Expr(Expr::Block(inner)) => collect_assignments(inner, assigned),
+            Statement::Expr(_) | Statement::Break { .. } => {}
+        }
+    }
+}
+
+/// Abstract interpreter state; `None` depth means unknown
+struct Validator {
+    assigned: HashSet<String>,
+    warnings: Vec<ValidationWarning>,
+}
+
+impl Validator {
+    /// Walk a block, returning the stack depth after it
+    fn block(&mut self, block: &Block, mut depth: Option<usize>) -> Option<usize> {
+        for statement in &block.statements {
+            depth = self.statement(statement, depth);
+        }
+        depth
+    }
+
+    fn statement(&mut self, statement: &Statement, depth: Option<usize>) -> Option<usize> {
+        match statement {
+            Statement::Expr(expr) => self.expr(expr, depth),
+            Statement::Assign { name, pos } => self.apply(name, *pos, depth, 1, Some(0)),
+            Statement::If {
+                then_block,
+                else_block,
+                pos,
+                ..
+            } => {
+                let depth = self.apply("IF condition", *pos, depth, 1, Some(0));
+                let then_depth = self.block(then_block, depth);
+                let else_depth = match else_block {
+                    Some(else_block) => self.block(else_block, depth),
+                    None => depth,
+                };
+                if then_depth == else_depth {
+                    then_depth
+                } else {
+                    None
+                }
+            }
+            Statement::While { body, pos, .. } => {
+                let entry = depth;
+                let depth = self.apply("WHILE condition", *pos, depth, 1, Some(0));
+                // Balanced only if the body leaves exactly one new condition behind
+                if self.block(body, depth) == entry {
+                    depth
+                } else {
+                    None
+                }
+            }
+            Statement::Break { .. } => depth,
+            Statement::Case {
+                arms, default, pos, ..
+            } => {
+                let depth = self.apply("CASE value", *pos, depth, 1, Some(0));
+                // Like IF: known only if every path (including no match) agrees
+                let mut exits = arms
+                    .iter()
+                    .map(|arm| self.block(&arm.body, depth))
+                    .collect::<Vec<_>>();
+                exits.push(match default {
+                    Some(default) => self.block(default, depth),
+                    None => depth,
+                });
+                if exits.iter().all(|exit| *exit == exits[0]) {
+                    exits[0]
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, depth: Option<usize>) -> Option<usize> {
+        match expr {
+            Expr::Literal { .. } => depth.map(|d| d + 1),
+            Expr::Variable { name, pos } => {
+                if !self.assigned.contains(name) {
+                    self.warnings.push(ValidationWarning::UndefinedVariable {
+                        name: name.clone(),
+                        pos: *pos,
+                    });
+                }
+                depth.map(|d| d + 1)
+            }
+            Expr::Call { name, pos } => {
+                let name = name.to_uppercase();
+                match builtins::stack_effect(&name) {
+                    Some(StackEffect::Fixed { pops, pushes }) => {
+                        self.apply(&name, *pos, depth, pops, Some(pushes))
+                    }
+                    Some(StackEffect::Variadic { pops }) => {
+                        self.apply(&name, *pos, depth, pops, None)
+                    }
+                    None => {
+                        self.warnings
+                            .push(ValidationWarning::UnknownBuiltin { name, pos: *pos });
+                        None
+                    }
+                }
+            }
+            Expr::BinOp { op, pos } => self.apply(&format!("{:?}", op), *pos, depth, 2, Some(1)),
+            Expr::UnaryOp { op, pos } => self.apply(&format!("{:?}", op), *pos, depth, 1, Some(1)),
+            Expr::Block(block) => self.block(block, depth),
+        }
+    }
+
+    /// Apply a stack effect, flagging underflow when the depth is known
+    fn apply(
+        &mut self,
+        operation: &str,
+        pos: SourcePos,
+        depth: Option<usize>,
+        pops: usize,
+        pushes: Option<usize>,
+    ) -> Option<usize> {
+        let remaining = match depth {
+            Some(d) if d < pops => {
+                self.warnings.push(ValidationWarning::StackUnderflow {
+                    operation: operation.to_string(),
+                    pos,
+                });
+                Some(0)
+            }
+            Some(d) => Some(d - pops),
+            None => None,
+        };
+        match (remaining, pushes) {
+            (Some(d), Some(pushes)) => Some(d + pushes),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iptscrae::{Lexer, Parser, Vm};
+
+    fn validate_source(source: &str) -> Vec<ValidationWarning> {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        Vm::validate(&script)
+    }
+
+    #[test]
+    fn test_validate_clean_script() {
+        let source = r#"
+            ON SELECT {
+                0 counter =
+                counter 1 + counter =
+                counter 10 < IF {
+                    counter ITOA " clicks" & SAY
+                } ELSE {
+                    "done" SAY
+                }
+            }
+        "#;
+        assert_eq!(validate_source(source), vec![]);
+    }
+
+    #[test]
+    fn test_validate_case() {
+        // Arms are checked from the depth after the value is popped
+        let source = r#"ON SELECT { 1 CASE { 1 { "a" } DEFAULT { "b" } } SAY }"#;
+        assert_eq!(validate_source(source), vec![]);
+
+        let warnings = validate_source("ON SELECT {\n    CASE { 1 { } }\n}\n");
+        assert!(matches!(
+            warnings.as_slice(),
+            [ValidationWarning::StackUnderflow { operation, .. }] if operation == "CASE value"
+        ));
+    }
+
+    #[test]
+    fn test_validate_stack_underflow() {
+        let warnings = validate_source("ON SELECT {\n    DROP\n}\n");
+        assert_eq!(
+            warnings,
+            vec![ValidationWarning::StackUnderflow {
+                operation: "DROP".to_string(),
+                pos: SourcePos::new(2, 5),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_unknown_builtin() {
+        let warnings = validate_source("ON ENTER {\n    \"hi\" SHOUT\n}\n");
+        assert_eq!(
+            warnings,
+            vec![ValidationWarning::UnknownBuiltin {
+                name: "SHOUT".to_string(),
+                pos: SourcePos::new(2, 10),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_undefined_variable() {
+        let warnings = validate_source("ON ENTER {\n    missing SAY\n}\n");
+        assert!(matches!(
+            warnings.as_slice(),
+            [ValidationWarning::UndefinedVariable { name, .. }] if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn test_validate_variadic_stops_tracking() {
+        // GETPROPS pushes a runtime-dependent count, so later DROPs can't be judged
+        let warnings = validate_source("ON ENTER {\n    GETPROPS DROP DROP DROP\n}\n");
+        assert_eq!(warnings, vec![]);
+    }
+}
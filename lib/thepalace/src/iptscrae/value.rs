@@ -1,14 +1,22 @@
 //! Value types for Iptscrae runtime.
 //!
-//! Iptscrae is loosely typed with values that can be integers or strings.
+//! Iptscrae is loosely typed with values that can be integers, strings, arrays
+//! or floats.
 //! The stack holds values that can be manipulated by operations.
 
+use std::sync::Arc;
+
 /// Runtime value on the stack
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Integer(i32),
     String(String),
-    Array(Vec<Value>),
+    /// Elements are shared, so copying an array onto the stack or into a
+    /// variable is cheap; [`as_array_mut`](Self::as_array_mut) copies them
+    /// only if another value still refers to them.
+    Array(Arc<Vec<Value>>),
+    /// Real number, produced by float math builtins (e.g. SINRAD)
+    Float(f64),
 }
 
 impl Value {
@@ -24,14 +32,19 @@ impl Value {
 
     /// Create an array value
     pub fn array(elements: Vec<Value>) -> Self {
-        Value::Array(elements)
+        Value::Array(Arc::new(elements))
+    }
+
+    /// Create a float value
+    pub const fn float(n: f64) -> Self {
+        Value::Float(n)
     }
 
     /// Try to get integer value
     pub const fn as_integer(&self) -> Option<i32> {
         match self {
             Value::Integer(n) => Some(*n),
-            Value::String(_) | Value::Array(_) => None,
+            Value::String(_) | Value::Array(_) | Value::Float(_) => None,
         }
     }
 
@@ -39,7 +52,7 @@ impl Value {
     pub fn as_string(&self) -> Option<&str> {
         match self {
             Value::String(s) => Some(s),
-            Value::Integer(_) | Value::Array(_) => None,
+            Value::Integer(_) | Value::Array(_) | Value::Float(_) => None,
         }
     }
 
@@ -47,24 +60,48 @@ impl Value {
     pub fn as_array(&self) -> Option<&Vec<Value>> {
         match self {
             Value::Array(arr) => Some(arr),
-            Value::Integer(_) | Value::String(_) => None,
+            Value::Integer(_) | Value::String(_) | Value::Float(_) => None,
         }
     }
 
     /// Try to get mutable array value
+    ///
+    /// Copies the elements first if they're shared with another value, so
+    /// changes never show through other references to the array.
     pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
         match self {
-            Value::Array(arr) => Some(arr),
-            Value::Integer(_) | Value::String(_) => None,
+            Value::Array(arr) => Some(Arc::make_mut(arr)),
+            Value::Integer(_) | Value::String(_) | Value::Float(_) => None,
+        }
+    }
+
+    /// Try to get float value
+    pub const fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(n) => Some(*n),
+            Value::Integer(_) | Value::String(_) | Value::Array(_) => None,
         }
     }
 
     /// Convert to integer (string "123" -> 123, or 0 if invalid)
+    ///
+    /// Floats are truncated toward zero, saturating at the `i32` range.
     pub fn to_integer(&self) -> i32 {
         match self {
             Value::Integer(n) => *n,
             Value::String(s) => s.parse().unwrap_or(0),
             Value::Array(_) => 0,
+            Value::Float(n) => *n as i32,
+        }
+    }
+
+    /// Convert to float (string "1.5" -> 1.5, or 0.0 if invalid)
+    pub fn to_float(&self) -> f64 {
+        match self {
+            Value::Integer(n) => f64::from(*n),
+            Value::String(s) => s.parse().unwrap_or(0.0),
+            Value::Array(_) => 0.0,
+            Value::Float(n) => *n,
         }
     }
 
@@ -74,6 +111,7 @@ impl Value {
             Value::Integer(n) => *n != 0,
             Value::String(s) => !s.is_empty(),
             Value::Array(arr) => !arr.is_empty(),
+            Value::Float(n) => *n != 0.0,
         }
     }
 
@@ -92,12 +130,18 @@ impl Value {
         matches!(self, Value::Array(_))
     }
 
+    /// Check if value is a float
+    pub const fn is_float(&self) -> bool {
+        matches!(self, Value::Float(_))
+    }
+
     /// Get type name for debugging
     pub const fn type_name(&self) -> &'static str {
         match self {
             Value::Integer(_) => "integer",
             Value::String(_) => "string",
             Value::Array(_) => "array",
+            Value::Float(_) => "float",
         }
     }
 }
@@ -108,6 +152,12 @@ impl From<i32> for Value {
     }
 }
 
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Float(n)
+    }
+}
+
 impl From<String> for Value {
     fn from(s: String) -> Self {
         Value::String(s)
@@ -120,11 +170,18 @@ impl From<&str> for Value {
     }
 }
 
+/// Text form used by `&`, `SAY` and other string conversions
+///
+/// Integers are decimal, strings are written as-is (no quotes), floats drop
+/// trailing zeros, and arrays are a bracketed, comma-separated list of their
+/// elements' text: `[1, two, [3.5]]`.
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Integer(n) => write!(f, "{}", n),
             Value::String(s) => write!(f, "{}", s),
+            // `{}` already drops trailing zeros (1.0 -> "1", 0.5 -> "0.5")
+            Value::Float(n) => write!(f, "{}", n),
             Value::Array(arr) => {
                 write!(f, "[")?;
                 for (i, v) in arr.iter().enumerate() {
@@ -186,9 +243,61 @@ mod tests {
         assert_eq!(v3, Value::String("world".to_string()));
     }
 
+    #[test]
+    fn test_value_float() {
+        let v = Value::float(2.75);
+        assert!(v.is_float());
+        assert_eq!(v.type_name(), "float");
+        assert_eq!(v.to_integer(), 2);
+        assert_eq!(Value::float(-2.75).to_integer(), -2);
+        assert_eq!(Value::Integer(3).to_float(), 3.0);
+        assert_eq!(Value::string("1.5").to_float(), 1.5);
+        assert!(!Value::float(0.0).to_bool());
+        assert_eq!(Value::from(0.5), Value::Float(0.5));
+        assert_eq!(format!("{}", Value::float(1.0)), "1");
+        assert_eq!(format!("{}", Value::float(0.25)), "0.25");
+    }
+
     #[test]
     fn test_value_display() {
         assert_eq!(format!("{}", Value::Integer(42)), "42");
         assert_eq!(format!("{}", Value::String("hello".to_string())), "hello");
+        assert_eq!(format!("{}", Value::Integer(-7)), "-7");
+        assert_eq!(format!("{}", Value::float(2.0)), "2");
+        assert_eq!(format!("{}", Value::float(-0.125)), "-0.125");
+        assert_eq!(format!("{}", Value::array(vec![])), "[]");
+        let mixed = Value::array(vec![
+            Value::Integer(1),
+            Value::string("two"),
+            Value::float(3.5),
+        ]);
+        assert_eq!(format!("{}", mixed), "[1, two, 3.5]");
+    }
+
+    #[test]
+    fn test_array_copy_on_write() {
+        let original = Value::array(vec![Value::Integer(1), Value::Integer(2)]);
+        let mut copy = original.clone();
+        match (&original, &copy) {
+            (Value::Array(a), Value::Array(b)) => assert!(Arc::ptr_eq(a, b)),
+            _ => unreachable!(),
+        }
+
+        copy.as_array_mut().unwrap()[0] = Value::Integer(9);
+        assert_eq!(
+            original,
+            Value::array(vec![Value::Integer(1), Value::Integer(2)])
+        );
+        assert_eq!(copy.as_array().unwrap()[0], Value::Integer(9));
+    }
+
+    #[test]
+    fn test_value_display_nested_array() {
+        let nested = Value::array(vec![
+            Value::Integer(1),
+            Value::array(vec![Value::string("a"), Value::array(vec![])]),
+            Value::Integer(2),
+        ]);
+        assert_eq!(nested.to_string(), "[1, [a, []], 2]");
     }
 }
@@ -9,9 +9,18 @@ use std::time::{Duration, Instant};
 
 use crate::iptscrae::ast::{BinOp, Block, Expr, Script, Statement, UnaryOp};
 use crate::iptscrae::builtins;
-use crate::iptscrae::context::ScriptContext;
+use crate::iptscrae::clock::{Clock, SystemClock};
+use crate::iptscrae::context::{ScriptAction, ScriptContext};
+use crate::iptscrae::token::SourcePos;
+use crate::iptscrae::validate::{self, ValidationWarning};
 use crate::iptscrae::value::Value;
 
+/// Maximum nesting of MACRO calls
+///
+/// Applies regardless of [`ExecutionLimits`] so a self-calling macro can't
+/// exhaust the native stack under unlimited server limits.
+pub const MAX_MACRO_DEPTH: usize = 64;
+
 /// VM error types
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum VmError {
@@ -33,6 +42,14 @@ pub enum VmError {
     InstructionLimitExceeded,
     /// Security violation - function not allowed at current security level
     SecurityViolation { function: String },
+    /// Stack depth limit exceeded (for sandboxed scripts)
+    StackOverflow,
+    /// Array size limit exceeded (for sandboxed scripts)
+    AllocationLimitExceeded,
+    /// MACRO calls nested deeper than [`MAX_MACRO_DEPTH`]
+    MacroDepthExceeded,
+    /// Too many messages, or too much message text, in one run (for sandboxed scripts)
+    OutputLimitExceeded,
 }
 
 impl std::fmt::Display for VmError {
@@ -65,12 +82,49 @@ impl std::fmt::Display for VmError {
             VmError::SecurityViolation { function } => {
                 write!(f, "Security violation: {} not allowed at this security level", function)
             }
+            VmError::StackOverflow => {
+                write!(f, "Stack depth limit exceeded")
+            }
+            VmError::AllocationLimitExceeded => {
+                write!(f, "Array size limit exceeded")
+            }
+            VmError::MacroDepthExceeded => {
+                write!(f, "Macro nesting limit exceeded")
+            }
+            VmError::OutputLimitExceeded => {
+                write!(f, "Message output limit exceeded")
+            }
         }
     }
 }
 
 impl std::error::Error for VmError {}
 
+/// VM error annotated with the source position of the failing statement or expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmErrorAt {
+    /// The underlying runtime error
+    pub error: VmError,
+    /// Position of the statement or expression that raised the error
+    pub pos: SourcePos,
+}
+
+impl std::fmt::Display for VmErrorAt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.error, self.pos.line, self.pos.column
+        )
+    }
+}
+
+impl std::error::Error for VmErrorAt {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
 /// Control flow signals
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ControlFlow {
@@ -81,10 +135,14 @@ enum ControlFlow {
 }
 
 /// VM execution limits for sandboxing
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ExecutionLimits {
     max_instructions: Option<usize>,
     max_duration: Option<Duration>,
+    max_stack_depth: Option<usize>,
+    max_array_size: Option<usize>,
+    max_say_calls: Option<usize>,
+    max_total_say_bytes: Option<usize>,
 }
 
 impl ExecutionLimits {
@@ -93,6 +151,10 @@ impl ExecutionLimits {
         Self {
             max_instructions: None,
             max_duration: None,
+            max_stack_depth: None,
+            max_array_size: None,
+            max_say_calls: None,
+            max_total_say_bytes: None,
         }
     }
 
@@ -101,6 +163,10 @@ impl ExecutionLimits {
         Self {
             max_instructions: Some(100_000),
             max_duration: Some(Duration::from_secs(5)),
+            max_stack_depth: Some(1_024),
+            max_array_size: Some(10_000),
+            max_say_calls: Some(16),
+            max_total_say_bytes: Some(2_048),
         }
     }
 
@@ -109,6 +175,10 @@ impl ExecutionLimits {
         Self {
             max_instructions: None,
             max_duration: None,
+            max_stack_depth: None,
+            max_array_size: None,
+            max_say_calls: None,
+            max_total_say_bytes: None,
         }
     }
 
@@ -123,6 +193,113 @@ impl ExecutionLimits {
         self.max_duration = Some(duration);
         self
     }
+
+    /// Set maximum number of values on the stack
+    pub const fn with_max_stack_depth(mut self, depth: usize) -> Self {
+        self.max_stack_depth = Some(depth);
+        self
+    }
+
+    /// Set maximum number of elements in a single array
+    pub const fn with_max_array_size(mut self, size: usize) -> Self {
+        self.max_array_size = Some(size);
+        self
+    }
+
+    /// Set maximum number of messages (SAY, CHAT, ROOMMSG, ...) per run
+    pub const fn with_max_say_calls(mut self, max: usize) -> Self {
+        self.max_say_calls = Some(max);
+        self
+    }
+
+    /// Set maximum total bytes of message text per run
+    pub const fn with_max_total_say_bytes(mut self, max: usize) -> Self {
+        self.max_total_say_bytes = Some(max);
+        self
+    }
+}
+
+/// Instrumentation for a single `execute_handler` call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunStats {
+    /// Number of statements and expressions executed
+    pub instructions: usize,
+    /// Wall-clock time spent executing
+    pub elapsed: Duration,
+}
+
+/// A handler run that failed, with the actions it performed before failing
+///
+/// Returned by [`Vm::execute_handler_partial`]. For a run cut short by
+/// [`VmError::Timeout`] or [`VmError::InstructionLimitExceeded`], `actions`
+/// holds everything the script did before the limit, e.g. lines it said,
+/// so the caller can still deliver them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialRun {
+    /// Why the run stopped
+    pub error: VmErrorAt,
+    /// Actions performed before the error, in order
+    pub actions: Vec<ScriptAction>,
+}
+
+impl PartialRun {
+    /// Whether the run was stopped by the instruction or time limit
+    pub fn hit_limit(&self) -> bool {
+        matches!(
+            self.error.error,
+            VmError::Timeout | VmError::InstructionLimitExceeded
+        )
+    }
+}
+
+impl std::fmt::Display for PartialRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} after {} actions", self.error, self.actions.len())
+    }
+}
+
+impl std::error::Error for PartialRun {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Variables one user's scripts keep between events
+///
+/// A server running cyborg scripts can share one [`Vm`] between connections
+/// by keeping a `UserScriptState` per connection and running each event
+/// through [`Vm::execute_handler_with_state`], so one user's variables are
+/// never visible to another's scripts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserScriptState {
+    globals: HashMap<String, Value>,
+}
+
+impl UserScriptState {
+    /// Create an empty state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a variable value
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name)
+    }
+
+    /// Set a variable value
+    pub fn set(&mut self, name: impl Into<String>, value: Value) {
+        self.globals.insert(name.into(), value);
+    }
+
+    /// Get the number of variables
+    pub fn len(&self) -> usize {
+        self.globals.len()
+    }
+
+    /// Check if no variables are set
+    pub fn is_empty(&self) -> bool {
+        self.globals.is_empty()
+    }
 }
 
 /// Virtual Machine for executing Iptscrae scripts
@@ -139,6 +316,20 @@ pub struct Vm {
     start_time: Option<Instant>,
     /// Output buffer (for SAY commands, etc.)
     output: Vec<String>,
+    /// Position of the statement or expression currently executing
+    current_pos: SourcePos,
+    /// Stats from the most recent handler run
+    last_run_stats: RunStats,
+    /// Time source for time builtins
+    clock: Box<dyn Clock>,
+    /// Number of MACRO calls currently executing
+    macro_depth: usize,
+    /// Read unset variables as 0 instead of failing
+    lenient_variables: bool,
+    /// Messages sent during the current run
+    say_calls: usize,
+    /// Bytes of message text sent during the current run
+    say_bytes: usize,
 }
 
 impl Vm {
@@ -156,9 +347,28 @@ impl Vm {
             instruction_count: 0,
             start_time: None,
             output: Vec::new(),
+            current_pos: SourcePos::new(0, 0),
+            last_run_stats: RunStats::default(),
+            clock: Box::new(SystemClock),
+            macro_depth: 0,
+            lenient_variables: false,
+            say_calls: 0,
+            say_bytes: 0,
         }
     }
 
+    /// Read unset variables as 0 instead of failing with `UndefinedVariable`
+    ///
+    /// The original Palace interpreter treats a variable that was never
+    /// assigned as 0, and older scripts rely on that (`count 1 + count =`
+    /// without initializing `count`). Lenient mode runs them unchanged, at
+    /// the cost of hiding typos: a misspelled variable or builtin name reads
+    /// as 0 rather than stopping the script. Strict mode is the default.
+    pub fn lenient_variables(mut self, lenient: bool) -> Self {
+        self.lenient_variables = lenient;
+        self
+    }
+
     /// Execute a script
     pub fn execute(&mut self, _script: &Script) -> Result<(), VmError> {
         self.start_time = Some(Instant::now());
@@ -169,26 +379,197 @@ impl Vm {
         Ok(())
     }
 
+    /// Statically check a script for obvious errors without executing it
+    ///
+    /// Flags stack underflows, reads of variables the script never assigns,
+    /// and calls to unknown builtins. No actions are performed.
+    pub fn validate(script: &Script) -> Vec<ValidationWarning> {
+        validate::validate_script(script)
+    }
+
     /// Execute a specific event handler from a script with context
+    ///
+    /// Errors carry the source position of the statement or expression that failed.
     pub fn execute_handler(
         &mut self,
         script: &Script,
         event_type: crate::iptscrae::events::EventType,
         context: &mut ScriptContext,
-    ) -> Result<(), VmError> {
-        self.start_time = Some(Instant::now());
+    ) -> Result<(), VmErrorAt> {
+        let start = Instant::now();
+        self.start_time = Some(start);
         self.instruction_count = 0;
+        self.say_calls = 0;
+        self.say_bytes = 0;
+        self.last_run_stats = RunStats::default();
 
-        // Find handlers matching the event type
+        let result = self.run_handlers(script, event_type, context);
+
+        self.last_run_stats = RunStats {
+            instructions: self.instruction_count,
+            elapsed: start.elapsed(),
+        };
+        result
+    }
+
+    /// Execute an event handler, holding back its actions until it finishes
+    ///
+    /// The handler's actions are buffered rather than sent straight to
+    /// `context.actions`. If it completes they are performed in order. If it
+    /// fails, nothing is performed and the buffered actions come back in the
+    /// [`PartialRun`], so the caller can decide what to flush; after a
+    /// timeout, for instance, the server can still deliver the chat the
+    /// script produced before it was stopped.
+    pub fn execute_handler_partial(
+        &mut self,
+        script: &Script,
+        event_type: crate::iptscrae::events::EventType,
+        context: &mut ScriptContext,
+    ) -> Result<(), PartialRun> {
+        let mut buffered: Vec<ScriptAction> = Vec::new();
+        let result = context.with_actions(&mut buffered, |redirected| {
+            self.execute_handler(script, event_type, redirected)
+        });
+
+        match result {
+            Ok(()) => {
+                for action in buffered {
+                    action.apply(context.actions);
+                }
+                Ok(())
+            }
+            Err(error) => Err(PartialRun {
+                error,
+                actions: buffered,
+            }),
+        }
+    }
+
+    /// Load a user's variables, replacing the VM's own
+    ///
+    /// The stack is cleared too, so nothing left over from another user's
+    /// run is visible.
+    pub fn bind_state(&mut self, state: UserScriptState) {
+        self.variables = state.globals;
+        self.stack.clear();
+    }
+
+    /// Take the VM's variables out as a user's state, leaving the VM with none
+    pub fn take_state(&mut self) -> UserScriptState {
+        self.stack.clear();
+        UserScriptState {
+            globals: std::mem::take(&mut self.variables),
+        }
+    }
+
+    /// Run an event handler with `state` bound as the VM's variables
+    ///
+    /// `state` is updated with whatever the handler assigned, even if it
+    /// failed partway through, and the VM is left with no variables.
+    pub fn execute_handler_with_state(
+        &mut self,
+        script: &Script,
+        event_type: crate::iptscrae::events::EventType,
+        context: &mut ScriptContext,
+        state: &mut UserScriptState,
+    ) -> Result<(), VmErrorAt> {
+        self.bind_state(std::mem::take(state));
+        let result = self.execute_handler(script, event_type, context);
+        *state = self.take_state();
+        result
+    }
+
+    /// Fire an event at a script, running its matching handlers
+    ///
+    /// Sets `context.event_type` before running, so the handler sees the
+    /// event it was invoked for.
+    pub fn fire_event(
+        &mut self,
+        script: &Script,
+        event_type: crate::iptscrae::events::EventType,
+        context: &mut ScriptContext,
+    ) -> Result<(), VmErrorAt> {
+        context.event_type = event_type;
+        self.execute_handler(script, event_type, context)
+    }
+
+    /// Fire an event at several scripts in order (e.g. every spot in a room)
+    ///
+    /// A failing script doesn't stop the rest from running; the errors are
+    /// returned in script order. [`Vm::last_run_stats`] covers only the last
+    /// script.
+    pub fn fire_events<'a>(
+        &mut self,
+        scripts: impl IntoIterator<Item = &'a Script>,
+        event_type: crate::iptscrae::events::EventType,
+        context: &mut ScriptContext,
+    ) -> Vec<VmErrorAt> {
+        scripts
+            .into_iter()
+            .filter_map(|script| self.fire_event(script, event_type, context).err())
+            .collect()
+    }
+
+    /// Run every handler in the script matching the event type
+    fn run_handlers(
+        &mut self,
+        script: &Script,
+        event_type: crate::iptscrae::events::EventType,
+        context: &mut ScriptContext,
+    ) -> Result<(), VmErrorAt> {
         for handler in &script.handlers {
             if handler.event == event_type {
-                self.execute_block_with_context(&handler.body, Some(context))?;
+                self.current_pos = handler.pos;
+                self.execute_block_with_context(&handler.body, Some(context))
+                    .map_err(|error| VmErrorAt {
+                        error,
+                        pos: self.current_pos,
+                    })?;
             }
         }
 
         Ok(())
     }
 
+    /// Run a macro script's handlers for the current event (for MACRO)
+    ///
+    /// The macro shares this VM's stack, variables and limits, so its
+    /// instructions count toward the running handler's budget.
+    pub(crate) fn run_macro(
+        &mut self,
+        script: &Script,
+        context: &mut ScriptContext,
+    ) -> Result<(), VmError> {
+        if self.macro_depth >= MAX_MACRO_DEPTH {
+            return Err(VmError::MacroDepthExceeded);
+        }
+
+        self.macro_depth += 1;
+        let event_type = context.event_type;
+        let result = script
+            .handlers
+            .iter()
+            .filter(|handler| handler.event == event_type)
+            .try_for_each(|handler| {
+                self.execute_block_with_context(&handler.body, Some(&mut *context))
+                    .map(|_| ())
+            });
+        self.macro_depth -= 1;
+        result
+    }
+
+    /// Get instruction count and elapsed time of the most recent `execute_handler` call
+    ///
+    /// Populated whether or not the handler succeeded, and independent of any limits.
+    pub const fn last_run_stats(&self) -> RunStats {
+        self.last_run_stats
+    }
+
+    /// Get the source position of the most recently executed statement or expression
+    pub const fn current_pos(&self) -> SourcePos {
+        self.current_pos
+    }
+
     /// Execute a block of statements with optional context
     fn execute_block_with_context(
         &mut self,
@@ -210,6 +591,9 @@ impl Vm {
         statement: &Statement,
         mut context: Option<&mut ScriptContext>,
     ) -> Result<ControlFlow, VmError> {
+        if let Some(pos) = statement_pos(statement) {
+            self.current_pos = pos;
+        }
         self.check_limits()?;
 
         match statement {
@@ -260,6 +644,20 @@ impl Vm {
             }
 
             Statement::Break { .. } => Ok(ControlFlow::Break),
+
+            Statement::Case { arms, default, .. } => {
+                let value = self.pop("CASE value")?;
+                let body = arms
+                    .iter()
+                    .find(|arm| case_matches(&value, &arm.label))
+                    .map(|arm| &arm.body)
+                    .or(default.as_ref());
+                // A BREAK inside an arm leaves the enclosing loop
+                match body {
+                    Some(body) => self.execute_block_with_context(body, context),
+                    None => Ok(ControlFlow::Continue),
+                }
+            }
         }
     }
 
@@ -269,44 +667,43 @@ impl Vm {
         expr: &Expr,
         context: Option<&mut ScriptContext>,
     ) -> Result<(), VmError> {
+        if let Some(pos) = expr_pos(expr) {
+            self.current_pos = pos;
+        }
         self.check_limits()?;
 
         match expr {
             Expr::Literal { value, .. } => {
-                self.push(value.clone());
-                Ok(())
+                self.push(value.clone())?;
             }
 
             Expr::Variable { name, .. } => {
-                let value = self
-                    .variables
-                    .get(name)
-                    .cloned()
-                    .ok_or_else(|| VmError::UndefinedVariable { name: name.clone() })?;
-                self.push(value);
-                Ok(())
+                let value = match self.variables.get(name) {
+                    Some(value) => value.clone(),
+                    None if self.lenient_variables => Value::Integer(0),
+                    None => return Err(VmError::UndefinedVariable { name: name.clone() }),
+                };
+                self.push(value)?;
             }
 
             Expr::Call { name, .. } => {
                 self.execute_builtin_with_context(name, context)?;
-                Ok(())
             }
 
             Expr::BinOp { op, .. } => {
                 self.execute_binop(*op)?;
-                Ok(())
             }
 
             Expr::UnaryOp { op, .. } => {
                 self.execute_unaryop(*op)?;
-                Ok(())
             }
 
             Expr::Block(block) => {
                 self.execute_block_with_context(block, context)?;
-                Ok(())
             }
         }
+
+        Ok(())
     }
 
     /// Execute a block of statements
@@ -316,6 +713,14 @@ impl Vm {
         let right = self.pop("binary operation right operand")?;
         let left = self.pop("binary operation left operand")?;
 
+        // Arithmetic and comparisons switch to floating point if either side is a float
+        if (left.is_float() || right.is_float())
+            && let Some(result) = float_binop(op, left.to_float(), right.to_float())?
+        {
+            self.push(result)?;
+            return Ok(());
+        }
+
         let result = match op {
             BinOp::Add => Value::Integer(left.to_integer() + right.to_integer()),
             BinOp::Sub => Value::Integer(left.to_integer() - right.to_integer()),
@@ -382,7 +787,7 @@ impl Vm {
             }),
         };
 
-        self.push(result);
+        self.push(result)?;
         Ok(())
     }
 
@@ -391,65 +796,35 @@ impl Vm {
         let operand = self.pop("unary operation")?;
 
         let result = match op {
-            UnaryOp::Neg => Value::Integer(-operand.to_integer()),
+            UnaryOp::Neg => match operand {
+                Value::Float(n) => Value::Float(-n),
+                _ => Value::Integer(-operand.to_integer()),
+            },
             UnaryOp::Not => Value::Integer(if operand.to_bool() { 0 } else { 1 }),
         };
 
-        self.push(result);
+        self.push(result)?;
         Ok(())
     }
 
     /// Execute a built-in function with optional context
-    fn execute_builtin_with_context(
+    pub(crate) fn execute_builtin_with_context(
         &mut self,
         name: &str,
         context: Option<&mut ScriptContext>,
     ) -> Result<(), VmError> {
-        let name_upper = name.to_uppercase();
-        let name_str = name_upper.as_str();
-
-        // Try stack operations first (most common)
-        match builtins::execute_stack_builtin(self, name_str) {
-            Ok(()) => return Ok(()),
-            Err(VmError::UndefinedFunction { .. }) => {}
-            Err(e) => return Err(e),
-        }
-
-        // Try string operations
-        match builtins::execute_string_builtin(self, name_str) {
-            Ok(()) => return Ok(()),
-            Err(VmError::UndefinedFunction { .. }) => {}
-            Err(e) => return Err(e),
-        }
-
-        // Try math operations
-        match builtins::execute_math_builtin(self, name_str) {
-            Ok(()) => return Ok(()),
-            Err(VmError::UndefinedFunction { .. }) => {}
-            Err(e) => return Err(e),
-        }
-
-        // Try logic operations
-        match builtins::execute_logic_builtin(self, name_str) {
-            Ok(()) => return Ok(()),
-            Err(VmError::UndefinedFunction { .. }) => {}
-            Err(e) => return Err(e),
-        }
-
-        // Try array operations
-        match builtins::execute_array_builtin(self, name_str) {
-            Ok(()) => return Ok(()),
-            Err(VmError::UndefinedFunction { .. }) => {}
-            Err(e) => return Err(e),
-        }
-
-        // Try Palace operations
-        builtins::execute_palace_builtin(self, name_str, context)
+        builtins::execute_builtin(self, &name.to_uppercase(), context)
     }
 
-    /// Push a value onto the stack
-    pub(crate) fn push(&mut self, value: Value) {
+    /// Push a value onto the stack, enforcing the stack depth limit
+    pub(crate) fn push(&mut self, value: Value) -> Result<(), VmError> {
+        if let Some(max_depth) = self.limits.max_stack_depth
+            && self.stack.len() >= max_depth
+        {
+            return Err(VmError::StackOverflow);
+        }
         self.stack.push(value);
+        Ok(())
     }
 
     /// Pop a value from the stack
@@ -474,6 +849,11 @@ impl Vm {
         self.stack.len()
     }
 
+    /// Drop every value on the stack (for builtin modules)
+    pub(crate) fn clear_stack(&mut self) {
+        self.stack.clear();
+    }
+
     /// Get stack element at index (for builtin modules)
     pub(crate) fn stack_get(&self, index: usize) -> &Value {
         &self.stack[index]
@@ -506,6 +886,33 @@ impl Vm {
         Ok(())
     }
 
+    /// Check an array length against the array size limit (for builtin modules)
+    pub(crate) fn check_array_size(&self, len: usize) -> Result<(), VmError> {
+        match self.limits.max_array_size {
+            Some(max_size) if len > max_size => Err(VmError::AllocationLimitExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count one outgoing message against the output limits (for builtin modules)
+    pub(crate) fn check_output(&mut self, message: &str) -> Result<(), VmError> {
+        self.say_calls += 1;
+        self.say_bytes += message.len();
+
+        if let Some(max_calls) = self.limits.max_say_calls
+            && self.say_calls > max_calls
+        {
+            return Err(VmError::OutputLimitExceeded);
+        }
+        if let Some(max_bytes) = self.limits.max_total_say_bytes
+            && self.say_bytes > max_bytes
+        {
+            return Err(VmError::OutputLimitExceeded);
+        }
+
+        Ok(())
+    }
+
     /// Get the current stack (for debugging)
     pub fn stack(&self) -> &[Value] {
         &self.stack
@@ -521,6 +928,16 @@ impl Vm {
         self.variables.insert(name, value);
     }
 
+    /// Replace the time source used by time builtins (TICKS, DATETIME, TIMESTAMP)
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Get the time source (for builtin modules)
+    pub(crate) fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
     /// Get output buffer
     pub fn output(&self) -> &[String] {
         &self.output
@@ -537,15 +954,20 @@ impl Vm {
     }
 
     /// Helper: Push a value from context or a default value
-    pub(crate) fn push_from_context_or<F, D>(&mut self, context: Option<&ScriptContext>, f: F, default: D)
+    pub(crate) fn push_from_context_or<F, D>(
+        &mut self,
+        context: Option<&ScriptContext>,
+        f: F,
+        default: D,
+    ) -> Result<(), VmError>
     where
         F: FnOnce(&ScriptContext) -> Value,
         D: FnOnce() -> Value,
     {
         if let Some(ctx) = context {
-            self.push(f(ctx));
+            self.push(f(ctx))
         } else {
-            self.push(default());
+            self.push(default())
         }
     }
 
@@ -581,10 +1003,72 @@ impl Default for Vm {
     }
 }
 
+/// Whether a CASE value selects an arm labeled `label`
+///
+/// Strings compare as text (so `1` matches `"1"`), other numbers numerically,
+/// and arrays only match an identical array.
+fn case_matches(value: &Value, label: &Value) -> bool {
+    match (value, label) {
+        (Value::Array(_), _) | (_, Value::Array(_)) => value == label,
+        (Value::String(_), _) | (_, Value::String(_)) => value.to_string() == label.to_string(),
+        (Value::Float(_), _) | (_, Value::Float(_)) => value.to_float() == label.to_float(),
+        _ => value.to_integer() == label.to_integer(),
+    }
+}
+
+/// Source position of a statement, if it carries one directly
+const fn statement_pos(statement: &Statement) -> Option<SourcePos> {
+    match statement {
+        Statement::Expr(expr) => expr_pos(expr),
+        Statement::Assign { pos, .. }
+        | Statement::If { pos, .. }
+        | Statement::While { pos, .. }
+        | Statement::Case { pos, .. }
+        | Statement::Break { pos } => Some(*pos),
+    }
+}
+
+/// Source position of an expression (blocks carry no position of their own)
+const fn expr_pos(expr: &Expr) -> Option<SourcePos> {
+    match expr {
+        Expr::Literal { pos, .. }
+        | Expr::Variable { pos, .. }
+        | Expr::Call { pos, .. }
+        | Expr::BinOp { pos, .. }
+        | Expr::UnaryOp { pos, .. } => Some(*pos),
+        Expr::Block(_) => None,
+    }
+}
+
+/// Apply a binary operator to floats, or `None` if it has no float form
+fn float_binop(op: BinOp, left: f64, right: f64) -> Result<Option<Value>, VmError> {
+    let compare = |result: bool| Value::Integer(i32::from(result));
+    let result = match op {
+        BinOp::Add => Value::Float(left + right),
+        BinOp::Sub => Value::Float(left - right),
+        BinOp::Mul => Value::Float(left * right),
+        BinOp::Div => {
+            if right == 0.0 {
+                return Err(VmError::DivisionByZero);
+            }
+            Value::Float(left / right)
+        }
+        BinOp::Eq => compare(left == right),
+        BinOp::NotEq => compare(left != right),
+        BinOp::Less => compare(left < right),
+        BinOp::Greater => compare(left > right),
+        BinOp::LessEq => compare(left <= right),
+        BinOp::GreaterEq => compare(left >= right),
+        _ => return Ok(None),
+    };
+    Ok(Some(result))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::iptscrae::{Lexer, Parser};
+    use std::sync::Arc;
 
     /// Helper: Parse Iptscrae source code into a Script
     #[allow(dead_code)]
@@ -621,8 +1105,8 @@ mod tests {
     #[test]
     fn test_vm_push_pop() {
         let mut vm = Vm::new();
-        vm.push(Value::Integer(42));
-        vm.push(Value::String("test".to_string()));
+        vm.push(Value::Integer(42)).unwrap();
+        vm.push(Value::String("test".to_string())).unwrap();
 
         assert_eq!(vm.pop("test").unwrap(), Value::String("test".to_string()));
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(42));
@@ -640,26 +1124,26 @@ mod tests {
         let mut vm = Vm::new();
 
         // 5 + 3
-        vm.push(Value::Integer(5));
-        vm.push(Value::Integer(3));
+        vm.push(Value::Integer(5)).unwrap();
+        vm.push(Value::Integer(3)).unwrap();
         vm.execute_binop(BinOp::Add).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(8));
 
         // 10 - 4
-        vm.push(Value::Integer(10));
-        vm.push(Value::Integer(4));
+        vm.push(Value::Integer(10)).unwrap();
+        vm.push(Value::Integer(4)).unwrap();
         vm.execute_binop(BinOp::Sub).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(6));
 
         // 6 * 7
-        vm.push(Value::Integer(6));
-        vm.push(Value::Integer(7));
+        vm.push(Value::Integer(6)).unwrap();
+        vm.push(Value::Integer(7)).unwrap();
         vm.execute_binop(BinOp::Mul).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(42));
 
         // 15 / 3
-        vm.push(Value::Integer(15));
-        vm.push(Value::Integer(3));
+        vm.push(Value::Integer(15)).unwrap();
+        vm.push(Value::Integer(3)).unwrap();
         vm.execute_binop(BinOp::Div).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(5));
     }
@@ -667,8 +1151,8 @@ mod tests {
     #[test]
     fn test_vm_division_by_zero() {
         let mut vm = Vm::new();
-        vm.push(Value::Integer(10));
-        vm.push(Value::Integer(0));
+        vm.push(Value::Integer(10)).unwrap();
+        vm.push(Value::Integer(0)).unwrap();
         let result = vm.execute_binop(BinOp::Div);
         assert!(matches!(result, Err(VmError::DivisionByZero)));
     }
@@ -678,20 +1162,20 @@ mod tests {
         let mut vm = Vm::new();
 
         // 5 < 10
-        vm.push(Value::Integer(5));
-        vm.push(Value::Integer(10));
+        vm.push(Value::Integer(5)).unwrap();
+        vm.push(Value::Integer(10)).unwrap();
         vm.execute_binop(BinOp::Less).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1));
 
         // 10 > 5
-        vm.push(Value::Integer(10));
-        vm.push(Value::Integer(5));
+        vm.push(Value::Integer(10)).unwrap();
+        vm.push(Value::Integer(5)).unwrap();
         vm.execute_binop(BinOp::Greater).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1));
 
         // 5 == 5
-        vm.push(Value::Integer(5));
-        vm.push(Value::Integer(5));
+        vm.push(Value::Integer(5)).unwrap();
+        vm.push(Value::Integer(5)).unwrap();
         vm.execute_binop(BinOp::Eq).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1));
     }
@@ -699,8 +1183,8 @@ mod tests {
     #[test]
     fn test_vm_string_concat() {
         let mut vm = Vm::new();
-        vm.push(Value::String("Hello ".to_string()));
-        vm.push(Value::String("World".to_string()));
+        vm.push(Value::String("Hello ".to_string())).unwrap();
+        vm.push(Value::String("World".to_string())).unwrap();
         vm.execute_binop(BinOp::Concat).unwrap();
         assert_eq!(
             vm.pop("test").unwrap(),
@@ -708,6 +1192,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_vm_concat_string_and_array() {
+        let mut vm = Vm::new();
+        vm.push(Value::string("items: ")).unwrap();
+        vm.push(Value::array(vec![Value::Integer(1), Value::string("b")]))
+            .unwrap();
+        vm.execute_binop(BinOp::Concat).unwrap();
+        assert_eq!(vm.pop("test").unwrap(), Value::string("items: [1, b]"));
+    }
+
     #[test]
     fn test_vm_variables() {
         let mut vm = Vm::new();
@@ -721,15 +1215,15 @@ mod tests {
         let mut vm = Vm::new();
 
         // Test DUP
-        vm.push(Value::Integer(42));
+        vm.push(Value::Integer(42)).unwrap();
         vm.execute_builtin_with_context("DUP", None).unwrap();
         assert_eq!(vm.stack.len(), 2);
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(42));
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(42));
 
         // Test SWAP
-        vm.push(Value::Integer(1));
-        vm.push(Value::Integer(2));
+        vm.push(Value::Integer(1)).unwrap();
+        vm.push(Value::Integer(2)).unwrap();
         vm.execute_builtin_with_context("SWAP", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1));
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(2));
@@ -740,12 +1234,12 @@ mod tests {
         let mut vm = Vm::new();
 
         // ITOA
-        vm.push(Value::Integer(42));
+        vm.push(Value::Integer(42)).unwrap();
         vm.execute_builtin_with_context("ITOA", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::String("42".to_string()));
 
         // ATOI
-        vm.push(Value::String("123".to_string()));
+        vm.push(Value::String("123".to_string())).unwrap();
         vm.execute_builtin_with_context("ATOI", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(123));
     }
@@ -753,7 +1247,7 @@ mod tests {
     #[test]
     fn test_vm_say_command() {
         let mut vm = Vm::new();
-        vm.push(Value::String("Hello World".to_string()));
+        vm.push(Value::String("Hello World".to_string())).unwrap();
         vm.execute_builtin_with_context("SAY", None).unwrap();
         assert_eq!(vm.output(), &["Hello World"]);
     }
@@ -776,106 +1270,331 @@ mod tests {
     }
 
     #[test]
-    fn test_vm_new_builtins() {
-        // Test PICK
-        let vm = test_builtin("PICK", |vm| {
-            vm.push(Value::Integer(1));
-            vm.push(Value::Integer(2));
-            vm.push(Value::Integer(3));
-            vm.push(Value::Integer(1)); // Pick index 1 (should get value 2)
-        });
-        assert_eq!(vm.stack().last(), Some(&Value::Integer(2)));
+    fn test_vm_stack_depth_limit() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
 
-        // Test STRLEN
-        let vm = test_builtin("STRLEN", |vm| {
-            vm.push(Value::String("hello".to_string()));
-        });
-        assert_eq!(vm.stack().last(), Some(&Value::Integer(5)));
+        // Each iteration leaves one extra value behind
+        let script = parse_script("ON SELECT {\n    1 WHILE { 1 1 }\n}\n").unwrap();
 
-        // Test UPPERCASE
-        let vm = test_builtin("UPPERCASE", |vm| {
-            vm.push(Value::String("hello".to_string()));
-        });
-        assert_eq!(vm.stack().last(), Some(&Value::String("HELLO".to_string())));
+        let mut actions = ();
+        let mut context = ScriptContext::new(SecurityLevel::Cyborg, &mut actions);
+        let limits = ExecutionLimits::custom()
+            .with_max_instructions(100_000)
+            .with_max_stack_depth(64);
+        let mut vm = Vm::with_limits(limits);
+        let err = vm
+            .execute_handler(&script, EventType::Select, &mut context)
+            .unwrap_err();
 
-        // Test LOWERCASE
-        let vm = test_builtin("LOWERCASE", |vm| {
-            vm.push(Value::String("WORLD".to_string()));
-        });
-        assert_eq!(vm.stack().last(), Some(&Value::String("world".to_string())));
+        assert_eq!(err.error, VmError::StackOverflow);
+        assert_eq!(vm.stack().len(), 64);
     }
 
     #[test]
-    fn test_vm_integration_greeting() {
-        use crate::iptscrae::{EventType, Lexer, Parser, ScriptActions, ScriptContext, SecurityLevel};
-        use crate::AssetSpec;
-
-        // Test action handler that captures SAY output
-        struct TestActions {
-            output: Vec<String>,
-        }
-        impl ScriptActions for TestActions {
-            fn say(&mut self, message: &str) {
-                self.output.push(message.to_string());
-            }
-            fn chat(&mut self, _message: &str) {}
-            fn local_msg(&mut self, _message: &str) {}
-            fn room_msg(&mut self, _message: &str) {}
-            fn private_msg(&mut self, _user_id: i32, _message: &str) {}
-            fn goto_room(&mut self, _room_id: i16) {}
-            fn lock_door(&mut self, _door_id: i32) {}
-            fn unlock_door(&mut self, _door_id: i32) {}
-            fn set_face(&mut self, _face_id: i16) {}
-            fn set_color(&mut self, _color: i16) {}
-            fn set_props(&mut self, _props: Vec<AssetSpec>) {}
-            fn set_pos(&mut self, _x: i16, _y: i16) {}
-            fn move_user(&mut self, _dx: i16, _dy: i16) {}
-            fn goto_url(&mut self, _url: &str) {}
-            fn goto_url_frame(&mut self, _url: &str, _frame: &str) {}
-            fn global_msg(&mut self, _message: &str) {}
-            fn status_msg(&mut self, _message: &str) {}
-            fn superuser_msg(&mut self, _message: &str) {}
-            fn log_msg(&mut self, _message: &str) {}
-            fn set_spot_state(&mut self, _spot_id: i32, _state: i32) {}
-            fn add_loose_prop(&mut self, _prop_id: i32, _x: i16, _y: i16) {}
-            fn clear_loose_props(&mut self) {}
-            fn play_sound(&mut self, _sound_id: i32) {}
-            fn play_midi(&mut self, _midi_id: i32) {}
-            fn stop_midi(&mut self) {}
-            fn beep(&mut self) {}
-            fn launch_app(&mut self, _url: &str) {}
+    fn test_vm_stack_depth_limit_within_builtin() {
+        // MOUSEPOS pushes two values; the second one would overflow
+        let mut vm = Vm::with_limits(ExecutionLimits::custom().with_max_stack_depth(4));
+        for n in 0..3 {
+            vm.push(Value::Integer(n)).unwrap();
         }
+        let err = vm
+            .execute_builtin_with_context("MOUSEPOS", None)
+            .unwrap_err();
+        assert_eq!(err, VmError::StackOverflow);
+        assert_eq!(vm.stack().len(), 4);
+    }
 
-        // Test a simple greeting script
-        let source = r#"
-            ON ENTER {
-                USERNAME " has entered!" & SAY
-            }
-        "#;
+    #[test]
+    fn test_vm_array_size_limit() {
+        let mut vm = Vm::with_limits(ExecutionLimits::custom().with_max_array_size(10));
 
-        let mut lexer = Lexer::new(source);
-        let tokens = lexer.tokenize().unwrap();
-        let mut parser = Parser::new(tokens);
-        let script = parser.parse().unwrap();
+        vm.push(Value::Integer(10)).unwrap();
+        vm.execute_builtin_with_context("ARRAY", None).unwrap();
+        let arr = vm.pop("test").unwrap();
 
-        let mut actions = TestActions {
-            output: Vec::new(),
+        vm.push(Value::Integer(100)).unwrap();
+        let result = vm.execute_builtin_with_context("ARRAY", None);
+        assert_eq!(result, Err(VmError::AllocationLimitExceeded));
+
+        // APPEND may not grow past the limit either
+        vm.push(arr).unwrap();
+        vm.push(Value::Integer(1)).unwrap();
+        let result = vm.execute_builtin_with_context("APPEND", None);
+        assert_eq!(result, Err(VmError::AllocationLimitExceeded));
+    }
+
+    #[test]
+    fn test_vm_put_on_shared_array() {
+        use crate::iptscrae::{EventType, ScriptAction, ScriptContext, SecurityLevel};
+
+        let script =
+            parse_script("ON SELECT {\n    3 ARRAY a =\n    a b =\n    b 0 99 PUT b =\n}\n")
+                .unwrap();
+        let mut actions: Vec<ScriptAction> = Vec::new();
+        let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+        let mut vm = Vm::new();
+        vm.execute_handler(&script, EventType::Select, &mut context)
+            .unwrap();
+
+        let a = vm.get_variable("a").unwrap().as_array().unwrap();
+        let b = vm.get_variable("b").unwrap().as_array().unwrap();
+        assert_eq!(a[0], Value::Integer(0));
+        assert_eq!(b[0], Value::Integer(99));
+    }
+
+    #[test]
+    fn test_vm_large_array_reads() {
+        use crate::iptscrae::{EventType, ScriptAction, ScriptContext, SecurityLevel};
+
+        // Reading an array variable shares its storage instead of copying it,
+        // so summing it is linear rather than quadratic in its length
+        let script = parse_script(
+            r#"
+            ON SELECT {
+                0 i =
+                0 ARRAY
+                1 WHILE { i APPEND i 1 + i = i 1000 < }
+                a =
+                0 i = 0 sum =
+                1 WHILE { sum a i GET + sum = i 1 + i = i a LENGTH < }
+                a
+            }
+            "#,
+        )
+        .unwrap();
+        let mut actions: Vec<ScriptAction> = Vec::new();
+        let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+        let mut vm = Vm::new();
+        vm.execute_handler(&script, EventType::Select, &mut context)
+            .unwrap();
+
+        assert_eq!(vm.get_variable("sum"), Some(&Value::Integer(499_500)));
+        let (Some(Value::Array(read)), Some(Value::Array(stored))) =
+            (vm.stack().last(), vm.get_variable("a"))
+        else {
+            panic!("expected arrays");
         };
-        {
-            let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
-            context.user_name = "Alice".to_string();
-            context.event_type = EventType::Enter;
+        assert_eq!(stored.len(), 1000);
+        assert!(Arc::ptr_eq(read, stored));
+        assert_eq!(Arc::strong_count(stored), 2);
+    }
 
-            let mut vm = Vm::new();
-            vm.execute_handler(&script, EventType::Enter, &mut context)
+    #[test]
+    fn test_vm_output_limits() {
+        use crate::iptscrae::{EventType, ScriptAction, ScriptContext, SecurityLevel};
+
+        let spam = parse_script(
+            "ON SELECT {\n    0 i =\n    1 WHILE { \"spam\" SAY i 1 + i = i 1000 < }\n}\n",
+        )
+        .unwrap();
+        let greet = parse_script("ON SELECT {\n    \"hi\" SAY \"there\" CHAT \"all\" ROOMMSG\n}\n")
+            .unwrap();
+        let run = |limits: ExecutionLimits, script: &Script| {
+            let mut actions: Vec<ScriptAction> = Vec::new();
+            let mut context = ScriptContext::new(SecurityLevel::Cyborg, &mut actions);
+            let result = Vm::with_limits(limits)
+                .execute_handler(script, EventType::Select, &mut context)
+                .map_err(|err| err.error);
+            (result, actions.len())
+        };
+
+        // A loop of 1000 SAYs is cut off at the cyborg cap
+        let (result, said) = run(ExecutionLimits::cyborg(), &spam);
+        assert_eq!(result, Err(VmError::OutputLimitExceeded));
+        assert_eq!(said, 16);
+
+        // A handful of messages is fine
+        assert_eq!(run(ExecutionLimits::cyborg(), &greet), (Ok(()), 3));
+
+        // Server scripts aren't capped
+        assert_eq!(run(ExecutionLimits::server(), &spam), (Ok(()), 1000));
+
+        // The byte cap counts text across every message builtin: "hi" and
+        // "there" fill it exactly, so only ROOMMSG is refused
+        let limits = ExecutionLimits::custom().with_max_total_say_bytes(7);
+        assert_eq!(run(limits, &greet), (Err(VmError::OutputLimitExceeded), 2));
+    }
+
+    #[test]
+    fn test_vm_typed_event_data() {
+        use crate::iptscrae::{ScriptContext, SecurityLevel};
+
+        let mut actions = ();
+        let mut ctx = ScriptContext::new(SecurityLevel::Server, &mut actions);
+        ctx.user_id = 1;
+        ctx.set_chat_user(11);
+        ctx.set_target_user(22);
+        ctx.set_door_id(3);
+        ctx.set_spot_id(4);
+        assert_eq!(ctx.chat_user(), Some(11));
+        assert_eq!(ctx.target_user(), Some(22));
+        assert_eq!(ctx.door_id(), Some(3));
+        assert_eq!(ctx.spot_id(), Some(4));
+
+        let mut vm = Vm::new();
+        for (builtin, expected) in [
+            ("WHOCHAT", 11),
+            ("WHOTARGET", 22),
+            ("DOORIDX", 3),
+            ("SPOTIDX", 4),
+        ] {
+            vm.execute_builtin_with_context(builtin, Some(&mut ctx))
                 .unwrap();
+            assert_eq!(vm.pop(builtin).unwrap(), Value::Integer(expected));
         }
+    }
 
-        assert_eq!(actions.output, vec!["Alice has entered!"]);
+    #[test]
+    fn test_vm_user_flags() {
+        use crate::iptscrae::{ScriptContext, SecurityLevel};
+        use crate::messages::flags::UserFlags;
+
+        let mut actions = ();
+        let mut ctx = ScriptContext::builder(SecurityLevel::Server, &mut actions)
+            .user_flags(UserFlags::GUEST)
+            .build();
+
+        let mut vm = Vm::new();
+        vm.execute_builtin_with_context("ISGUEST", Some(&mut ctx))
+            .unwrap();
+        assert_eq!(vm.pop("ISGUEST").unwrap(), Value::Integer(1));
+
+        ctx.user_flags.remove(UserFlags::GUEST);
+        vm.execute_builtin_with_context("ISGUEST", Some(&mut ctx))
+            .unwrap();
+        assert_eq!(vm.pop("ISGUEST").unwrap(), Value::Integer(0));
+
+        // Wizard flag grants ISWIZARD but not ISGOD
+        ctx.user_flags = UserFlags::SUPERUSER;
+        vm.execute_builtin_with_context("ISWIZARD", Some(&mut ctx))
+            .unwrap();
+        assert_eq!(vm.pop("ISWIZARD").unwrap(), Value::Integer(1));
+        vm.execute_builtin_with_context("ISGOD", Some(&mut ctx))
+            .unwrap();
+        assert_eq!(vm.pop("ISGOD").unwrap(), Value::Integer(0));
+
+        // God flag grants both
+        ctx.user_flags = UserFlags::GOD;
+        for builtin in ["ISGOD", "ISWIZARD"] {
+            vm.execute_builtin_with_context(builtin, Some(&mut ctx))
+                .unwrap();
+            assert_eq!(vm.pop(builtin).unwrap(), Value::Integer(1));
+        }
     }
 
     #[test]
-    fn test_vm_integration_counter() {
+    fn test_vm_event_data_defaults() {
+        use crate::iptscrae::{ScriptContext, SecurityLevel};
+
+        let mut actions = ();
+        let mut ctx = ScriptContext::new(SecurityLevel::Server, &mut actions);
+        ctx.user_id = 5;
+        assert_eq!(ctx.chat_user(), None);
+
+        let mut vm = Vm::new();
+        for (builtin, expected) in [
+            ("WHOCHAT", 5),
+            ("WHOTARGET", 0),
+            ("DOORIDX", -1),
+            ("SPOTIDX", -1),
+        ] {
+            vm.execute_builtin_with_context(builtin, Some(&mut ctx))
+                .unwrap();
+            assert_eq!(vm.pop(builtin).unwrap(), Value::Integer(expected));
+        }
+    }
+
+    #[test]
+    fn test_vm_datetime_with_fixed_clock() {
+        use crate::iptscrae::Clock;
+        use std::time::SystemTime;
+
+        struct FixedClock;
+        impl Clock for FixedClock {
+            fn now(&self) -> SystemTime {
+                SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+            }
+        }
+
+        let mut vm = Vm::new();
+        vm.set_clock(Box::new(FixedClock));
+
+        vm.execute_builtin_with_context("DATETIME", None).unwrap();
+        assert_eq!(
+            vm.pop("test").unwrap(),
+            Value::String("11/14/2023 22:13:20".to_string())
+        );
+
+        vm.execute_builtin_with_context("TIMESTAMP", None).unwrap();
+        assert_eq!(vm.pop("test").unwrap(), Value::Integer(1_700_000_000));
+    }
+
+    #[test]
+    fn test_vm_ticks_with_fake_clock() {
+        use crate::iptscrae::Clock;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicI64, Ordering};
+        use std::time::SystemTime;
+
+        struct FakeClock {
+            ticks: Arc<AtomicI64>,
+        }
+        impl Clock for FakeClock {
+            fn now(&self) -> SystemTime {
+                SystemTime::UNIX_EPOCH
+            }
+            fn ticks(&self) -> i64 {
+                self.ticks.load(Ordering::SeqCst)
+            }
+        }
+
+        let ticks = Arc::new(AtomicI64::new(12_345));
+        let mut vm = Vm::new();
+        vm.set_clock(Box::new(FakeClock {
+            ticks: Arc::clone(&ticks),
+        }));
+
+        vm.execute_builtin_with_context("TICKS", None).unwrap();
+        assert_eq!(vm.pop("test").unwrap(), Value::Integer(12_345));
+
+        // Advancing the fake clock is visible to the next call
+        ticks.store(12_945, Ordering::SeqCst);
+        vm.execute_builtin_with_context("TICKS", None).unwrap();
+        assert_eq!(vm.pop("test").unwrap(), Value::Integer(12_945));
+    }
+
+    #[test]
+    fn test_vm_new_builtins() {
+        // Test PICK
+        let vm = test_builtin("PICK", |vm| {
+            vm.push(Value::Integer(1)).unwrap();
+            vm.push(Value::Integer(2)).unwrap();
+            vm.push(Value::Integer(3)).unwrap();
+            vm.push(Value::Integer(1)).unwrap(); // Pick index 1 (should get value 2)
+        });
+        assert_eq!(vm.stack().last(), Some(&Value::Integer(2)));
+
+        // Test STRLEN
+        let vm = test_builtin("STRLEN", |vm| {
+            vm.push(Value::String("hello".to_string())).unwrap();
+        });
+        assert_eq!(vm.stack().last(), Some(&Value::Integer(5)));
+
+        // Test UPPERCASE
+        let vm = test_builtin("UPPERCASE", |vm| {
+            vm.push(Value::String("hello".to_string())).unwrap();
+        });
+        assert_eq!(vm.stack().last(), Some(&Value::String("HELLO".to_string())));
+
+        // Test LOWERCASE
+        let vm = test_builtin("LOWERCASE", |vm| {
+            vm.push(Value::String("WORLD".to_string())).unwrap();
+        });
+        assert_eq!(vm.stack().last(), Some(&Value::String("world".to_string())));
+    }
+
+    #[test]
+    fn test_vm_integration_greeting() {
         use crate::iptscrae::{EventType, Lexer, Parser, ScriptActions, ScriptContext, SecurityLevel};
         use crate::AssetSpec;
 
@@ -915,6 +1634,210 @@ mod tests {
             fn launch_app(&mut self, _url: &str) {}
         }
 
+        // Test a simple greeting script
+        let source = r#"
+            ON ENTER {
+                USERNAME " has entered!" & SAY
+            }
+        "#;
+
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+
+        let mut actions = TestActions {
+            output: Vec::new(),
+        };
+        {
+            let mut context = ScriptContext::builder(SecurityLevel::Server, &mut actions)
+                .user_name("Alice")
+                .event_type(EventType::Enter)
+                .build();
+
+            let mut vm = Vm::new();
+            vm.execute_handler(&script, EventType::Enter, &mut context)
+                .unwrap();
+        }
+
+        assert_eq!(actions.output, vec!["Alice has entered!"]);
+    }
+
+    #[test]
+    fn test_vm_macro() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
+
+        let parse = |source: &str| {
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            Parser::new(tokens).parse().unwrap()
+        };
+        let script = parse("ON SELECT { 1 5 MACRO + 7 MACRO }");
+
+        let mut actions = ();
+        let mut context = ScriptContext::builder(SecurityLevel::Server, &mut actions)
+            .macro_script(5, parse("ON SELECT { 41 \"ran\" flag = } ON ENTER { 0 }"))
+            .build();
+
+        // Macro 5 pushes 41 and sets a variable; unknown macro 7 does nothing
+        let mut vm = Vm::new();
+        vm.fire_event(&script, EventType::Select, &mut context)
+            .unwrap();
+        assert_eq!(vm.stack(), &[Value::Integer(42)]);
+        assert_eq!(
+            vm.get_variable("flag"),
+            Some(&Value::String("ran".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_vm_macro_recursion_limits() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
+
+        let tokens = Lexer::new("ON SELECT { 1 MACRO }").tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+
+        let mut actions = ();
+        let mut context = ScriptContext::builder(SecurityLevel::Server, &mut actions)
+            .macro_script(1, script.clone())
+            .build();
+
+        // Unlimited VMs still stop at the nesting cap
+        let mut vm = Vm::new();
+        let err = vm
+            .fire_event(&script, EventType::Select, &mut context)
+            .unwrap_err();
+        assert_eq!(err.error, VmError::MacroDepthExceeded);
+
+        // Macro instructions count toward the handler's instruction limit
+        let mut vm = Vm::with_limits(ExecutionLimits::custom().with_max_instructions(20));
+        let err = vm
+            .fire_event(&script, EventType::Select, &mut context)
+            .unwrap_err();
+        assert_eq!(err.error, VmError::InstructionLimitExceeded);
+    }
+
+    #[test]
+    fn test_vm_lenient_variables() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
+
+        let tokens = Lexer::new("ON SELECT { count 1 + count = }")
+            .tokenize()
+            .unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let mut actions = ();
+        let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+
+        let mut strict = Vm::new();
+        let err = strict
+            .fire_event(&script, EventType::Select, &mut context)
+            .unwrap_err();
+        assert!(matches!(
+            err.error,
+            VmError::UndefinedVariable { ref name } if name == "count"
+        ));
+
+        let mut lenient = Vm::new().lenient_variables(true);
+        lenient
+            .fire_event(&script, EventType::Select, &mut context)
+            .unwrap();
+        assert_eq!(lenient.get_variable("count"), Some(&Value::Integer(1)));
+    }
+
+    #[test]
+    fn test_vm_fire_event() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
+
+        let parse = |source: &str| {
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            Parser::new(tokens).parse().unwrap()
+        };
+        let script = parse("ON SELECT { 0 entered = } ON ENTER { 1 entered = }");
+
+        let mut actions = ();
+        let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+        assert_eq!(context.event_type, EventType::Select);
+
+        let mut vm = Vm::new();
+        vm.fire_event(&script, EventType::Enter, &mut context)
+            .unwrap();
+        assert_eq!(context.event_type, EventType::Enter);
+        assert_eq!(vm.get_variable("entered"), Some(&Value::Integer(1)));
+
+        // A failing script doesn't stop later ones
+        let broken = parse("ON LEAVE { DROP }");
+        let leave = parse("ON LEAVE { 2 entered = }");
+        let errors = vm.fire_events([&broken, &leave], EventType::Leave, &mut context);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0].error, VmError::StackUnderflow { .. }));
+        assert_eq!(context.event_type, EventType::Leave);
+        assert_eq!(vm.get_variable("entered"), Some(&Value::Integer(2)));
+    }
+
+    #[test]
+    fn test_vm_signon_sets_up_globals() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
+
+        let tokens = Lexer::new(
+            r#"ON SIGNON { signons 1 + signons = "hello" greeting = }
+               ON ENTER { rooms 1 + rooms = }
+               ON SIGNOFF { 0 greeting = }"#,
+        )
+        .tokenize()
+        .unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+
+        let mut actions = ();
+        let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+        let mut vm = Vm::new();
+        vm.set_variable("signons".to_string(), Value::Integer(0));
+        vm.set_variable("rooms".to_string(), Value::Integer(0));
+
+        // A session: sign on, wander through a few rooms, sign off
+        vm.fire_event(&script, EventType::SignOn, &mut context)
+            .unwrap();
+        for event in [EventType::Enter, EventType::Leave, EventType::Enter] {
+            vm.fire_event(&script, event, &mut context).unwrap();
+        }
+        assert_eq!(vm.get_variable("signons"), Some(&Value::Integer(1)));
+        assert_eq!(vm.get_variable("rooms"), Some(&Value::Integer(2)));
+        assert_eq!(vm.get_variable("greeting"), Some(&Value::string("hello")));
+
+        vm.fire_event(&script, EventType::SignOff, &mut context)
+            .unwrap();
+        assert_eq!(context.event_type.to_mask(), crate::EventMask::SIGNOFF);
+        assert_eq!(vm.get_variable("signons"), Some(&Value::Integer(1)));
+        assert_eq!(vm.get_variable("greeting"), Some(&Value::Integer(0)));
+    }
+
+    #[test]
+    fn test_vm_integration_inchat() {
+        use crate::iptscrae::{EventType, ScriptAction, ScriptContext, SecurityLevel};
+
+        let script = parse_script("ON INCHAT {\n    CHATSTR UPPERCASE SAY\n}\n").unwrap();
+        let mut actions: Vec<ScriptAction> = Vec::new();
+        {
+            let mut context = ScriptContext::builder(SecurityLevel::Server, &mut actions)
+                .event_type(EventType::InChat)
+                .chat_text("hello there")
+                .build();
+            let mut vm = Vm::new();
+            vm.execute_handler(&script, EventType::InChat, &mut context)
+                .unwrap();
+
+            // Without chat text, CHATSTR pushes an empty string
+            context.chat_text = None;
+            vm.execute_builtin_with_context("INCHATSTR", Some(&mut context))
+                .unwrap();
+            assert_eq!(vm.pop("test").unwrap(), Value::String(String::new()));
+        }
+
+        assert_eq!(actions, [ScriptAction::Say("HELLO THERE".to_string())]);
+    }
+
+    #[test]
+    fn test_vm_integration_counter() {
+        use crate::iptscrae::{EventType, Lexer, Parser, ScriptAction, ScriptContext, SecurityLevel};
+
         // Test a script with variables and arithmetic
         let source = r#"
             ON SELECT {
@@ -930,31 +1853,27 @@ mod tests {
         let mut parser = Parser::new(tokens);
         let script = parser.parse().unwrap();
 
-        let mut actions = TestActions {
-            output: Vec::new(),
-        };
+        let mut actions: Vec<ScriptAction> = Vec::new();
         let mut vm = Vm::new();
         vm.set_variable("counter".to_string(), Value::Integer(0));
 
         // Click once
         {
             let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
-            context.event_type = EventType::Select;
-            vm.execute_handler(&script, EventType::Select, &mut context)
+            vm.fire_event(&script, EventType::Select, &mut context)
                 .unwrap();
         }
-        assert_eq!(actions.output, vec!["1 clicks"]);
+        assert_eq!(actions, [ScriptAction::Say("1 clicks".to_string())]);
         assert_eq!(vm.get_variable("counter"), Some(&Value::Integer(1)));
 
         // Click again
-        actions.output.clear();
+        actions.clear();
         {
             let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
-            context.event_type = EventType::Select;
-            vm.execute_handler(&script, EventType::Select, &mut context)
+            vm.fire_event(&script, EventType::Select, &mut context)
                 .unwrap();
         }
-        assert_eq!(actions.output, vec!["2 clicks"]);
+        assert_eq!(actions, [ScriptAction::Say("2 clicks".to_string())]);
         assert_eq!(vm.get_variable("counter"), Some(&Value::Integer(2)));
     }
 
@@ -981,7 +1900,56 @@ mod tests {
         let mut vm = Vm::new();
         let result = vm.execute_handler(&script, EventType::Select, &mut context);
 
-        assert!(matches!(result, Err(VmError::SecurityViolation { .. })));
+        assert!(matches!(
+            result,
+            Err(VmErrorAt {
+                error: VmError::SecurityViolation { .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_vm_last_run_stats() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
+
+        // Five expression statements: two literals, a binop, a literal, an assignment
+        let script = parse_script("ON SELECT {\n    1 2 + total =\n}\n").unwrap();
+
+        let mut actions = ();
+        let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+        let mut vm = Vm::new();
+        assert_eq!(vm.last_run_stats(), RunStats::default());
+
+        vm.execute_handler(&script, EventType::Select, &mut context)
+            .unwrap();
+        let stats = vm.last_run_stats();
+        assert!((4..=10).contains(&stats.instructions));
+        assert!(stats.elapsed > Duration::ZERO);
+
+        // Stats reset on each call; a non-matching event runs nothing
+        vm.execute_handler(&script, EventType::Enter, &mut context)
+            .unwrap();
+        assert_eq!(vm.last_run_stats().instructions, 0);
+    }
+
+    #[test]
+    fn test_vm_error_reports_source_position() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
+
+        let source = "ON SELECT {\n    \"ok\" SAY\n    10 0 /\n}\n";
+        let script = parse_script(source).unwrap();
+
+        let mut actions = ();
+        let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+        let mut vm = Vm::new();
+        let err = vm
+            .execute_handler(&script, EventType::Select, &mut context)
+            .unwrap_err();
+
+        assert_eq!(err.error, VmError::DivisionByZero);
+        assert_eq!(err.pos, SourcePos::new(3, 10));
+        assert_eq!(err.to_string(), "Division by zero at line 3, column 10");
     }
 
     #[test]
@@ -1046,10 +2014,8 @@ mod tests {
         };
         {
             let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
-            context.event_type = EventType::Select;
-
             let mut vm = Vm::new();
-            vm.execute_handler(&script, EventType::Select, &mut context)
+            vm.fire_event(&script, EventType::Select, &mut context)
                 .unwrap();
         }
 
@@ -1110,10 +2076,8 @@ mod tests {
         };
         {
             let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
-            context.event_type = EventType::Select;
-
             let mut vm = Vm::new();
-            vm.execute_handler(&script, EventType::Select, &mut context)
+            vm.fire_event(&script, EventType::Select, &mut context)
                 .unwrap();
         }
 
@@ -1129,29 +2093,30 @@ mod tests {
         let mut vm = Vm::new();
 
         // Test POP (alias for DROP)
-        vm.push(Value::Integer(42));
-        vm.push(Value::Integer(99));
+        vm.push(Value::Integer(42)).unwrap();
+        vm.push(Value::Integer(99)).unwrap();
         vm.execute_builtin_with_context("POP", None).unwrap();
         assert_eq!(vm.stack.len(), 1);
         assert_eq!(vm.stack[0], Value::Integer(42));
 
         // Test STACKDEPTH
-        vm.push(Value::Integer(1));
-        vm.push(Value::Integer(2));
+        vm.push(Value::Integer(1)).unwrap();
+        vm.push(Value::Integer(2)).unwrap();
         // Stack now has: 42, 1, 2 (3 items)
         vm.execute_builtin_with_context("STACKDEPTH", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(3));
 
         // Test TOPTYPE
-        vm.push(Value::Integer(123));
+        vm.push(Value::Integer(123)).unwrap();
         vm.execute_builtin_with_context("TOPTYPE", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1)); // 1 = integer
 
-        vm.push(Value::String("test".to_string()));
+        vm.push(Value::String("test".to_string())).unwrap();
         vm.execute_builtin_with_context("TOPTYPE", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(2)); // 2 = string
 
-        vm.push(Value::array(vec![Value::Integer(1), Value::Integer(2)]));
+        vm.push(Value::array(vec![Value::Integer(1), Value::Integer(2)]))
+            .unwrap();
         vm.execute_builtin_with_context("TOPTYPE", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(3)); // 3 = array
 
@@ -1160,20 +2125,20 @@ mod tests {
         vm.set_variable("mystr".to_string(), Value::String("hello".to_string()));
         vm.set_variable("myarr".to_string(), Value::array(vec![Value::Integer(1)]));
 
-        vm.push(Value::String("myint".to_string()));
+        vm.push(Value::String("myint".to_string())).unwrap();
         vm.execute_builtin_with_context("VARTYPE", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1)); // integer
 
-        vm.push(Value::String("mystr".to_string()));
+        vm.push(Value::String("mystr".to_string())).unwrap();
         vm.execute_builtin_with_context("VARTYPE", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(2)); // string
 
-        vm.push(Value::String("myarr".to_string()));
+        vm.push(Value::String("myarr".to_string())).unwrap();
         vm.execute_builtin_with_context("VARTYPE", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(3)); // array
 
         // Non-existent variable should return 0
-        vm.push(Value::String("nonexistent".to_string()));
+        vm.push(Value::String("nonexistent".to_string())).unwrap();
         vm.execute_builtin_with_context("VARTYPE", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(0));
     }
@@ -1183,43 +2148,43 @@ mod tests {
         let mut vm = Vm::new();
 
         // Test UPPERCASE
-        vm.push(Value::String("hello world".to_string()));
+        vm.push(Value::String("hello world".to_string())).unwrap();
         vm.execute_builtin_with_context("UPPERCASE", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::String("HELLO WORLD".to_string()));
 
         // Test LOWERCASE
-        vm.push(Value::String("HELLO WORLD".to_string()));
+        vm.push(Value::String("HELLO WORLD".to_string())).unwrap();
         vm.execute_builtin_with_context("LOWERCASE", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::String("hello world".to_string()));
 
         // Test SUBSTR - found
-        vm.push(Value::String("hello world".to_string()));
-        vm.push(Value::String("world".to_string()));
+        vm.push(Value::String("hello world".to_string())).unwrap();
+        vm.push(Value::String("world".to_string())).unwrap();
         vm.execute_builtin_with_context("SUBSTR", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1));
 
         // Test SUBSTR - not found
-        vm.push(Value::String("hello world".to_string()));
-        vm.push(Value::String("xyz".to_string()));
+        vm.push(Value::String("hello world".to_string())).unwrap();
+        vm.push(Value::String("xyz".to_string())).unwrap();
         vm.execute_builtin_with_context("SUBSTR", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(0));
 
         // Test SUBSTRING
-        vm.push(Value::String("hello world".to_string()));
-        vm.push(Value::Integer(6)); // start
-        vm.push(Value::Integer(5)); // length
+        vm.push(Value::String("hello world".to_string())).unwrap();
+        vm.push(Value::Integer(6)).unwrap(); // start
+        vm.push(Value::Integer(5)).unwrap(); // length
         vm.execute_builtin_with_context("SUBSTRING", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::String("world".to_string()));
 
         // Test STRINDEX - found
-        vm.push(Value::String("hello world".to_string()));
-        vm.push(Value::String("world".to_string()));
+        vm.push(Value::String("hello world".to_string())).unwrap();
+        vm.push(Value::String("world".to_string())).unwrap();
         vm.execute_builtin_with_context("STRINDEX", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(6));
 
         // Test STRINDEX - not found
-        vm.push(Value::String("hello world".to_string()));
-        vm.push(Value::String("xyz".to_string()));
+        vm.push(Value::String("hello world".to_string())).unwrap();
+        vm.push(Value::String("xyz".to_string())).unwrap();
         vm.execute_builtin_with_context("STRINDEX", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(-1));
     }
@@ -1229,7 +2194,7 @@ mod tests {
         let mut vm = Vm::new();
 
         // Test RANDOM - should return 0..max-1
-        vm.push(Value::Integer(100));
+        vm.push(Value::Integer(100)).unwrap();
         vm.execute_builtin_with_context("RANDOM", None).unwrap();
         let result = vm.pop("test").unwrap();
         if let Value::Integer(n) = result {
@@ -1239,23 +2204,23 @@ mod tests {
         }
 
         // Test SQUAREROOT
-        vm.push(Value::Integer(16));
+        vm.push(Value::Integer(16)).unwrap();
         vm.execute_builtin_with_context("SQUAREROOT", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(4));
 
-        vm.push(Value::Integer(100));
+        vm.push(Value::Integer(100)).unwrap();
         vm.execute_builtin_with_context("SQUAREROOT", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(10));
 
         // Test SINE (returns sine * 1000)
-        vm.push(Value::Integer(0));
+        vm.push(Value::Integer(0)).unwrap();
         vm.execute_builtin_with_context("SINE", None).unwrap();
         let result = vm.pop("test").unwrap();
         if let Value::Integer(n) = result {
             assert_eq!(n, 0);
         }
 
-        vm.push(Value::Integer(90));
+        vm.push(Value::Integer(90)).unwrap();
         vm.execute_builtin_with_context("SINE", None).unwrap();
         let result = vm.pop("test").unwrap();
         if let Value::Integer(n) = result {
@@ -1263,14 +2228,14 @@ mod tests {
         }
 
         // Test COSINE (returns cosine * 1000)
-        vm.push(Value::Integer(0));
+        vm.push(Value::Integer(0)).unwrap();
         vm.execute_builtin_with_context("COSINE", None).unwrap();
         let result = vm.pop("test").unwrap();
         if let Value::Integer(n) = result {
             assert!((n - 1000).abs() < 10); // Should be close to 1000
         }
 
-        vm.push(Value::Integer(90));
+        vm.push(Value::Integer(90)).unwrap();
         vm.execute_builtin_with_context("COSINE", None).unwrap();
         let result = vm.pop("test").unwrap();
         if let Value::Integer(n) = result {
@@ -1278,14 +2243,14 @@ mod tests {
         }
 
         // Test TANGENT (returns tangent * 1000)
-        vm.push(Value::Integer(0));
+        vm.push(Value::Integer(0)).unwrap();
         vm.execute_builtin_with_context("TANGENT", None).unwrap();
         let result = vm.pop("test").unwrap();
         if let Value::Integer(n) = result {
             assert_eq!(n, 0);
         }
 
-        vm.push(Value::Integer(45));
+        vm.push(Value::Integer(45)).unwrap();
         vm.execute_builtin_with_context("TANGENT", None).unwrap();
         let result = vm.pop("test").unwrap();
         if let Value::Integer(n) = result {
@@ -1293,60 +2258,208 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_abs_min_max_clamp() {
+        let mut vm = Vm::new();
+        let mut call = |name: &str, args: &[Value]| {
+            for arg in args {
+                vm.push(arg.clone()).unwrap();
+            }
+            vm.execute_builtin_with_context(name, None).unwrap();
+            vm.pop("test").unwrap()
+        };
+        let int = Value::Integer;
+
+        assert_eq!(call("ABS", &[int(-7)]), int(7));
+        assert_eq!(call("ABS", &[int(7)]), int(7));
+        assert_eq!(call("ABS", &[int(i32::MIN)]), int(i32::MAX));
+        assert_eq!(call("ABS", &[Value::Float(-2.5)]), Value::Float(2.5));
+
+        assert_eq!(call("MIN", &[int(-3), int(2)]), int(-3));
+        assert_eq!(call("MAX", &[int(-3), int(2)]), int(2));
+        assert_eq!(call("MAX", &[int(-3), int(-8)]), int(-3));
+        assert_eq!(call("MIN", &[int(1), Value::Float(0.5)]), Value::Float(0.5));
+
+        // n lo hi: below, within and above the range
+        assert_eq!(call("CLAMP", &[int(-5), int(0), int(10)]), int(0));
+        assert_eq!(call("CLAMP", &[int(4), int(0), int(10)]), int(4));
+        assert_eq!(call("CLAMP", &[int(15), int(0), int(10)]), int(10));
+        assert_eq!(call("CLAMP", &[int(-15), int(-10), int(-1)]), int(-10));
+        // lo > hi clamps to lo
+        assert_eq!(call("CLAMP", &[int(5), int(10), int(0)]), int(10));
+        assert_eq!(
+            call("CLAMP", &[Value::Float(1.5), int(0), int(1)]),
+            Value::Float(1.0)
+        );
+    }
+
+    #[test]
+    fn test_pow_log_exp() {
+        use std::f64::consts::E;
+
+        let mut vm = Vm::new();
+        let mut call = |name: &str, args: &[Value]| {
+            for arg in args {
+                vm.push(arg.clone()).unwrap();
+            }
+            vm.execute_builtin_with_context(name, None)
+                .map(|()| vm.pop("test").unwrap().to_float())
+        };
+
+        assert_eq!(
+            call("POW", &[Value::Integer(2), Value::Integer(10)]),
+            Ok(1024.0)
+        );
+        assert!((call("LOG", &[Value::Float(E)]).unwrap() - 1.0).abs() < 1e-12);
+        assert!((call("LOG10", &[Value::Integer(1000)]).unwrap() - 3.0).abs() < 1e-12);
+        assert!((call("EXP", &[Value::Integer(1)]).unwrap() - E).abs() < 1e-12);
+
+        for value in [Value::Integer(0), Value::Float(-1.0)] {
+            assert!(matches!(
+                call("LOG", std::slice::from_ref(&value)),
+                Err(VmError::TypeError { .. })
+            ));
+            assert!(matches!(
+                call("LOG10", &[value]),
+                Err(VmError::TypeError { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_floor_div_mod() {
+        let mut vm = Vm::new();
+        let int = Value::Integer;
+
+        // `/` and `%` truncate toward zero
+        vm.push(int(-7)).unwrap();
+        vm.push(int(3)).unwrap();
+        vm.execute_binop(BinOp::Div).unwrap();
+        assert_eq!(vm.pop("test").unwrap(), int(-2));
+        vm.push(int(-7)).unwrap();
+        vm.push(int(3)).unwrap();
+        vm.execute_binop(BinOp::Mod).unwrap();
+        assert_eq!(vm.pop("test").unwrap(), int(-1));
+
+        // FLOORDIV/FLOORMOD round down, so the modulo follows the divisor's sign
+        let mut call = |name: &str, left: Value, right: Value| {
+            vm.push(left).unwrap();
+            vm.push(right).unwrap();
+            vm.execute_builtin_with_context(name, None)
+                .map(|()| vm.pop("test").unwrap())
+        };
+        assert_eq!(call("FLOORDIV", int(-7), int(3)), Ok(int(-3)));
+        assert_eq!(call("FLOORMOD", int(-7), int(3)), Ok(int(2)));
+        assert_eq!(call("FLOORDIV", int(7), int(-3)), Ok(int(-3)));
+        assert_eq!(call("FLOORMOD", int(7), int(-3)), Ok(int(-2)));
+        assert_eq!(call("FLOORDIV", int(7), int(3)), Ok(int(2)));
+        assert_eq!(call("FLOORMOD", int(-6), int(3)), Ok(int(0)));
+        assert_eq!(
+            call("FLOORMOD", Value::Float(-7.5), int(2)),
+            Ok(Value::Float(0.5))
+        );
+        assert_eq!(
+            call("FLOORMOD", int(1), int(0)),
+            Err(VmError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn test_float_math_operations() {
+        use std::f64::consts::{FRAC_PI_2, PI};
+
+        let mut vm = Vm::new();
+        let mut float_builtin = |name: &str, value: Value| {
+            vm.push(value).unwrap();
+            vm.execute_builtin_with_context(name, None).unwrap();
+            vm.pop("test").unwrap().as_float().unwrap()
+        };
+
+        assert!((float_builtin("SINRAD", Value::Float(FRAC_PI_2)) - 1.0).abs() < 1e-12);
+        assert!(float_builtin("COSRAD", Value::Float(PI)) + 1.0 < 1e-12);
+        assert!(float_builtin("TANRAD", Value::Integer(0)).abs() < 1e-12);
+        assert!((float_builtin("DEG2RAD", Value::Integer(180)) - PI).abs() < 1e-12);
+        assert!((float_builtin("RAD2DEG", Value::Float(FRAC_PI_2)) - 90.0).abs() < 1e-12);
+
+        // Arithmetic promotes to float when either operand is a float
+        let tokens = Lexer::new("ON SELECT { 180 DEG2RAD 2 / SINRAD result = }")
+            .tokenize()
+            .unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let mut vm = Vm::new();
+        let mut actions = ();
+        let mut context = ScriptContext::new(crate::iptscrae::SecurityLevel::Server, &mut actions);
+        vm.fire_event(&script, crate::iptscrae::EventType::Select, &mut context)
+            .unwrap();
+        let result = vm.get_variable("result").and_then(Value::as_float).unwrap();
+        assert!((result - 1.0).abs() < 1e-12);
+
+        // Float literals
+        let tokens = Lexer::new("ON SELECT { 0.5 SINRAD result = }")
+            .tokenize()
+            .unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let mut vm = Vm::new();
+        vm.fire_event(&script, crate::iptscrae::EventType::Select, &mut context)
+            .unwrap();
+        let result = vm.get_variable("result").and_then(Value::as_float).unwrap();
+        assert!((result - 0.5f64.sin()).abs() < 1e-12);
+    }
+
     #[test]
     fn test_phase1_array_operations() {
         let mut vm = Vm::new();
 
         // Test ARRAY
-        vm.push(Value::Integer(5));
+        vm.push(Value::Integer(5)).unwrap();
         vm.execute_builtin_with_context("ARRAY", None).unwrap();
         let arr = vm.pop("test").unwrap();
         assert!(arr.is_array());
         if let Value::Array(ref a) = arr {
             assert_eq!(a.len(), 5);
             // All elements should be initialized to 0
-            for elem in a {
+            for elem in a.iter() {
                 assert_eq!(*elem, Value::Integer(0));
             }
         }
 
         // Test PUT and GET
         // Create array [0, 0, 0]
-        vm.push(Value::Integer(3));
+        vm.push(Value::Integer(3)).unwrap();
         vm.execute_builtin_with_context("ARRAY", None).unwrap();
         let arr = vm.pop("test").unwrap();
         
         // PUT value 42 at index 1
-        vm.push(arr.clone());
-        vm.push(Value::Integer(1));
-        vm.push(Value::Integer(42));
+        vm.push(arr.clone()).unwrap();
+        vm.push(Value::Integer(1)).unwrap();
+        vm.push(Value::Integer(42)).unwrap();
         vm.execute_builtin_with_context("PUT", None).unwrap();
         let arr = vm.pop("test").unwrap();
 
         // GET value at index 1
-        vm.push(arr.clone());
-        vm.push(Value::Integer(1));
+        vm.push(arr.clone()).unwrap();
+        vm.push(Value::Integer(1)).unwrap();
         vm.execute_builtin_with_context("GET", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(42));
 
         // GET value at index 0 (should still be 0)
-        vm.push(arr.clone());
-        vm.push(Value::Integer(0));
+        vm.push(arr.clone()).unwrap();
+        vm.push(Value::Integer(0)).unwrap();
         vm.execute_builtin_with_context("GET", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(0));
 
         // Test LENGTH on array
-        vm.push(arr);
+        vm.push(arr).unwrap();
         vm.execute_builtin_with_context("LENGTH", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(3));
 
         // Test LENGTH on string
-        vm.push(Value::String("hello".to_string()));
+        vm.push(Value::String("hello".to_string())).unwrap();
         vm.execute_builtin_with_context("LENGTH", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(5));
 
         // Test LENGTH on integer (should return 0)
-        vm.push(Value::Integer(42));
+        vm.push(Value::Integer(42)).unwrap();
         vm.execute_builtin_with_context("LENGTH", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(0));
     }
@@ -1356,33 +2469,33 @@ mod tests {
         let mut vm = Vm::new();
 
         // Create array of size 3
-        vm.push(Value::Integer(3));
+        vm.push(Value::Integer(3)).unwrap();
         vm.execute_builtin_with_context("ARRAY", None).unwrap();
         let arr = vm.pop("test").unwrap();
 
         // Try to GET at negative index - should error
-        vm.push(arr.clone());
-        vm.push(Value::Integer(-1));
+        vm.push(arr.clone()).unwrap();
+        vm.push(Value::Integer(-1)).unwrap();
         let result = vm.execute_builtin_with_context("GET", None);
         assert!(matches!(result, Err(VmError::TypeError { .. })));
 
         // Try to GET at index >= length - should error
-        vm.push(arr.clone());
-        vm.push(Value::Integer(3));
+        vm.push(arr.clone()).unwrap();
+        vm.push(Value::Integer(3)).unwrap();
         let result = vm.execute_builtin_with_context("GET", None);
         assert!(matches!(result, Err(VmError::TypeError { .. })));
 
         // Try to PUT at negative index - should error
-        vm.push(arr.clone());
-        vm.push(Value::Integer(-1));
-        vm.push(Value::Integer(42));
+        vm.push(arr.clone()).unwrap();
+        vm.push(Value::Integer(-1)).unwrap();
+        vm.push(Value::Integer(42)).unwrap();
         let result = vm.execute_builtin_with_context("PUT", None);
         assert!(matches!(result, Err(VmError::TypeError { .. })));
 
         // Try to PUT at index >= length - should error
-        vm.push(arr.clone());
-        vm.push(Value::Integer(3));
-        vm.push(Value::Integer(42));
+        vm.push(arr.clone()).unwrap();
+        vm.push(Value::Integer(3)).unwrap();
+        vm.push(Value::Integer(42)).unwrap();
         let result = vm.execute_builtin_with_context("PUT", None);
         assert!(matches!(result, Err(VmError::TypeError { .. })));
     }
@@ -1392,45 +2505,306 @@ mod tests {
         let mut vm = Vm::new();
 
         // Test AND
-        vm.push(Value::Integer(1)); // true
-        vm.push(Value::Integer(1)); // true
+        vm.push(Value::Integer(1)).unwrap(); // true
+        vm.push(Value::Integer(1)).unwrap(); // true
         vm.execute_builtin_with_context("AND", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1));
 
-        vm.push(Value::Integer(1)); // true
-        vm.push(Value::Integer(0)); // false
+        vm.push(Value::Integer(1)).unwrap(); // true
+        vm.push(Value::Integer(0)).unwrap(); // false
         vm.execute_builtin_with_context("AND", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(0));
 
         // Test OR
-        vm.push(Value::Integer(0)); // false
-        vm.push(Value::Integer(1)); // true
+        vm.push(Value::Integer(0)).unwrap(); // false
+        vm.push(Value::Integer(1)).unwrap(); // true
         vm.execute_builtin_with_context("OR", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1));
 
-        vm.push(Value::Integer(0)); // false
-        vm.push(Value::Integer(0)); // false
+        vm.push(Value::Integer(0)).unwrap(); // false
+        vm.push(Value::Integer(0)).unwrap(); // false
         vm.execute_builtin_with_context("OR", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(0));
 
         // Test XOR
-        vm.push(Value::Integer(1)); // true
-        vm.push(Value::Integer(0)); // false
+        vm.push(Value::Integer(1)).unwrap(); // true
+        vm.push(Value::Integer(0)).unwrap(); // false
         vm.execute_builtin_with_context("XOR", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1));
 
-        vm.push(Value::Integer(1)); // true
-        vm.push(Value::Integer(1)); // true
+        vm.push(Value::Integer(1)).unwrap(); // true
+        vm.push(Value::Integer(1)).unwrap(); // true
         vm.execute_builtin_with_context("XOR", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(0));
 
         // Test NOT
-        vm.push(Value::Integer(1)); // true
+        vm.push(Value::Integer(1)).unwrap(); // true
         vm.execute_builtin_with_context("NOT", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(0));
 
-        vm.push(Value::Integer(0)); // false
+        vm.push(Value::Integer(0)).unwrap(); // false
         vm.execute_builtin_with_context("NOT", None).unwrap();
         assert_eq!(vm.pop("test").unwrap(), Value::Integer(1));
     }
+
+    #[test]
+    fn test_bitwise_operations() {
+        let mut vm = Vm::new();
+        let mut call = |name: &str, args: &[i32]| {
+            for &arg in args {
+                vm.push(Value::Integer(arg)).unwrap();
+            }
+            vm.execute_builtin_with_context(name, None).unwrap();
+            vm.pop("test").unwrap().to_integer()
+        };
+
+        assert_eq!(call("BITOR", &[0xF0, 0x0F]), 0xFF);
+        assert_eq!(call("BITAND", &[0xF0, 0x3C]), 0x30);
+        assert_eq!(call("BITXOR", &[0xFF, 0x0F]), 0xF0);
+        assert_eq!(call("BITNOT", &[5]), -6);
+        assert_eq!(call("BITNOT", &[0]), -1);
+
+        assert_eq!(call("SHL", &[1, 4]), 16);
+        assert_eq!(call("SHR", &[0x100, 4]), 0x10);
+        // SHR zero-fills, so negative values become large positive ones
+        assert_eq!(call("SHR", &[-1, 28]), 0xF);
+        assert_eq!(call("SHL", &[1, 32]), 0);
+        assert_eq!(call("SHR", &[1, -1]), 0);
+
+        // Logical AND still treats its operands as booleans
+        assert_eq!(call("AND", &[0xF0, 0x0F]), 1);
+    }
+
+    #[test]
+    fn test_isnum_tonum() {
+        let mut vm = Vm::new();
+        let mut call = |name: &str, arg: Value| {
+            vm.push(arg).unwrap();
+            vm.execute_builtin_with_context(name, None).unwrap();
+            let mut results = Vec::new();
+            while vm.stack_len() > 0 {
+                results.insert(0, vm.pop("test").unwrap());
+            }
+            results
+        };
+
+        assert_eq!(call("ISNUM", Value::string("42")), vec![Value::Integer(1)]);
+        assert_eq!(call("ISNUM", Value::string("-7")), vec![Value::Integer(1)]);
+        assert_eq!(call("ISNUM", Value::string("4x")), vec![Value::Integer(0)]);
+        assert_eq!(call("ISNUM", Value::string("")), vec![Value::Integer(0)]);
+        assert_eq!(call("ISNUM", Value::Integer(0)), vec![Value::Integer(1)]);
+
+        // Value then success flag, so "0" and "hello" are told apart
+        assert_eq!(
+            call("TONUM", Value::string("42")),
+            vec![Value::Integer(42), Value::Integer(1)]
+        );
+        assert_eq!(
+            call("TONUM", Value::string("0")),
+            vec![Value::Integer(0), Value::Integer(1)]
+        );
+        assert_eq!(
+            call("TONUM", Value::string("hello")),
+            vec![Value::Integer(0), Value::Integer(0)]
+        );
+    }
+
+    #[test]
+    fn test_vm_case() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
+
+        let tokens = Lexer::new(
+            r#"ON SELECT {
+                choice CASE {
+                    1 { "one" picked = }
+                    2 { "two" picked = }
+                    3 { "three" picked = }
+                    DEFAULT { "other" picked = }
+                }
+            }"#,
+        )
+        .tokenize()
+        .unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let mut actions = ();
+        let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+
+        let mut pick = |choice: i32| {
+            let mut vm = Vm::new();
+            vm.set_variable("choice".to_string(), Value::Integer(choice));
+            vm.fire_event(&script, EventType::Select, &mut context)
+                .unwrap();
+            // Nothing is left behind on the stack
+            assert!(vm.stack().is_empty());
+            vm.get_variable("picked").cloned()
+        };
+
+        assert_eq!(pick(1), Some(Value::string("one")));
+        // Only the matching arm runs; no fallthrough into 3 or DEFAULT
+        assert_eq!(pick(2), Some(Value::string("two")));
+        assert_eq!(pick(3), Some(Value::string("three")));
+        assert_eq!(pick(42), Some(Value::string("other")));
+    }
+
+    #[test]
+    fn test_vm_case_without_default() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
+
+        let tokens = Lexer::new(r#"ON SELECT { "b" CASE { "a" { 1 hit = } 1 { 2 hit = } } }"#)
+            .tokenize()
+            .unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let mut actions = ();
+        let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+
+        let mut vm = Vm::new();
+        vm.fire_event(&script, EventType::Select, &mut context)
+            .unwrap();
+        assert_eq!(vm.get_variable("hit"), None);
+
+        assert!(case_matches(&Value::Integer(1), &Value::string("1")));
+        assert!(!case_matches(&Value::string("abc"), &Value::string("xyz")));
+        assert!(case_matches(&Value::float(2.0), &Value::Integer(2)));
+    }
+
+    #[test]
+    fn test_vm_dumpstack_clearstack() {
+        use crate::iptscrae::{EventType, ScriptActions, ScriptContext, SecurityLevel};
+        use crate::AssetSpec;
+
+        #[derive(Default)]
+        struct TestActions {
+            logged: Vec<String>,
+        }
+
+        impl ScriptActions for TestActions {
+            fn say(&mut self, _message: &str) {}
+            fn chat(&mut self, _message: &str) {}
+            fn local_msg(&mut self, _message: &str) {}
+            fn room_msg(&mut self, _message: &str) {}
+            fn private_msg(&mut self, _user_id: i32, _message: &str) {}
+            fn goto_room(&mut self, _room_id: i16) {}
+            fn lock_door(&mut self, _door_id: i32) {}
+            fn unlock_door(&mut self, _door_id: i32) {}
+            fn set_face(&mut self, _face_id: i16) {}
+            fn set_color(&mut self, _color: i16) {}
+            fn set_props(&mut self, _props: Vec<AssetSpec>) {}
+            fn set_pos(&mut self, _x: i16, _y: i16) {}
+            fn move_user(&mut self, _dx: i16, _dy: i16) {}
+            fn goto_url(&mut self, _url: &str) {}
+            fn goto_url_frame(&mut self, _url: &str, _frame: &str) {}
+            fn global_msg(&mut self, _message: &str) {}
+            fn status_msg(&mut self, _message: &str) {}
+            fn superuser_msg(&mut self, _message: &str) {}
+            fn log_msg(&mut self, message: &str) {
+                self.logged.push(message.to_string());
+            }
+            fn set_spot_state(&mut self, _spot_id: i32, _state: i32) {}
+            fn add_loose_prop(&mut self, _prop_id: i32, _x: i16, _y: i16) {}
+            fn clear_loose_props(&mut self) {}
+            fn play_sound(&mut self, _sound_id: i32) {}
+            fn play_midi(&mut self, _midi_id: i32) {}
+            fn stop_midi(&mut self) {}
+            fn beep(&mut self) {}
+            fn launch_app(&mut self, _url: &str) {}
+        }
+
+        let script = Parser::new(
+            Lexer::new(r#"ON SELECT { 1 "two" 3 DUMPSTACK }"#)
+                .tokenize()
+                .unwrap(),
+        )
+        .parse()
+        .unwrap();
+
+        let mut actions = TestActions::default();
+        let mut vm = Vm::new();
+        {
+            let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+            vm.fire_event(&script, EventType::Select, &mut context)
+                .unwrap();
+        }
+        assert_eq!(
+            vm.stack(),
+            &[Value::Integer(1), Value::string("two"), Value::Integer(3)]
+        );
+        assert_eq!(actions.logged, vec!["Stack (3): [1, two, 3]".to_string()]);
+
+        vm.execute_builtin_with_context("CLEARSTACK", None).unwrap();
+        assert!(vm.stack().is_empty());
+        vm.execute_builtin_with_context("DUMPSTACK", None).unwrap();
+        assert_eq!(vm.output(), &["Stack (0): []".to_string()]);
+    }
+
+    #[test]
+    fn test_user_script_state_isolated() {
+        use crate::iptscrae::{EventType, ScriptContext, SecurityLevel};
+
+        let tokens = Lexer::new(r#"ON SELECT { clicks 1 + clicks = }"#)
+            .tokenize()
+            .unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+
+        // Strict VM: a variable leaking from one user would be the only way
+        // the other could read `clicks` without setting it first
+        let mut vm = Vm::new();
+        let mut alice = UserScriptState::new();
+        alice.set("clicks", Value::Integer(0));
+        let mut bob = UserScriptState::new();
+        bob.set("clicks", Value::Integer(10));
+
+        let mut run = |state: &mut UserScriptState, user_id: i32| {
+            let mut actions = ();
+            let mut context = ScriptContext::new(SecurityLevel::Server, &mut actions);
+            context.user_id = user_id;
+            vm.execute_handler_with_state(&script, EventType::Select, &mut context, state)
+        };
+        run(&mut alice, 1).unwrap();
+        run(&mut bob, 2).unwrap();
+        run(&mut alice, 1).unwrap();
+
+        assert_eq!(alice.get("clicks"), Some(&Value::Integer(2)));
+        assert_eq!(bob.get("clicks"), Some(&Value::Integer(11)));
+
+        // A user with no state fails rather than seeing someone else's
+        let mut carol = UserScriptState::new();
+        assert!(run(&mut carol, 3).is_err());
+        assert!(carol.is_empty());
+        assert!(vm.get_variable("clicks").is_none());
+    }
+
+    #[test]
+    fn test_execute_handler_partial_keeps_output() {
+        use crate::iptscrae::{EventType, ScriptAction, ScriptContext, SecurityLevel};
+
+        let script =
+            parse_script("ON SELECT {\n    \"hello\" SAY\n    1 WHILE { 1 }\n}\n").unwrap();
+
+        let mut delivered: Vec<ScriptAction> = Vec::new();
+        let mut context = ScriptContext::new(SecurityLevel::Cyborg, &mut delivered);
+        let mut vm = Vm::with_limits(ExecutionLimits::custom().with_max_instructions(50));
+        let partial = vm
+            .execute_handler_partial(&script, EventType::Select, &mut context)
+            .unwrap_err();
+
+        assert_eq!(partial.error.error, VmError::InstructionLimitExceeded);
+        assert!(partial.hit_limit());
+        assert_eq!(
+            partial.actions,
+            vec![ScriptAction::Say("hello".to_string())]
+        );
+        // Held back until the caller flushes them
+        drop(context);
+        assert!(delivered.is_empty());
+
+        // A run that finishes performs its actions as usual
+        let script = parse_script("ON SELECT {\n    \"bye\" SAY\n}\n").unwrap();
+        let mut context = ScriptContext::new(SecurityLevel::Cyborg, &mut delivered);
+        context.user_name = "Alice".to_string();
+        vm.execute_handler_partial(&script, EventType::Select, &mut context)
+            .unwrap();
+        assert_eq!(context.user_name, "Alice");
+        drop(context);
+        assert_eq!(delivered, vec![ScriptAction::Say("bye".to_string())]);
+    }
 }
@@ -22,6 +22,7 @@
 //! ```
 
 use cfg_if::cfg_if;
+use std::collections::HashSet;
 use std::fmt;
 use std::ops::{Add, Sub};
 
@@ -60,6 +61,8 @@ pub use algo::{crc32, crypt, pseudo_crc32, PalaceCryptError};
 /// In Palace, coordinates use the Mac convention:
 /// - `v` (vertical) increases downward from top of screen
 /// - `h` (horizontal) increases rightward from left of screen
+///
+/// On the wire a point is 4 bytes: `v` then `h`, each a big-endian `i16`.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(C)]
 pub struct Point {
@@ -87,27 +90,57 @@ impl Point {
         dh.hypot(dv)
     }
 
+    /// Scale both coordinates by `factor` about the origin
+    ///
+    /// Results are rounded to the nearest pixel and saturate at the `i16`
+    /// bounds, like `+` and `-`.
+    pub fn scale(self, factor: f32) -> Point {
+        let factor = factor as f64;
+        Self {
+            v: saturate(self.v as f64 * factor),
+            h: saturate(self.h as f64 * factor),
+        }
+    }
+
+    /// Rotate by `degrees` around `about`
+    ///
+    /// Since `v` grows downward, positive angles turn clockwise on screen:
+    /// (10, 0) rotated 90° about the origin lands at (0, 10). Results are
+    /// rounded and saturate like [`Point::scale`].
+    pub fn rotate(self, degrees: f32, about: Point) -> Point {
+        let (sin, cos) = (degrees as f64).to_radians().sin_cos();
+        let dh = self.h as f64 - about.h as f64;
+        let dv = self.v as f64 - about.v as f64;
+        Self {
+            v: saturate(about.v as f64 + dh * sin + dv * cos),
+            h: saturate(about.h as f64 + dh * cos - dv * sin),
+        }
+    }
+
     /// Parse a Point from bytes (v, h order - 4 bytes total)
     #[cfg(feature = "net")]
-    #[allow(unused_imports)]
     pub fn from_bytes(buf: &mut impl bytes::Buf) -> std::io::Result<Self> {
-        use bytes::Buf;
+        use crate::buffer::BufExt;
         Ok(Self {
-            v: buf.get_i16(),
-            h: buf.get_i16(),
+            v: buf.get_i16_checked()?,
+            h: buf.get_i16_checked()?,
         })
     }
 
     /// Serialize this Point to bytes (v, h order - 4 bytes total)
     #[cfg(feature = "net")]
-    #[allow(unused_imports)]
     pub fn to_bytes(&self, buf: &mut impl bytes::BufMut) {
-        use bytes::BufMut;
         buf.put_i16(self.v);
         buf.put_i16(self.h);
     }
 }
 
+/// Round a coordinate to the nearest pixel, saturating at the `i16` bounds
+fn saturate(value: f64) -> i16 {
+    // `as` saturates out-of-range floats (and maps NaN to 0)
+    value.round() as i16
+}
+
 impl Add for Point {
     type Output = Self;
 
@@ -135,11 +168,15 @@ impl Sub for Point {
 /// Assets (props, backgrounds, etc.) are identified by a unique ID within
 /// their type namespace, and verified using a CRC32 checksum.
 ///
-/// Wire format: 10 bytes
-/// - id: 4 bytes (i32, big-endian)
-/// - crc: 4 bytes (u32, big-endian)
-/// - padding: 2 bytes (always 0)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+/// Wire format: [`WIRE_SIZE`](Self::WIRE_SIZE) bytes, big-endian
+/// - bytes 0..4: id (i32)
+/// - bytes 4..8: crc (u32)
+/// - bytes 8..10: padding, written as 0 and ignored when read
+///
+/// The padding trails both fields; nothing sits between `id` and `crc`.
+///
+/// Specs order by `id`, then `crc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 #[repr(C)]
 pub struct AssetSpec {
     /// Asset ID number
@@ -149,6 +186,9 @@ pub struct AssetSpec {
 }
 
 impl AssetSpec {
+    /// Size of a serialized spec, including the 2 trailing padding bytes
+    pub const WIRE_SIZE: usize = 10;
+
     /// Create a new asset spec
     pub const fn new(id: i32, crc: u32) -> Self {
         Self { id, crc }
@@ -159,10 +199,30 @@ impl AssetSpec {
         self.crc == 0
     }
 
+    /// Check prop data against this spec's CRC
+    ///
+    /// Always true for a don't-care spec; otherwise compares against
+    /// [`prop::prop_crc`] of `data`.
+    #[cfg(feature = "prop")]
+    pub fn verify(&self, data: &[u8]) -> bool {
+        self.crc_is_dont_care() || prop::prop_crc(data) == self.crc
+    }
+
     /// Parse an AssetSpec from bytes
     #[allow(unused_imports)]
     pub fn from_bytes(buf: &mut impl bytes::Buf) -> std::io::Result<Self> {
         use bytes::Buf;
+        // Checked by hand: the `room` feature uses this without `buffer`
+        if buf.remaining() < Self::WIRE_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "truncated AssetSpec: {} of {} bytes",
+                    buf.remaining(),
+                    Self::WIRE_SIZE
+                ),
+            ));
+        }
         let spec = Self {
             id: buf.get_i32(),
             crc: buf.get_u32(),
@@ -183,6 +243,15 @@ impl AssetSpec {
     }
 }
 
+/// Remove repeated specs from a prop list, keeping the first of each
+///
+/// The order of the remaining props is unchanged, since it decides how
+/// they layer on the avatar.
+pub fn dedup_props(props: &mut Vec<AssetSpec>) {
+    let mut seen = HashSet::with_capacity(props.len());
+    props.retain(|spec| seen.insert(*spec));
+}
+
 /// Asset type identifier.
 ///
 /// Identifies the type of asset in the Palace Protocol.
@@ -199,6 +268,11 @@ pub enum AssetType {
 
 impl AssetType {
     /// Convert AssetType to its 4-character ASCII representation
+    ///
+    /// Only the known types have a name, so this is one-way for raw codes:
+    /// an unrecognized code read off the wire never becomes an AssetType and
+    /// has no string form here. Use [`from_ascii`](Self::from_ascii) to go
+    /// back from text.
     pub const fn as_str(&self) -> &'static str {
         match self {
             AssetType::Prop => "Prop",
@@ -217,6 +291,18 @@ impl AssetType {
         }
     }
 
+    /// Create AssetType from its 4-character code (e.g. "Prop")
+    ///
+    /// Returns `None` unless `code` is exactly four printable ASCII bytes
+    /// naming a known type, so user-supplied text is safe to display.
+    pub fn from_ascii(code: &str) -> Option<Self> {
+        let bytes: [u8; 4] = code.as_bytes().try_into().ok()?;
+        if !bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            return None;
+        }
+        Self::from_u32(u32::from_be_bytes(bytes))
+    }
+
     /// Get the raw u32 value
     pub const fn as_u32(&self) -> u32 {
         *self as u32
@@ -334,6 +420,18 @@ mod tests {
         assert_eq!(p.v, 200);
     }
 
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_point_wire_order() {
+        let mut buf = Vec::new();
+        Point { v: 1, h: 2 }.to_bytes(&mut buf);
+        assert_eq!(buf, [0x00, 0x01, 0x00, 0x02]);
+
+        let parsed = Point::from_bytes(&mut &buf[..]).unwrap();
+        assert_eq!(parsed, Point { v: 1, h: 2 });
+        assert_eq!(parsed, Point::new(2, 1));
+    }
+
     #[test]
     fn test_point_origin() {
         let origin = Point::origin();
@@ -366,6 +464,32 @@ mod tests {
         assert_eq!(result.v, 5);
     }
 
+    #[test]
+    fn test_point_scale() {
+        assert_eq!(Point::new(10, -20).scale(2.0), Point::new(20, -40));
+        assert_eq!(Point::new(3, 5).scale(0.5), Point::new(2, 3));
+        assert_eq!(
+            Point::new(i16::MAX, i16::MIN).scale(2.0),
+            Point::new(i16::MAX, i16::MIN)
+        );
+    }
+
+    #[test]
+    fn test_point_rotate() {
+        let origin = Point::origin();
+        assert_eq!(Point::new(10, 0).rotate(90.0, origin), Point::new(0, 10));
+        assert_eq!(Point::new(10, 0).rotate(-90.0, origin), Point::new(0, -10));
+        assert_eq!(
+            Point::new(110, 50).rotate(180.0, Point::new(100, 50)),
+            Point::new(90, 50)
+        );
+
+        // Rotating a far corner overshoots i16 and clamps, like `+` does
+        let corner = Point::new(i16::MAX, i16::MAX);
+        assert_eq!(corner.rotate(45.0, origin), Point::new(0, i16::MAX));
+        assert_eq!(corner.rotate(-135.0, origin), Point::new(0, i16::MIN));
+    }
+
     #[test]
     fn test_asset_spec() {
         let spec = AssetSpec::new(123, 0xA95ADE76);
@@ -374,12 +498,95 @@ mod tests {
         assert!(!spec.crc_is_dont_care());
     }
 
+    #[test]
+    fn test_asset_spec_wire_layout() {
+        let spec = AssetSpec::new(0x0102_0304, 0xA95A_DE76);
+        let mut buf = Vec::new();
+        spec.to_bytes(&mut buf);
+        assert_eq!(buf.len(), AssetSpec::WIRE_SIZE);
+        assert_eq!(
+            buf,
+            [0x01, 0x02, 0x03, 0x04, 0xA9, 0x5A, 0xDE, 0x76, 0x00, 0x00]
+        );
+        assert_eq!(AssetSpec::from_bytes(&mut &buf[..]).unwrap(), spec);
+
+        // Padding is ignored when read, and the next spec starts after it
+        let mut buf = vec![0xFF, 0xFF, 0xFF, 0xFE, 0, 0, 0, 1, 0xAB, 0xCD];
+        AssetSpec::new(7, 0).to_bytes(&mut buf);
+        let mut reader = &buf[..];
+        assert_eq!(
+            AssetSpec::from_bytes(&mut reader).unwrap(),
+            AssetSpec::new(-2, 1)
+        );
+        assert_eq!(
+            AssetSpec::from_bytes(&mut reader).unwrap(),
+            AssetSpec::new(7, 0)
+        );
+        assert!(AssetSpec::from_bytes(&mut &buf[..9]).is_err());
+    }
+
     #[test]
     fn test_asset_spec_dont_care() {
         let spec = AssetSpec::new(123, 0);
         assert!(spec.crc_is_dont_care());
     }
 
+    #[cfg(feature = "prop")]
+    #[test]
+    fn test_asset_spec_verify() {
+        let blob = [
+            0x00, 0x2C, 0x00, 0x2C, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0x01, 0x02, 0xFF, 0x10,
+        ];
+
+        assert!(AssetSpec::new(1, 0xC5323BAC).verify(&blob));
+        assert!(!AssetSpec::new(1, 0xC5323BAD).verify(&blob));
+        assert!(!AssetSpec::new(1, 0xC5323BAC).verify(&blob[..16]));
+
+        // Don't-care spec accepts anything
+        assert!(AssetSpec::new(1, 0).verify(&blob));
+        assert!(AssetSpec::new(1, 0).verify(&[]));
+    }
+
+    #[test]
+    fn test_asset_spec_ordering() {
+        let mut props = vec![
+            AssetSpec::new(7, 2),
+            AssetSpec::new(3, 9),
+            AssetSpec::new(7, 1),
+            AssetSpec::new(-1, 0),
+        ];
+        props.sort();
+        assert_eq!(
+            props,
+            [
+                AssetSpec::new(-1, 0),
+                AssetSpec::new(3, 9),
+                AssetSpec::new(7, 1),
+                AssetSpec::new(7, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dedup_props() {
+        let mut props = vec![
+            AssetSpec::new(5, 1),
+            AssetSpec::new(2, 0),
+            AssetSpec::new(5, 1),
+            AssetSpec::new(5, 2),
+            AssetSpec::new(2, 0),
+        ];
+        dedup_props(&mut props);
+        assert_eq!(
+            props,
+            [
+                AssetSpec::new(5, 1),
+                AssetSpec::new(2, 0),
+                AssetSpec::new(5, 2)
+            ]
+        );
+    }
+
     #[test]
     fn test_asset_types() {
         // Verify 4-char ASCII codes
@@ -401,4 +608,19 @@ mod tests {
         let bytes = AssetType::Prop.as_u32().to_be_bytes();
         assert_eq!(&bytes, b"Prop");
     }
+
+    #[test]
+    fn test_asset_type_from_ascii() {
+        assert_eq!(AssetType::from_ascii("Prop"), Some(AssetType::Prop));
+        for asset in [AssetType::Prop, AssetType::Userbase, AssetType::IpUserbase] {
+            assert_eq!(AssetType::from_ascii(asset.as_str()), Some(asset));
+        }
+
+        // Four bytes, but not ASCII
+        assert_eq!("Pr\u{f3}".len(), 4);
+        assert_eq!(AssetType::from_ascii("Pr\u{f3}"), None);
+        assert_eq!(AssetType::from_ascii("Pro\0"), None);
+        assert_eq!(AssetType::from_ascii("Props"), None);
+        assert_eq!(AssetType::from_ascii("prop"), None);
+    }
 }
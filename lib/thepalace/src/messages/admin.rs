@@ -4,6 +4,11 @@
 //! - MessageId::SuperUser: Enter wizard/god mode with password
 //! - MessageId::KillUser: Forcibly disconnect a user
 //! - MessageId::ServerDown: Server shutdown/disconnect notification
+//!
+//! Superuser broadcasts (the wire form of the Iptscrae `SUSRMSG` command) are
+//! `MessageId::Smsg` and live in the chat module as [`SmsgMsg`](crate::messages::SmsgMsg).
+//! The protocol has no dedicated ban message; banning is a server-side
+//! decision reported to the victim via [`ServerDownReason::Banished`].
 
 use bytes::{Buf, BufMut};
 
@@ -67,6 +72,12 @@ impl MessagePayload for KillUserMsg {
     }
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
+        if buf.remaining() < 4 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "KillUser payload needs a 4-byte target id",
+            ));
+        }
         Ok(Self {
             target_id: buf.get_i32(),
         })
@@ -204,6 +215,7 @@ impl MessagePayload for ServerDownMsg {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::messages::SmsgMsg;
 
     #[test]
     fn test_super_user_msg() {
@@ -229,6 +241,36 @@ mod tests {
         assert_eq!(parsed.target_id, 12345);
     }
 
+    #[test]
+    fn test_super_user_msg_rejects_overrun() {
+        // Length byte claims 20 bytes but only 3 follow
+        let buf = [20u8, b'a', b'b', b'c'];
+        let err = SuperUserMsg::from_bytes(&mut &buf[..]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_kill_user_msg_short_payload() {
+        let buf = [0u8, 1];
+        let err = KillUserMsg::from_bytes(&mut &buf[..]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_admin_messages_roundtrip_through_message() {
+        let kill = KillUserMsg::new(42).to_message(0);
+        assert_eq!(kill.msg_id, MessageId::KillUser);
+        assert_eq!(kill.parse_payload::<KillUserMsg>().unwrap().target_id, 42);
+
+        let broadcast = SmsgMsg::new("wizards only").to_message(0);
+        assert_eq!(broadcast.msg_id, MessageId::Smsg);
+        assert_ne!(broadcast.msg_id, SuperUserMsg::message_id());
+        assert_eq!(
+            broadcast.parse_payload::<SmsgMsg>().unwrap().text,
+            "wizards only"
+        );
+    }
+
     #[test]
     fn test_server_down_reason_conversions() {
         assert_eq!(i32::from(ServerDownReason::LoggedOff), 1);
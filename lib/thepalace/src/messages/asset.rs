@@ -35,7 +35,7 @@ pub struct AssetQueryMsg {
 
 impl AssetQueryMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
-        let type_raw = buf.get_u32();
+        let type_raw = buf.get_u32_checked()?;
         let asset_type = AssetType::from_u32(type_raw).ok_or_else(|| {
             std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -87,8 +87,8 @@ pub struct AssetDescriptor {
 impl AssetDescriptor {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            flags: buf.get_u32(),
-            size: buf.get_u32(),
+            flags: buf.get_u32_checked()?,
+            size: buf.get_u32_checked()?,
             name: buf.get_str31()?,
         })
     }
@@ -144,7 +144,7 @@ pub struct AssetSendMsg {
 
 impl AssetSendMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
-        let type_raw = buf.get_u32();
+        let type_raw = buf.get_u32_checked()?;
         let asset_type = AssetType::from_u32(type_raw).ok_or_else(|| {
             std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -153,10 +153,10 @@ impl AssetSendMsg {
         })?;
 
         let spec = AssetSpec::from_bytes(buf)?;
-        let block_size = buf.get_i32();
-        let block_offset = buf.get_i32();
-        let block_nbr = buf.get_i16();
-        let nbr_blocks = buf.get_i16();
+        let block_size = buf.get_i32_checked()?;
+        let block_offset = buf.get_i32_checked()?;
+        let block_nbr = buf.get_i16_checked()?;
+        let nbr_blocks = buf.get_i16_checked()?;
 
         // AssetDescriptor is only present if this is the first block
         let desc = if block_nbr == 0 {
@@ -167,7 +167,7 @@ impl AssetSendMsg {
 
         // Read asset data
         let data = if block_size > 0 {
-            buf.copy_to_bytes(block_size as usize)
+            buf.copy_to_bytes_checked(block_size as usize)?
         } else {
             Bytes::new()
         };
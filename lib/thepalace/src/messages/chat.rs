@@ -8,12 +8,15 @@
 //! - MessageId::Gmsg: Global message (server-wide)
 //! - MessageId::Rmsg: Room message (flagged for superusers)
 //! - MessageId::Smsg: Superuser message
+//!
+//! [`ChatMsg`] unifies plaintext room chat, whispers and thought-balloon emotes
+//! for consumers that don't care about the exact wire message.
 
 use bytes::{Buf, BufMut};
 
 use crate::algo::crypt;
-use crate::buffer::{BufExt, BufMutExt};
-use crate::messages::{MessageId, MessagePayload};
+use crate::buffer::{decode_chat_text, BufExt, BufMutExt};
+use crate::messages::{Message, MessageId, MessagePayload};
 
 /// MessageId::Talk - Normal chat message
 ///
@@ -29,7 +32,7 @@ pub struct TalkMsg {
 impl TalkMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            text: buf.get_cstring()?,
+            text: buf.get_chat_cstring()?,
         })
     }
 
@@ -45,7 +48,7 @@ impl MessagePayload for TalkMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            text: buf.get_cstring()?,
+            text: buf.get_chat_cstring()?,
         })
     }
 
@@ -58,6 +61,17 @@ impl MessagePayload for TalkMsg {
     }
 }
 
+/// Read the i16 length in front of encrypted chat text
+fn get_text_len(buf: &mut impl Buf) -> std::io::Result<usize> {
+    let len = buf.get_i16_checked()?;
+    usize::try_from(len).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("negative text length {}", len),
+        )
+    })
+}
+
 /// MessageId::XTalk - Encrypted chat message
 ///
 /// Similar to MessageId::Talk but text is encrypted to prevent sniffing.
@@ -73,9 +87,9 @@ pub struct XTalkMsg {
 
 impl XTalkMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
-        let len = buf.get_i16() as usize;
+        let len = get_text_len(buf)?;
         let mut text = vec![0u8; len];
-        buf.copy_to_slice(&mut text);
+        buf.copy_to_slice_checked(&mut text)?;
 
         Ok(Self { text })
     }
@@ -86,6 +100,8 @@ impl XTalkMsg {
     }
 
     /// Decrypt the text using the Palace XOR cipher
+    ///
+    /// The plaintext may be UTF-8 or MacRoman; see [`decode_chat_text`].
     pub fn decrypt(&self) -> Result<String, std::io::Error> {
         let decrypted = crypt(&self.text, true).map_err(|e| {
             std::io::Error::new(
@@ -94,12 +110,7 @@ impl XTalkMsg {
             )
         })?;
 
-        String::from_utf8(decrypted).map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Invalid UTF-8 after decryption: {}", e),
-            )
-        })
+        Ok(decode_chat_text(&decrypted))
     }
 
     /// Encrypt plaintext using the Palace XOR cipher
@@ -150,8 +161,8 @@ pub struct WhisperMsg {
 impl WhisperMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            target: buf.get_i32(),
-            text: buf.get_cstring()?,
+            target: buf.get_i32_checked()?,
+            text: buf.get_chat_cstring()?,
         })
     }
 
@@ -191,10 +202,10 @@ pub struct XWhisperMsg {
 
 impl XWhisperMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
-        let target = buf.get_i32();
-        let len = buf.get_i16() as usize;
+        let target = buf.get_i32_checked()?;
+        let len = get_text_len(buf)?;
         let mut text = vec![0u8; len];
-        buf.copy_to_slice(&mut text);
+        buf.copy_to_slice_checked(&mut text)?;
 
         Ok(Self { target, text })
     }
@@ -206,6 +217,8 @@ impl XWhisperMsg {
     }
 
     /// Decrypt the text using the Palace XOR cipher
+    ///
+    /// The plaintext may be UTF-8 or MacRoman; see [`decode_chat_text`].
     pub fn decrypt(&self) -> Result<String, std::io::Error> {
         let decrypted = crypt(&self.text, true).map_err(|e| {
             std::io::Error::new(
@@ -214,12 +227,7 @@ impl XWhisperMsg {
             )
         })?;
 
-        String::from_utf8(decrypted).map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Invalid UTF-8 after decryption: {}", e),
-            )
-        })
+        Ok(decode_chat_text(&decrypted))
     }
 
     /// Encrypt plaintext using the Palace XOR cipher
@@ -264,7 +272,7 @@ pub struct GmsgMsg {
 impl GmsgMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            text: buf.get_cstring()?,
+            text: buf.get_chat_cstring()?,
         })
     }
 
@@ -301,7 +309,7 @@ pub struct RmsgMsg {
 impl RmsgMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            text: buf.get_cstring()?,
+            text: buf.get_chat_cstring()?,
         })
     }
 
@@ -327,16 +335,22 @@ impl MessagePayload for RmsgMsg {
 /// MessageId::Smsg - Superuser message
 ///
 /// Message sent only to superusers (wizards/gods) in the room.
-/// Text is a CString, limited to 255 characters.
+/// Text is a CString, limited to 255 characters. This is the wire form of
+/// the Iptscrae `SUSRMSG` command.
 #[derive(Debug, Clone, PartialEq)]
 pub struct SmsgMsg {
     pub text: String,
 }
 
 impl SmsgMsg {
+    /// Create a new superuser message
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into() }
+    }
+
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            text: buf.get_cstring()?,
+            text: buf.get_chat_cstring()?,
         })
     }
 
@@ -359,6 +373,120 @@ impl MessagePayload for SmsgMsg {
     }
 }
 
+/// Text prefix that makes the client draw a thought balloon
+pub const THOUGHT_PREFIX: char = ':';
+
+/// How a chat line is delivered and displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatKind {
+    /// Normal room chat (word balloon), sent as MessageId::Talk
+    Say,
+    /// Private chat to one user, sent as MessageId::Whisper
+    Whisper { target: i32 },
+    /// Thought balloon, sent as MessageId::Talk with a `:` prefix
+    Emote,
+}
+
+/// Chat line of any kind
+///
+/// Maps onto TalkMsg / WhisperMsg on the wire; use [`ChatMsg::to_message`] and
+/// [`ChatMsg::from_message`] rather than `MessagePayload`, since the message ID
+/// depends on the kind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMsg {
+    pub kind: ChatKind,
+    pub text: String,
+}
+
+impl ChatMsg {
+    /// Create a room chat line
+    pub fn say(text: impl Into<String>) -> Self {
+        Self {
+            kind: ChatKind::Say,
+            text: text.into(),
+        }
+    }
+
+    /// Create a whisper to `target`
+    pub fn whisper(target: i32, text: impl Into<String>) -> Self {
+        Self {
+            kind: ChatKind::Whisper { target },
+            text: text.into(),
+        }
+    }
+
+    /// Create a thought-balloon emote (text without the `:` prefix)
+    pub fn emote(text: impl Into<String>) -> Self {
+        Self {
+            kind: ChatKind::Emote,
+            text: text.into(),
+        }
+    }
+
+    /// Get the whisper target, if this is a whisper
+    pub const fn target(&self) -> Option<i32> {
+        match self.kind {
+            ChatKind::Whisper { target } => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Build the wire message; `ref_num` is the speaker's UserID
+    pub fn to_message(&self, ref_num: i32) -> Message {
+        match self.kind {
+            ChatKind::Say => TalkMsg {
+                text: self.text.clone(),
+            }
+            .to_message(ref_num),
+            ChatKind::Whisper { target } => WhisperMsg {
+                target,
+                text: self.text.clone(),
+            }
+            .to_message(ref_num),
+            ChatKind::Emote => TalkMsg {
+                text: format!("{}{}", THOUGHT_PREFIX, self.text),
+            }
+            .to_message(ref_num),
+        }
+    }
+
+    /// Parse a chat line from a Talk, XTalk, Whisper or XWhisper message
+    ///
+    /// Talk text starting with `:` is read as an emote.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidInput` for other message types, or the payload's parse
+    /// or decryption error.
+    pub fn from_message(message: &Message) -> std::io::Result<Self> {
+        match message.msg_id {
+            MessageId::Talk => Ok(Self::from_talk(message.parse_payload::<TalkMsg>()?.text)),
+            MessageId::XTalk => Ok(Self::from_talk(
+                message.parse_payload::<XTalkMsg>()?.decrypt()?,
+            )),
+            MessageId::Whisper => {
+                let whisper = message.parse_payload::<WhisperMsg>()?;
+                Ok(Self::whisper(whisper.target, whisper.text))
+            }
+            MessageId::XWhisper => {
+                let whisper = message.parse_payload::<XWhisperMsg>()?;
+                Ok(Self::whisper(whisper.target, whisper.decrypt()?))
+            }
+            other => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{:?} is not a chat message", other),
+            )),
+        }
+    }
+
+    fn from_talk(text: String) -> Self {
+        match text.strip_prefix(THOUGHT_PREFIX) {
+            Some(thought) => Self::emote(thought),
+            None => Self::say(text),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -395,6 +523,14 @@ mod tests {
         assert_eq!(parsed.text, msg.text);
     }
 
+    #[test]
+    fn test_talk_msg_either_encoding() {
+        let utf8 = TalkMsg::from_bytes(&mut &b"na\xC3\xAFve\0"[..]).unwrap();
+        assert_eq!(utf8.text, "naïve");
+        let mac_roman = TalkMsg::from_bytes(&mut &b"na\x95ve\0"[..]).unwrap();
+        assert_eq!(mac_roman.text, "naïve");
+    }
+
     #[test]
     fn test_xtalk_msg_roundtrip() {
         let msg = XTalkMsg {
@@ -503,4 +639,53 @@ mod tests {
 
         assert_eq!(parsed, msg);
     }
+
+    #[test]
+    fn test_chat_msg_say_roundtrip() {
+        let chat = ChatMsg::say("Hello, Palace!");
+        let message = chat.to_message(7);
+        assert_eq!(message.msg_id, MessageId::Talk);
+        assert_eq!(ChatMsg::from_message(&message).unwrap(), chat);
+        assert_eq!(chat.target(), None);
+    }
+
+    #[test]
+    fn test_chat_msg_whisper_roundtrip() {
+        let chat = ChatMsg::whisper(12345, "Secret message");
+        let message = chat.to_message(7);
+        assert_eq!(message.msg_id, MessageId::Whisper);
+        assert_eq!(&message.payload[..4], &12345i32.to_be_bytes());
+
+        let parsed = ChatMsg::from_message(&message).unwrap();
+        assert_eq!(parsed, chat);
+        assert_eq!(parsed.target(), Some(12345));
+
+        // Encrypted whispers keep their target too
+        let message = XWhisperMsg::encrypt(42, "psst").unwrap().to_message(7);
+        assert_eq!(
+            ChatMsg::from_message(&message).unwrap(),
+            ChatMsg::whisper(42, "psst")
+        );
+    }
+
+    #[test]
+    fn test_chat_msg_emote_roundtrip() {
+        let chat = ChatMsg::emote("ponders");
+        let message = chat.to_message(7);
+        assert_eq!(message.msg_id, MessageId::Talk);
+        assert_eq!(message.parse_payload::<TalkMsg>().unwrap().text, ":ponders");
+        assert_eq!(ChatMsg::from_message(&message).unwrap(), chat);
+    }
+
+    #[test]
+    fn test_chat_msg_rejects_non_chat() {
+        let message = GmsgMsg {
+            text: "Server announcement".to_string(),
+        }
+        .to_message(0);
+        assert_eq!(
+            ChatMsg::from_message(&message).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
 }
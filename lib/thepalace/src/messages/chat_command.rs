@@ -0,0 +1,99 @@
+//! Slash-command routing for bots
+//!
+//! [`ChatCommandRouter`] maps command words like `/roll` to handlers, so a
+//! bot can answer commands natively instead of parsing them in Iptscrae.
+
+use std::collections::BTreeMap;
+
+/// Prefix that marks a chat line as a command
+pub const COMMAND_PREFIX: char = '/';
+
+type Handler<'a, R> = Box<dyn FnMut(&[&str]) -> R + 'a>;
+
+/// Dispatches `/command arg ...` chat lines to registered handlers
+///
+/// Command words match case-insensitively. Arguments are the rest of the
+/// line split on whitespace. Lines that aren't commands, and commands with no
+/// handler, fall through so the bot can treat them as ordinary chat.
+pub struct ChatCommandRouter<'a, R> {
+    handlers: BTreeMap<String, Handler<'a, R>>,
+}
+
+impl<'a, R> ChatCommandRouter<'a, R> {
+    /// Create a router with no commands
+    pub fn new() -> Self {
+        Self {
+            handlers: BTreeMap::new(),
+        }
+    }
+
+    /// Register `handler` for `command`, replacing any existing one
+    ///
+    /// `command` may be given with or without the leading `/`.
+    pub fn register(&mut self, command: &str, handler: impl FnMut(&[&str]) -> R + 'a) -> &mut Self {
+        let word = command.strip_prefix(COMMAND_PREFIX).unwrap_or(command);
+        self.handlers
+            .insert(word.to_ascii_lowercase(), Box::new(handler));
+        self
+    }
+
+    /// Registered command words, sorted and without the prefix
+    pub fn commands(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+
+    /// Run the handler for a chat line
+    ///
+    /// Returns `None` if the line isn't a command or no handler is
+    /// registered for it.
+    pub fn dispatch(&mut self, text: &str) -> Option<R> {
+        let line = text.trim_start().strip_prefix(COMMAND_PREFIX)?;
+        let mut words = line.split_whitespace();
+        let command = words.next()?.to_ascii_lowercase();
+        let args: Vec<&str> = words.collect();
+        let handler = self.handlers.get_mut(&command)?;
+        Some(handler(&args))
+    }
+}
+
+impl<R> Default for ChatCommandRouter<'_, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_command_router() {
+        let mut rolls = Vec::new();
+        {
+            let mut router = ChatCommandRouter::new();
+            router
+                .register("/roll", |args: &[&str]| {
+                    rolls.push(args.join(" "));
+                    format!("rolled {}", args.first().unwrap_or(&"1d6"))
+                })
+                .register("help", |_: &[&str]| "commands: help, roll".to_string());
+
+            assert_eq!(router.commands().collect::<Vec<_>>(), ["help", "roll"]);
+            assert_eq!(
+                router.dispatch("/roll 2d6  +1"),
+                Some("rolled 2d6".to_string())
+            );
+            assert_eq!(router.dispatch("  /ROLL"), Some("rolled 1d6".to_string()));
+            assert_eq!(
+                router.dispatch("/help"),
+                Some("commands: help, roll".to_string())
+            );
+
+            // Ordinary chat and unknown commands fall through
+            assert_eq!(router.dispatch("roll 2d6"), None);
+            assert_eq!(router.dispatch("/dance"), None);
+            assert_eq!(router.dispatch("/"), None);
+        }
+        assert_eq!(rolls, ["2d6 +1", ""]);
+    }
+}
@@ -38,6 +38,23 @@ bitflags! {
     }
 }
 
+impl UserFlags {
+    /// Whether the user is a wizard (`SUPERUSER` or `GOD`; gods count as wizards)
+    pub const fn is_wizard(&self) -> bool {
+        self.intersects(Self::SUPERUSER.union(Self::GOD))
+    }
+
+    /// Whether the user is a guest (no registration code)
+    pub const fn is_guest(&self) -> bool {
+        self.contains(Self::GUEST)
+    }
+
+    /// Whether the user is barred from speaking (`GAG`)
+    pub const fn is_gagged(&self) -> bool {
+        self.contains(Self::GAG)
+    }
+}
+
 bitflags! {
     /// Room flags describing room attributes and restrictions.
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -65,6 +82,41 @@ bitflags! {
     }
 }
 
+impl RoomFlags {
+    /// Whether cyborg scripts run in this room (`CYBORG_FREE_ZONE` unset)
+    pub const fn allows_cyborgs(&self) -> bool {
+        !self.contains(Self::CYBORG_FREE_ZONE)
+    }
+
+    /// Whether users may draw in this room (`NO_PAINTING` unset)
+    pub const fn allows_painting(&self) -> bool {
+        !self.contains(Self::NO_PAINTING)
+    }
+
+    /// Whether users may drop loose props here (`NO_LOOSE_PROPS` unset)
+    pub const fn allows_loose_props(&self) -> bool {
+        !self.contains(Self::NO_LOOSE_PROPS)
+    }
+
+    /// Whether guests may enter
+    ///
+    /// False for `NO_GUESTS` rooms and for `WIZARDS_ONLY` rooms, which shut
+    /// out guests along with every other non-wizard.
+    pub const fn allows_guests(&self) -> bool {
+        !self.intersects(Self::NO_GUESTS.union(Self::WIZARDS_ONLY))
+    }
+
+    /// Whether the room is private
+    pub const fn is_private(&self) -> bool {
+        self.contains(Self::PRIVATE)
+    }
+
+    /// Whether the room is left off the room list
+    pub const fn is_hidden(&self) -> bool {
+        self.contains(Self::HIDDEN)
+    }
+}
+
 bitflags! {
     /// Prop flags describing prop format and behavior.
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -103,6 +155,21 @@ impl PropFlags {
             PropFormat::Indexed8
         }
     }
+
+    /// Whether the prop replaces the avatar's face (`HEAD`)
+    pub const fn is_head(&self) -> bool {
+        self.contains(Self::HEAD)
+    }
+
+    /// Whether the prop is drawn translucent (`GHOST`)
+    pub const fn is_ghost(&self) -> bool {
+        self.contains(Self::GHOST)
+    }
+
+    /// Whether the prop is marked rare (`RARE`)
+    pub const fn is_rare(&self) -> bool {
+        self.contains(Self::RARE)
+    }
 }
 
 /// Prop color format enumeration.
@@ -340,6 +407,24 @@ mod tests {
         assert!(flags.contains(UserFlags::GAG));
     }
 
+    #[test]
+    fn test_user_flag_helpers() {
+        let flags = UserFlags::GUEST | UserFlags::GAG | UserFlags::PIN;
+        assert_eq!(flags.bits(), 0x0188);
+        assert_eq!(UserFlags::from_bits_truncate(flags.bits()), flags);
+        // Undefined bits are dropped
+        assert_eq!(UserFlags::from_bits_truncate(0xE188), flags);
+
+        assert!(flags.is_guest());
+        assert!(flags.is_gagged());
+        assert!(!flags.is_wizard());
+
+        assert!(UserFlags::SUPERUSER.is_wizard());
+        assert!(UserFlags::GOD.is_wizard());
+        assert!(!UserFlags::GOD.is_guest());
+        assert!(!UserFlags::empty().is_gagged());
+    }
+
     #[test]
     fn test_room_flags() {
         let flags = RoomFlags::WIZARDS_ONLY | RoomFlags::NO_GUESTS;
@@ -348,6 +433,36 @@ mod tests {
         assert!(!flags.contains(RoomFlags::PRIVATE));
     }
 
+    #[test]
+    fn test_room_flag_helpers() {
+        let open = RoomFlags::empty();
+        assert!(open.allows_cyborgs());
+        assert!(open.allows_painting());
+        assert!(open.allows_loose_props());
+        assert!(open.allows_guests());
+        assert!(!open.is_private());
+        assert!(!open.is_hidden());
+
+        let locked_down = RoomFlags::CYBORG_FREE_ZONE
+            | RoomFlags::NO_PAINTING
+            | RoomFlags::NO_LOOSE_PROPS
+            | RoomFlags::NO_GUESTS
+            | RoomFlags::PRIVATE
+            | RoomFlags::HIDDEN;
+        assert!(!locked_down.allows_cyborgs());
+        assert!(!locked_down.allows_painting());
+        assert!(!locked_down.allows_loose_props());
+        assert!(!locked_down.allows_guests());
+        assert!(locked_down.is_private());
+        assert!(locked_down.is_hidden());
+
+        // Each helper only looks at its own bits
+        assert!(RoomFlags::NO_PAINTING.allows_cyborgs());
+        assert!(!RoomFlags::NO_PAINTING.allows_painting());
+        assert!(RoomFlags::CYBORG_FREE_ZONE.allows_painting());
+        assert!(!RoomFlags::WIZARDS_ONLY.allows_guests());
+    }
+
     #[test]
     fn test_prop_format() {
         let flags_8bit = PropFlags::FORMAT_8BIT | PropFlags::HEAD;
@@ -357,6 +472,16 @@ mod tests {
         assert_eq!(flags_32bit.format(), PropFormat::Rgb32);
     }
 
+    #[test]
+    fn test_prop_behavior_flags() {
+        let flags = PropFlags::HEAD | PropFlags::RARE;
+        assert!(flags.is_head());
+        assert!(flags.is_rare());
+        assert!(!flags.is_ghost());
+        assert!(PropFlags::GHOST.is_ghost());
+        assert!(!PropFlags::empty().is_head());
+    }
+
     #[test]
     fn test_server_flags() {
         let flags = ServerFlags::CLOSED_SERVER | ServerFlags::ALLOW_CYBORGS;
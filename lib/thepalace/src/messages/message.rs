@@ -6,7 +6,7 @@
 //! - 4 bytes: Reference number (big-endian i32, arbitrary parameter)
 //! - Variable: Message payload
 //!
-//! Total header size: 12 bytes
+//! Total header size: 12 bytes ([`MessageHeader`])
 //!
 //! # MessagePayload Trait
 //!
@@ -69,7 +69,7 @@
 //! ```
 
 use super::MessageId;
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::io;
 
 /// Trait for Palace Protocol message payloads.
@@ -92,6 +92,9 @@ pub trait MessagePayload: Sized {
     }
 
     /// Convenience method to create a complete Message from this payload
+    ///
+    /// The header length is derived from the serialized payload when the
+    /// message is encoded, so it always matches `to_bytes` output.
     fn to_message(&self, ref_num: i32) -> Message {
         let mut payload = BytesMut::new();
         self.to_bytes(&mut payload);
@@ -104,10 +107,99 @@ pub trait MessagePayload: Sized {
     }
 }
 
+/// The 12-byte header in front of every message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageHeader {
+    /// Message type identifier
+    pub msg_id: MessageId,
+    /// Payload size in bytes, excluding the header
+    pub length: u32,
+    /// Reference number (arbitrary parameter, usage varies by message type)
+    pub ref_num: i32,
+}
+
+impl MessageHeader {
+    /// Header size in bytes
+    pub const SIZE: usize = 12;
+
+    /// Largest payload length accepted when reading a header
+    ///
+    /// Generous for the biggest real messages (room descriptions, asset
+    /// transfers) while keeping a bogus length from making a reader buffer
+    /// gigabytes waiting for a payload that never arrives.
+    pub const MAX_LENGTH: usize = 1 << 24;
+
+    /// Read a header from a buffer, capping the length at [`Self::MAX_LENGTH`]
+    ///
+    /// # Errors
+    ///
+    /// Returns `UnexpectedEof` if fewer than 12 bytes remain, and
+    /// `InvalidData` for an unknown message ID or a length that is negative
+    /// as the original signed field or above the cap.
+    pub fn read_from(buf: &mut impl Buf) -> io::Result<Self> {
+        Self::read_from_with_limit(buf, Self::MAX_LENGTH)
+    }
+
+    /// Read a header from a buffer, rejecting lengths above `max_length`
+    ///
+    /// Lengths with the sign bit set are rejected whatever the cap.
+    ///
+    /// # Errors
+    ///
+    /// As [`read_from`](Self::read_from).
+    pub fn read_from_with_limit(buf: &mut impl Buf, max_length: usize) -> io::Result<Self> {
+        if buf.remaining() < Self::SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "need {} bytes for header, got {}",
+                    Self::SIZE,
+                    buf.remaining()
+                ),
+            ));
+        }
+
+        let msg_id_u32 = buf.get_u32();
+        let msg_id = MessageId::from_u32(msg_id_u32).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown message ID: 0x{:08x}", msg_id_u32),
+            )
+        })?;
+        let length = buf.get_u32();
+        let ref_num = buf.get_i32();
+
+        if length > i32::MAX as u32 || length as usize > max_length {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "message length {} out of range (max {})",
+                    length as i32, max_length
+                ),
+            ));
+        }
+
+        Ok(Self {
+            msg_id,
+            length,
+            ref_num,
+        })
+    }
+
+    /// Write the header to a buffer
+    pub fn write_to(&self, buf: &mut impl BufMut) {
+        buf.put_u32(self.msg_id.as_u32());
+        buf.put_u32(self.length);
+        buf.put_i32(self.ref_num);
+    }
+}
+
 /// Generic Palace Protocol message structure.
 ///
 /// All Palace messages share this common structure with a 12-byte header
-/// followed by message-specific payload data.
+/// followed by message-specific payload data. There is no stored length
+/// field: the header length is always computed from `payload` when the
+/// message is serialized, so a mismatched length can't be constructed.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Message {
     /// Message type identifier
@@ -120,7 +212,7 @@ pub struct Message {
 
 impl Message {
     /// Header size in bytes (event_type + length + ref_num)
-    pub const HEADER_SIZE: usize = 12;
+    pub const HEADER_SIZE: usize = MessageHeader::SIZE;
 
     /// Create a new message
     pub fn new(msg_id: MessageId, ref_num: i32, payload: Vec<u8>) -> Self {
@@ -157,35 +249,38 @@ impl Message {
         self.payload.len()
     }
 
+    /// Get the header this message is sent with
+    pub fn header(&self) -> MessageHeader {
+        MessageHeader {
+            msg_id: self.msg_id,
+            length: self.payload.len() as u32,
+            ref_num: self.ref_num,
+        }
+    }
+
     /// Parse a message from a buffer.
     ///
     /// Reads the 12-byte header and then the payload based on the length field.
     ///
     /// # Errors
     ///
-    /// Returns an error if there aren't enough bytes or if the message is malformed.
+    /// Returns an error if there aren't enough bytes or if the message is malformed
+    /// (see [`MessageHeader::read_from`]).
     pub fn parse<B: Buf>(buf: &mut B) -> io::Result<Self> {
-        if buf.remaining() < Self::HEADER_SIZE {
-            return Err(io::Error::new(
-                io::ErrorKind::UnexpectedEof,
-                format!(
-                    "need {} bytes for header, got {}",
-                    Self::HEADER_SIZE,
-                    buf.remaining()
-                ),
-            ));
-        }
+        Self::parse_with_limit(buf, MessageHeader::MAX_LENGTH)
+    }
 
-        // Read header
-        let msg_id_u32 = buf.get_u32();
-        let msg_id = MessageId::from_u32(msg_id_u32).ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("unknown message ID: 0x{:08x}", msg_id_u32),
-            )
-        })?;
-        let length = buf.get_u32() as usize;
-        let ref_num = buf.get_i32();
+    /// Parse a message from a buffer, rejecting payloads above `max_length`
+    ///
+    /// The length is checked as soon as the header is read, before waiting
+    /// for or allocating the payload.
+    ///
+    /// # Errors
+    ///
+    /// As [`parse`](Self::parse).
+    pub fn parse_with_limit<B: Buf>(buf: &mut B, max_length: usize) -> io::Result<Self> {
+        let header = MessageHeader::read_from_with_limit(buf, max_length)?;
+        let length = header.length as usize;
 
         // Check if we have enough bytes for payload
         if buf.remaining() < length {
@@ -200,8 +295,8 @@ impl Message {
         buf.copy_to_slice(&mut payload);
 
         Ok(Self {
-            msg_id,
-            ref_num,
+            msg_id: header.msg_id,
+            ref_num: header.ref_num,
             payload,
         })
     }
@@ -210,12 +305,7 @@ impl Message {
     ///
     /// Writes the 12-byte header followed by the payload.
     pub fn serialize<B: BufMut>(&self, buf: &mut B) {
-        // Write header
-        buf.put_u32(self.msg_id.as_u32());
-        buf.put_u32(self.payload.len() as u32);
-        buf.put_i32(self.ref_num);
-
-        // Write payload
+        self.header().write_to(buf);
         buf.put_slice(&self.payload);
     }
 
@@ -225,12 +315,87 @@ impl Message {
         self.serialize(&mut buf);
         buf
     }
+
+    /// Describe the message for debugging: a header summary line followed by
+    /// a hex dump of the payload
+    pub fn describe(&self) -> String {
+        format!(
+            "{:?} (0x{:08x}) ref={} len={}\n{}",
+            self.msg_id,
+            self.msg_id.as_u32(),
+            self.ref_num,
+            self.payload.len(),
+            crate::buffer::hex_dump(&self.payload)
+        )
+    }
+
+    /// Encode the full wire form (12-byte header plus payload)
+    ///
+    /// The big-endian length field is written from the payload size.
+    pub fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.total_size());
+        self.serialize(&mut buf);
+        buf.freeze()
+    }
+}
+
+/// Parse `data` as exactly one message, for untrusted input such as a fuzz target
+///
+/// Never panics, whatever the input: payload parsers read through the checked
+/// [`BufExt`](crate::buffer::BufExt) getters, so short data is an error
+/// rather than a `bytes` bounds panic.
+///
+/// # Errors
+///
+/// As [`Message::parse`], plus `InvalidData` if bytes follow the payload.
+pub fn parse_message_safe(data: &[u8]) -> io::Result<Message> {
+    let mut buf = data;
+    let message = Message::parse(&mut buf)?;
+    if !buf.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} bytes after the message payload", buf.len()),
+        ));
+    }
+    Ok(message)
+}
+
+/// Assert that `msg` encodes to exactly `expected` and decodes back from it
+///
+/// Checking against hand-written big-endian bytes catches byte-order bugs
+/// that a plain `decode(encode(x)) == x` check hides, since a native-endian
+/// writer and reader agree with each other. The full message is checked too,
+/// so the header's msg_id, length and ref_num must be big-endian as well.
+#[cfg(test)]
+pub(crate) fn roundtrip_be<T: MessagePayload + PartialEq + std::fmt::Debug>(
+    msg: &T,
+    expected: &[u8],
+) {
+    let mut payload = BytesMut::new();
+    msg.to_bytes(&mut payload);
+    assert_eq!(&payload[..], expected, "payload bytes of {msg:?}");
+
+    let mut reader = expected;
+    let parsed = T::from_bytes(&mut reader).unwrap();
+    assert_eq!(&parsed, msg);
+    assert!(reader.is_empty(), "{} bytes left unread", reader.len());
+
+    let ref_num = 0x0102_0304;
+    let mut wire = T::message_id().as_u32().to_be_bytes().to_vec();
+    wire.extend_from_slice(&(expected.len() as u32).to_be_bytes());
+    wire.extend_from_slice(&[0x01, 0x02, 0x03, 0x04]);
+    wire.extend_from_slice(expected);
+    assert_eq!(&msg.to_message(ref_num).encode()[..], &wire[..]);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bytes::{Bytes, BytesMut};
+    use crate::messages::{
+        AssetSendMsg, DoorLockMsg, DrawMsg, PropMoveMsg, RoomGotoMsg, RoomRec, UserDescMsg,
+        UserPropMsg, UserRec, XTalkMsg, XWhisperMsg,
+    };
+    use crate::{AssetSpec, Point};
 
     #[test]
     fn test_message_new() {
@@ -305,4 +470,189 @@ mod tests {
         assert_eq!(&bytes[8..12], &42i32.to_be_bytes()); // ref_num
         assert_eq!(&bytes[12..14], &[0xAA, 0xBB]); // payload
     }
+
+    #[test]
+    fn test_message_encode_length() {
+        let msg = RoomGotoMsg { dest: 86 }.to_message(0);
+        let bytes = msg.encode();
+
+        assert_eq!(bytes.len(), Message::HEADER_SIZE + msg.payload_size());
+        assert_eq!(&bytes[0..4], &MessageId::RoomGoto.as_u32().to_be_bytes());
+        assert_eq!(&bytes[4..8], &(msg.payload_size() as u32).to_be_bytes());
+        assert_eq!(&bytes[4..8], &2u32.to_be_bytes());
+        assert_eq!(&bytes[12..], &86i16.to_be_bytes());
+        assert_eq!(bytes.as_ref(), msg.to_bytes().as_slice());
+    }
+
+    #[test]
+    fn test_header_roundtrip() {
+        let bytes: [u8; 12] = [
+            0x74, 0x61, 0x6c, 0x6b, // 'talk'
+            0x00, 0x00, 0x00, 0x06, // length
+            0xff, 0xff, 0xff, 0xfe, // ref_num -2
+        ];
+        let header = MessageHeader::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(
+            header,
+            MessageHeader {
+                msg_id: MessageId::Talk,
+                length: 6,
+                ref_num: -2,
+            }
+        );
+
+        let mut buf = Vec::new();
+        header.write_to(&mut buf);
+        assert_eq!(buf, bytes);
+        assert_eq!(
+            Message::new(MessageId::Talk, -2, vec![0; 6]).encode()[..12],
+            bytes
+        );
+    }
+
+    #[test]
+    fn test_header_rejects_oversized_length() {
+        let mut bytes = Message::new_empty(MessageId::Ping, 0).to_bytes();
+        bytes[4..8].copy_from_slice(&(MessageHeader::MAX_LENGTH as u32 + 1).to_be_bytes());
+        let err = MessageHeader::read_from(&mut &bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // A negative signed length too, even before the payload arrives
+        bytes[4..8].copy_from_slice(&(-1i32).to_be_bytes());
+        let err = Message::parse(&mut &bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        bytes[4..8].copy_from_slice(&(MessageHeader::MAX_LENGTH as u32).to_be_bytes());
+        let err = Message::parse(&mut &bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // A tighter cap
+        bytes[4..8].copy_from_slice(&16u32.to_be_bytes());
+        let err = Message::parse_with_limit(&mut &bytes[..], 15).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = Message::parse_with_limit(&mut &bytes[..], usize::MAX).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_message_describe() {
+        let msg = RoomGotoMsg { dest: 86 }.to_message(5);
+        assert_eq!(
+            msg.describe(),
+            "RoomGoto (0x6e617652) ref=5 len=2\n\
+             00000000  00 56                                             |.V|\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_message_safe() {
+        let wire = RoomGotoMsg { dest: 7 }.to_message(1).encode();
+        let message = parse_message_safe(&wire).unwrap();
+        assert_eq!(message.msg_id, MessageId::RoomGoto);
+
+        // Every truncation of a valid message fails
+        for len in 0..wire.len() {
+            assert!(parse_message_safe(&wire[..len]).is_err());
+        }
+
+        let mut trailing = wire.to_vec();
+        trailing.push(0);
+        let err = parse_message_safe(&trailing).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Unknown id, then a length far beyond the data
+        assert!(parse_message_safe(&[0xDE, 0xAD, 0xBE, 0xEF, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        assert!(parse_message_safe(&[b'r', b'o', b'o', b'm', 0x7F, 0xFF, 0xFF, 0xFF]).is_err());
+    }
+
+    /// Assert that every strict prefix of `payload` fails to parse as `T`
+    fn assert_truncations_fail<T: MessagePayload + std::fmt::Debug>(payload: &[u8]) {
+        for len in 0..payload.len() {
+            let result = T::from_bytes(&mut &payload[..len]);
+            assert!(
+                result.is_err(),
+                "{} of {} bytes parsed as {:?}",
+                len,
+                payload.len(),
+                result
+            );
+        }
+    }
+
+    fn payload_bytes(payload: &impl MessagePayload) -> Vec<u8> {
+        let mut buf = Vec::new();
+        payload.to_bytes(&mut buf);
+        buf
+    }
+
+    #[test]
+    fn test_truncated_payloads_error() {
+        assert_truncations_fail::<DoorLockMsg>(&payload_bytes(&DoorLockMsg::new(1, 2)));
+        assert_truncations_fail::<PropMoveMsg>(&payload_bytes(&PropMoveMsg::new(
+            1,
+            Point { h: 2, v: 3 },
+        )));
+        assert_truncations_fail::<UserDescMsg>(&payload_bytes(&UserDescMsg {
+            face_nbr: 1,
+            color_nbr: 2,
+            props: vec![AssetSpec::new(3, 4), AssetSpec::new(5, 6)],
+        }));
+        assert_truncations_fail::<XWhisperMsg>(&payload_bytes(&XWhisperMsg {
+            target: 1,
+            text: b"secret".to_vec(),
+        }));
+
+        // RoomRec's fixed part is 40 bytes, followed by len_vars bytes
+        let mut room = vec![0u8; 40];
+        room[38..40].copy_from_slice(&8i16.to_be_bytes());
+        room.extend_from_slice(&[0; 8]);
+        assert!(RoomRec::from_bytes(&mut &room[..]).is_ok());
+        for len in 0..room.len() {
+            assert!(RoomRec::from_bytes(&mut &room[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_hostile_lengths_error() {
+        // A negative text length must not turn into a huge allocation
+        let err = XTalkMsg::from_bytes(&mut &[0xFF, 0xFF, 1, 2][..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Negative and oversized prop counts
+        let err = UserPropMsg::from_bytes(&mut &[0xFF, 0xFF, 0xFF, 0xFF][..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = UserPropMsg::from_bytes(&mut &[0x7F, 0xFF, 0xFF, 0xFF][..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_garbage_never_panics() {
+        // Small xorshift generator so the inputs are the same on every run
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..2000 {
+            let len = (next() % 96) as usize;
+            let mut data: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            // Give some inputs a real message id so parsing gets past the header
+            if data.len() >= 4 && next() % 2 == 0 {
+                data[..4].copy_from_slice(&MessageId::Talk.as_u32().to_be_bytes());
+            }
+
+            let _ = parse_message_safe(&data);
+            let _ = RoomRec::from_bytes(&mut &data[..]);
+            let _ = UserRec::from_bytes(&mut &data[..]);
+            let _ = XTalkMsg::from_bytes(&mut &data[..]);
+            let _ = XWhisperMsg::from_bytes(&mut &data[..]);
+            let _ = UserPropMsg::from_bytes(&mut &data[..]);
+            let _ = UserDescMsg::from_bytes(&mut &data[..]);
+            let _ = AssetSendMsg::from_bytes(&mut &data[..]);
+            let _ = <DrawMsg as MessagePayload>::from_bytes(&mut &data[..]);
+        }
+    }
 }
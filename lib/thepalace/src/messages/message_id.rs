@@ -3,7 +3,8 @@
 //! Message types are 4-byte ASCII codes stored as big-endian u32 values.
 //! For example, 'tiyr' = 0x74697972.
 //!
-//! All message IDs in this file are from the official Palace Protocol specification.
+//! All message IDs in this file are from the official Palace Protocol specification,
+//! except [`MessageId::ServerStatus`], an extension of this server.
 
 use std::fmt;
 use std::str::FromStr;
@@ -161,6 +162,10 @@ pub enum MessageId {
     DoorLock = 0x6c6f636b,
     /// Unlock door ('unlk' = 0x756e6c6b)
     DoorUnlock = 0x756e6c6b,
+
+    // Server extensions (not in the Palace Protocol spec)
+    /// Server status for admin tools ('sStt' = 0x73537474)
+    ServerStatus = 0x73537474,
 }
 
 impl MessageId {
@@ -236,6 +241,7 @@ impl MessageId {
             Self::AssetRegi => "rAst",
             Self::DoorLock => "lock",
             Self::DoorUnlock => "unlk",
+            Self::ServerStatus => "sStt",
         }
     }
 
@@ -250,7 +256,7 @@ impl MessageId {
     /// 2. MessageId is #[repr(u32)] so layout is guaranteed
     /// 3. All discriminants are explicitly defined
     pub fn from_u32(value: u32) -> Option<Self> {
-        // Check if the value matches any valid discriminant (59 total)
+        // Check if the value matches any valid discriminant (59 total, plus extensions)
         match value {
             // Connection & Auth
             0x74697972 | 0x72657032 | 0x72656769 | 0x61757468 | 0x61757472 | 0x626c6f77 |
@@ -272,7 +278,9 @@ impl MessageId {
             // Version & Assets
             0x76657273 | 0x71417374 | 0x73417374 | 0x72417374 |
             // Doors
-            0x6c6f636b | 0x756e6c6b => {
+            0x6c6f636b | 0x756e6c6b |
+            // Server extensions
+            0x73537474 => {
                 // SAFETY: We've verified the value is a valid discriminant
                 Some(unsafe { std::mem::transmute::<u32, MessageId>(value) })
             }
@@ -356,6 +364,7 @@ impl FromStr for MessageId {
             "rAst" => Ok(Self::AssetRegi),
             "lock" => Ok(Self::DoorLock),
             "unlk" => Ok(Self::DoorUnlock),
+            "sStt" => Ok(Self::ServerStatus),
             _ => Err(()),
         }
     }
@@ -441,6 +450,7 @@ mod tests {
             MessageId::Rmsg,
             MessageId::Smsg,
             MessageId::HttpServer,
+            MessageId::ServerStatus,
         ];
 
         for id in ids {
@@ -13,10 +13,12 @@ pub mod admin;
 pub mod asset;
 pub mod auth;
 pub mod chat;
+pub mod chat_command;
 pub mod flags;
 pub mod message;
 pub mod message_id;
 pub mod protocol;
+pub mod replay;
 pub mod room;
 pub mod server;
 pub mod user;
@@ -25,8 +27,9 @@ pub use admin::*;
 pub use asset::*;
 pub use auth::*;
 pub use chat::*;
+pub use chat_command::{ChatCommandRouter, COMMAND_PREFIX};
 pub use flags::*;
-pub use message::{Message, MessagePayload};
+pub use message::{parse_message_safe, Message, MessageHeader, MessagePayload};
 pub use message_id::MessageId;
 pub use protocol::*;
 pub use room::*;
@@ -7,6 +7,8 @@
 
 use bytes::{Buf, BufMut};
 
+use crate::buffer::BufExt;
+use crate::messages::flags::UserFlags;
 use crate::messages::{MessageId, MessagePayload};
 
 // ============================================================================
@@ -50,15 +52,15 @@ impl MessagePayload for VersionMsg {
 /// The UserID is in the message's refNum field.
 ///
 /// Contains:
-/// - flags: Status bit flags (see UserFlags in MessageId::ListOfAllUsers)
+/// - flags: Status bit flags (undefined bits are dropped when decoding)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct UserStatusMsg {
-    pub flags: i16,
+    pub flags: UserFlags,
 }
 
 impl UserStatusMsg {
     /// Create a new UserStatusMsg
-    pub const fn new(flags: i16) -> Self {
+    pub const fn new(flags: UserFlags) -> Self {
         Self { flags }
     }
 }
@@ -70,12 +72,12 @@ impl MessagePayload for UserStatusMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            flags: buf.get_i16(),
+            flags: UserFlags::from_bits_truncate(buf.get_u16_checked()?),
         })
     }
 
     fn to_bytes(&self, buf: &mut impl BufMut) {
-        buf.put_i16(self.flags);
+        buf.put_u16(self.flags.bits());
     }
 }
 
@@ -167,14 +169,16 @@ mod tests {
 
     #[test]
     fn test_user_status_msg() {
-        let msg = UserStatusMsg::new(0x0001); // Some flag value
+        let msg = UserStatusMsg::new(UserFlags::SUPERUSER | UserFlags::GAG);
 
         let mut buf = vec![];
         msg.to_bytes(&mut buf);
-        assert_eq!(buf.len(), 2);
+        assert_eq!(buf, [0x00, 0x81]);
 
         let parsed = UserStatusMsg::from_bytes(&mut &buf[..]).unwrap();
-        assert_eq!(parsed.flags, 0x0001);
+        assert_eq!(parsed, msg);
+        assert!(parsed.flags.is_wizard());
+        assert!(parsed.flags.is_gagged());
     }
 
     #[test]
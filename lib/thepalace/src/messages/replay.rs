@@ -0,0 +1,242 @@
+//! Replaying captured sessions
+//!
+//! [`parse_session`] reads a capture of raw wire messages, each framed by its
+//! own 12-byte header, and runs every payload through the library's decoder
+//! for its message type. Pointing it at a capture from a real server shows
+//! which messages the library understands and which it chokes on.
+
+use std::io::{self, ErrorKind, Read};
+
+use bytes::Buf;
+
+use crate::messages::*;
+
+/// Message ID of a captured message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayId {
+    /// An ID the library recognizes
+    Known(MessageId),
+    /// A raw ID with no [`MessageId`] variant
+    Unknown(u32),
+}
+
+/// Parse every message in a captured session
+///
+/// Returns one entry per message, in capture order: the message on success,
+/// or the error from decoding it. A failure doesn't stop the replay. Message
+/// types the library has no decoder for report `Unsupported`, and IDs it
+/// doesn't recognize are reported as [`ReplayId::Unknown`] with an
+/// `InvalidData` error.
+///
+/// Replay ends at the end of the capture, at a read error, at a header whose
+/// length is out of range (reported as `InvalidData`, since the next frame
+/// can't be found), or at a message cut off by the end of the capture
+/// (reported as `UnexpectedEof`).
+pub fn parse_session(mut reader: impl Read) -> Vec<(ReplayId, io::Result<Message>)> {
+    let mut results = Vec::new();
+    loop {
+        let mut raw_header = [0u8; MessageHeader::SIZE];
+        if reader.read_exact(&mut raw_header).is_err() {
+            break;
+        }
+        let mut fields = &raw_header[..];
+        let raw_id = fields.get_u32();
+        let length = fields.get_u32();
+        let id = MessageId::from_u32(raw_id).map_or(ReplayId::Unknown(raw_id), ReplayId::Known);
+
+        let header = match MessageHeader::read_from(&mut &raw_header[..]) {
+            Ok(header) => header,
+            Err(e) if matches!(id, ReplayId::Known(_)) => {
+                // Only the length can be wrong, and without it there's no next frame
+                results.push((id, Err(e)));
+                break;
+            }
+            Err(e) => {
+                if length as usize > MessageHeader::MAX_LENGTH {
+                    results.push((
+                        id,
+                        Err(io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!("message length {} out of range", length as i32),
+                        )),
+                    ));
+                    break;
+                }
+                let mut payload = vec![0u8; length as usize];
+                if reader.read_exact(&mut payload).is_err() {
+                    results.push((id, Err(truncated(id))));
+                    break;
+                }
+                results.push((id, Err(e)));
+                continue;
+            }
+        };
+
+        let mut payload = vec![0u8; header.length as usize];
+        if reader.read_exact(&mut payload).is_err() {
+            results.push((id, Err(truncated(id))));
+            break;
+        }
+
+        let message = Message::new(header.msg_id, header.ref_num, payload);
+        let result = decode_payload(&message).map(|()| message);
+        results.push((id, result));
+    }
+    results
+}
+
+fn truncated(id: ReplayId) -> io::Error {
+    io::Error::new(
+        ErrorKind::UnexpectedEof,
+        format!("capture ends inside a {:?} payload", id),
+    )
+}
+
+/// Decode a message's payload with the decoder for its type, discarding the result
+fn decode_payload(message: &Message) -> io::Result<()> {
+    match message.msg_id {
+        MessageId::Talk => decode::<TalkMsg>(message),
+        MessageId::XTalk => decode::<XTalkMsg>(message),
+        MessageId::Whisper => decode::<WhisperMsg>(message),
+        MessageId::XWhisper => decode::<XWhisperMsg>(message),
+        MessageId::Gmsg => decode::<GmsgMsg>(message),
+        MessageId::Rmsg => decode::<RmsgMsg>(message),
+        MessageId::Smsg => decode::<SmsgMsg>(message),
+        MessageId::Tiyid => decode::<TiyidMsg>(message),
+        MessageId::Logon => decode::<LogonMsg>(message),
+        MessageId::AltLogonReply => decode::<AltLogonReplyMsg>(message),
+        MessageId::Ping => decode::<PingMsg>(message),
+        MessageId::Pong => decode::<PongMsg>(message),
+        MessageId::ServerInfo => decode::<ServerInfoMsg>(message),
+        MessageId::Version => decode::<VersionMsg>(message),
+        MessageId::UserStatus => decode::<UserStatusMsg>(message),
+        MessageId::NavError => decode::<NavErrorMsg>(message),
+        MessageId::ServerDown => decode::<ServerDownMsg>(message),
+        MessageId::SuperUser => decode::<SuperUserMsg>(message),
+        MessageId::KillUser => decode::<KillUserMsg>(message),
+        MessageId::UserList => decode::<UserListMsg>(message),
+        MessageId::ListOfAllUsers => decode::<ListOfAllUsersMsg>(message),
+        MessageId::UserLog => decode::<UserLogMsg>(message),
+        MessageId::UserNew => decode::<UserNewMsg>(message),
+        MessageId::UserExit => decode::<UserExitMsg>(message),
+        MessageId::UserMove => decode::<UserMoveMsg>(message),
+        MessageId::UserName => decode::<UserNameMsg>(message),
+        MessageId::UserColor => decode::<UserColorMsg>(message),
+        MessageId::UserFace => decode::<UserFaceMsg>(message),
+        MessageId::UserProp => decode::<UserPropMsg>(message),
+        MessageId::UserDesc => decode::<UserDescMsg>(message),
+        MessageId::RoomGoto => decode::<RoomGotoMsg>(message),
+        MessageId::RoomDesc => decode::<RoomDescMsg>(message),
+        MessageId::RoomDescEnd => decode::<RoomDescEndMsg>(message),
+        MessageId::ListOfAllRooms => decode::<ListOfAllRoomsMsg>(message),
+        MessageId::Draw => decode::<DrawMsg>(message),
+        MessageId::PictMove => decode::<PictMoveMsg>(message),
+        MessageId::SpotNew => decode::<SpotNewMsg>(message),
+        MessageId::SpotDel => decode::<SpotDelMsg>(message),
+        MessageId::SpotMove => decode::<SpotMoveMsg>(message),
+        MessageId::SpotState => decode::<SpotStateMsg>(message),
+        MessageId::DoorLock => decode::<DoorLockMsg>(message),
+        MessageId::DoorUnlock => decode::<DoorUnlockMsg>(message),
+        MessageId::PropNew => decode::<PropNewMsg>(message),
+        MessageId::PropDel => decode::<PropDelMsg>(message),
+        MessageId::PropMove => decode::<PropMoveMsg>(message),
+        MessageId::AssetQuery => decode::<AssetQueryMsg>(message),
+        MessageId::AssetSend => decode::<AssetSendMsg>(message),
+        MessageId::ServerStatus => decode::<ServerStatusMsg>(message),
+        other => Err(io::Error::new(
+            ErrorKind::Unsupported,
+            format!("no decoder for {:?}", other),
+        )),
+    }
+}
+
+fn decode<P: MessagePayload>(message: &Message) -> io::Result<()> {
+    message.parse_payload::<P>().map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_session() {
+        let mut capture = Vec::new();
+        capture.extend(
+            TalkMsg {
+                text: "hello".to_string(),
+            }
+            .to_message(7)
+            .to_bytes(),
+        );
+        capture.extend(RoomGotoMsg { dest: 86 }.to_message(7).to_bytes());
+        // A RoomGoto too short for its room ID
+        capture.extend(Message::new(MessageId::RoomGoto, 7, vec![0]).to_bytes());
+        // An ID the library doesn't know
+        capture.extend(&[b'z', b'z', b'z', b'z', 0, 0, 0, 1, 0, 0, 0, 0, 0xFF]);
+        capture.extend(PingMsg.to_message(0).to_bytes());
+        // Cut off partway through the payload
+        capture.extend(&Message::new(MessageId::Talk, 7, b"bye\0".to_vec()).to_bytes()[..14]);
+
+        let results = parse_session(&capture[..]);
+        let ids: Vec<_> = results.iter().map(|(id, _)| *id).collect();
+        assert_eq!(
+            ids,
+            [
+                ReplayId::Known(MessageId::Talk),
+                ReplayId::Known(MessageId::RoomGoto),
+                ReplayId::Known(MessageId::RoomGoto),
+                ReplayId::Unknown(u32::from_be_bytes(*b"zzzz")),
+                ReplayId::Known(MessageId::Ping),
+                ReplayId::Known(MessageId::Talk)
+            ]
+        );
+
+        let talk = results[0].1.as_ref().unwrap();
+        assert_eq!(talk.parse_payload::<TalkMsg>().unwrap().text, "hello");
+        assert_eq!(talk.ref_num, 7);
+        let goto = results[1].1.as_ref().unwrap();
+        assert_eq!(goto.parse_payload::<RoomGotoMsg>().unwrap().dest, 86);
+        assert!(results[2].1.is_err());
+        assert_eq!(
+            results[3].1.as_ref().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(results[4].1.is_ok());
+        assert_eq!(
+            results[5].1.as_ref().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_parse_session_oversized_frame() {
+        let mut capture = PingMsg.to_message(0).to_bytes().to_vec();
+        let mut oversized = Message::new(MessageId::Talk, 0, Vec::new())
+            .to_bytes()
+            .to_vec();
+        oversized[4..8].copy_from_slice(&u32::MAX.to_be_bytes());
+        capture.extend(oversized);
+        capture.extend(PingMsg.to_message(0).to_bytes());
+
+        let results = parse_session(&capture[..]);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].1.is_ok());
+        assert_eq!(results[1].0, ReplayId::Known(MessageId::Talk));
+        assert_eq!(
+            results[1].1.as_ref().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_parse_session_unsupported() {
+        let capture = Message::new(MessageId::Blowthru, 0, vec![1, 2, 3]).to_bytes();
+        let results = parse_session(&capture[..]);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, ReplayId::Known(MessageId::Blowthru));
+        assert_eq!(
+            results[0].1.as_ref().unwrap_err().kind(),
+            ErrorKind::Unsupported
+        );
+    }
+}
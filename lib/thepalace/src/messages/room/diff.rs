@@ -0,0 +1,255 @@
+//! Room record diffing
+//!
+//! This module compares two versions of a room so a server can send targeted
+//! updates instead of a full MessageId::RoomDesc:
+//! - Moved hotspots map to MessageId::SpotMove
+//! - State changes map to MessageId::SpotState
+//! - Anything else still needs a full room description
+
+use crate::messages::room::records::{Hotspot, RoomRec};
+use crate::Point;
+
+/// Differences between two versions of a room
+///
+/// Hotspots are matched by ID. Runtime fields (people, loose props, draw
+/// commands) are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoomDiff {
+    /// Hotspots only in the new room
+    pub added: Vec<Hotspot>,
+    /// IDs of hotspots only in the old room
+    pub removed: Vec<i16>,
+    /// Hotspots whose location changed, with the new location
+    pub moved: Vec<(i16, Point)>,
+    /// Hotspots whose state changed, with the new state
+    pub state_changed: Vec<(i16, i16)>,
+    /// IDs of hotspots changed in some other way (outline, name, destination, ...)
+    pub modified: Vec<i16>,
+    /// Whether the flags, face, name, background, artist, password, or
+    /// picture layers changed
+    pub metadata_changed: bool,
+}
+
+impl RoomDiff {
+    /// Check whether the rooms are equivalent
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.state_changed.is_empty()
+            && self.modified.is_empty()
+            && !self.metadata_changed
+    }
+
+    /// Check whether SpotMove/SpotState messages can't describe the change
+    pub fn needs_full_desc(&self) -> bool {
+        !self.added.is_empty()
+            || !self.removed.is_empty()
+            || !self.modified.is_empty()
+            || self.metadata_changed
+    }
+}
+
+impl RoomRec {
+    /// Compare this room with a newer version of it
+    ///
+    /// Purely computational. A hotspot or picture array that can't be read
+    /// from varBuf is treated as empty. Script text and state pictures are
+    /// not compared.
+    pub fn diff(&self, other: &RoomRec) -> RoomDiff {
+        let old_spots = self.hotspots().unwrap_or_default();
+        let new_spots = other.hotspots().unwrap_or_default();
+        let mut diff = RoomDiff {
+            metadata_changed: self.metadata_differs(other),
+            ..RoomDiff::default()
+        };
+
+        for new in &new_spots {
+            let Some(old) = old_spots.iter().find(|old| old.id == new.id) else {
+                diff.added.push(new.clone());
+                continue;
+            };
+            if old.loc != new.loc {
+                diff.moved.push((new.id, new.loc));
+            }
+            if old.state != new.state {
+                diff.state_changed.push((new.id, new.state));
+            }
+            if self.hotspot_shape(old) != other.hotspot_shape(new) {
+                diff.modified.push(new.id);
+            }
+        }
+        diff.removed = old_spots
+            .iter()
+            .filter(|old| !new_spots.iter().any(|new| new.id == old.id))
+            .map(|old| old.id)
+            .collect();
+
+        diff
+    }
+
+    /// Whether room-level fields differ from `other`
+    fn metadata_differs(&self, other: &RoomRec) -> bool {
+        let strings = |room: &RoomRec| {
+            [
+                room.room_name().ok(),
+                room.pict_name().ok(),
+                room.artist_name().ok(),
+                room.password().ok(),
+            ]
+        };
+        let pictures = |room: &RoomRec| {
+            room.pictures()
+                .unwrap_or_default()
+                .iter()
+                .map(|pic| {
+                    (
+                        pic.pic_id,
+                        pic.trans_color,
+                        room.get_pstring(pic.pic_name_ofst).ok(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        self.room_flags != other.room_flags
+            || self.faces_id != other.faces_id
+            || strings(self) != strings(other)
+            || pictures(self) != pictures(other)
+    }
+
+    /// Everything about a hotspot except its location and state, with
+    /// varBuf offsets resolved so rooms laid out differently still compare
+    fn hotspot_shape<'a>(&'a self, spot: &Hotspot) -> impl PartialEq + 'a {
+        let outline = self
+            .var_slice(spot.pts_ofst, spot.nbr_pts.max(0) as usize * 4)
+            .ok();
+        (
+            (
+                spot.script_event_mask,
+                spot.flags,
+                spot.secure_info,
+                spot.ref_con,
+            ),
+            (
+                spot.dest,
+                spot.hotspot_type,
+                spot.group_id,
+                spot.nbr_scripts,
+                spot.nbr_states,
+            ),
+            self.get_pstring(spot.name_ofst).ok(),
+            outline,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::flags::RoomFlags;
+    use crate::room::{HotspotState, HotspotType};
+    use crate::EventMask;
+    use bytes::BytesMut;
+
+    fn hotspot(id: i16, loc: Point) -> Hotspot {
+        Hotspot {
+            script_event_mask: EventMask::SELECT,
+            flags: 0,
+            secure_info: 0,
+            ref_con: 0,
+            loc,
+            id,
+            dest: 0,
+            nbr_pts: 0,
+            pts_ofst: 0,
+            hotspot_type: HotspotType::Normal,
+            group_id: 0,
+            nbr_scripts: 0,
+            script_rec_ofst: 0,
+            state: HotspotState::Unlocked.as_i16(),
+            nbr_states: 0,
+            state_rec_ofst: 0,
+            name_ofst: -1,
+            script_text_ofst: 0,
+        }
+    }
+
+    /// Build a room whose varBuf holds only the hotspot array
+    fn room(hotspots: &[Hotspot]) -> RoomRec {
+        let mut var_buf = BytesMut::new();
+        for spot in hotspots {
+            spot.to_bytes(&mut var_buf);
+        }
+        RoomRec {
+            room_flags: RoomFlags::empty(),
+            faces_id: 0,
+            room_id: 7,
+            room_name_ofst: -1,
+            pict_name_ofst: -1,
+            artist_name_ofst: -1,
+            password_ofst: -1,
+            nbr_hotspots: hotspots.len() as i16,
+            hotspot_ofst: 0,
+            nbr_pictures: 0,
+            picture_ofst: 0,
+            nbr_draw_cmds: 0,
+            first_draw_cmd: 0,
+            nbr_people: 0,
+            nbr_lprops: 0,
+            first_lprop: 0,
+            len_vars: var_buf.len() as i16,
+            var_buf: var_buf.freeze(),
+        }
+    }
+
+    #[test]
+    fn test_diff_single_move() {
+        let old = room(&[
+            hotspot(1, Point::new(10, 10)),
+            hotspot(2, Point::new(50, 50)),
+        ]);
+        let new = room(&[
+            hotspot(1, Point::new(10, 10)),
+            hotspot(2, Point::new(60, 55)),
+        ]);
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.moved, [(2, Point::new(60, 55))]);
+        assert_eq!(
+            diff,
+            RoomDiff {
+                moved: diff.moved.clone(),
+                ..RoomDiff::default()
+            }
+        );
+        assert!(!diff.needs_full_desc());
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn test_diff_added_removed_changed() {
+        let mut locked = hotspot(2, Point::new(50, 50));
+        locked.state = HotspotState::Locked.as_i16();
+        let mut door = hotspot(3, Point::new(0, 0));
+        door.dest = 12;
+
+        let old = room(&[
+            hotspot(1, Point::new(10, 10)),
+            hotspot(2, Point::new(50, 50)),
+            hotspot(3, Point::new(0, 0)),
+        ]);
+        let mut new = room(&[locked, door, hotspot(4, Point::new(5, 5))]);
+        new.room_flags = RoomFlags::NO_PAINTING;
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.removed, [1]);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].id, 4);
+        assert_eq!(diff.state_changed, [(2, HotspotState::Locked.as_i16())]);
+        assert_eq!(diff.modified, [3]);
+        assert!(diff.moved.is_empty());
+        assert!(diff.metadata_changed);
+        assert!(diff.needs_full_desc());
+    }
+}
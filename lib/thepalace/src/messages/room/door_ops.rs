@@ -6,6 +6,7 @@
 
 use bytes::{Buf, BufMut};
 
+use crate::buffer::BufExt;
 use crate::messages::{MessageId, MessagePayload};
 
 /// MessageId::DoorLock
@@ -36,8 +37,8 @@ impl MessagePayload for DoorLockMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            room_id: buf.get_i16(),
-            door_id: buf.get_i32(),
+            room_id: buf.get_i16_checked()?,
+            door_id: buf.get_i32_checked()?,
         })
     }
 
@@ -75,8 +76,8 @@ impl MessagePayload for DoorUnlockMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            room_id: buf.get_i16(),
-            door_id: buf.get_i32(),
+            room_id: buf.get_i16_checked()?,
+            door_id: buf.get_i32_checked()?,
         })
     }
 
@@ -89,6 +90,7 @@ impl MessagePayload for DoorUnlockMsg {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::messages::message::roundtrip_be;
 
     #[test]
     fn test_door_lock_msg() {
@@ -103,6 +105,18 @@ mod tests {
         assert_eq!(parsed.door_id, 42);
     }
 
+    #[test]
+    fn test_door_lock_msg_big_endian() {
+        roundtrip_be(
+            &DoorLockMsg::new(0x0102, 0x0304_0506),
+            &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+        );
+        roundtrip_be(
+            &DoorLockMsg::new(-2, -1),
+            &[0xFF, 0xFE, 0xFF, 0xFF, 0xFF, 0xFF],
+        );
+    }
+
     #[test]
     fn test_door_unlock_msg() {
         let msg = DoorUnlockMsg::new(15, 88);
@@ -115,4 +129,12 @@ mod tests {
         assert_eq!(parsed.room_id, 15);
         assert_eq!(parsed.door_id, 88);
     }
+
+    #[test]
+    fn test_door_unlock_msg_big_endian() {
+        roundtrip_be(
+            &DoorUnlockMsg::new(0x0102, 0x0304_0506),
+            &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+        );
+    }
 }
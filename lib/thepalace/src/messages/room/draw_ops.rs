@@ -0,0 +1,384 @@
+//! Drawing messages
+//!
+//! This module contains messages for painting on the room:
+//! - DrawMsg: One or more draw commands (paths, erase, undo)
+//!
+//! Draw payloads come straight from clients, so parsing is bounded by
+//! [`DrawLimits`]: a packet claiming more commands or polygon points than
+//! allowed is rejected with `InvalidData` before anything is allocated for it.
+
+use std::io::{self, ErrorKind};
+
+use bytes::{Buf, BufMut};
+
+use crate::messages::{MessageId, MessagePayload};
+use crate::Point;
+
+/// Draw command type for a line or polygon
+pub const DRAW_PATH: u16 = 0;
+/// Draw command type that erases every drawing in the room
+pub const DRAW_DETONATE: u16 = 3;
+/// Draw command type that removes the most recent drawing
+pub const DRAW_DELETE: u16 = 4;
+
+/// `drawCmd` flag: fill the shape with the pen color
+pub const DRAW_FILL: u16 = 0x0100;
+/// `drawCmd` flag: draw in front of users instead of behind them
+pub const DRAW_FRONT: u16 = 0x8000;
+
+/// Size of a draw record header (link, command, length, data offset)
+pub(crate) const RECORD_HEADER_SIZE: usize = 10;
+/// Size of a path's fixed data (pen size, point count, RGB pen color)
+const PATH_HEADER_SIZE: usize = 10;
+
+/// Caps applied while parsing a draw stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawLimits {
+    /// Most commands accepted in one payload
+    pub max_commands: usize,
+    /// Most points accepted in one path
+    pub max_points: usize,
+}
+
+impl DrawLimits {
+    /// Limits used by [`DrawMsg::from_bytes`]
+    pub const DEFAULT: Self = Self {
+        max_commands: 256,
+        max_points: 2048,
+    };
+}
+
+impl Default for DrawLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A single draw command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DrawCmd {
+    /// Line or polygon
+    ///
+    /// The first point is absolute; each later point is relative to the one
+    /// before it, as on the wire.
+    Path {
+        /// Raw `drawCmd` word; the high byte carries layer and fill flags
+        cmd: u16,
+        pen_size: i16,
+        /// Pen color as 16-bit red, green, blue
+        color: [u16; 3],
+        points: Vec<Point>,
+    },
+    /// Erase every drawing in the room
+    Detonate,
+    /// Remove the most recent drawing
+    Delete,
+    /// Any other command (text, ellipse, ...), kept as raw data
+    Other { cmd: u16, data: Vec<u8> },
+}
+
+impl DrawCmd {
+    /// Parse one draw record
+    pub(crate) fn from_bytes(buf: &mut impl Buf, limits: &DrawLimits) -> io::Result<Self> {
+        if buf.remaining() < RECORD_HEADER_SIZE {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("draw record needs {} bytes", RECORD_HEADER_SIZE),
+            ));
+        }
+        // Link fields only matter for the list stored in a room's varBuf
+        let _next_ofst = buf.get_i16();
+        let _reserved = buf.get_i16();
+        let cmd = buf.get_u16();
+        let cmd_length = buf.get_u16() as usize;
+        let _data_ofst = buf.get_u16();
+        if buf.remaining() < cmd_length {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!(
+                    "draw command needs {} bytes, only {} remain",
+                    cmd_length,
+                    buf.remaining()
+                ),
+            ));
+        }
+
+        match cmd & 0x00FF {
+            DRAW_PATH => {
+                if cmd_length < PATH_HEADER_SIZE {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("draw path data too short: {} bytes", cmd_length),
+                    ));
+                }
+                let pen_size = buf.get_i16();
+                let nbr_pts = buf.get_i16();
+                if nbr_pts < 0 || nbr_pts as usize > limits.max_points {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "draw path point count {} out of range (max {})",
+                            nbr_pts, limits.max_points
+                        ),
+                    ));
+                }
+                let color = [buf.get_u16(), buf.get_u16(), buf.get_u16()];
+
+                let points_len = nbr_pts as usize * 4;
+                if cmd_length - PATH_HEADER_SIZE < points_len {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "draw path claims {} points but has {} bytes of data",
+                            nbr_pts, cmd_length
+                        ),
+                    ));
+                }
+                let points = (0..nbr_pts)
+                    .map(|_| Point::from_bytes(buf))
+                    .collect::<io::Result<_>>()?;
+                // Skip anything after the points (e.g. a fill color)
+                buf.advance(cmd_length - PATH_HEADER_SIZE - points_len);
+
+                Ok(DrawCmd::Path {
+                    cmd,
+                    pen_size,
+                    color,
+                    points,
+                })
+            }
+            DRAW_DETONATE | DRAW_DELETE => {
+                buf.advance(cmd_length);
+                Ok(if cmd & 0x00FF == DRAW_DETONATE {
+                    DrawCmd::Detonate
+                } else {
+                    DrawCmd::Delete
+                })
+            }
+            _ => {
+                let mut data = vec![0u8; cmd_length];
+                buf.copy_to_slice(&mut data);
+                Ok(DrawCmd::Other { cmd, data })
+            }
+        }
+    }
+
+    /// Serialize as a draw record with its data directly after the header
+    ///
+    /// Returns `InvalidData`, writing nothing, if the record's data or a
+    /// path's point count doesn't fit its 16-bit field.
+    fn to_bytes(&self, buf: &mut impl BufMut) -> io::Result<()> {
+        let (cmd, cmd_length) = match self {
+            DrawCmd::Path { cmd, points, .. } => (*cmd, PATH_HEADER_SIZE + points.len() * 4),
+            DrawCmd::Detonate => (DRAW_DETONATE, 0),
+            DrawCmd::Delete => (DRAW_DELETE, 0),
+            DrawCmd::Other { cmd, data } => (*cmd, data.len()),
+        };
+        let too_long = || {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("draw record of {} bytes is too long to encode", cmd_length),
+            )
+        };
+        let cmd_length = u16::try_from(cmd_length).map_err(|_| too_long())?;
+        let nbr_points = match self {
+            DrawCmd::Path { points, .. } => i16::try_from(points.len()).map_err(|_| too_long())?,
+            _ => 0,
+        };
+        buf.put_i16(0);
+        buf.put_i16(0);
+        buf.put_u16(cmd);
+        buf.put_u16(cmd_length);
+        buf.put_u16(RECORD_HEADER_SIZE as u16);
+
+        match self {
+            DrawCmd::Path {
+                pen_size,
+                color,
+                points,
+                ..
+            } => {
+                buf.put_i16(*pen_size);
+                buf.put_i16(nbr_points);
+                for channel in color {
+                    buf.put_u16(*channel);
+                }
+                for point in points {
+                    point.to_bytes(buf);
+                }
+            }
+            DrawCmd::Detonate | DrawCmd::Delete => {}
+            DrawCmd::Other { data, .. } => buf.put_slice(data),
+        }
+        Ok(())
+    }
+}
+
+/// MessageId::Draw
+///
+/// Client-to-server: Paint on the current room
+/// Server-to-clients: Relay of another user's painting
+///
+/// Contains back-to-back draw records until the end of the payload. Clients
+/// usually send one per message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrawMsg {
+    pub commands: Vec<DrawCmd>,
+}
+
+impl DrawMsg {
+    /// Create a new DrawMsg
+    pub fn new(commands: Vec<DrawCmd>) -> Self {
+        Self { commands }
+    }
+
+    /// Parse a draw payload, enforcing `limits`
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidData` if the payload holds more than
+    /// `limits.max_commands` commands or a path claims more than
+    /// `limits.max_points` points, and `UnexpectedEof` if a record is cut off.
+    pub fn from_bytes_with_limits(buf: &mut impl Buf, limits: &DrawLimits) -> io::Result<Self> {
+        let mut commands = Vec::new();
+        while buf.has_remaining() {
+            if commands.len() == limits.max_commands {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("too many draw commands (max {})", limits.max_commands),
+                ));
+            }
+            commands.push(DrawCmd::from_bytes(buf, limits)?);
+        }
+        Ok(Self { commands })
+    }
+
+    /// Serialize the draw commands
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidData`, writing nothing, if a command's data is longer
+    /// than its 16-bit length field allows (a path of more than 16381 points).
+    pub fn try_to_bytes(&self, buf: &mut impl BufMut) -> io::Result<()> {
+        let mut data = Vec::new();
+        for command in &self.commands {
+            command.to_bytes(&mut data)?;
+        }
+        buf.put_slice(&data);
+        Ok(())
+    }
+}
+
+impl MessagePayload for DrawMsg {
+    fn message_id() -> MessageId {
+        MessageId::Draw
+    }
+
+    fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
+        Self::from_bytes_with_limits(buf, &DrawLimits::DEFAULT)
+    }
+
+    /// Commands too long to encode are left out; use
+    /// [`try_to_bytes`](DrawMsg::try_to_bytes) to reject them instead.
+    fn to_bytes(&self, buf: &mut impl BufMut) {
+        for command in &self.commands {
+            let _ = command.to_bytes(buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(nbr_points: usize) -> DrawCmd {
+        DrawCmd::Path {
+            cmd: DRAW_PATH,
+            pen_size: 3,
+            color: [0xFFFF, 0, 0x8000],
+            points: (0..nbr_points).map(|i| Point::new(i as i16, 1)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_draw_msg_roundtrip() {
+        let msg = DrawMsg::new(vec![
+            path(3),
+            DrawCmd::Delete,
+            DrawCmd::Other {
+                cmd: 2,
+                data: vec![5, b'h', b'e', b'l', b'l', b'o'],
+            },
+            DrawCmd::Detonate,
+        ]);
+
+        let mut buf = vec![];
+        msg.to_bytes(&mut buf);
+        assert_eq!(buf.len(), (10 + 10 + 12) + 10 + (10 + 6) + 10);
+
+        let parsed = DrawMsg::from_bytes(&mut &buf[..]).unwrap();
+        assert_eq!(parsed, msg);
+    }
+
+    #[test]
+    fn test_draw_msg_command_cap() {
+        let msg = DrawMsg::new(vec![DrawCmd::Delete; 3]);
+        let mut buf = vec![];
+        msg.to_bytes(&mut buf);
+
+        let limits = DrawLimits {
+            max_commands: 2,
+            ..DrawLimits::DEFAULT
+        };
+        let err = DrawMsg::from_bytes_with_limits(&mut &buf[..], &limits).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let limits = DrawLimits {
+            max_commands: 3,
+            ..DrawLimits::DEFAULT
+        };
+        assert!(DrawMsg::from_bytes_with_limits(&mut &buf[..], &limits).is_ok());
+    }
+
+    #[test]
+    fn test_draw_msg_oversized_polygon() {
+        let mut buf = vec![];
+        DrawMsg::new(vec![path(DrawLimits::DEFAULT.max_points + 1)]).to_bytes(&mut buf);
+        let err = DrawMsg::from_bytes(&mut &buf[..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        // A count the data can't back is rejected rather than read past
+        let mut buf = vec![];
+        DrawMsg::new(vec![path(2)]).to_bytes(&mut buf);
+        buf[RECORD_HEADER_SIZE + 2..RECORD_HEADER_SIZE + 4].copy_from_slice(&3i16.to_be_bytes());
+        let err = DrawMsg::from_bytes(&mut &buf[..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        // Negative counts too
+        buf[RECORD_HEADER_SIZE + 2..RECORD_HEADER_SIZE + 4].copy_from_slice(&(-1i16).to_be_bytes());
+        let err = DrawMsg::from_bytes(&mut &buf[..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_draw_msg_too_long_to_encode() {
+        // 10 + 4 * 16382 bytes overflows the record's u16 length
+        let msg = DrawMsg::new(vec![DrawCmd::Delete, path(16382)]);
+        let mut buf = vec![];
+        let err = msg.try_to_bytes(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(buf.is_empty());
+
+        // The payload encoding keeps the rest of the stream well-formed
+        msg.to_bytes(&mut buf);
+        assert_eq!(
+            DrawMsg::from_bytes(&mut &buf[..]).unwrap().commands,
+            vec![DrawCmd::Delete]
+        );
+
+        let msg = DrawMsg::new(vec![path(16381)]);
+        let mut buf = vec![];
+        msg.try_to_bytes(&mut buf).unwrap();
+        assert_eq!(buf.len(), RECORD_HEADER_SIZE + 10 + 4 * 16381);
+    }
+}
@@ -0,0 +1,193 @@
+//! SVG rendering of room paintings
+//!
+//! Turns a list of [`DrawCmd`]s into a standalone SVG document, e.g. for a
+//! room preview in a web gallery. Commands are replayed in order, so
+//! `Delete` drops the most recent drawing and `Detonate` clears everything
+//! drawn so far; back-layer paths are emitted before front-layer ones.
+
+use std::fmt::Write;
+
+use crate::messages::room::draw_ops::{DrawCmd, DRAW_FILL, DRAW_FRONT};
+
+/// A path ready to emit, with absolute coordinates
+struct Stroke<'a> {
+    cmd: u16,
+    pen_size: i16,
+    color: &'a [u16; 3],
+    /// (x, y) pairs
+    points: Vec<(i32, i32)>,
+}
+
+impl Stroke<'_> {
+    fn write_to(&self, out: &mut String) {
+        let color = format!(
+            "#{:02x}{:02x}{:02x}",
+            self.color[0] >> 8,
+            self.color[1] >> 8,
+            self.color[2] >> 8
+        );
+        let pen = format!(
+            r#"stroke="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round""#,
+            color,
+            self.pen_size.max(1)
+        );
+        let points = self
+            .points
+            .iter()
+            .map(|(x, y)| format!("{},{}", x, y))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let _ = match self.points.as_slice() {
+            _ if self.cmd & DRAW_FILL != 0 => writeln!(
+                out,
+                r#"<polygon points="{}" fill="{}" {}/>"#,
+                points, color, pen
+            ),
+            [(x1, y1)] => writeln!(
+                out,
+                r#"<line x1="{x1}" y1="{y1}" x2="{x1}" y2="{y1}" {pen}/>"#
+            ),
+            [(x1, y1), (x2, y2)] => writeln!(
+                out,
+                r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" {pen}/>"#
+            ),
+            _ => writeln!(
+                out,
+                r#"<polyline points="{}" fill="none" {}/>"#,
+                points, pen
+            ),
+        };
+    }
+}
+
+/// Render draw commands as an SVG document `width` by `height` pixels
+///
+/// Pen colors are taken from the top byte of each 16-bit channel. Paths
+/// with the fill flag become filled polygons, two-point paths `<line>`s and
+/// longer ones `<polyline>`s. Commands other than paths, `Delete` and
+/// `Detonate` (text, ellipses, ...) are skipped.
+pub fn render_draw_commands_svg(cmds: &[DrawCmd], width: u16, height: u16) -> String {
+    let mut strokes: Vec<Stroke> = Vec::new();
+    for cmd in cmds {
+        match cmd {
+            DrawCmd::Path {
+                cmd,
+                pen_size,
+                color,
+                points,
+            } => {
+                // The first point is absolute, the rest relative to the previous one
+                let mut pos = (0i32, 0i32);
+                let points = points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, point)| {
+                        let (h, v) = (point.h as i32, point.v as i32);
+                        pos = if i == 0 {
+                            (h, v)
+                        } else {
+                            (pos.0 + h, pos.1 + v)
+                        };
+                        pos
+                    })
+                    .collect::<Vec<_>>();
+                if !points.is_empty() {
+                    strokes.push(Stroke {
+                        cmd: *cmd,
+                        pen_size: *pen_size,
+                        color,
+                        points,
+                    });
+                }
+            }
+            DrawCmd::Delete => {
+                strokes.pop();
+            }
+            DrawCmd::Detonate => strokes.clear(),
+            DrawCmd::Other { .. } => {}
+        }
+    }
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+        width, height
+    );
+    let (front, back): (Vec<_>, Vec<_>) = strokes
+        .iter()
+        .partition(|stroke| stroke.cmd & DRAW_FRONT != 0);
+    for stroke in back.into_iter().chain(front) {
+        stroke.write_to(&mut out);
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::room::draw_ops::DRAW_PATH;
+    use crate::Point;
+
+    fn path(cmd: u16, color: [u16; 3], points: &[(i16, i16)]) -> DrawCmd {
+        DrawCmd::Path {
+            cmd,
+            pen_size: 2,
+            color,
+            points: points.iter().map(|&(h, v)| Point::new(h, v)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_render_two_lines() {
+        let cmds = [
+            path(DRAW_PATH, [0xFFFF, 0, 0], &[(10, 20), (30, 5)]),
+            path(DRAW_PATH, [0, 0, 0x8000], &[(100, 100), (-50, 0)]),
+        ];
+        let svg = render_draw_commands_svg(&cmds, 512, 384);
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(r#"width="512" height="384""#));
+        assert_eq!(svg.matches("<line ").count(), 2);
+        assert!(svg.contains(
+            r##"<line x1="10" y1="20" x2="40" y2="25" stroke="#ff0000" stroke-width="2""##
+        ));
+        assert!(svg.contains(r##"<line x1="100" y1="100" x2="50" y2="100" stroke="#000080""##));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_render_layers_and_undo() {
+        let cmds = [
+            path(
+                DRAW_PATH | DRAW_FRONT,
+                [0, 0xFFFF, 0],
+                &[(0, 0), (1, 1), (1, 1)],
+            ),
+            path(DRAW_PATH, [0, 0, 0], &[(5, 5), (1, 0)]),
+            path(
+                DRAW_PATH | DRAW_FILL,
+                [0xFFFF, 0xFFFF, 0],
+                &[(0, 0), (4, 0), (0, 4)],
+            ),
+            DrawCmd::Delete,
+        ];
+        let svg = render_draw_commands_svg(&cmds, 10, 10);
+
+        // The filled shape was undone; back layer comes first
+        assert!(!svg.contains("<polygon"));
+        let line = svg.find("<line").unwrap();
+        let polyline = svg
+            .find(r#"<polyline points="0,0 1,1 2,2" fill="none""#)
+            .unwrap();
+        assert!(line < polyline);
+
+        let cmds = [cmds[2].clone()];
+        let svg = render_draw_commands_svg(&cmds, 10, 10);
+        assert!(svg.contains(r##"<polygon points="0,0 4,0 4,4" fill="#ffff00""##));
+
+        let cmds = [cmds[0].clone(), DrawCmd::Detonate];
+        let svg = render_draw_commands_svg(&cmds, 10, 10);
+        assert_eq!(svg.lines().count(), 2);
+    }
+}
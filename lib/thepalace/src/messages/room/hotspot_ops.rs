@@ -8,6 +8,7 @@
 
 use bytes::{Buf, BufMut};
 
+use crate::buffer::BufExt;
 use crate::messages::{MessageId, MessagePayload};
 use crate::Point;
 
@@ -35,7 +36,7 @@ impl MessagePayload for SpotDelMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            spot_id: buf.get_i32(),
+            spot_id: buf.get_i32_checked()?,
         })
     }
 
@@ -75,8 +76,8 @@ impl MessagePayload for SpotMoveMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            room_id: buf.get_i16(),
-            spot_id: buf.get_i32(),
+            room_id: buf.get_i16_checked()?,
+            spot_id: buf.get_i32_checked()?,
             pos: Point::from_bytes(buf)?,
         })
     }
@@ -117,7 +118,8 @@ pub struct SpotStateMsg {
     pub room_id: i16,
     /// Hotspot ID to modify
     pub spot_id: i32,
-    /// New state value
+    /// New state: a [`HotspotState`](crate::room::HotspotState) for lockable
+    /// doors, otherwise a picture index
     pub state: i16,
 }
 
@@ -139,9 +141,9 @@ impl MessagePayload for SpotStateMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            room_id: buf.get_i16(),
-            spot_id: buf.get_i32(),
-            state: buf.get_i16(),
+            room_id: buf.get_i16_checked()?,
+            spot_id: buf.get_i32_checked()?,
+            state: buf.get_i16_checked()?,
         })
     }
 
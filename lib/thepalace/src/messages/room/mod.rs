@@ -6,18 +6,30 @@
 //! - MessageId::RoomDescEnd: Marks end of room description sequence
 //! - MessageId::RoomNew: Create a new room
 //! - MessageId::RoomSetDesc: Update room description
+//! - MessageId::Draw: Paint on the room
+//!
+//! [`render_draw_commands_svg`] renders a room's painting as SVG.
+//!
+//! [`RoomRec::diff`] compares two versions of a room so small edits can be
+//! sent as SpotMove/SpotState instead of a full RoomDesc.
 //!
 //! RoomRec is a complex structure with variable-length data including hotspots,
 //! pictures, loose props, draw commands, and embedded strings.
 
 // Sub-modules
+mod diff;
 mod door_ops;
+mod draw_ops;
+mod draw_svg;
 mod hotspot_ops;
 mod picture_ops;
 mod prop_ops;
 mod records;
 mod room_ops;
 
+// Re-export all public items from diff
+pub use diff::RoomDiff;
+
 // Re-export all public items from records
 pub use records::{Hotspot, LPropRec, PictureRec, RoomRec};
 
@@ -33,5 +45,13 @@ pub use hotspot_ops::{SpotDelMsg, SpotMoveMsg, SpotNewMsg, SpotStateMsg};
 // Re-export all public items from door_ops
 pub use door_ops::{DoorLockMsg, DoorUnlockMsg};
 
+// Re-export all public items from draw_ops
+pub use draw_ops::{
+    DrawCmd, DrawLimits, DrawMsg, DRAW_DELETE, DRAW_DETONATE, DRAW_FILL, DRAW_FRONT, DRAW_PATH,
+};
+
+// Re-export all public items from draw_svg
+pub use draw_svg::render_draw_commands_svg;
+
 // Re-export all public items from picture_ops
 pub use picture_ops::PictMoveMsg;
@@ -5,6 +5,7 @@
 
 use bytes::{Buf, BufMut};
 
+use crate::buffer::BufExt;
 use crate::messages::{MessageId, MessagePayload};
 use crate::Point;
 
@@ -42,8 +43,8 @@ impl MessagePayload for PictMoveMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            room_id: buf.get_i16(),
-            spot_id: buf.get_i32(),
+            room_id: buf.get_i16_checked()?,
+            spot_id: buf.get_i32_checked()?,
             pos: Point::from_bytes(buf)?,
         })
     }
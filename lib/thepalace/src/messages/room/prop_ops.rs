@@ -32,9 +32,9 @@ pub struct RoomListRec {
 impl RoomListRec {
     /// Parse a RoomListRec from bytes
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
-        let room_id = buf.get_i32();
-        let flags = RoomFlags::from_bits_truncate(buf.get_i16() as u16);
-        let nbr_users = buf.get_i16();
+        let room_id = buf.get_i32_checked()?;
+        let flags = RoomFlags::from_bits_truncate(buf.get_i16_checked()? as u16);
+        let nbr_users = buf.get_i16_checked()?;
         let name = buf.get_pstring()?;
 
         Ok(Self {
@@ -136,7 +136,7 @@ impl MessagePayload for PropDelMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            prop_num: buf.get_i32(),
+            prop_num: buf.get_i32_checked()?,
         })
     }
 
@@ -170,7 +170,7 @@ impl MessagePayload for PropMoveMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            prop_num: buf.get_i32(),
+            prop_num: buf.get_i32_checked()?,
             pos: Point::from_bytes(buf)?,
         })
     }
@@ -218,6 +218,7 @@ impl MessagePayload for PropNewMsg {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::messages::message::roundtrip_be;
 
     #[test]
     fn test_room_list_rec() {
@@ -284,6 +285,22 @@ mod tests {
         assert_eq!(parsed.pos.v, 200);
     }
 
+    #[test]
+    fn test_prop_move_msg_big_endian() {
+        // prop_num, then the point in v, h order
+        let msg = PropMoveMsg::new(
+            0x0102_0304,
+            Point {
+                h: 0x0708,
+                v: 0x0506,
+            },
+        );
+        roundtrip_be(&msg, &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+
+        let msg = PropMoveMsg::new(-1, Point { h: 300, v: -300 });
+        roundtrip_be(&msg, &[0xFF, 0xFF, 0xFF, 0xFF, 0xFE, 0xD4, 0x01, 0x2C]);
+    }
+
     #[test]
     fn test_prop_new_msg() {
         let msg = PropNewMsg::new(AssetSpec { id: 42, crc: 12345 }, Point { h: 150, v: 250 });
@@ -298,4 +315,25 @@ mod tests {
         assert_eq!(parsed.pos.h, 150);
         assert_eq!(parsed.pos.v, 250);
     }
+
+    #[test]
+    fn test_prop_new_msg_big_endian() {
+        // id, crc, two bytes of padding, then the point in v, h order
+        let msg = PropNewMsg::new(
+            AssetSpec {
+                id: 0x0102_0304,
+                crc: 0x0506_0708,
+            },
+            Point {
+                h: 0x0B0C,
+                v: 0x090A,
+            },
+        );
+        roundtrip_be(
+            &msg,
+            &[
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x00, 0x00, 0x09, 0x0A, 0x0B, 0x0C,
+            ],
+        );
+    }
 }
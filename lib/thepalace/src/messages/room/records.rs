@@ -9,19 +9,20 @@
 use bytes::{Buf, BufMut, Bytes};
 
 use crate::buffer::BufExt;
-use crate::messages::flags::RoomFlags;
-use crate::room::{HotspotState, HotspotType};
+use crate::messages::flags::{PropFlags, RoomFlags};
+use crate::messages::room::draw_ops::{DrawCmd, DrawLimits, RECORD_HEADER_SIZE};
+use crate::room::HotspotType;
 use crate::EventMask;
 use crate::{AssetSpec, Point};
 
 /// Loose prop record - describes a prop in the room.
 ///
-/// Size: 24 bytes (4 padding + 8 + 4 + 4 + 4)
+/// Size: 26 bytes (4 padding + 10 + 4 + 4 + 4)
 #[derive(Debug, Clone, PartialEq)]
 pub struct LPropRec {
     /// Asset identifier for the prop
     pub prop_spec: AssetSpec,
-    /// Prop behavior flags
+    /// Prop behavior flags; the low word holds the prop's [`PropFlags`]
     pub flags: i32,
     /// Arbitrary use variable (used by client)
     pub ref_con: i32,
@@ -30,14 +31,17 @@ pub struct LPropRec {
 }
 
 impl LPropRec {
+    /// Size of an LPropRec in bytes
+    pub const SIZE: usize = 4 + AssetSpec::WIRE_SIZE + 4 + 4 + 4;
+
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         // Skip 4 bytes of padding (originally a linked list pointer for client use)
-        let _ = buf.get_i32();
+        let _ = buf.get_i32_checked()?;
 
         Ok(Self {
             prop_spec: AssetSpec::from_bytes(buf)?,
-            flags: buf.get_i32(),
-            ref_con: buf.get_i32(),
+            flags: buf.get_i32_checked()?,
+            ref_con: buf.get_i32_checked()?,
             loc: Point::from_bytes(buf)?,
         })
     }
@@ -51,6 +55,13 @@ impl LPropRec {
         buf.put_i32(self.ref_con);
         self.loc.to_bytes(buf);
     }
+
+    /// Typed view of the prop header flags carried in `flags`
+    ///
+    /// Only the low 16 bits are meaningful; undefined bits are dropped.
+    pub fn prop_flags(&self) -> PropFlags {
+        PropFlags::from_bits_truncate(self.flags as u16)
+    }
 }
 
 /// Picture record - describes a picture layer in the room.
@@ -69,15 +80,18 @@ pub struct PictureRec {
 }
 
 impl PictureRec {
+    /// Size of a PictureRec in bytes
+    pub const SIZE: usize = 12;
+
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         let rec = Self {
-            ref_con: buf.get_i32(),
-            pic_id: buf.get_i16(),
-            pic_name_ofst: buf.get_i16(),
-            trans_color: buf.get_i16(),
+            ref_con: buf.get_i32_checked()?,
+            pic_id: buf.get_i16_checked()?,
+            pic_name_ofst: buf.get_i16_checked()?,
+            trans_color: buf.get_i16_checked()?,
         };
         // Skip 2 bytes of padding
-        let _ = buf.get_i16();
+        let _ = buf.get_i16_checked()?;
         Ok(rec)
     }
 
@@ -127,40 +141,44 @@ pub struct Hotspot {
     pub nbr_scripts: i16,
     /// Offset into varBuf for script records
     pub script_rec_ofst: i16,
-    /// Current state (locked/unlocked)
-    pub state: HotspotState,
+    /// Current state: a [`HotspotState`](crate::room::HotspotState) for
+    /// lockable doors, otherwise a picture index (`0..nbr_states`)
+    pub state: i16,
     /// Number of states
     pub nbr_states: i16,
     /// Offset into varBuf for state records
     pub state_rec_ofst: i16,
     /// Offset into varBuf for hotspot name (PString)
     pub name_ofst: i16,
-    /// Offset into varBuf for script text (PString)
+    /// Offset into varBuf for script text (C string)
     pub script_text_ofst: i16,
 }
 
 impl Hotspot {
+    /// Size of the fixed part of a Hotspot in bytes
+    pub const SIZE: usize = 48;
+
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
-        let script_event_mask = buf.get_i32().into();
-        let flags = buf.get_i32();
-        let secure_info = buf.get_i32();
-        let ref_con = buf.get_i32();
+        let script_event_mask = buf.get_i32_checked()?.into();
+        let flags = buf.get_i32_checked()?;
+        let secure_info = buf.get_i32_checked()?;
+        let ref_con = buf.get_i32_checked()?;
         let loc = Point::from_bytes(buf)?;
-        let id = buf.get_i16();
-        let dest = buf.get_i16();
-        let nbr_pts = buf.get_i16();
-        let pts_ofst = buf.get_i16();
-        let type_raw = buf.get_i16();
-        let group_id = buf.get_i16();
-        let nbr_scripts = buf.get_i16();
-        let script_rec_ofst = buf.get_i16();
-        let state_raw = buf.get_i16();
-        let nbr_states = buf.get_i16();
-        let state_rec_ofst = buf.get_i16();
-        let name_ofst = buf.get_i16();
-        let script_text_ofst = buf.get_i16();
+        let id = buf.get_i16_checked()?;
+        let dest = buf.get_i16_checked()?;
+        let nbr_pts = buf.get_i16_checked()?;
+        let pts_ofst = buf.get_i16_checked()?;
+        let type_raw = buf.get_i16_checked()?;
+        let group_id = buf.get_i16_checked()?;
+        let nbr_scripts = buf.get_i16_checked()?;
+        let script_rec_ofst = buf.get_i16_checked()?;
+        let state = buf.get_i16_checked()?;
+        let nbr_states = buf.get_i16_checked()?;
+        let state_rec_ofst = buf.get_i16_checked()?;
+        let name_ofst = buf.get_i16_checked()?;
+        let script_text_ofst = buf.get_i16_checked()?;
         // Skip 2 bytes of padding
-        let _ = buf.get_i16();
+        let _ = buf.get_i16_checked()?;
 
         let hotspot_type = HotspotType::from_i16(type_raw).ok_or_else(|| {
             std::io::Error::new(
@@ -169,13 +187,6 @@ impl Hotspot {
             )
         })?;
 
-        let state = HotspotState::from_i16(state_raw).ok_or_else(|| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Invalid hotspot state: {}", state_raw),
-            )
-        })?;
-
         Ok(Self {
             script_event_mask,
             flags,
@@ -212,7 +223,7 @@ impl Hotspot {
         buf.put_i16(self.group_id);
         buf.put_i16(self.nbr_scripts);
         buf.put_i16(self.script_rec_ofst);
-        buf.put_i16(self.state.as_i16());
+        buf.put_i16(self.state);
         buf.put_i16(self.nbr_states);
         buf.put_i16(self.state_rec_ofst);
         buf.put_i16(self.name_ofst);
@@ -278,31 +289,31 @@ pub struct RoomRec {
 
 impl RoomRec {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
-        let room_flags_raw = buf.get_i32();
-        let faces_id = buf.get_i32();
-        let room_id = buf.get_i16();
-        let room_name_ofst = buf.get_i16();
-        let pict_name_ofst = buf.get_i16();
-        let artist_name_ofst = buf.get_i16();
-        let password_ofst = buf.get_i16();
-        let nbr_hotspots = buf.get_i16();
-        let hotspot_ofst = buf.get_i16();
-        let nbr_pictures = buf.get_i16();
-        let picture_ofst = buf.get_i16();
-        let nbr_draw_cmds = buf.get_i16();
-        let first_draw_cmd = buf.get_i16();
-        let nbr_people = buf.get_i16();
-        let nbr_lprops = buf.get_i16();
-        let first_lprop = buf.get_i16();
+        let room_flags_raw = buf.get_i32_checked()?;
+        let faces_id = buf.get_i32_checked()?;
+        let room_id = buf.get_i16_checked()?;
+        let room_name_ofst = buf.get_i16_checked()?;
+        let pict_name_ofst = buf.get_i16_checked()?;
+        let artist_name_ofst = buf.get_i16_checked()?;
+        let password_ofst = buf.get_i16_checked()?;
+        let nbr_hotspots = buf.get_i16_checked()?;
+        let hotspot_ofst = buf.get_i16_checked()?;
+        let nbr_pictures = buf.get_i16_checked()?;
+        let picture_ofst = buf.get_i16_checked()?;
+        let nbr_draw_cmds = buf.get_i16_checked()?;
+        let first_draw_cmd = buf.get_i16_checked()?;
+        let nbr_people = buf.get_i16_checked()?;
+        let nbr_lprops = buf.get_i16_checked()?;
+        let first_lprop = buf.get_i16_checked()?;
         // Skip 2 bytes of padding
-        let _ = buf.get_i16();
-        let len_vars = buf.get_i16();
+        let _ = buf.get_i16_checked()?;
+        let len_vars = buf.get_i16_checked()?;
 
         let room_flags = RoomFlags::from_bits_truncate(room_flags_raw as u16);
 
         // Read variable buffer
         let var_buf = if len_vars > 0 {
-            buf.copy_to_bytes(len_vars as usize)
+            buf.copy_to_bytes_checked(len_vars as usize)?
         } else {
             Bytes::new()
         };
@@ -372,8 +383,89 @@ impl RoomRec {
         self.get_pstring(self.password_ofst)
     }
 
+    /// Parse the hotspot array from varBuf
+    pub fn hotspots(&self) -> std::io::Result<Vec<Hotspot>> {
+        let count = self.nbr_hotspots.max(0) as usize;
+        let mut buf = self.var_slice(self.hotspot_ofst, count * Hotspot::SIZE)?;
+        (0..count).map(|_| Hotspot::from_bytes(&mut buf)).collect()
+    }
+
+    /// Parse the picture array from varBuf
+    pub fn pictures(&self) -> std::io::Result<Vec<PictureRec>> {
+        let count = self.nbr_pictures.max(0) as usize;
+        let mut buf = self.var_slice(self.picture_ofst, count * PictureRec::SIZE)?;
+        (0..count).map(|_| PictureRec::from_bytes(&mut buf)).collect()
+    }
+
+    /// Parse the loose prop array from varBuf
+    pub fn loose_props(&self) -> std::io::Result<Vec<LPropRec>> {
+        let count = self.nbr_lprops.max(0) as usize;
+        let mut buf = self.var_slice(self.first_lprop, count * LPropRec::SIZE)?;
+        (0..count).map(|_| LPropRec::from_bytes(&mut buf)).collect()
+    }
+
+    /// Parse the room's painting from varBuf
+    ///
+    /// Draw records form a linked list starting at `first_draw_cmd`: each
+    /// header holds the varBuf offsets of the next record and of its own
+    /// data. Every offset is bounds-checked, and paths are held to the point
+    /// limit of [`DrawLimits::DEFAULT`].
+    pub fn draw_commands(&self) -> std::io::Result<Vec<DrawCmd>> {
+        let count = self.nbr_draw_cmds.max(0) as usize;
+        let mut commands = Vec::with_capacity(count);
+        let mut offset = self.first_draw_cmd;
+        for _ in 0..count {
+            let header = self.var_slice(offset, RECORD_HEADER_SIZE)?;
+            let mut fields = header;
+            let next_ofst = fields.get_i16();
+            // Skip the reserved word and command
+            fields.advance(4);
+            let cmd_length = fields.get_u16() as usize;
+            let data_ofst = fields.get_i16();
+
+            let data = self.var_slice(data_ofst, cmd_length)?;
+            commands.push(DrawCmd::from_bytes(
+                &mut header.chain(data),
+                &DrawLimits::DEFAULT,
+            )?);
+            offset = next_ofst;
+        }
+        Ok(commands)
+    }
+
+    /// Get the props a client needs to display this room, without repeats
+    ///
+    /// These are the loose props' specs in room order, for the client to
+    /// check against its cache before asking for the missing ones. Specs
+    /// with a don't-care CRC are kept; [`AssetSpec::crc_is_dont_care`]
+    /// marks them, since a cached copy can't be verified against them.
+    /// Users' props arrive separately with the user list, and pictures are
+    /// fetched by file name rather than by spec.
+    pub fn required_assets(&self) -> std::io::Result<Vec<AssetSpec>> {
+        let mut specs: Vec<AssetSpec> = self
+            .loose_props()?
+            .into_iter()
+            .map(|prop| prop.prop_spec)
+            .collect();
+        crate::dedup_props(&mut specs);
+        Ok(specs)
+    }
+
+    /// Get `len` bytes of varBuf starting at `offset`
+    pub(crate) fn var_slice(&self, offset: i16, len: usize) -> std::io::Result<&[u8]> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.var_buf.get(start..start.checked_add(len)?))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("varBuf range {}+{} out of bounds", offset, len),
+                )
+            })
+    }
+
     /// Helper to extract PString from varBuf at given offset
-    fn get_pstring(&self, offset: i16) -> std::io::Result<String> {
+    pub(crate) fn get_pstring(&self, offset: i16) -> std::io::Result<String> {
         if offset < 0 || offset as usize >= self.var_buf.len() {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -389,6 +481,8 @@ impl RoomRec {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::messages::flags::PropFormat;
+    use crate::room::HotspotState;
     use bytes::BytesMut;
 
     #[test]
@@ -398,7 +492,7 @@ mod tests {
                 id: 12345,
                 crc: 0xABCDEF01,
             },
-            flags: 0x00100000, // 20-bit format
+            flags: 0x00100000, // high-word bits survive the roundtrip
             ref_con: 42,
             loc: Point { v: 100, h: 200 },
         };
@@ -414,6 +508,30 @@ mod tests {
         assert_eq!(parsed, rec);
     }
 
+    #[test]
+    fn test_lprop_rec_prop_flags() {
+        let mut rec = LPropRec {
+            prop_spec: AssetSpec { id: 1, crc: 0 },
+            flags: PropFlags::FORMAT_20BIT.bits() as i32,
+            ref_con: 0,
+            loc: Point { v: 0, h: 0 },
+        };
+        assert_eq!(rec.prop_flags(), PropFlags::FORMAT_20BIT);
+        assert_eq!(rec.prop_flags().format(), PropFormat::Rgb20);
+
+        // 0x00100000 sets no header bits at all
+        rec.flags = 0x00100000;
+        assert_eq!(rec.prop_flags(), PropFlags::empty());
+        assert_eq!(rec.prop_flags().format(), PropFormat::Indexed8);
+
+        let behavior = PropFlags::HEAD | PropFlags::GHOST | PropFlags::BOUNCE;
+        rec.flags = 0x7FFF_0000 | behavior.bits() as i32;
+        let flags = rec.prop_flags();
+        assert!(flags.is_head() && flags.is_ghost() && !flags.is_rare());
+        assert!(flags.contains(PropFlags::BOUNCE));
+        assert_eq!(flags.format(), PropFormat::Indexed8);
+    }
+
     #[test]
     fn test_picture_rec_roundtrip() {
         let rec = PictureRec {
@@ -452,7 +570,7 @@ mod tests {
             group_id: 0,
             nbr_scripts: 1,
             script_rec_ofst: 100,
-            state: HotspotState::Unlocked,
+            state: HotspotState::Unlocked.as_i16(),
             nbr_states: 0,
             state_rec_ofst: 0,
             name_ofst: 50,
@@ -516,4 +634,122 @@ mod tests {
         assert_eq!(parsed, room);
         assert_eq!(parsed.room_name().unwrap(), room_name);
     }
+
+    #[test]
+    fn test_required_assets() {
+        let specs = [
+            AssetSpec::new(100, 0x1111_1111),
+            AssetSpec::new(200, 0),
+            AssetSpec::new(100, 0x1111_1111),
+        ];
+        // A name first, so the props aren't at offset 0
+        let mut var_buf = BytesMut::new();
+        var_buf.put_u8(3);
+        var_buf.put_slice(b"Bar");
+        let first_lprop = var_buf.len() as i16;
+        for (i, spec) in specs.iter().enumerate() {
+            let prop = LPropRec {
+                prop_spec: *spec,
+                flags: 0,
+                ref_con: 0,
+                loc: Point::new(i as i16 * 10, 50),
+            };
+            prop.to_bytes(&mut var_buf);
+        }
+        assert_eq!(var_buf.len(), 4 + 3 * LPropRec::SIZE);
+
+        let mut room = RoomRec {
+            room_flags: RoomFlags::empty(),
+            faces_id: 0,
+            room_id: 7,
+            room_name_ofst: 0,
+            pict_name_ofst: -1,
+            artist_name_ofst: -1,
+            password_ofst: -1,
+            nbr_hotspots: 0,
+            hotspot_ofst: 0,
+            nbr_pictures: 0,
+            picture_ofst: 0,
+            nbr_draw_cmds: 0,
+            first_draw_cmd: 0,
+            nbr_people: 0,
+            nbr_lprops: specs.len() as i16,
+            first_lprop,
+            len_vars: var_buf.len() as i16,
+            var_buf: var_buf.freeze(),
+        };
+
+        assert_eq!(room.loose_props().unwrap().len(), 3);
+        let required = room.required_assets().unwrap();
+        assert_eq!(required, [specs[0], specs[1]]);
+        assert!(!required[0].crc_is_dont_care());
+        assert!(required[1].crc_is_dont_care());
+
+        room.nbr_lprops = 4;
+        assert!(room.required_assets().is_err());
+    }
+
+    #[test]
+    fn test_draw_commands() {
+        use crate::messages::room::draw_ops::{DRAW_FRONT, DRAW_PATH};
+        use crate::messages::room::DrawMsg;
+        use crate::messages::MessagePayload;
+
+        // Append a draw record with its data right after the header
+        fn put_record(var_buf: &mut BytesMut, cmd: &DrawCmd, next_ofst: i16) -> i16 {
+            let mut record = BytesMut::new();
+            DrawMsg::new(vec![cmd.clone()]).to_bytes(&mut record);
+            let start = var_buf.len() as i16;
+            var_buf.put_i16(next_ofst);
+            var_buf.put_slice(&record[2..8]);
+            var_buf.put_i16(start + RECORD_HEADER_SIZE as i16);
+            var_buf.put_slice(&record[RECORD_HEADER_SIZE..]);
+            start
+        }
+
+        let line = DrawCmd::Path {
+            cmd: DRAW_PATH | DRAW_FRONT,
+            pen_size: 3,
+            color: [0xFFFF, 0, 0],
+            points: vec![Point::new(10, 20), Point::new(30, -5)],
+        };
+        // Stored out of order so the links have to be followed
+        let mut var_buf = BytesMut::new();
+        var_buf.put_u8(3);
+        var_buf.put_slice(b"Bar");
+        let second = put_record(&mut var_buf, &DrawCmd::Delete, 0);
+        let first = put_record(&mut var_buf, &line, second);
+
+        let room = RoomRec {
+            room_flags: RoomFlags::empty(),
+            faces_id: 0,
+            room_id: 9,
+            room_name_ofst: 0,
+            pict_name_ofst: -1,
+            artist_name_ofst: -1,
+            password_ofst: -1,
+            nbr_hotspots: 0,
+            hotspot_ofst: 0,
+            nbr_pictures: 0,
+            picture_ofst: 0,
+            nbr_draw_cmds: 2,
+            first_draw_cmd: first,
+            nbr_people: 0,
+            nbr_lprops: 0,
+            first_lprop: 0,
+            len_vars: var_buf.len() as i16,
+            var_buf: var_buf.freeze(),
+        };
+
+        let mut buf = BytesMut::new();
+        room.to_bytes(&mut buf);
+        let mut parsed = RoomRec::from_bytes(&mut buf.freeze()).unwrap();
+        assert_eq!(parsed.draw_commands().unwrap(), [line, DrawCmd::Delete]);
+
+        // Offsets outside varBuf are errors, not panics
+        parsed.first_draw_cmd = -1;
+        assert!(parsed.draw_commands().is_err());
+        parsed.first_draw_cmd = parsed.len_vars - 4;
+        assert!(parsed.draw_commands().is_err());
+    }
 }
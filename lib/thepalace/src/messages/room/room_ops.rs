@@ -7,6 +7,7 @@
 
 use bytes::{Buf, BufMut};
 
+use crate::buffer::BufExt;
 use crate::messages::{MessageId, MessagePayload};
 
 use super::records::RoomRec;
@@ -33,7 +34,7 @@ pub struct RoomGotoMsg {
 impl RoomGotoMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            dest: buf.get_i16(),
+            dest: buf.get_i16_checked()?,
         })
     }
 
@@ -138,6 +139,7 @@ impl MessagePayload for RoomDescMsg {
 mod tests {
     use super::*;
     use crate::messages::flags::RoomFlags;
+    use crate::messages::message::roundtrip_be;
     use bytes::{Bytes, BytesMut};
 
     #[test]
@@ -169,6 +171,12 @@ mod tests {
         assert_eq!(parsed.dest, msg.dest);
     }
 
+    #[test]
+    fn test_room_goto_msg_big_endian() {
+        roundtrip_be(&RoomGotoMsg { dest: 0x0102 }, &[0x01, 0x02]);
+        roundtrip_be(&RoomGotoMsg { dest: -2 }, &[0xFF, 0xFE]);
+    }
+
     #[test]
     fn test_room_desc_end_msg() {
         let msg = RoomDescEndMsg;
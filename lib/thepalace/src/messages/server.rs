@@ -4,7 +4,7 @@
 //! - MessageId::Ping: Keepalive ping from server/client
 //! - MessageId::Pong: Keepalive pong response
 //! - MessageId::ServerInfo: Server configuration and capabilities
-//! - MessageId::ExtendedInfo: Extended server information
+//! - MessageId::ServerStatus: Server status for wizards
 //! - MessageId::UserList: List of users in a room
 //! - MessageId::ListOfAllUsers: Complete list of all users on server
 //! - MessageId::UserLog: Notification that a user logged on
@@ -98,11 +98,11 @@ impl MessagePayload for ServerInfoMsg {
     }
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
-        let server_permissions = ServerFlags::from_bits_truncate(buf.get_u32());
+        let server_permissions = ServerFlags::from_bits_truncate(buf.get_u32_checked()?);
         let server_name = buf.get_str63()?;
-        let server_options = buf.get_u32();
-        let upload_caps = UploadCaps::from_bits_truncate(buf.get_u32());
-        let download_caps = DownloadCaps::from_bits_truncate(buf.get_u32());
+        let server_options = buf.get_u32_checked()?;
+        let upload_caps = UploadCaps::from_bits_truncate(buf.get_u32_checked()?);
+        let download_caps = DownloadCaps::from_bits_truncate(buf.get_u32_checked()?);
 
         Ok(Self {
             server_permissions,
@@ -122,6 +122,42 @@ impl MessagePayload for ServerInfoMsg {
     }
 }
 
+/// MessageId::ServerStatus - Server status for admin tools
+///
+/// A wizard requests it with an empty payload; the server answers with the
+/// current counts. This is a server extension with its own ID, so classic
+/// clients never mistake it for an extended-info ('sInf') record.
+/// Size: 12 bytes (4 + 4 + 4)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ServerStatusMsg {
+    /// Number of connected users
+    pub user_count: u32,
+    /// Number of rooms with at least one user in them
+    pub room_count: u32,
+    /// Seconds since the server started
+    pub uptime_secs: u32,
+}
+
+impl MessagePayload for ServerStatusMsg {
+    fn message_id() -> MessageId {
+        MessageId::ServerStatus
+    }
+
+    fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
+        Ok(Self {
+            user_count: buf.get_u32_checked()?,
+            room_count: buf.get_u32_checked()?,
+            uptime_secs: buf.get_u32_checked()?,
+        })
+    }
+
+    fn to_bytes(&self, buf: &mut impl BufMut) {
+        buf.put_u32(self.user_count);
+        buf.put_u32(self.room_count);
+        buf.put_u32(self.uptime_secs);
+    }
+}
+
 /// MessageId::UserList - List of users in current room
 ///
 /// Sent from server to client as part of room entry process.
@@ -229,7 +265,7 @@ impl MessagePayload for UserLogMsg {
 
     fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            nbr_users: buf.get_i32(),
+            nbr_users: buf.get_i32_checked()?,
         })
     }
 
@@ -295,6 +331,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_server_status_msg() {
+        let status = ServerStatusMsg {
+            user_count: 12,
+            room_count: 3,
+            uptime_secs: 86400,
+        };
+
+        let mut buf = vec![];
+        status.to_bytes(&mut buf);
+        assert_eq!(buf.len(), 12);
+        assert_eq!(ServerStatusMsg::from_bytes(&mut &buf[..]).unwrap(), status);
+        assert!(ServerStatusMsg::from_bytes(&mut &buf[..8]).is_err());
+    }
+
     #[test]
     fn test_user_log_msg() {
         let user_log = UserLogMsg::new(42);
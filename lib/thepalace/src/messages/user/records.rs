@@ -1,10 +1,14 @@
 //! User data structures
 
+use std::io::{self, ErrorKind};
+
 use bytes::{Buf, BufMut};
 
 use crate::buffer::{BufExt, BufMutExt};
 use crate::{AssetSpec, Point};
 
+use super::user_ops::UserDescMsg;
+
 /// UserRec - Complete user record structure
 ///
 /// This structure is used in MessageId::UserNew and MessageId::UserList to describe
@@ -39,8 +43,119 @@ impl UserRec {
     /// Size of UserRec in bytes (always 142)
     pub const SIZE: usize = 4 + 4 + (9 * 10) + 2 + 2 + 2 + 2 + 2 + 2 + 32;
 
+    /// Maximum number of props a user can wear
+    pub const MAX_PROPS: usize = 9;
+
+    /// Maximum length of a user name in bytes (Str31)
+    pub const MAX_NAME_LEN: usize = 31;
+
+    /// Create a user record from a name, face, color, and prop list
+    ///
+    /// All other fields start at zero; set them directly as needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidInput` if the name is longer than 31 bytes or more
+    /// than 9 props are given.
+    pub fn new(name: &str, face_nbr: i16, color_nbr: i16, props: &[AssetSpec]) -> io::Result<Self> {
+        if name.len() > Self::MAX_NAME_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("user name too long: {} bytes (max 31)", name.len()),
+            ));
+        }
+        if props.len() > Self::MAX_PROPS {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("too many props: {} (max 9)", props.len()),
+            ));
+        }
+
+        let mut prop_spec = [AssetSpec::default(); 9];
+        prop_spec[..props.len()].copy_from_slice(props);
+
+        Ok(Self {
+            user_id: 0,
+            room_pos: Point::origin(),
+            prop_spec,
+            room_id: 0,
+            face_nbr,
+            color_nbr,
+            away_flag: 0,
+            open_to_msgs: 0,
+            nbr_props: props.len() as i16,
+            name: name.to_string(),
+        })
+    }
+
+    /// Get the user name, checking it fits the 31-byte wire field
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidData` if the name is longer than 31 bytes.
+    pub fn name(&self) -> io::Result<String> {
+        if self.name.len() > Self::MAX_NAME_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("user name too long: {} bytes (max 31)", self.name.len()),
+            ));
+        }
+        Ok(self.name.clone())
+    }
+
+    /// Get the props the user is actually wearing
+    ///
+    /// Only the first `nbr_props` entries of `prop_spec` are in use; the count
+    /// is clamped to 0..=9 so an out-of-range value can never index past the array.
+    pub fn props(&self) -> &[AssetSpec] {
+        let count = self.nbr_props.clamp(0, Self::MAX_PROPS as i16) as usize;
+        &self.prop_spec[..count]
+    }
+
+    /// Apply a UserDesc appearance change: face, color and props together
+    ///
+    /// Unused prop slots are cleared. Nothing is changed if the description
+    /// is rejected.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidInput` if the description has more than 9 props.
+    pub fn apply_desc(&mut self, desc: &UserDescMsg) -> io::Result<()> {
+        if desc.props.len() > Self::MAX_PROPS {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("too many props: {} (max 9)", desc.props.len()),
+            ));
+        }
+
+        let mut prop_spec = [AssetSpec::default(); 9];
+        prop_spec[..desc.props.len()].copy_from_slice(&desc.props);
+
+        self.face_nbr = desc.face_nbr;
+        self.color_nbr = desc.color_nbr;
+        self.prop_spec = prop_spec;
+        self.nbr_props = desc.props.len() as i16;
+        Ok(())
+    }
+
     /// Parse a UserRec from bytes
+    ///
+    /// # Errors
+    ///
+    /// Returns `UnexpectedEof` if fewer than 142 bytes remain, or `InvalidData`
+    /// if the prop count is outside 0..=9.
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
+        if buf.remaining() < Self::SIZE {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!(
+                    "UserRec needs {} bytes, only {} remain",
+                    Self::SIZE,
+                    buf.remaining()
+                ),
+            ));
+        }
+
         let user_id = buf.get_i32();
         let room_pos = Point {
             v: buf.get_i16(),
@@ -59,6 +174,12 @@ impl UserRec {
         let away_flag = buf.get_i16();
         let open_to_msgs = buf.get_i16();
         let nbr_props = buf.get_i16();
+        if !(0..=Self::MAX_PROPS as i16).contains(&nbr_props) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("UserRec prop count {} out of range (max 9)", nbr_props),
+            ));
+        }
         let name = buf.get_str31()?;
 
         Ok(Self {
@@ -131,4 +252,89 @@ mod tests {
 
         assert_eq!(parsed, user);
     }
+
+    #[test]
+    fn test_user_rec_new_roundtrip() {
+        let props = [
+            AssetSpec::new(100, 0x11111111),
+            AssetSpec::new(200, 0x22222222),
+            AssetSpec::new(300, 0x33333333),
+        ];
+        let mut user = UserRec::new("Alice", 4, 11, &props).unwrap();
+        user.user_id = 7;
+
+        let mut buf = BytesMut::new();
+        user.to_bytes(&mut buf);
+        assert_eq!(buf.len(), UserRec::SIZE);
+
+        let parsed = UserRec::from_bytes(&mut buf.freeze()).unwrap();
+        assert_eq!(parsed, user);
+        assert_eq!(parsed.name().unwrap(), "Alice");
+        assert_eq!(parsed.props(), &props);
+        assert_eq!(parsed.face_nbr, 4);
+        assert_eq!(parsed.color_nbr, 11);
+    }
+
+    #[test]
+    fn test_user_rec_new_rejects_invalid() {
+        let props = [AssetSpec::default(); 10];
+        assert!(UserRec::new("Alice", 0, 0, &props).is_err());
+        assert!(UserRec::new(&"x".repeat(32), 0, 0, &[]).is_err());
+    }
+
+    #[test]
+    fn test_user_rec_props_clamped() {
+        let mut user = UserRec::new("Bob", 0, 0, &[]).unwrap();
+        user.nbr_props = 42;
+        assert_eq!(user.props().len(), UserRec::MAX_PROPS);
+        user.nbr_props = -1;
+        assert!(user.props().is_empty());
+    }
+
+    #[test]
+    fn test_user_rec_rejects_bad_prop_count() {
+        let mut user = UserRec::new("Bob", 0, 0, &[]).unwrap();
+        user.nbr_props = 10;
+
+        let mut buf = BytesMut::new();
+        user.to_bytes(&mut buf);
+        let err = UserRec::from_bytes(&mut buf.freeze()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_user_rec_truncated() {
+        let mut buf = BytesMut::new();
+        UserRec::new("Bob", 0, 0, &[]).unwrap().to_bytes(&mut buf);
+        let err = UserRec::from_bytes(&mut buf.freeze().slice(..100)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_apply_desc() {
+        let hat = AssetSpec { id: 1, crc: 0xAA };
+        let shoe = AssetSpec { id: 2, crc: 0xBB };
+        let mut user = UserRec::new("Bob", 1, 2, &[hat, shoe]).unwrap();
+
+        let desc = UserDescMsg {
+            face_nbr: 5,
+            color_nbr: 9,
+            props: vec![shoe],
+        };
+        user.apply_desc(&desc).unwrap();
+        assert_eq!((user.face_nbr, user.color_nbr), (5, 9));
+        assert_eq!(user.props(), [shoe]);
+        assert_eq!(user.prop_spec[1], AssetSpec::default());
+
+        // Too many props: rejected without touching face or color
+        let before = user.clone();
+        let desc = UserDescMsg {
+            face_nbr: 0,
+            color_nbr: 0,
+            props: vec![hat; 10],
+        };
+        let err = user.apply_desc(&desc).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(user, before);
+    }
 }
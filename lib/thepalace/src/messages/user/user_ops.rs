@@ -87,8 +87,8 @@ impl UserMoveMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
             pos: Point {
-                v: buf.get_i16(),
-                h: buf.get_i16(),
+                v: buf.get_i16_checked()?,
+                h: buf.get_i16_checked()?,
             },
         })
     }
@@ -160,7 +160,7 @@ pub struct UserColorMsg {
 impl UserColorMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            color_nbr: buf.get_i16(),
+            color_nbr: buf.get_i16_checked()?,
         })
     }
 
@@ -195,7 +195,7 @@ pub struct UserFaceMsg {
 impl UserFaceMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
         Ok(Self {
-            face_nbr: buf.get_i16(),
+            face_nbr: buf.get_i16_checked()?,
         })
     }
 
@@ -229,12 +229,8 @@ pub struct UserPropMsg {
 
 impl UserPropMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
-        let nbr_props = buf.get_i32();
-        let mut props = Vec::with_capacity(nbr_props as usize);
-
-        for _ in 0..nbr_props {
-            props.push(AssetSpec::from_bytes(buf)?);
-        }
+        let nbr_props = buf.get_i32_checked()?;
+        let props = get_props(buf, nbr_props)?;
 
         Ok(Self { props })
     }
@@ -261,6 +257,28 @@ impl MessagePayload for UserPropMsg {
     }
 }
 
+/// Read `count` prop specs
+///
+/// The count comes off the wire, so it is checked against the bytes actually
+/// present before anything is allocated for it.
+fn get_props(buf: &mut impl Buf, count: i32) -> std::io::Result<Vec<AssetSpec>> {
+    // Each AssetSpec is 10 bytes on the wire
+    let count = usize::try_from(count)
+        .ok()
+        .filter(|&count| count <= buf.remaining() / 10)
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "prop count {} doesn't fit in {} bytes",
+                    count,
+                    buf.remaining()
+                ),
+            )
+        })?;
+    (0..count).map(|_| AssetSpec::from_bytes(buf)).collect()
+}
+
 /// MessageId::UserDesc - Bulk user appearance change
 ///
 /// Sent bidirectionally to change face, color, and props all at once.
@@ -274,14 +292,10 @@ pub struct UserDescMsg {
 
 impl UserDescMsg {
     pub fn from_bytes(buf: &mut impl Buf) -> std::io::Result<Self> {
-        let face_nbr = buf.get_i16();
-        let color_nbr = buf.get_i16();
-        let nbr_props = buf.get_i32();
-
-        let mut props = Vec::with_capacity(nbr_props as usize);
-        for _ in 0..nbr_props {
-            props.push(AssetSpec::from_bytes(buf)?);
-        }
+        let face_nbr = buf.get_i16_checked()?;
+        let color_nbr = buf.get_i16_checked()?;
+        let nbr_props = buf.get_i32_checked()?;
+        let props = get_props(buf, nbr_props)?;
 
         Ok(Self {
             face_nbr,
@@ -35,6 +35,7 @@
 
 use bytes::{Buf, BufMut};
 use std::io::{self, Read, Write};
+use thiserror::Error;
 
 use crate::messages::flags::{PropFlags, PropFormat};
 
@@ -43,6 +44,141 @@ pub const PROP_WIDTH: usize = 44;
 pub const PROP_HEIGHT: usize = 44;
 pub const PROP_PIXELS: usize = PROP_WIDTH * PROP_HEIGHT; // 1936
 
+/// Compute the CRC32 a Palace client expects in a prop's `AssetSpec`
+///
+/// The checksum covers the prop record exactly as [`PropRec::to_bytes`]
+/// writes it: the 12-byte header (width through flags) followed by the image
+/// data. Transfer framing is not included - the AssetSend type, spec, block
+/// and name fields, as well as the asset name stored alongside a prop, are
+/// outside the checksummed span. The CRC uses the Palace variant
+/// ([`crate::crc32`]) with the default seed.
+///
+/// Checksum the bytes as received: [`PropRec::from_bytes`] accepts
+/// little-endian headers but `to_bytes` always writes big-endian, so a
+/// re-serialized little-endian prop has a different CRC.
+pub fn prop_crc(data: &[u8]) -> u32 {
+    crate::crc32(data, 0)
+}
+
+/// Size of the fixed prop header in bytes
+pub const PROP_HEADER_SIZE: usize = 12;
+
+/// Largest width or height, in pixels, a prop may declare
+///
+/// Classic props are 44x44. Decoding allocates the full image up front, so
+/// a header claiming more is rejected rather than trusted.
+pub const MAX_PROP_DIMENSION: u16 = 256;
+
+/// Most animation cels decoded from one prop
+///
+/// Also bounds how far compressed image data may inflate, so a small zlib
+/// stream can't expand without limit.
+pub const MAX_PROP_FRAMES: usize = 32;
+
+/// Flag bits that select the image format
+///
+/// The low six bits are behavior flags (head, ghost, rare, ...); everything
+/// from 0x0040 up is reserved for the format.
+const FORMAT_BITS: u16 = 0xFFC0;
+
+/// Errors from classifying or decoding a raw prop blob
+#[derive(Error, Debug)]
+pub enum PropError {
+    /// Blob is shorter than the 12-byte header
+    #[error("Prop data too short for header: {0} bytes")]
+    Truncated(usize),
+
+    /// Header flags don't name a known image format
+    #[error("Unknown prop format flags 0x{0:04X}")]
+    UnknownFormat(u16),
+
+    /// Image data is malformed for its format
+    #[error("Failed to decode prop image: {0}")]
+    Decode(#[from] io::Error),
+}
+
+/// Classify a raw prop blob's image format from its header flags
+///
+/// Accepts big- or little-endian headers, like [`PropRec::from_bytes`].
+/// Unlike [`PropFlags::format`], which picks a format from whichever bits are
+/// present, this rejects flag values that set unknown or conflicting format
+/// bits.
+pub fn detect_format(data: &[u8]) -> Result<PropFormat, PropError> {
+    if data.len() < PROP_HEADER_SIZE {
+        return Err(PropError::Truncated(data.len()));
+    }
+
+    // Same endianness heuristic as PropRec::from_bytes
+    let flags_bytes = [data[10], data[11]];
+    let flags = if data[1] == 0 {
+        u16::from_le_bytes(flags_bytes)
+    } else {
+        u16::from_be_bytes(flags_bytes)
+    };
+
+    match flags & FORMAT_BITS {
+        0 => Ok(PropFormat::Indexed8),
+        bits if bits == PropFlags::FORMAT_20BIT.bits() => Ok(PropFormat::Rgb20),
+        bits if bits == PropFlags::FORMAT_32BIT.bits() => Ok(PropFormat::Rgb32),
+        bits if bits == PropFlags::FORMAT_S20BIT.bits() => Ok(PropFormat::S20Bit),
+        _ => Err(PropError::UnknownFormat(flags)),
+    }
+}
+
+/// Prop header together with its decoded frames
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedProp {
+    /// Parsed prop record, including the raw image data
+    pub prop: PropRec,
+    /// Animation frames; static props have exactly one
+    pub frames: Vec<Frame>,
+}
+
+impl DecodedProp {
+    /// Get the prop's image format
+    pub fn format(&self) -> PropFormat {
+        self.prop.format()
+    }
+
+    /// Get the number of animation frames (at least 1)
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Get an animation frame by index
+    pub fn frame(&self, index: usize) -> Option<&Frame> {
+        self.frames.get(index)
+    }
+
+    /// Get the first frame's RGBA pixels in row-major order
+    pub fn pixels(&self) -> &[Color] {
+        &self.frames[0].pixels
+    }
+}
+
+/// One decoded cel of a prop
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Frame width in pixels
+    pub width: u16,
+    /// Frame height in pixels
+    pub height: u16,
+    /// RGBA pixels in row-major order
+    pub pixels: Vec<Color>,
+}
+
+/// Decode a raw prop blob of any format
+///
+/// The format is checked with [`detect_format`] first, so unknown format
+/// flags are an error rather than falling back to 8-bit. 8-bit props use
+/// [`Palette::DEFAULT`]. Animated props decode to one frame per cel.
+pub fn decode_prop(data: &[u8]) -> Result<DecodedProp, PropError> {
+    detect_format(data)?;
+    let prop = PropRec::from_bytes(&mut &data[..])?;
+    let frames = prop.decode_frames_with_palette(None)?;
+    Ok(DecodedProp { prop, frames })
+}
+
 /// RGBA pixel color (alpha, red, green, blue)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color {
@@ -77,6 +213,218 @@ impl Color {
     pub const TRANSPARENT: Color = Color::new(0, 0, 0, 0);
 }
 
+/// Number of entries in an 8-bit prop palette
+pub const PALETTE_SIZE: usize = 256;
+
+/// Color table used to look up 8-bit prop pixels
+///
+/// The default is the standard Mac OS 8-bit system palette, which the Palace
+/// client uses for indexed props. Some historical props were drawn against a
+/// different table; load one with [`Palette::from_bytes`] and pass it to
+/// [`PropRec::decode_with_palette`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: [Color; PALETTE_SIZE],
+}
+
+impl Palette {
+    /// The built-in Palace (Mac OS system) palette
+    ///
+    /// Entries 0-214 are the 6x6x6 color cube from white down to 0x000033,
+    /// followed by ten-step red, green, blue and gray ramps and finally black.
+    pub const DEFAULT: Palette = Palette::mac_system();
+
+    /// Load a palette from 256 packed RGB triplets (768 bytes)
+    ///
+    /// All entries are opaque.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() != PALETTE_SIZE * 3 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Palette must be {} bytes ({} RGB triplets), got {}",
+                    PALETTE_SIZE * 3,
+                    PALETTE_SIZE,
+                    bytes.len()
+                ),
+            ));
+        }
+
+        let mut colors = [Color::TRANSPARENT; PALETTE_SIZE];
+        for (color, rgb) in colors.iter_mut().zip(bytes.chunks_exact(3)) {
+            *color = Color::new(255, rgb[0], rgb[1], rgb[2]);
+        }
+        Ok(Self { colors })
+    }
+
+    /// Get the color for a palette index
+    pub const fn get(&self, index: u8) -> Color {
+        self.colors[index as usize]
+    }
+
+    /// Get all palette entries in index order
+    pub const fn colors(&self) -> &[Color; PALETTE_SIZE] {
+        &self.colors
+    }
+
+    /// Find the index of the entry closest to `color` (alpha is ignored)
+    ///
+    /// This checks every entry. When matching many pixels against the same
+    /// palette, build a [`PaletteIndex`] with [`indexer`](Self::indexer).
+    pub fn nearest(&self, color: Color) -> u8 {
+        // min_by_key keeps the first of equally close entries
+        self.colors
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, entry)| color_distance(**entry, color))
+            .map_or(0, |(index, _)| index as u8)
+    }
+
+    /// Build a [`PaletteIndex`] for fast nearest-color lookups
+    pub fn indexer(&self) -> PaletteIndex {
+        PaletteIndex::new(self)
+    }
+
+    const fn mac_system() -> Self {
+        const CUBE: [u8; 6] = [0xFF, 0xCC, 0x99, 0x66, 0x33, 0x00];
+        const RAMP: [u8; 10] = [0xEE, 0xDD, 0xBB, 0xAA, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+        let mut colors = [Color::TRANSPARENT; PALETTE_SIZE];
+        let mut i = 0;
+        // 6x6x6 cube, minus black which ends the table
+        while i < 215 {
+            colors[i] = Color::new(255, CUBE[i / 36], CUBE[(i / 6) % 6], CUBE[i % 6]);
+            i += 1;
+        }
+        let mut step = 0;
+        while step < RAMP.len() {
+            let v = RAMP[step];
+            colors[215 + step] = Color::new(255, v, 0, 0);
+            colors[225 + step] = Color::new(255, 0, v, 0);
+            colors[235 + step] = Color::new(255, 0, 0, v);
+            colors[245 + step] = Color::new(255, v, v, v);
+            step += 1;
+        }
+        colors[255] = Color::new(255, 0, 0, 0);
+        Self { colors }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Squared RGB distance between two colors (alpha is ignored)
+fn color_distance(a: Color, b: Color) -> i32 {
+    let dr = a.r as i32 - b.r as i32;
+    let dg = a.g as i32 - b.g as i32;
+    let db = a.b as i32 - b.b as i32;
+    dr * dr + dg * dg + db * db
+}
+
+/// Side of a [`PaletteIndex`] cell, per channel
+const INDEX_CELL: i32 = 16;
+
+/// Cells along each channel of a [`PaletteIndex`]
+const INDEX_CELLS: usize = 256 / INDEX_CELL as usize;
+
+/// Precomputed nearest-color lookup for a [`Palette`]
+///
+/// RGB space is split into 16x16x16 cells, and each cell keeps only the
+/// entries that can be nearest to some color inside it. A lookup then
+/// compares against a handful of entries instead of all 256, giving exactly
+/// the same answers as [`Palette::nearest`], ties included. Building the
+/// index costs about as much as a few thousand naive lookups, so build it
+/// once per palette and reuse it, e.g. with [`PropRec::encode_with_index`].
+#[derive(Debug, Clone)]
+pub struct PaletteIndex {
+    colors: [Color; PALETTE_SIZE],
+    /// Where each cell's entries start in `candidates`, plus the final end
+    offsets: Vec<u32>,
+    /// Entry indices per cell, in ascending order
+    candidates: Vec<u8>,
+}
+
+impl PaletteIndex {
+    /// Index the entries of `palette`
+    pub fn new(palette: &Palette) -> Self {
+        let colors = palette.colors;
+        let mut offsets = Vec::with_capacity(INDEX_CELLS.pow(3) + 1);
+        let mut candidates = Vec::new();
+
+        for cell in 0..INDEX_CELLS.pow(3) {
+            offsets.push(candidates.len() as u32);
+            let [r, g, b] = Self::cell_origin(cell);
+
+            // Squared distance from an entry to the nearest and farthest
+            // points of the cell
+            let span = |lo: i32, v: u8| {
+                let v = v as i32;
+                let hi = lo + INDEX_CELL - 1;
+                let near = if v < lo { lo - v } else { (v - hi).max(0) };
+                let far = (v - lo).abs().max((v - hi).abs());
+                (near * near, far * far)
+            };
+            let bounds: Vec<(i32, i32)> = colors
+                .iter()
+                .map(|c| {
+                    let (rn, rf) = span(r, c.r);
+                    let (gn, gf) = span(g, c.g);
+                    let (bn, bf) = span(b, c.b);
+                    (rn + gn + bn, rf + gf + bf)
+                })
+                .collect();
+
+            // Every color in the cell is within `worst` of some entry, so
+            // entries that can't get that close are never nearest
+            let worst = bounds.iter().map(|&(_, far)| far).min().unwrap_or(0);
+            candidates.extend(
+                bounds
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &(near, _))| near <= worst)
+                    .map(|(index, _)| index as u8),
+            );
+        }
+        offsets.push(candidates.len() as u32);
+
+        Self {
+            colors,
+            offsets,
+            candidates,
+        }
+    }
+
+    /// Find the index of the entry closest to `color` (alpha is ignored)
+    ///
+    /// Returns the same index as [`Palette::nearest`].
+    pub fn nearest(&self, color: Color) -> u8 {
+        let cell = Self::cell_of(color);
+        let range = self.offsets[cell] as usize..self.offsets[cell + 1] as usize;
+        // Candidates are in index order, so min_by_key keeps the first tie
+        self.candidates[range]
+            .iter()
+            .copied()
+            .min_by_key(|&index| color_distance(self.colors[index as usize], color))
+            .unwrap_or(0)
+    }
+
+    fn cell_of(color: Color) -> usize {
+        let cell = |v: u8| v as usize / INDEX_CELL as usize;
+        (cell(color.r) * INDEX_CELLS + cell(color.g)) * INDEX_CELLS + cell(color.b)
+    }
+
+    /// Lowest red, green and blue values in a cell
+    fn cell_origin(cell: usize) -> [i32; 3] {
+        let r = cell / (INDEX_CELLS * INDEX_CELLS);
+        let g = (cell / INDEX_CELLS) % INDEX_CELLS;
+        let b = cell % INDEX_CELLS;
+        [r, g, b].map(|c| c as i32 * INDEX_CELL)
+    }
+}
+
 /// Palace prop record with metadata and image data
 #[derive(Debug, Clone, PartialEq)]
 pub struct PropRec {
@@ -119,7 +467,7 @@ impl PropRec {
 
     /// Read prop from bytes (with endianness detection)
     pub fn from_bytes(buf: &mut impl Buf) -> io::Result<Self> {
-        if buf.remaining() < 12 {
+        if buf.remaining() < PROP_HEADER_SIZE {
             return Err(io::Error::new(
                 io::ErrorKind::UnexpectedEof,
                 "Not enough bytes for prop header",
@@ -192,19 +540,116 @@ impl PropRec {
 
     /// Decode the prop's image data to RGBA pixels
     ///
-    /// Returns a vector of RGBA pixels in row-major order.
+    /// Returns a vector of RGBA pixels in row-major order. 8-bit props use
+    /// [`Palette::DEFAULT`]; see [`decode_with_palette`](Self::decode_with_palette).
     pub fn decode(&self) -> io::Result<Vec<Color>> {
-        match self.format() {
-            PropFormat::Indexed8 => decode_8bit(&self.image_data, self.width, self.height),
-            PropFormat::Rgb20 => decode_20bit(&self.image_data, self.width, self.height),
-            PropFormat::Rgb32 => decode_32bit(&self.image_data, self.width, self.height),
-            PropFormat::S20Bit => decode_s20bit(&self.image_data, self.width, self.height),
+        self.decode_with_palette(None)
+    }
+
+    /// Decode the prop's image data, looking up 8-bit pixels in `palette`
+    ///
+    /// `None` uses [`Palette::DEFAULT`]. The palette is ignored for the
+    /// direct-color formats.
+    pub fn decode_with_palette(&self, palette: Option<&Palette>) -> io::Result<Vec<Color>> {
+        let mut cels = self.decode_cels(palette, false)?;
+        Ok(cels.swap_remove(0))
+    }
+
+    /// Decode every animation frame, looking up 8-bit pixels in `palette`
+    ///
+    /// Animated props ([`PropFlags::ANIMATE`]) store their cels back to back:
+    /// consecutive run-length images for 8-bit props, or consecutive
+    /// `width * height` images in the inflated data for the other formats.
+    /// Static props, and animated props holding a single image, decode to
+    /// one frame; trailing data too short for another cel is ignored.
+    pub fn decode_frames_with_palette(&self, palette: Option<&Palette>) -> io::Result<Vec<Frame>> {
+        let cels = self.decode_cels(palette, self.flags.contains(PropFlags::ANIMATE))?;
+        Ok(cels
+            .into_iter()
+            .map(|pixels| Frame {
+                width: self.width,
+                height: self.height,
+                pixels,
+            })
+            .collect())
+    }
+
+    /// Decode the first cel, or every cel if `all_cels` is set (always at least one)
+    ///
+    /// At most [`MAX_PROP_FRAMES`] cels are decoded. Dimensions outside
+    /// 1..=[`MAX_PROP_DIMENSION`] are rejected before anything is allocated.
+    fn decode_cels(
+        &self,
+        palette: Option<&Palette>,
+        all_cels: bool,
+    ) -> io::Result<Vec<Vec<Color>>> {
+        let (width, height) = (self.width, self.height);
+        let valid = 1..=MAX_PROP_DIMENSION;
+        if !valid.contains(&width) || !valid.contains(&height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Prop dimensions {}x{} out of range", width, height),
+            ));
+        }
+        let total_pixels = (width as usize) * (height as usize);
+        let format = self.format();
+
+        if format == PropFormat::Indexed8 {
+            let palette = palette.unwrap_or(&Palette::DEFAULT);
+            let (pixels, mut used) = decode_8bit(&self.image_data, width, height, palette)?;
+            let mut cels = vec![pixels];
+            // Trailing bytes that don't form a complete cel are ignored
+            while all_cels
+                && cels.len() < MAX_PROP_FRAMES
+                && used > 0
+                && used < self.image_data.len()
+            {
+                let Ok((pixels, n)) = decode_8bit(&self.image_data[used..], width, height, palette)
+                else {
+                    break;
+                };
+                cels.push(pixels);
+                used += n;
+            }
+            return Ok(cels);
+        }
+
+        let cel_bytes = match format {
+            PropFormat::Rgb20 => total_pixels.div_ceil(2) * 5,
+            PropFormat::Rgb32 => total_pixels * 4,
+            _ => total_pixels.div_ceil(2) * 5,
+        };
+        let data = inflate(&self.image_data, format, cel_bytes * MAX_PROP_FRAMES)?;
+        if data.len() < cel_bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{:?} prop data is {} bytes, {}x{} needs {}",
+                    format,
+                    data.len(),
+                    width,
+                    height,
+                    cel_bytes
+                ),
+            ));
+        }
+        let decode_cel = |cel: &[u8]| match format {
+            PropFormat::Rgb20 => decode_20bit(cel, width, height),
+            PropFormat::Rgb32 => decode_32bit(cel, width, height),
+            _ => decode_s20bit(cel, width, height),
+        };
+
+        if !all_cels || data.len() < cel_bytes * 2 {
+            return Ok(vec![decode_cel(&data)]);
         }
+        Ok(data.chunks_exact(cel_bytes).map(decode_cel).collect())
     }
 
     /// Encode RGBA pixels to the prop's format
     ///
     /// The input must be exactly width * height pixels in row-major order.
+    /// 8-bit props use [`Palette::DEFAULT`]; see
+    /// [`encode_with_palette`](Self::encode_with_palette).
     pub fn encode(
         pixels: &[Color],
         width: u16,
@@ -212,6 +657,58 @@ impl PropRec {
         h_offset: i16,
         v_offset: i16,
         flags: PropFlags,
+    ) -> io::Result<Self> {
+        Self::encode_with_palette(pixels, width, height, h_offset, v_offset, flags, None)
+    }
+
+    /// Encode RGBA pixels, mapping 8-bit pixels to the nearest `palette` entry
+    ///
+    /// `None` uses [`Palette::DEFAULT`]. For 8-bit props, pixels with alpha
+    /// below 128 become transparent, and the top row is always transparent
+    /// because the 8-bit decoder starts one row down.
+    pub fn encode_with_palette(
+        pixels: &[Color],
+        width: u16,
+        height: u16,
+        h_offset: i16,
+        v_offset: i16,
+        flags: PropFlags,
+        palette: Option<&Palette>,
+    ) -> io::Result<Self> {
+        let palette = palette.unwrap_or(&Palette::DEFAULT);
+        Self::encode_with_nearest(pixels, width, height, h_offset, v_offset, flags, |color| {
+            palette.nearest(color)
+        })
+    }
+
+    /// Encode RGBA pixels, mapping 8-bit pixels through a prebuilt index
+    ///
+    /// Same output as [`encode_with_palette`](Self::encode_with_palette) with
+    /// the indexed palette, but faster when encoding many 8-bit props against
+    /// one palette.
+    pub fn encode_with_index(
+        pixels: &[Color],
+        width: u16,
+        height: u16,
+        h_offset: i16,
+        v_offset: i16,
+        flags: PropFlags,
+        index: &PaletteIndex,
+    ) -> io::Result<Self> {
+        Self::encode_with_nearest(pixels, width, height, h_offset, v_offset, flags, |color| {
+            index.nearest(color)
+        })
+    }
+
+    /// Encode RGBA pixels, with `nearest` mapping colors to 8-bit indices
+    fn encode_with_nearest(
+        pixels: &[Color],
+        width: u16,
+        height: u16,
+        h_offset: i16,
+        v_offset: i16,
+        flags: PropFlags,
+        nearest: impl Fn(Color) -> u8,
     ) -> io::Result<Self> {
         let expected_len = (width as usize) * (height as usize);
         if pixels.len() != expected_len {
@@ -223,6 +720,7 @@ impl PropRec {
 
         let format = flags.format();
         let image_data = match format {
+            PropFormat::Indexed8 => encode_8bit(pixels, width, height, nearest),
             PropFormat::S20Bit => encode_s20bit(pixels, width, height)?,
             _ => {
                 return Err(io::Error::new(
@@ -239,7 +737,14 @@ impl PropRec {
 }
 
 /// Decode 8-bit indexed color prop (run-length encoded)
-fn decode_8bit(data: &[u8], width: u16, height: u16) -> io::Result<Vec<Color>> {
+///
+/// Returns the pixels and the number of bytes of `data` consumed.
+fn decode_8bit(
+    data: &[u8],
+    width: u16,
+    height: u16,
+    palette: &Palette,
+) -> io::Result<(Vec<Color>, usize)> {
     let total_pixels = (width as usize) * (height as usize);
     let mut pixels = vec![Color::TRANSPARENT; total_pixels];
 
@@ -295,32 +800,81 @@ fn decode_8bit(data: &[u8], width: u16, height: u16) -> io::Result<Vec<Color>> {
                     ));
                 }
 
-                let palette_idx = data[data_idx] as usize;
+                let palette_idx = data[data_idx];
                 data_idx += 1;
 
                 if pixel_idx < pixels.len() {
-                    pixels[pixel_idx] = palette_lookup(palette_idx);
+                    pixels[pixel_idx] = palette.get(palette_idx);
                     pixel_idx += 1;
                 }
             }
         }
     }
 
-    Ok(pixels)
+    Ok((pixels, data_idx))
 }
 
-/// Decode 20-bit RGB prop (6+6+6+2 bits per pixel, compressed)
-fn decode_20bit(compressed_data: &[u8], width: u16, height: u16) -> io::Result<Vec<Color>> {
-    // Decompress using zlib
-    let mut decoder = flate2::read::ZlibDecoder::new(compressed_data);
+/// Encode 8-bit indexed color prop (run-length encoded)
+///
+/// Inverse of [`decode_8bit`]: each data row fills the pixel row below it,
+/// so pixel row 0 is dropped and the last data row is all transparent.
+fn encode_8bit(
+    pixels: &[Color],
+    width: u16,
+    height: u16,
+    nearest: impl Fn(Color) -> u8,
+) -> Vec<u8> {
+    let width = width as usize;
+    let is_transparent =
+        |x: usize, row: usize| pixels.get(row * width + x).is_none_or(|c| c.a < 128);
+
     let mut data = Vec::new();
-    decoder.read_to_end(&mut data).map_err(|e| {
-        io::Error::new(
+    for row in 1..=height as usize {
+        let mut x = 0;
+        while x < width {
+            // Up to 15 transparent pixels, then up to 15 opaque ones
+            let mut skip = 0;
+            while x < width && skip < 15 && is_transparent(x, row) {
+                skip += 1;
+                x += 1;
+            }
+            let start = x;
+            while x < width && x - start < 15 && !is_transparent(x, row) {
+                x += 1;
+            }
+            data.push(((skip << 4) | (x - start)) as u8);
+            for px in start..x {
+                data.push(nearest(pixels[row * width + px]));
+            }
+        }
+    }
+    data
+}
+
+/// Inflate a zlib-compressed prop image, failing if it exceeds `max_len` bytes
+fn inflate(compressed_data: &[u8], format: PropFormat, max_len: usize) -> io::Result<Vec<u8>> {
+    let decoder = flate2::read::ZlibDecoder::new(compressed_data);
+    let mut data = Vec::new();
+    decoder
+        .take((max_len as u64).saturating_add(1))
+        .read_to_end(&mut data)
+        .map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to decompress {:?} prop: {}", format, e),
+            )
+        })?;
+    if data.len() > max_len {
+        return Err(io::Error::new(
             io::ErrorKind::InvalidData,
-            format!("Failed to decompress 20-bit prop: {}", e),
-        )
-    })?;
+            format!("{:?} prop inflates past {} bytes", format, max_len),
+        ));
+    }
+    Ok(data)
+}
 
+/// Decode 20-bit RGB prop data (6+6+6+2 bits per pixel, after inflating)
+fn decode_20bit(data: &[u8], width: u16, height: u16) -> Vec<Color> {
     let total_pixels = (width as usize) * (height as usize);
     let mut pixels = Vec::with_capacity(total_pixels);
 
@@ -330,7 +884,7 @@ fn decode_20bit(compressed_data: &[u8], width: u16, height: u16) -> io::Result<V
     const DITHER_20BIT: f32 = 255.0 / 63.0; // Scale 6-bit to 8-bit
 
     let mut pos = 0;
-    for _ in 0..(total_pixels / 2) {
+    for _ in 0..total_pixels.div_ceil(2) {
         if pos + 5 > data.len() {
             break;
         }
@@ -357,26 +911,17 @@ fn decode_20bit(compressed_data: &[u8], width: u16, height: u16) -> io::Result<V
         pos += 5;
     }
 
-    // Pad if needed
+    // An odd pixel count leaves an unused half in the last pair; pad if short
+    pixels.truncate(total_pixels);
     while pixels.len() < total_pixels {
         pixels.push(Color::TRANSPARENT);
     }
 
-    Ok(pixels)
+    pixels
 }
 
-/// Decode 32-bit RGBA prop (8+8+8+8 bits per pixel, compressed)
-fn decode_32bit(compressed_data: &[u8], width: u16, height: u16) -> io::Result<Vec<Color>> {
-    // Decompress using zlib
-    let mut decoder = flate2::read::ZlibDecoder::new(compressed_data);
-    let mut data = Vec::new();
-    decoder.read_to_end(&mut data).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("Failed to decompress 32-bit prop: {}", e),
-        )
-    })?;
-
+/// Decode 32-bit RGBA prop data (8+8+8+8 bits per pixel, after inflating)
+fn decode_32bit(data: &[u8], width: u16, height: u16) -> Vec<Color> {
     let total_pixels = (width as usize) * (height as usize);
     let mut pixels = Vec::with_capacity(total_pixels);
 
@@ -400,21 +945,11 @@ fn decode_32bit(compressed_data: &[u8], width: u16, height: u16) -> io::Result<V
         pixels.push(Color::TRANSPARENT);
     }
 
-    Ok(pixels)
+    pixels
 }
 
-/// Decode S20-bit prop (5+5+5+5 bits per pixel, compressed)
-fn decode_s20bit(compressed_data: &[u8], width: u16, height: u16) -> io::Result<Vec<Color>> {
-    // Decompress using zlib
-    let mut decoder = flate2::read::ZlibDecoder::new(compressed_data);
-    let mut data = Vec::new();
-    decoder.read_to_end(&mut data).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("Failed to decompress S20-bit prop: {}", e),
-        )
-    })?;
-
+/// Decode S20-bit prop data (5+5+5+5 bits per pixel, after inflating)
+fn decode_s20bit(data: &[u8], width: u16, height: u16) -> Vec<Color> {
     let total_pixels = (width as usize) * (height as usize);
     let mut pixels = Vec::with_capacity(total_pixels);
 
@@ -424,7 +959,7 @@ fn decode_s20bit(compressed_data: &[u8], width: u16, height: u16) -> io::Result<
     const DITHER_S20BIT: f32 = 255.0 / 31.0; // Scale 5-bit to 8-bit
 
     let mut pos = 0;
-    for _ in 0..(total_pixels / 2).max(1) {
+    for _ in 0..total_pixels.div_ceil(2) {
         if pos + 5 > data.len() {
             break;
         }
@@ -452,12 +987,13 @@ fn decode_s20bit(compressed_data: &[u8], width: u16, height: u16) -> io::Result<
         pos += 5;
     }
 
-    // Pad if needed
+    // An odd pixel count leaves an unused half in the last pair; pad if short
+    pixels.truncate(total_pixels);
     while pixels.len() < total_pixels {
         pixels.push(Color::TRANSPARENT);
     }
 
-    Ok(pixels)
+    pixels
 }
 
 /// Encode RGBA pixels to S20-bit format (compressed)
@@ -514,21 +1050,26 @@ fn encode_s20bit(pixels: &[Color], width: u16, height: u16) -> io::Result<Vec<u8
         .map_err(|e| io::Error::other(format!("Failed to finish S20-bit compression: {}", e)))
 }
 
-/// Look up a palette color by index
-///
-/// This is a simplified Palace color palette. A full implementation would
-/// use the exact Palace CLUT (Color Look-Up Table).
-fn palette_lookup(index: usize) -> Color {
-    // TODO: Use actual Palace palette (256 colors)
-    // For now, use a simple grayscale mapping
-    let gray = (index & 0xFF) as u8;
-    Color::new(255, gray, gray, gray)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_prop_crc_known_blob() {
+        // 44x44 header, no offsets/script/flags, then five bytes of image data
+        let blob = [
+            0x00, 0x2C, 0x00, 0x2C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x01,
+            0x02, 0xFF, 0x10,
+        ];
+        assert_eq!(prop_crc(&blob), 0xC5323BAC);
+
+        // The same bytes serialized from a PropRec
+        let prop = PropRec::from_bytes(&mut &blob[..]).unwrap();
+        let mut bytes = Vec::new();
+        prop.to_bytes(&mut bytes);
+        assert_eq!(prop_crc(&bytes), 0xC5323BAC);
+    }
+
     #[test]
     fn test_color_argb_conversion() {
         let color = Color::new(255, 128, 64, 32);
@@ -580,4 +1121,307 @@ mod tests {
         assert!((decoded[1].g as i16 - 255).abs() <= 8);
         assert!((decoded[2].b as i16 - 255).abs() <= 8);
     }
+
+    #[test]
+    fn test_default_palette() {
+        let palette = Palette::default();
+        assert_eq!(palette.get(0), Color::new(255, 0xFF, 0xFF, 0xFF));
+        assert_eq!(palette.get(214), Color::new(255, 0x00, 0x00, 0x33));
+        assert_eq!(palette.get(215), Color::new(255, 0xEE, 0x00, 0x00));
+        assert_eq!(palette.get(254), Color::new(255, 0x11, 0x11, 0x11));
+        assert_eq!(palette.get(255), Color::new(255, 0x00, 0x00, 0x00));
+        assert_eq!(palette.nearest(Color::new(255, 0xFE, 0x01, 0x02)), 35); // FF0000
+    }
+
+    #[test]
+    fn test_palette_index_matches_brute_force() {
+        let samples = [
+            Color::new(255, 0, 0, 0),
+            Color::new(255, 255, 255, 255),
+            Color::new(255, 0xFE, 0x01, 0x02),
+            Color::new(255, 0x80, 0x80, 0x80),
+            Color::new(255, 0x12, 0x34, 0x56),
+            Color::new(0, 0xCC, 0x99, 0x66),
+            Color::new(255, 0x0F, 0x10, 0xEF),
+        ];
+
+        // Duplicate entries check that ties still go to the first index
+        let bytes: Vec<u8> = (0..=255u8)
+            .flat_map(|i| [i & 0xF0, i.wrapping_mul(37) & 0xF0, 255 - (i & 0xF0)])
+            .collect();
+        let custom = Palette::from_bytes(&bytes).unwrap();
+
+        for palette in [Palette::DEFAULT, custom] {
+            let index = palette.indexer();
+            for color in samples {
+                assert_eq!(index.nearest(color), palette.nearest(color), "{:?}", color);
+            }
+        }
+    }
+
+    #[test]
+    fn test_palette_index_full_image() {
+        let palette = Palette::DEFAULT;
+        let index = palette.indexer();
+
+        // Every red/green pair, with blue sweeping across rows
+        let pixels: Vec<Color> = (0..256 * 256)
+            .map(|i| Color::new(255, (i % 256) as u8, (i / 256) as u8, (i * 7 % 256) as u8))
+            .collect();
+        let indexed: Vec<u8> = pixels.iter().map(|&c| index.nearest(c)).collect();
+        let brute: Vec<u8> = pixels.iter().map(|&c| palette.nearest(c)).collect();
+        assert_eq!(indexed, brute);
+
+        // Encoding through the index gives the same prop
+        let prop = &pixels[..PROP_PIXELS];
+        let flags = PropFlags::empty();
+        assert_eq!(
+            PropRec::encode_with_index(prop, 44, 44, 0, 0, flags, &index).unwrap(),
+            PropRec::encode(prop, 44, 44, 0, 0, flags).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_palette_from_bytes() {
+        assert!(Palette::from_bytes(&[0; 767]).is_err());
+        assert!(Palette::from_bytes(&[0; 769]).is_err());
+        assert!(Palette::from_bytes(&[]).is_err());
+
+        let bytes: Vec<u8> = (0..=255u8).flat_map(|i| [i, 255 - i, 7]).collect();
+        let palette = Palette::from_bytes(&bytes).unwrap();
+        assert_eq!(palette.get(10), Color::new(255, 10, 245, 7));
+    }
+
+    #[test]
+    fn test_8bit_decode_with_custom_palette() {
+        // 2x2 prop: the decoder writes data row 0 to pixel row 1
+        let prop = PropRec::new(2, 2, 0, 0, PropFlags::empty(), vec![0x02, 5, 200, 0x20]);
+        let grayscale: Vec<u8> = (0..=255u8).flat_map(|i| [i, i, i]).collect();
+        let grayscale = Palette::from_bytes(&grayscale).unwrap();
+
+        let default = prop.decode().unwrap();
+        let custom = prop.decode_with_palette(Some(&grayscale)).unwrap();
+
+        assert_eq!(default[..2], [Color::TRANSPARENT; 2]);
+        assert_eq!(default[2], Palette::DEFAULT.get(5));
+        assert_eq!(default[3], Palette::DEFAULT.get(200));
+        assert_eq!(custom[2], Color::new(255, 5, 5, 5));
+        assert_eq!(custom[3], Color::new(255, 200, 200, 200));
+        assert_ne!(default, custom);
+    }
+
+    #[test]
+    fn test_8bit_encode_decode_roundtrip() {
+        let palette = Palette::DEFAULT;
+        let mut pixels = vec![Color::TRANSPARENT; PROP_PIXELS];
+        // Row 0 can't be represented; fill a long run and a gap further down
+        for (i, pixel) in pixels[PROP_WIDTH..PROP_WIDTH * 2].iter_mut().enumerate() {
+            *pixel = palette.get(i as u8);
+        }
+        pixels[PROP_PIXELS - 1] = palette.get(255);
+
+        let prop = PropRec::encode(&pixels, 44, 44, 0, 0, PropFlags::empty()).unwrap();
+        assert_eq!(prop.format(), PropFormat::Indexed8);
+        assert_eq!(prop.decode().unwrap(), pixels);
+
+        // Encoding against another palette picks that palette's indices
+        let grayscale: Vec<u8> = (0..=255u8).flat_map(|i| [i, i, i]).collect();
+        let grayscale = Palette::from_bytes(&grayscale).unwrap();
+        let mut gray_pixels = vec![Color::TRANSPARENT; 4];
+        gray_pixels[2] = Color::new(255, 90, 90, 90);
+        let prop = PropRec::encode_with_palette(
+            &gray_pixels,
+            2,
+            2,
+            0,
+            0,
+            PropFlags::empty(),
+            Some(&grayscale),
+        )
+        .unwrap();
+        assert_eq!(prop.image_data, vec![0x01, 90, 0x10, 0x20]);
+    }
+
+    fn prop_header(flags: u16) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.put_u16(44);
+        buf.put_u16(44);
+        buf.put_i16(0);
+        buf.put_i16(0);
+        buf.put_u16(0);
+        buf.put_u16(flags);
+        buf
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let cases = [
+            (0x0000, PropFormat::Indexed8),
+            (PropFlags::HEAD.bits(), PropFormat::Indexed8),
+            (0x0040, PropFormat::Rgb20),
+            (0x0100, PropFormat::Rgb32),
+            (0x0200 | PropFlags::GHOST.bits(), PropFormat::S20Bit),
+        ];
+        for (flags, format) in cases {
+            assert_eq!(detect_format(&prop_header(flags)).unwrap(), format);
+        }
+
+        // Little-endian header
+        let mut le = prop_header(0);
+        le[..4].copy_from_slice(&[44, 0, 44, 0]);
+        le[10..12].copy_from_slice(&0x0100u16.to_le_bytes());
+        assert_eq!(detect_format(&le).unwrap(), PropFormat::Rgb32);
+
+        assert!(matches!(
+            detect_format(&prop_header(0)[..11]),
+            Err(PropError::Truncated(11))
+        ));
+    }
+
+    #[test]
+    fn test_detect_format_unknown_flags() {
+        for flags in [0x0080, 0x0140, 0x1002] {
+            match detect_format(&prop_header(flags)) {
+                Err(PropError::UnknownFormat(raw)) => assert_eq!(raw, flags),
+                other => panic!(
+                    "expected UnknownFormat for 0x{:04X}, got {:?}",
+                    flags, other
+                ),
+            }
+        }
+        assert!(matches!(
+            decode_prop(&prop_header(0x0080)),
+            Err(PropError::UnknownFormat(0x0080))
+        ));
+    }
+
+    #[test]
+    fn test_decode_prop_dispatches() {
+        let pixels = vec![Color::new(255, 255, 0, 0); PROP_PIXELS];
+        let s20 = PropRec::encode(&pixels, 44, 44, 0, 0, PropFlags::FORMAT_S20BIT).unwrap();
+        let mut blob = Vec::new();
+        s20.to_bytes(&mut blob);
+
+        let decoded = decode_prop(&blob).unwrap();
+        assert_eq!(decoded.format(), PropFormat::S20Bit);
+        assert_eq!(decoded.frame_count(), 1);
+        assert_eq!(decoded.pixels(), s20.decode().unwrap());
+
+        // Truncated image data surfaces as a decode error
+        let mut eight_bit = prop_header(0);
+        eight_bit.push(0x01);
+        assert!(matches!(decode_prop(&eight_bit), Err(PropError::Decode(_))));
+    }
+
+    #[test]
+    fn test_decode_rejects_hostile_headers() {
+        // 65535x65535 would need 17 GB of pixels
+        for flags in [0, PropFlags::FORMAT_32BIT.bits()] {
+            let mut blob = prop_header(flags);
+            blob[..4].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+            blob.extend([0x00; 64]);
+            assert!(matches!(decode_prop(&blob), Err(PropError::Decode(_))));
+        }
+        let empty = PropRec::new(0, 44, 0, 0, PropFlags::empty(), vec![0x00; 44]);
+        assert!(empty.decode().is_err());
+
+        // A zlib bomb: a few KB that inflates far past what 44x44 cels need
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&vec![0; 16 << 20]).unwrap();
+        let bomb = encoder.finish().unwrap();
+        let flags = PropFlags::FORMAT_32BIT | PropFlags::ANIMATE;
+        let mut blob = Vec::new();
+        PropRec::new(44, 44, 0, 0, flags, bomb).to_bytes(&mut blob);
+        assert!(matches!(decode_prop(&blob), Err(PropError::Decode(_))));
+
+        // Image data shorter than the header's dimensions need
+        let pixels = vec![Color::new(255, 0, 0, 255); 16];
+        let small = PropRec::encode(&pixels, 4, 4, 0, 0, PropFlags::FORMAT_S20BIT).unwrap();
+        let mut blob = Vec::new();
+        PropRec {
+            width: 44,
+            height: 44,
+            ..small
+        }
+        .to_bytes(&mut blob);
+        assert!(matches!(decode_prop(&blob), Err(PropError::Decode(_))));
+    }
+
+    #[test]
+    fn test_decode_animated_prop_frames() {
+        // Three 4x2 S20-bit cels back to back in one zlib stream
+        let (width, height) = (4u16, 2u16);
+        let cel = |shade: u8| vec![Color::new(255, shade, shade, shade); 8];
+        let mut inflated = Vec::new();
+        for shade in [0, 128, 255] {
+            let single = encode_s20bit(&cel(shade), width, height).unwrap();
+            inflated.extend(inflate(&single, PropFormat::S20Bit, usize::MAX).unwrap());
+        }
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&inflated).unwrap();
+        let image_data = encoder.finish().unwrap();
+
+        let flags = PropFlags::FORMAT_S20BIT | PropFlags::ANIMATE;
+        let mut blob = Vec::new();
+        PropRec::new(width, height, 0, 0, flags, image_data.clone()).to_bytes(&mut blob);
+
+        let decoded = decode_prop(&blob).unwrap();
+        assert_eq!(decoded.frame_count(), 3);
+        for frame in &decoded.frames {
+            assert_eq!((frame.width, frame.height), (width, height));
+            assert_eq!(frame.pixels.len(), 8);
+        }
+        assert_eq!(decoded.frame(0).unwrap().pixels[0].r, 0);
+        assert_eq!(decoded.frame(2).unwrap().pixels[0].r, 255);
+        assert!(decoded.frame(3).is_none());
+
+        // Without ANIMATE the same data is a single static image
+        let mut blob = Vec::new();
+        PropRec::new(width, height, 0, 0, PropFlags::FORMAT_S20BIT, image_data).to_bytes(&mut blob);
+        let decoded = decode_prop(&blob).unwrap();
+        assert_eq!(decoded.frame_count(), 1);
+        assert_eq!(decoded.pixels().len(), 8);
+    }
+
+    #[test]
+    fn test_decode_animated_odd_pixel_count() {
+        // 3x1 cels end on a half-filled 5-byte pair, which still belongs to the cel
+        let (width, height) = (3u16, 1u16);
+        let mut inflated = Vec::new();
+        for shade in [0, 255] {
+            let cel = vec![Color::new(255, shade, shade, shade); 3];
+            let single = encode_s20bit(&cel, width, height).unwrap();
+            inflated.extend(inflate(&single, PropFormat::S20Bit, usize::MAX).unwrap());
+        }
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&inflated).unwrap();
+        let image_data = encoder.finish().unwrap();
+
+        let flags = PropFlags::FORMAT_S20BIT | PropFlags::ANIMATE;
+        let mut blob = Vec::new();
+        PropRec::new(width, height, 0, 0, flags, image_data).to_bytes(&mut blob);
+
+        let decoded = decode_prop(&blob).unwrap();
+        assert_eq!(decoded.frame_count(), 2);
+        for (frame, shade) in decoded.frames.iter().zip([0, 255]) {
+            assert_eq!(frame.pixels.len(), 3);
+            assert!(frame.pixels.iter().all(|p| p.r == shade));
+        }
+    }
+
+    #[test]
+    fn test_decode_animated_8bit_frames() {
+        // Two 2x2 cels; the second has a trailing partial byte run
+        let image_data = vec![0x02, 5, 200, 0x20, 0x11, 7, 0x20, 0x01];
+        let prop = PropRec::new(2, 2, 0, 0, PropFlags::ANIMATE, image_data);
+
+        let frames = prop.decode_frames_with_palette(None).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].pixels[2], Palette::DEFAULT.get(5));
+        assert_eq!(frames[1].pixels[2], Color::TRANSPARENT);
+        assert_eq!(frames[1].pixels[3], Palette::DEFAULT.get(7));
+        assert_eq!(prop.decode().unwrap(), frames[0].pixels);
+    }
 }
@@ -0,0 +1,89 @@
+//! Polygon helpers for hotspot outlines.
+//!
+//! Hotspot outlines are closed polygons: the last point connects back to the
+//! first. All arithmetic is done in `i64` so no `i16` coordinate can overflow.
+
+use crate::Point;
+
+/// Check whether segments `a1-a2` and `b1-b2` touch or cross
+pub fn segments_intersect(a1: Point, a2: Point, b1: Point, b2: Point) -> bool {
+    let d1 = orientation(b1, b2, a1);
+    let d2 = orientation(b1, b2, a2);
+    let d3 = orientation(a1, a2, b1);
+    let d4 = orientation(a1, a2, b2);
+
+    if d1 * d2 < 0 && d3 * d4 < 0 {
+        return true;
+    }
+
+    // Collinear cases: an endpoint lying on the other segment
+    (d1 == 0 && on_segment(b1, b2, a1))
+        || (d2 == 0 && on_segment(b1, b2, a2))
+        || (d3 == 0 && on_segment(a1, a2, b1))
+        || (d4 == 0 && on_segment(a1, a2, b2))
+}
+
+/// Find the first pair of non-adjacent edges that intersect
+///
+/// Edge `i` runs from `points[i]` to `points[(i + 1) % len]`. Returns `None`
+/// for simple polygons and for outlines with fewer than 4 points, which
+/// cannot self-intersect.
+pub fn find_self_intersection(points: &[Point]) -> Option<(usize, usize)> {
+    let n = points.len();
+    if n < 4 {
+        return None;
+    }
+
+    for i in 0..n {
+        // Skip the neighbouring edge; the first edge also neighbours the last
+        for j in (i + 2)..n {
+            if i == 0 && j == n - 1 {
+                continue;
+            }
+            let (a1, a2) = (points[i], points[(i + 1) % n]);
+            let (b1, b2) = (points[j], points[(j + 1) % n]);
+            if segments_intersect(a1, a2, b1, b2) {
+                return Some((i, j));
+            }
+        }
+    }
+    None
+}
+
+/// Sign of the cross product (q - p) x (r - p)
+fn orientation(p: Point, q: Point, r: Point) -> i64 {
+    let cross = (i64::from(q.h) - i64::from(p.h)) * (i64::from(r.v) - i64::from(p.v))
+        - (i64::from(q.v) - i64::from(p.v)) * (i64::from(r.h) - i64::from(p.h));
+    cross.signum()
+}
+
+/// Whether collinear point `r` lies within the bounding box of `p-q`
+fn on_segment(p: Point, q: Point, r: Point) -> bool {
+    r.h >= p.h.min(q.h) && r.h <= p.h.max(q.h) && r.v >= p.v.min(q.v) && r.v <= p.v.max(q.v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments_intersect() {
+        let p = Point::new;
+        assert!(segments_intersect(p(0, 0), p(10, 10), p(0, 10), p(10, 0)));
+        assert!(!segments_intersect(p(0, 0), p(10, 0), p(0, 5), p(10, 5)));
+        // Touching at an endpoint counts
+        assert!(segments_intersect(p(0, 0), p(10, 0), p(10, 0), p(10, 10)));
+        // Collinear but disjoint
+        assert!(!segments_intersect(p(0, 0), p(5, 0), p(6, 0), p(10, 0)));
+    }
+
+    #[test]
+    fn test_find_self_intersection() {
+        let p = Point::new;
+        let quad = [p(0, 0), p(10, 0), p(10, 10), p(0, 10)];
+        assert_eq!(find_self_intersection(&quad), None);
+
+        let bowtie = [p(0, 0), p(10, 10), p(10, 0), p(0, 10)];
+        assert_eq!(find_self_intersection(&bowtie), Some((0, 2)));
+    }
+}
@@ -8,6 +8,15 @@
 //! - Scripts (Iptscrae event handlers)
 //! - Door links to other rooms
 
+pub mod geometry;
+
+use crate::Point;
+
+/// Width of a standard room background, in pixels
+pub const ROOM_WIDTH: i16 = 512;
+/// Height of a standard room background, in pixels
+pub const ROOM_HEIGHT: i16 = 384;
+
 /// Hotspot type enumeration.
 ///
 /// Hotspots are interactive areas within a room that can trigger scripts,
@@ -64,6 +73,12 @@ impl From<HotspotType> for i16 {
 }
 
 /// Hotspot state enumeration.
+///
+/// These are the states of a lockable door. Other spots use the state as
+/// an index into their state pictures (`0..nbr_states`), which this enum
+/// doesn't cover: [`from_i16`](Self::from_i16) returns `None` for anything
+/// but 0 and 1, and servers check SpotState values against the spot itself,
+/// rejecting rather than clamping states it doesn't have.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(i16)]
 pub enum HotspotState {
@@ -95,6 +110,40 @@ impl From<HotspotState> for i16 {
     }
 }
 
+/// Side of the square outline given to new spots
+pub const DEFAULT_SPOT_SIZE: i16 = 64;
+
+/// Outline of a spot created by SpotNew: a 64x64 square in the middle of a
+/// standard room.
+///
+/// Points are absolute room coordinates. A hotspot's location is the first
+/// point of its outline, as in `convert_room`.
+pub fn default_spot_outline() -> [Point; 4] {
+    let (x, y) = (ROOM_WIDTH / 2, ROOM_HEIGHT / 2);
+    let half = DEFAULT_SPOT_SIZE / 2;
+    [
+        Point::new(x - half, y - half),
+        Point::new(x + half, y - half),
+        Point::new(x + half, y + half),
+        Point::new(x - half, y + half),
+    ]
+}
+
+/// Pick an ID for a new hotspot given the IDs already in the room.
+///
+/// This is one past the highest ID, falling back to the lowest free
+/// positive ID once `i16::MAX` is taken. Returns `None` if every positive
+/// ID is used.
+pub fn next_hotspot_id(used: &[i16]) -> Option<i16> {
+    match used.iter().max() {
+        None => Some(1),
+        Some(&max) => max
+            .max(0)
+            .checked_add(1)
+            .or_else(|| (1..i16::MAX).find(|id| !used.contains(id))),
+    }
+}
+
 // TODO: Implement room data structures
 // - RoomRec structure
 // - Hotspot structure
@@ -137,4 +186,23 @@ mod tests {
         assert_eq!(HotspotState::from_i16(1), Some(HotspotState::Locked));
         assert_eq!(HotspotState::from_i16(2), None);
     }
+
+    #[test]
+    fn test_default_spot_outline() {
+        let outline = default_spot_outline();
+        assert_eq!(outline[0], Point::new(224, 160));
+        assert_eq!(outline[2], Point::new(288, 224));
+        assert!(geometry::find_self_intersection(&outline).is_none());
+    }
+
+    #[test]
+    fn test_next_hotspot_id() {
+        assert_eq!(next_hotspot_id(&[]), Some(1));
+        assert_eq!(next_hotspot_id(&[5, 2]), Some(6));
+        assert_eq!(next_hotspot_id(&[-3]), Some(1));
+        assert_eq!(next_hotspot_id(&[i16::MAX, 1, 2]), Some(3));
+
+        let full: Vec<i16> = (1..=i16::MAX).collect();
+        assert_eq!(next_hotspot_id(&full), None);
+    }
 }
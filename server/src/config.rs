@@ -1,10 +1,18 @@
 //! Server configuration
 
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::fs;
 use std::net::SocketAddr;
 use std::path::Path;
+use std::time::Duration;
+use thepalace::assets::AssetStore;
+use thepalace::messages::MessageHeader;
+
+use crate::db::{DatabaseOptions, JournalMode};
+use crate::net::handler::ConnectionLimits;
+use crate::net::send_queue::{SendPolicy, DEFAULT_SEND_QUEUE_SIZE};
+use crate::state::{DEFAULT_GUEST_NAME_PREFIX, DEFAULT_MAX_LOOSE_PROPS};
 
 /// Server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +21,8 @@ pub struct Config {
     pub database: DatabaseConfig,
     pub security: SecurityConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub assets: AssetsConfig,
 }
 
 /// Server network configuration
@@ -20,15 +30,83 @@ pub struct Config {
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// Addresses to accept connections on, overriding `host`/`port`
+    ///
+    /// Either a single `"ip:port"` string or a list of them.
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub bind: Vec<String>,
+    /// Optional WebSocket port for browser clients (requires the `ws` feature)
+    #[serde(default)]
+    pub ws_port: Option<u16>,
     pub max_connections: usize,
     pub server_name: String,
+    /// Largest message payload accepted from a client, in bytes
+    ///
+    /// A client declaring a longer message is disconnected before its payload
+    /// is buffered.
+    #[serde(default = "default_max_message_size")]
+    pub max_message_size: usize,
+    /// Outgoing messages, and broadcasts, queued per client before
+    /// `send_policy` applies
+    #[serde(default = "default_send_queue_size")]
+    pub send_queue_size: usize,
+    /// Whether a full send queue holds up the connection or drops messages
+    #[serde(default)]
+    pub send_policy: SendPolicy,
+}
+
+/// Accept either a single string or a list of strings
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(addr) => vec![addr],
+        OneOrMany::Many(addrs) => addrs,
+    })
+}
+
+fn default_max_message_size() -> usize {
+    MessageHeader::MAX_LENGTH
+}
+
+fn default_send_queue_size() -> usize {
+    DEFAULT_SEND_QUEUE_SIZE
 }
 
 /// Database configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
     pub path: String,
-    pub pool_size: u32,
+    /// Most connections the pool opens at once
+    #[serde(alias = "pool_size", default = "default_db_max_connections")]
+    pub max_connections: u32,
+    /// SQLite journal mode: `wal`, `delete`, `truncate`, `persist`, `memory` or `off`
+    #[serde(default)]
+    pub journal_mode: JournalMode,
+    /// How long a query waits for a locked database, in milliseconds
+    #[serde(default = "default_db_busy_timeout")]
+    pub busy_timeout: u64,
+    /// Replace rooms already in the database with their room script
+    /// declarations at startup, discarding edits made while running
+    #[serde(default)]
+    pub overwrite_rooms: bool,
+}
+
+fn default_db_max_connections() -> u32 {
+    DatabaseOptions::default().max_connections
+}
+
+fn default_db_busy_timeout() -> u64 {
+    DatabaseOptions::default().busy_timeout.as_millis() as u64
 }
 
 /// Security configuration
@@ -37,12 +115,54 @@ pub struct SecurityConfig {
     pub allow_guests: bool,
     pub allow_cyborgs: bool,
     pub max_prop_size: u64,
+    /// Most loose props a room holds; further drops are refused
+    #[serde(default = "default_max_loose_props")]
+    pub max_loose_props: usize,
+    /// Start of the names given to guests who log on without one
+    #[serde(default = "default_guest_name_prefix")]
+    pub guest_name_prefix: String,
+}
+
+fn default_max_loose_props() -> usize {
+    DEFAULT_MAX_LOOSE_PROPS
+}
+
+fn default_guest_name_prefix() -> String {
+    DEFAULT_GUEST_NAME_PREFIX.to_string()
+}
+
+/// Asset storage configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetsConfig {
+    /// Root directory for props, backgrounds and other assets
+    pub path: String,
+}
+
+impl Default for AssetsConfig {
+    fn default() -> Self {
+        Self {
+            path: "./assets".to_string(),
+        }
+    }
 }
 
 /// Logging configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoggingConfig {
     pub level: String,
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+/// How log lines are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
 }
 
 impl Config {
@@ -60,28 +180,223 @@ impl Config {
             server: ServerConfig {
                 host: "0.0.0.0".to_string(),
                 port: 9998,
+                bind: Vec::new(),
+                ws_port: None,
                 max_connections: 100,
                 server_name: "Palace Server".to_string(),
+                max_message_size: default_max_message_size(),
+                send_queue_size: default_send_queue_size(),
+                send_policy: SendPolicy::default(),
             },
             database: DatabaseConfig {
                 path: "palace.db".to_string(),
-                pool_size: 10,
+                max_connections: default_db_max_connections(),
+                journal_mode: JournalMode::default(),
+                busy_timeout: default_db_busy_timeout(),
+                overwrite_rooms: false,
             },
             security: SecurityConfig {
                 allow_guests: true,
                 allow_cyborgs: true,
                 max_prop_size: 1048576, // 1MB
+                max_loose_props: default_max_loose_props(),
+                guest_name_prefix: default_guest_name_prefix(),
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
+                format: LogFormat::default(),
             },
+            assets: AssetsConfig::default(),
+        }
+    }
+
+    /// Create the asset directory if needed and open a store rooted there
+    pub fn asset_store(&self) -> Result<AssetStore> {
+        fs::create_dir_all(&self.assets.path)
+            .with_context(|| format!("Failed to create asset directory {}", self.assets.path))?;
+        Ok(AssetStore::new(&self.assets.path))
+    }
+
+    /// Limits applied to each client connection
+    pub fn connection_limits(&self) -> ConnectionLimits {
+        ConnectionLimits {
+            max_message_size: self.server.max_message_size,
+            send_queue_size: self.server.send_queue_size,
+            send_policy: self.server.send_policy,
+        }
+    }
+
+    /// Connection settings for the database pool
+    pub fn database_options(&self) -> DatabaseOptions {
+        DatabaseOptions {
+            journal_mode: self.database.journal_mode,
+            max_connections: self.database.max_connections,
+            busy_timeout: Duration::from_millis(self.database.busy_timeout),
         }
     }
 
-    /// Get bind address for server
-    pub fn bind_addr(&self) -> Result<SocketAddr> {
-        let addr = format!("{}:{}", self.server.host, self.server.port);
-        addr.parse()
-            .context("Invalid server host/port configuration")
+    /// Get the addresses the server listens on
+    ///
+    /// These are the `bind` entries if any are set, otherwise `host:port`.
+    pub fn bind_addrs(&self) -> Result<Vec<SocketAddr>> {
+        if self.server.bind.is_empty() {
+            let addr = format!("{}:{}", self.server.host, self.server.port);
+            let addr = addr
+                .parse()
+                .context("Invalid server host/port configuration")?;
+            return Ok(vec![addr]);
+        }
+
+        self.server
+            .bind
+            .iter()
+            .map(|addr| {
+                addr.parse()
+                    .with_context(|| format!("Invalid bind address {:?}", addr))
+            })
+            .collect()
+    }
+
+    /// Get bind address for the WebSocket listener, if one is configured
+    pub fn ws_bind_addr(&self) -> Result<Option<SocketAddr>> {
+        self.server
+            .ws_port
+            .map(|port| {
+                format!("{}:{}", self.server.host, port)
+                    .parse()
+                    .context("Invalid server host/ws_port configuration")
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::state::ServerState;
+
+    #[tokio::test]
+    async fn test_custom_asset_path() {
+        let dir = std::env::temp_dir().join(format!("palace-config-test-{}", std::process::id()));
+        let assets_dir = dir.join("my-assets");
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut config = Config::default();
+        config.assets.path = assets_dir.to_string_lossy().into_owned();
+        let config_path = dir.join("palace.json");
+        fs::write(&config_path, serde_json::to_string(&config).unwrap()).unwrap();
+
+        let loaded = Config::from_file(&config_path).unwrap();
+        let store = loaded.asset_store().unwrap();
+        assert!(assets_dir.is_dir());
+
+        let db = Database::with_options("sqlite::memory:", DatabaseOptions::default())
+            .await
+            .unwrap();
+        let state = ServerState::new(db, store);
+        assert_eq!(state.assets().root(), assets_dir.as_path());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_custom_max_message_size() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        value["server"]["max_message_size"] = 4096.into();
+        let dir = std::env::temp_dir().join(format!("palace-max-msg-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("palace.json");
+        fs::write(&config_path, value.to_string()).unwrap();
+
+        let loaded = Config::from_file(&config_path).unwrap();
+        assert_eq!(loaded.server.max_message_size, 4096);
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Older config files without the field get the protocol cap
+        value["server"]
+            .as_object_mut()
+            .unwrap()
+            .remove("max_message_size");
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(config.server.max_message_size, MessageHeader::MAX_LENGTH);
+    }
+
+    #[test]
+    fn test_bind_addrs() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        let config: Config = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(
+            config.bind_addrs().unwrap(),
+            ["0.0.0.0:9998".parse::<SocketAddr>().unwrap()]
+        );
+
+        value["server"]["bind"] = serde_json::json!(["0.0.0.0:9998", "127.0.0.1:9990"]);
+        let config: Config = serde_json::from_value(value.clone()).unwrap();
+        let expected: Vec<SocketAddr> = vec![
+            "0.0.0.0:9998".parse().unwrap(),
+            "127.0.0.1:9990".parse().unwrap(),
+        ];
+        assert_eq!(config.bind_addrs().unwrap(), expected);
+
+        // A single address is a one-element list
+        value["server"]["bind"] = serde_json::json!("[::1]:9998");
+        let config: Config = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(
+            config.bind_addrs().unwrap(),
+            ["[::1]:9998".parse::<SocketAddr>().unwrap()]
+        );
+
+        value["server"]["bind"] = serde_json::json!(["127.0.0.1"]);
+        let config: Config = serde_json::from_value(value).unwrap();
+        let err = config.bind_addrs().unwrap_err();
+        assert!(err.to_string().contains("127.0.0.1"), "{}", err);
+    }
+
+    #[test]
+    fn test_database_options() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        value["database"] = serde_json::json!({
+            "path": "palace.db",
+            "journal_mode": "memory",
+            "max_connections": 3,
+            "busy_timeout": 1500,
+        });
+        let config: Config = serde_json::from_value(value.clone()).unwrap();
+        let options = config.database_options();
+        assert_eq!(options.journal_mode, JournalMode::Memory);
+        assert_eq!(options.max_connections, 3);
+        assert_eq!(options.busy_timeout, Duration::from_millis(1500));
+
+        // Older files only had pool_size
+        value["database"] = serde_json::json!({ "path": "palace.db", "pool_size": 4 });
+        let config: Config = serde_json::from_value(value.clone()).unwrap();
+        let options = config.database_options();
+        assert_eq!(options.max_connections, 4);
+        assert_eq!(options.journal_mode, JournalMode::Wal);
+
+        value["database"]["journal_mode"] = "journaled".into();
+        let dir = std::env::temp_dir().join(format!("palace-journal-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("palace.json");
+        fs::write(&config_path, value.to_string()).unwrap();
+        let err = Config::from_file(&config_path).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        let message = format!("{:#}", err);
+        assert!(
+            message.contains("unknown variant `journaled`"),
+            "{}",
+            message
+        );
+        assert!(message.contains("`wal`"), "{}", message);
+    }
+
+    #[test]
+    fn test_assets_path_defaults_when_missing() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        value.as_object_mut().unwrap().remove("assets");
+
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(config.assets.path, "./assets");
     }
 }
@@ -0,0 +1,471 @@
+//! Schema migrations
+//!
+//! The schema is built up by [`MIGRATIONS`], applied in order. Each one runs
+//! in a transaction together with a row in `schema_version` recording it, so
+//! a database is always at a well-defined version and re-running
+//! [`Database::init_schema`] only applies what is missing.
+//!
+//! To change the schema, append a migration with the next version number.
+//! Never edit one that has shipped: existing databases have already run it.
+
+use super::Database;
+use anyhow::{Context, Result};
+use sqlx::SqliteConnection;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// One step in the schema history
+pub struct Migration {
+    /// Version the schema is at once this has run
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every migration, oldest first
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Initial schema",
+        sql: r#"
+    -- Users table
+    CREATE TABLE users (
+        user_id INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        password_hash TEXT,
+        wizard_password TEXT,
+        flags INTEGER NOT NULL DEFAULT 8,
+        registration_date INTEGER NOT NULL,
+        last_login INTEGER
+    );
+
+    -- Create index on username for faster lookups
+    CREATE INDEX idx_users_username ON users(username);
+
+    -- Rooms table
+    CREATE TABLE rooms (
+        room_id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        artist TEXT,
+        background_image TEXT,
+        flags INTEGER NOT NULL DEFAULT 0,
+        max_occupancy INTEGER DEFAULT 0,
+        faces_id INTEGER DEFAULT 0,
+        room_data BLOB
+    );
+
+    -- Props registry
+    CREATE TABLE props (
+        prop_id INTEGER PRIMARY KEY AUTOINCREMENT,
+        crc32 INTEGER NOT NULL UNIQUE,
+        name TEXT NOT NULL,
+        flags INTEGER NOT NULL,
+        width INTEGER NOT NULL,
+        height INTEGER NOT NULL,
+        file_path TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+
+    -- Create index on CRC32 for asset lookups
+    CREATE INDEX idx_props_crc32 ON props(crc32);
+
+    -- Loose props in rooms
+    CREATE TABLE room_loose_props (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        room_id INTEGER NOT NULL,
+        prop_id INTEGER NOT NULL,
+        pos_h INTEGER NOT NULL,
+        pos_v INTEGER NOT NULL,
+        FOREIGN KEY (room_id) REFERENCES rooms(room_id) ON DELETE CASCADE,
+        FOREIGN KEY (prop_id) REFERENCES props(prop_id) ON DELETE CASCADE
+    );
+
+    -- Create index for faster room prop queries
+    CREATE INDEX idx_room_loose_props_room ON room_loose_props(room_id);
+
+    -- Hotspots table
+    CREATE TABLE hotspots (
+        hotspot_id INTEGER PRIMARY KEY AUTOINCREMENT,
+        room_id INTEGER NOT NULL,
+        id INTEGER NOT NULL,
+        name TEXT,
+        type INTEGER NOT NULL,
+        dest_room_id INTEGER,
+        dest_hotspot_id INTEGER,
+        loc_h INTEGER NOT NULL,
+        loc_v INTEGER NOT NULL,
+        script_event_mask INTEGER NOT NULL DEFAULT 0,
+        script_text TEXT,
+        state INTEGER NOT NULL DEFAULT 0,
+        FOREIGN KEY (room_id) REFERENCES rooms(room_id) ON DELETE CASCADE
+    );
+
+    -- Create index for room hotspot queries
+    CREATE INDEX idx_hotspots_room ON hotspots(room_id);
+
+    -- Hotspot points (polygon vertices)
+    CREATE TABLE hotspot_points (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        hotspot_id INTEGER NOT NULL,
+        point_order INTEGER NOT NULL,
+        pos_h INTEGER NOT NULL,
+        pos_v INTEGER NOT NULL,
+        FOREIGN KEY (hotspot_id) REFERENCES hotspots(hotspot_id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX idx_hotspot_points_hotspot ON hotspot_points(hotspot_id);
+
+    -- Ban list
+    CREATE TABLE bans (
+        ban_id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id INTEGER,
+        ip_address TEXT,
+        reason TEXT,
+        banned_at INTEGER NOT NULL,
+        expires_at INTEGER,
+        banned_by_user_id INTEGER,
+        FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+    );
+
+    -- Create index for active ban checks
+    CREATE INDEX idx_bans_user ON bans(user_id);
+    CREATE INDEX idx_bans_ip ON bans(ip_address);
+
+    -- Default rooms
+    INSERT INTO rooms (room_id, name, artist, flags, max_occupancy) VALUES
+        (0, 'Gate', 'System', 0, 50),
+        (1, 'Main Hall', 'System', 0, 100),
+        (2, 'Ballroom', 'System', 0, 75);
+"#,
+    },
+    Migration {
+        version: 2,
+        description: "Index ban expiry times",
+        sql: r#"
+    CREATE INDEX idx_bans_expires ON bans(expires_at);
+"#,
+    },
+    Migration {
+        version: 3,
+        description: "Store loose props by asset spec",
+        sql: r#"
+    -- prop_id/prop_crc are the protocol AssetSpec, which need not
+    -- have been uploaded to the props table
+    CREATE TABLE room_loose_props_v3 (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        room_id INTEGER NOT NULL,
+        prop_id INTEGER NOT NULL,
+        prop_crc INTEGER NOT NULL DEFAULT 0,
+        pos_h INTEGER NOT NULL,
+        pos_v INTEGER NOT NULL,
+        FOREIGN KEY (room_id) REFERENCES rooms(room_id) ON DELETE CASCADE
+    );
+
+    -- Existing rows point at uploaded props; keep their CRCs
+    INSERT INTO room_loose_props_v3 (id, room_id, prop_id, prop_crc, pos_h, pos_v)
+        SELECT lp.id, lp.room_id, lp.prop_id, COALESCE(p.crc32, 0), lp.pos_h, lp.pos_v
+        FROM room_loose_props lp LEFT JOIN props p ON p.prop_id = lp.prop_id;
+
+    DROP TABLE room_loose_props;
+    ALTER TABLE room_loose_props_v3 RENAME TO room_loose_props;
+    CREATE INDEX idx_room_loose_props_room ON room_loose_props(room_id);
+"#,
+    },
+    Migration {
+        version: 4,
+        description: "Record hotspot picture state counts",
+        sql: r#"
+    -- Number of pictures (states) declared for the hotspot; 0 for none
+    ALTER TABLE hotspots ADD COLUMN nbr_states INTEGER NOT NULL DEFAULT 0;
+"#,
+    },
+    Migration {
+        version: 5,
+        description: "Record prop asset IDs",
+        sql: r#"
+    -- ID from the uploader's AssetSpec, for queries that don't give a CRC
+    ALTER TABLE props ADD COLUMN asset_id INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX idx_props_asset_id ON props(asset_id);
+"#,
+    },
+];
+
+/// Version of the newest migration
+pub const LATEST_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+impl Database {
+    /// Current schema version, or 0 for an empty database
+    pub async fn schema_version(&self) -> Result<i64> {
+        self.ensure_version_table().await?;
+        let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to read schema version")?;
+        Ok(version.unwrap_or(0))
+    }
+
+    /// Apply migrations up to and including `target`, returning the new version
+    pub(super) async fn migrate_to(&self, target: i64) -> Result<i64> {
+        let mut version = self.schema_version().await?;
+
+        // Databases from before versioning have the initial schema but no
+        // record of it
+        if version == 0 && self.table_exists("users").await? {
+            info!("Adopting unversioned database as schema version 1");
+            let mut conn = self.pool.acquire().await?;
+            record_version(&mut conn, &MIGRATIONS[0]).await?;
+            version = 1;
+        }
+
+        let from = version;
+        for migration in MIGRATIONS
+            .iter()
+            .filter(|m| m.version > from && m.version <= target)
+        {
+            info!(
+                "Applying schema migration {}: {}",
+                migration.version, migration.description
+            );
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(migration.sql)
+                .execute(&mut *tx)
+                .await
+                .with_context(|| format!("Schema migration {} failed", migration.version))?;
+            record_version(&mut tx, migration).await?;
+            tx.commit().await?;
+            version = migration.version;
+        }
+
+        Ok(version)
+    }
+
+    async fn ensure_version_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                applied_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create schema_version table")?;
+        Ok(())
+    }
+
+    async fn table_exists(&self, name: &str) -> Result<bool> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?")
+                .bind(name)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(count > 0)
+    }
+}
+
+async fn record_version(conn: &mut SqliteConnection, migration: &Migration) -> Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)")
+        .bind(migration.version)
+        .bind(migration.description)
+        .bind(now)
+        .execute(conn)
+        .await
+        .context("Failed to record schema migration")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDatabase;
+    use thepalace::{AssetSpec, Point};
+
+    async fn applied_versions(db: &Database) -> Vec<i64> {
+        sqlx::query_scalar("SELECT version FROM schema_version ORDER BY version")
+            .fetch_all(db.pool())
+            .await
+            .unwrap()
+    }
+
+    async fn index_exists(db: &Database, name: &str) -> bool {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type='index' AND name=?")
+                .bind(name)
+                .fetch_one(db.pool())
+                .await
+                .unwrap();
+        count > 0
+    }
+
+    #[test]
+    fn test_versions_are_sequential() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i64 + 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fresh_database_reaches_latest() {
+        let test_db = TestDatabase::new("migrate-fresh").await;
+        let db = &test_db.db;
+        assert_eq!(db.schema_version().await.unwrap(), LATEST_VERSION);
+        let all: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert_eq!(applied_versions(db).await, all);
+
+        // Running again changes nothing
+        db.init_schema().await.unwrap();
+        assert_eq!(applied_versions(db).await, all);
+        let rooms: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM rooms")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(rooms, 3);
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_from_version_1() {
+        let test_db = TestDatabase::empty("migrate-v1").await;
+        let db = &test_db.db;
+        assert_eq!(db.migrate_to(1).await.unwrap(), 1);
+        assert!(!index_exists(db, "idx_bans_expires").await);
+
+        db.init_schema().await.unwrap();
+        assert_eq!(db.schema_version().await.unwrap(), LATEST_VERSION);
+        assert!(index_exists(db, "idx_bans_expires").await);
+    }
+
+    /// What init_schema created before versioning existed, verbatim
+    const UNVERSIONED_SCHEMA: &str = r#"
+        CREATE TABLE users (
+            user_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            password_hash TEXT,
+            wizard_password TEXT,
+            flags INTEGER NOT NULL DEFAULT 8,
+            registration_date INTEGER NOT NULL,
+            last_login INTEGER
+        );
+        CREATE INDEX idx_users_username ON users(username);
+
+        CREATE TABLE rooms (
+            room_id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            artist TEXT,
+            background_image TEXT,
+            flags INTEGER NOT NULL DEFAULT 0,
+            max_occupancy INTEGER DEFAULT 0,
+            faces_id INTEGER DEFAULT 0,
+            room_data BLOB
+        );
+
+        CREATE TABLE props (
+            prop_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            crc32 INTEGER NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            flags INTEGER NOT NULL,
+            width INTEGER NOT NULL,
+            height INTEGER NOT NULL,
+            file_path TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX idx_props_crc32 ON props(crc32);
+
+        CREATE TABLE room_loose_props (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            room_id INTEGER NOT NULL,
+            prop_id INTEGER NOT NULL,
+            pos_h INTEGER NOT NULL,
+            pos_v INTEGER NOT NULL,
+            FOREIGN KEY (room_id) REFERENCES rooms(room_id) ON DELETE CASCADE,
+            FOREIGN KEY (prop_id) REFERENCES props(prop_id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_room_loose_props_room ON room_loose_props(room_id);
+
+        CREATE TABLE hotspots (
+            hotspot_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            room_id INTEGER NOT NULL,
+            id INTEGER NOT NULL,
+            name TEXT,
+            type INTEGER NOT NULL,
+            dest_room_id INTEGER,
+            dest_hotspot_id INTEGER,
+            loc_h INTEGER NOT NULL,
+            loc_v INTEGER NOT NULL,
+            script_event_mask INTEGER NOT NULL DEFAULT 0,
+            script_text TEXT,
+            state INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY (room_id) REFERENCES rooms(room_id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_hotspots_room ON hotspots(room_id);
+
+        CREATE TABLE hotspot_points (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            hotspot_id INTEGER NOT NULL,
+            point_order INTEGER NOT NULL,
+            pos_h INTEGER NOT NULL,
+            pos_v INTEGER NOT NULL,
+            FOREIGN KEY (hotspot_id) REFERENCES hotspots(hotspot_id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_hotspot_points_hotspot ON hotspot_points(hotspot_id);
+
+        CREATE TABLE bans (
+            ban_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER,
+            ip_address TEXT,
+            reason TEXT,
+            banned_at INTEGER NOT NULL,
+            expires_at INTEGER,
+            banned_by_user_id INTEGER,
+            FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_bans_user ON bans(user_id);
+        CREATE INDEX idx_bans_ip ON bans(ip_address);
+
+        INSERT INTO rooms (room_id, name, artist, flags, max_occupancy) VALUES
+            (0, 'Gate', 'System', 0, 50),
+            (1, 'Main Hall', 'System', 0, 100),
+            (2, 'Ballroom', 'System', 0, 75);
+    "#;
+
+    #[tokio::test]
+    async fn test_upgrade_unversioned_database() {
+        let test_db = TestDatabase::empty("migrate-unversioned").await;
+        let db = &test_db.db;
+        sqlx::query(UNVERSIONED_SCHEMA)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        // A loose prop placed under the old schema, pointing at an upload
+        sqlx::query(
+            "INSERT INTO props (prop_id, crc32, name, flags, width, height, file_path, created_at)
+             VALUES (5, 48879, 'Hat', 0, 44, 44, 'hat.prop', 0);
+             INSERT INTO room_loose_props (room_id, prop_id, pos_h, pos_v) VALUES (1, 5, 10, 20);",
+        )
+        .execute(db.pool())
+        .await
+        .unwrap();
+
+        db.init_schema().await.unwrap();
+        let all: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert_eq!(applied_versions(db).await, all);
+        assert!(index_exists(db, "idx_bans_expires").await);
+        assert!(index_exists(db, "idx_room_loose_props_room").await);
+
+        // Loose props no longer need an uploaded prop, and keep their CRC
+        let spec = AssetSpec::new(77, 0xCAFE);
+        db.add_loose_prop(1, spec, Point::new(30, 40), 10)
+            .await
+            .unwrap()
+            .unwrap();
+        let props = db.loose_props_for_room(1).await.unwrap();
+        assert_eq!(props.len(), 2);
+        assert_eq!(props[0].spec(), AssetSpec::new(5, 0xBEEF));
+        assert_eq!(props[1].spec(), spec);
+    }
+}
@@ -1,14 +1,70 @@
 //! Database layer for Palace server
 
+pub mod migrations;
 pub mod models;
+pub mod props;
 pub mod users;
 pub mod rooms;
 
+#[cfg(test)]
+pub(crate) mod test_support;
+
 use anyhow::{Context, Result};
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
 use std::str::FromStr;
+use std::time::Duration;
 use tracing::info;
 
+/// SQLite journal mode
+///
+/// WAL suits a server with concurrent readers; `delete` or `memory` can make
+/// more sense for an embedded or throwaway database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    #[default]
+    Wal,
+    Off,
+}
+
+impl From<JournalMode> for SqliteJournalMode {
+    fn from(mode: JournalMode) -> Self {
+        match mode {
+            JournalMode::Delete => SqliteJournalMode::Delete,
+            JournalMode::Truncate => SqliteJournalMode::Truncate,
+            JournalMode::Persist => SqliteJournalMode::Persist,
+            JournalMode::Memory => SqliteJournalMode::Memory,
+            JournalMode::Wal => SqliteJournalMode::Wal,
+            JournalMode::Off => SqliteJournalMode::Off,
+        }
+    }
+}
+
+/// Connection settings for [`Database::with_options`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DatabaseOptions {
+    pub journal_mode: JournalMode,
+    /// Most connections the pool opens at once
+    pub max_connections: u32,
+    /// How long a query waits for a locked database before failing
+    pub busy_timeout: Duration,
+}
+
+impl Default for DatabaseOptions {
+    fn default() -> Self {
+        Self {
+            journal_mode: JournalMode::Wal,
+            max_connections: 10,
+            busy_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
 /// Database connection pool
 #[derive(Clone)]
 pub struct Database {
@@ -16,19 +72,20 @@ pub struct Database {
 }
 
 impl Database {
-    /// Create a new database connection with the given path
-    pub async fn new(database_path: &str) -> Result<Self> {
+    /// Create a new database connection with the given path and settings
+    pub async fn with_options(database_path: &str, settings: DatabaseOptions) -> Result<Self> {
         info!("Connecting to database: {}", database_path);
 
         // Create options with proper settings
         let options = SqliteConnectOptions::from_str(database_path)?
             .create_if_missing(true)
-            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+            .journal_mode(settings.journal_mode.into())
+            .busy_timeout(settings.busy_timeout)
             .foreign_keys(true);
 
         // Create connection pool
         let pool = SqlitePoolOptions::new()
-            .max_connections(10)
+            .max_connections(settings.max_connections)
             .connect_with(options)
             .await
             .context("Failed to connect to database")?;
@@ -38,189 +95,12 @@ impl Database {
         Ok(Self { pool })
     }
 
-    /// Initialize database schema
+    /// Create the schema, or upgrade an existing one to the latest version
+    ///
+    /// See [`migrations`] for how versions are tracked.
     pub async fn init_schema(&self) -> Result<()> {
-        info!("Initializing database schema");
-
-        // Check if schema already exists
-        let table_count: i64 = sqlx::query_scalar(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='users'"
-        )
-        .fetch_one(&self.pool)
-        .await?;
-
-        if table_count > 0 {
-            info!("Database schema already exists, skipping initialization");
-            return Ok(());
-        }
-
-        // Create all tables
-        sqlx::query(
-            r#"
-            -- Users table
-            CREATE TABLE users (
-                user_id INTEGER PRIMARY KEY AUTOINCREMENT,
-                username TEXT NOT NULL UNIQUE COLLATE NOCASE,
-                password_hash TEXT,
-                wizard_password TEXT,
-                flags INTEGER NOT NULL DEFAULT 8,
-                registration_date INTEGER NOT NULL,
-                last_login INTEGER
-            );
-
-            -- Create index on username for faster lookups
-            CREATE INDEX idx_users_username ON users(username);
-            "#
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to create users table")?;
-
-        sqlx::query(
-            r#"
-            -- Rooms table
-            CREATE TABLE rooms (
-                room_id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL,
-                artist TEXT,
-                background_image TEXT,
-                flags INTEGER NOT NULL DEFAULT 0,
-                max_occupancy INTEGER DEFAULT 0,
-                faces_id INTEGER DEFAULT 0,
-                room_data BLOB
-            );
-            "#
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to create rooms table")?;
-
-        sqlx::query(
-            r#"
-            -- Props registry
-            CREATE TABLE props (
-                prop_id INTEGER PRIMARY KEY AUTOINCREMENT,
-                crc32 INTEGER NOT NULL UNIQUE,
-                name TEXT NOT NULL,
-                flags INTEGER NOT NULL,
-                width INTEGER NOT NULL,
-                height INTEGER NOT NULL,
-                file_path TEXT NOT NULL,
-                created_at INTEGER NOT NULL
-            );
-
-            -- Create index on CRC32 for asset lookups
-            CREATE INDEX idx_props_crc32 ON props(crc32);
-            "#
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to create props table")?;
-
-        sqlx::query(
-            r#"
-            -- Loose props in rooms
-            CREATE TABLE room_loose_props (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                room_id INTEGER NOT NULL,
-                prop_id INTEGER NOT NULL,
-                pos_h INTEGER NOT NULL,
-                pos_v INTEGER NOT NULL,
-                FOREIGN KEY (room_id) REFERENCES rooms(room_id) ON DELETE CASCADE,
-                FOREIGN KEY (prop_id) REFERENCES props(prop_id) ON DELETE CASCADE
-            );
-
-            -- Create index for faster room prop queries
-            CREATE INDEX idx_room_loose_props_room ON room_loose_props(room_id);
-            "#
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to create room_loose_props table")?;
-
-        sqlx::query(
-            r#"
-            -- Hotspots table
-            CREATE TABLE hotspots (
-                hotspot_id INTEGER PRIMARY KEY AUTOINCREMENT,
-                room_id INTEGER NOT NULL,
-                id INTEGER NOT NULL,
-                name TEXT,
-                type INTEGER NOT NULL,
-                dest_room_id INTEGER,
-                dest_hotspot_id INTEGER,
-                loc_h INTEGER NOT NULL,
-                loc_v INTEGER NOT NULL,
-                script_event_mask INTEGER NOT NULL DEFAULT 0,
-                script_text TEXT,
-                state INTEGER NOT NULL DEFAULT 0,
-                FOREIGN KEY (room_id) REFERENCES rooms(room_id) ON DELETE CASCADE
-            );
-
-            -- Create index for room hotspot queries
-            CREATE INDEX idx_hotspots_room ON hotspots(room_id);
-            "#
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to create hotspots table")?;
-
-        sqlx::query(
-            r#"
-            -- Hotspot points (polygon vertices)
-            CREATE TABLE hotspot_points (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                hotspot_id INTEGER NOT NULL,
-                point_order INTEGER NOT NULL,
-                pos_h INTEGER NOT NULL,
-                pos_v INTEGER NOT NULL,
-                FOREIGN KEY (hotspot_id) REFERENCES hotspots(hotspot_id) ON DELETE CASCADE
-            );
-
-            CREATE INDEX idx_hotspot_points_hotspot ON hotspot_points(hotspot_id);
-            "#
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to create hotspot_points table")?;
-
-        sqlx::query(
-            r#"
-            -- Ban list
-            CREATE TABLE bans (
-                ban_id INTEGER PRIMARY KEY AUTOINCREMENT,
-                user_id INTEGER,
-                ip_address TEXT,
-                reason TEXT,
-                banned_at INTEGER NOT NULL,
-                expires_at INTEGER,
-                banned_by_user_id INTEGER,
-                FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
-            );
-
-            -- Create index for active ban checks
-            CREATE INDEX idx_bans_user ON bans(user_id);
-            CREATE INDEX idx_bans_ip ON bans(ip_address);
-            "#
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to create bans table")?;
-
-        // Insert default rooms
-        sqlx::query(
-            r#"
-            INSERT INTO rooms (room_id, name, artist, flags, max_occupancy) VALUES
-                (0, 'Gate', 'System', 0, 50),
-                (1, 'Main Hall', 'System', 0, 100),
-                (2, 'Ballroom', 'System', 0, 75);
-            "#
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to insert default rooms")?;
-
-        info!("Database schema initialized successfully");
+        let version = self.migrate_to(migrations::LATEST_VERSION).await?;
+        info!("Database schema at version {}", version);
         Ok(())
     }
 
@@ -234,3 +114,35 @@ impl Database {
         self.pool.close().await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_custom_pool_options() {
+        let dir =
+            std::env::temp_dir().join(format!("palace-db-options-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let options = DatabaseOptions {
+            journal_mode: JournalMode::Delete,
+            max_connections: 2,
+            busy_timeout: Duration::from_millis(250),
+        };
+        let path = format!("sqlite:{}", dir.join("palace.db").display());
+        let db = Database::with_options(&path, options).await.unwrap();
+        db.init_schema().await.unwrap();
+
+        assert_eq!(db.pool().options().get_max_connections(), 2);
+        let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(mode, "delete");
+
+        db.close().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,9 @@
 //! Database models
 
 use serde::{Deserialize, Serialize};
+use thepalace::messages::flags::UserFlags;
+use thepalace::room::{HotspotState, HotspotType};
+use thepalace::{AssetSpec, Point};
 
 /// User record from database
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -14,6 +17,49 @@ pub struct User {
     pub last_login: Option<i64>,
 }
 
+impl User {
+    /// Protocol user flags from the `flags` column
+    ///
+    /// The column defaults to 8 (`UserFlags::GUEST`). The result can fill a
+    /// script context's `user_flags` directly.
+    pub fn user_flags(&self) -> UserFlags {
+        UserFlags::from_bits_truncate(self.flags as u16)
+    }
+
+    /// Flags for a session logging on as this user with `wiz_password`
+    ///
+    /// SUPERUSER and GOD are only granted when the password verifies against
+    /// `wizard_password`. Without it the user keeps the rest of their stored
+    /// flags, so restrictions such as GAG still apply.
+    pub fn logon_flags(&self, wiz_password: &str) -> UserFlags {
+        let flags = self.user_flags();
+        if self.verify_wizard_password(wiz_password) {
+            flags
+        } else {
+            flags.difference(UserFlags::SUPERUSER | UserFlags::GOD)
+        }
+    }
+
+    /// Check `password` against the argon2 hash in `wizard_password`
+    ///
+    /// Always false for an empty password or a user with no hash stored.
+    pub fn verify_wizard_password(&self, password: &str) -> bool {
+        use argon2::{Argon2, PasswordHash, PasswordVerifier};
+
+        let Some(hash) = self.wizard_password.as_deref() else {
+            return false;
+        };
+        if password.is_empty() {
+            return false;
+        }
+        PasswordHash::new(hash).is_ok_and(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+    }
+}
+
 /// Room record from database
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Room {
@@ -38,6 +84,30 @@ pub struct Prop {
     pub height: i64,
     pub file_path: String,
     pub created_at: i64,
+    pub asset_id: i64,
+}
+
+/// Loose prop lying in a room
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LooseProp {
+    pub id: i64,
+    pub room_id: i64,
+    pub prop_id: i64,
+    pub prop_crc: i64,
+    pub pos_h: i64,
+    pub pos_v: i64,
+}
+
+impl LooseProp {
+    /// Protocol asset spec of the prop
+    pub fn spec(&self) -> AssetSpec {
+        AssetSpec::new(self.prop_id as i32, self.prop_crc as u32)
+    }
+
+    /// Position of the prop in the room
+    pub fn pos(&self) -> Point {
+        Point::new(self.pos_h as i16, self.pos_v as i16)
+    }
 }
 
 /// Hotspot record from database
@@ -55,6 +125,27 @@ pub struct Hotspot {
     pub script_event_mask: i64,
     pub script_text: Option<String>,
     pub state: i64,
+    pub nbr_states: i64,
+}
+
+impl Hotspot {
+    /// Protocol hotspot type from the `type` column
+    pub fn hotspot_type(&self) -> HotspotType {
+        HotspotType::from_i16(self.r#type as i16).unwrap_or(HotspotType::Normal)
+    }
+
+    /// Whether a SpotState may put this hotspot in `state`
+    ///
+    /// Lockable doors take the [`HotspotState`] values. Other hotspots take
+    /// an index into their pictures, `0..nbr_states`; one without pictures
+    /// only has state 0.
+    pub fn accepts_state(&self, state: i16) -> bool {
+        if self.hotspot_type() == HotspotType::LockableDoor {
+            HotspotState::from_i16(state).is_some()
+        } else {
+            (0..self.nbr_states.max(1)).contains(&(state as i64))
+        }
+    }
 }
 
 /// Hotspot point (polygon vertex)
@@ -67,6 +158,14 @@ pub struct HotspotPoint {
     pub pos_v: i64,
 }
 
+/// Hotspot together with its polygon, as loaded for a room
+#[derive(Debug, Clone)]
+pub struct RoomHotspot {
+    pub hotspot: Hotspot,
+    /// Polygon vertices in order
+    pub points: Vec<Point>,
+}
+
 /// Ban record from database
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Ban {
@@ -78,3 +177,68 @@ pub struct Ban {
     pub expires_at: Option<i64>,
     pub banned_by_user_id: Option<i64>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_with_flags(flags: i64) -> User {
+        User {
+            user_id: 1,
+            username: "Newbie".to_string(),
+            password_hash: None,
+            wizard_password: None,
+            flags,
+            registration_date: 0,
+            last_login: None,
+        }
+    }
+
+    #[test]
+    fn test_user_flags_mapping() {
+        // Default for the users.flags column
+        let guest = user_with_flags(8);
+        assert_eq!(guest.user_flags(), UserFlags::GUEST);
+        assert!(guest.user_flags().is_guest());
+
+        let wizard = user_with_flags(1);
+        assert_eq!(wizard.user_flags(), UserFlags::SUPERUSER);
+        assert!(wizard.user_flags().is_wizard());
+    }
+
+    fn hotspot(hotspot_type: HotspotType, nbr_states: i64) -> Hotspot {
+        Hotspot {
+            hotspot_id: 1,
+            room_id: 0,
+            id: 1,
+            name: None,
+            r#type: hotspot_type.as_i16() as i64,
+            dest_room_id: None,
+            dest_hotspot_id: None,
+            loc_h: 0,
+            loc_v: 0,
+            script_event_mask: 0,
+            script_text: None,
+            state: 0,
+            nbr_states,
+        }
+    }
+
+    #[test]
+    fn test_hotspot_accepts_state() {
+        let door = hotspot(HotspotType::LockableDoor, 0);
+        assert!(door.accepts_state(HotspotState::Locked.as_i16()));
+        assert!(door.accepts_state(HotspotState::Unlocked.as_i16()));
+        assert!(!door.accepts_state(2));
+
+        // Picture states are indexes, not locked/unlocked
+        let pictures = hotspot(HotspotType::Normal, 3);
+        assert!((0..3).all(|state| pictures.accepts_state(state)));
+        assert!(!pictures.accepts_state(3));
+        assert!(!pictures.accepts_state(-1));
+
+        let plain = hotspot(HotspotType::Door, 0);
+        assert!(plain.accepts_state(0));
+        assert!(!plain.accepts_state(HotspotState::Locked.as_i16()));
+    }
+}
@@ -0,0 +1,158 @@
+//! Prop registry database operations
+
+use super::Database;
+use crate::db::models::Prop;
+use anyhow::{Context, Result};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thepalace::AssetSpec;
+use tracing::debug;
+
+impl Database {
+    /// Record an uploaded prop, returning its `prop_id`
+    ///
+    /// Props are keyed by `spec.crc`, so the same prop uploaded again (often
+    /// by a different user) updates the existing row in place. Its `prop_id`
+    /// and `created_at` are kept.
+    pub async fn register_prop(
+        &self,
+        spec: AssetSpec,
+        name: &str,
+        flags: u16,
+        width: u16,
+        height: u16,
+        file_path: &str,
+    ) -> Result<i64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let prop_id: i64 = sqlx::query_scalar(
+            "INSERT INTO props
+                 (crc32, asset_id, name, flags, width, height, file_path, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT (crc32) DO UPDATE SET
+                 asset_id = excluded.asset_id,
+                 name = excluded.name,
+                 flags = excluded.flags,
+                 width = excluded.width,
+                 height = excluded.height,
+                 file_path = excluded.file_path
+             RETURNING prop_id",
+        )
+        .bind(spec.crc as i64)
+        .bind(spec.id as i64)
+        .bind(name)
+        .bind(flags as i64)
+        .bind(width as i64)
+        .bind(height as i64)
+        .bind(file_path)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to register prop")?;
+
+        debug!("Registered prop {} (crc 0x{:08X})", prop_id, spec.crc);
+        Ok(prop_id)
+    }
+
+    /// Get a registered prop by its CRC
+    pub async fn get_prop_by_crc(&self, crc: u32) -> Result<Option<Prop>> {
+        let prop = sqlx::query_as::<_, Prop>(
+            "SELECT prop_id, crc32, name, flags, width, height, file_path, created_at, asset_id
+             FROM props WHERE crc32 = ?",
+        )
+        .bind(crc as i64)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query prop")?;
+        Ok(prop)
+    }
+
+    /// Get the most recently registered prop with asset ID `id`
+    ///
+    /// For queries with a don't-care CRC of 0. Asset IDs are chosen by
+    /// clients, so more than one prop may have the same one.
+    pub async fn get_prop_by_asset_id(&self, id: i32) -> Result<Option<Prop>> {
+        let prop = sqlx::query_as::<_, Prop>(
+            "SELECT prop_id, crc32, name, flags, width, height, file_path, created_at, asset_id
+             FROM props WHERE asset_id = ? ORDER BY prop_id DESC LIMIT 1",
+        )
+        .bind(id as i64)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query prop")?;
+        Ok(prop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDatabase;
+    use thepalace::AssetSpec;
+
+    #[tokio::test]
+    async fn test_register_prop_upsert() {
+        let test_db = TestDatabase::new("register-prop").await;
+        let db = &test_db.db;
+
+        let crc = 0xDEAD_BEEF;
+        let id = db
+            .register_prop(AssetSpec::new(1, crc), "Hat", 0, 44, 44, "props/hat.prop")
+            .await
+            .unwrap();
+        let first = db.get_prop_by_crc(crc).await.unwrap().unwrap();
+        assert_eq!(first.prop_id, id);
+        assert_eq!(first.crc32, crc as i64);
+
+        // Same prop uploaded again under another name
+        let again = db
+            .register_prop(
+                AssetSpec::new(2, crc),
+                "Party Hat",
+                2,
+                44,
+                44,
+                "props/party-hat.prop",
+            )
+            .await
+            .unwrap();
+        assert_eq!(again, id);
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM props")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        let prop = db.get_prop_by_crc(crc).await.unwrap().unwrap();
+        assert_eq!(prop.name, "Party Hat");
+        assert_eq!(prop.flags, 2);
+        assert_eq!(prop.file_path, "props/party-hat.prop");
+        assert_eq!(prop.created_at, first.created_at);
+        assert_eq!(prop.asset_id, 2);
+
+        // A different CRC is a different prop
+        let other = db
+            .register_prop(
+                AssetSpec::new(1, 0x1234),
+                "Shoe",
+                0,
+                20,
+                10,
+                "props/shoe.prop",
+            )
+            .await
+            .unwrap();
+        assert_ne!(other, id);
+
+        // Without a CRC, the newest prop with the asset ID is found
+        let by_id = db.get_prop_by_asset_id(1).await.unwrap().unwrap();
+        assert_eq!(by_id.prop_id, other);
+        assert_eq!(
+            db.get_prop_by_asset_id(2).await.unwrap().unwrap().prop_id,
+            id
+        );
+        assert!(db.get_prop_by_asset_id(3).await.unwrap().is_none());
+    }
+}
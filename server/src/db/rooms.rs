@@ -1,8 +1,14 @@
 //! Room database operations
 
 use super::Database;
-use crate::db::models::{Hotspot, HotspotPoint, Room};
+use crate::db::models::{Hotspot, HotspotPoint, LooseProp, Room, RoomHotspot};
 use anyhow::{Context, Result};
+use sqlx::SqliteConnection;
+use thepalace::iptscrae::{EventMask, RoomDecl, convert_room};
+use thepalace::messages::flags::RoomFlags;
+use thepalace::messages::RoomListRec;
+use thepalace::room::{self, HotspotState, HotspotType};
+use thepalace::{AssetSpec, Point};
 
 impl Database {
     /// Get a room by room_id
@@ -24,10 +30,31 @@ impl Database {
         Ok(rooms)
     }
 
+    /// Every room as a room-list entry, ordered by room_id
+    ///
+    /// `nbr_users` is left at 0: occupancy is live state, filled in by
+    /// [`ServerState::room_list`](crate::state::ServerState::room_list).
+    pub async fn room_list(&self) -> Result<Vec<RoomListRec>> {
+        let rooms = self
+            .get_all_rooms()
+            .await?
+            .into_iter()
+            .map(|room| RoomListRec {
+                room_id: room.room_id as i32,
+                flags: RoomFlags::from_bits_truncate(room.flags as u16),
+                nbr_users: 0,
+                name: room.name,
+            })
+            .collect();
+        Ok(rooms)
+    }
+
     /// Get hotspots for a room
     pub async fn get_room_hotspots(&self, room_id: i16) -> Result<Vec<Hotspot>> {
         let hotspots = sqlx::query_as::<_, Hotspot>(
-            "SELECT * FROM hotspots WHERE room_id = ? ORDER BY id",
+            "SELECT hotspot_id, room_id, id, name, type, dest_room_id, dest_hotspot_id, loc_h, loc_v,
+                    script_event_mask, script_text, state, nbr_states
+             FROM hotspots WHERE room_id = ? ORDER BY id",
         )
         .bind(room_id as i64)
         .fetch_all(&self.pool)
@@ -36,6 +63,21 @@ impl Database {
         Ok(hotspots)
     }
 
+    /// Get a single hotspot in a room
+    pub async fn get_hotspot(&self, room_id: i16, id: i16) -> Result<Option<Hotspot>> {
+        let hotspot = sqlx::query_as::<_, Hotspot>(
+            "SELECT hotspot_id, room_id, id, name, type, dest_room_id, dest_hotspot_id, loc_h, loc_v,
+                    script_event_mask, script_text, state, nbr_states
+             FROM hotspots WHERE room_id = ? AND id = ?",
+        )
+        .bind(room_id as i64)
+        .bind(id as i64)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query hotspot")?;
+        Ok(hotspot)
+    }
+
     /// Get points for a hotspot
     pub async fn get_hotspot_points(&self, hotspot_id: i64) -> Result<Vec<HotspotPoint>> {
         let points = sqlx::query_as::<_, HotspotPoint>(
@@ -48,6 +90,324 @@ impl Database {
         Ok(points)
     }
 
+    /// Load a room's hotspots with their polygon points
+    pub async fn load_room_hotspots(&self, room_id: i16) -> Result<Vec<RoomHotspot>> {
+        let mut hotspots = Vec::new();
+        for hotspot in self.get_room_hotspots(room_id).await? {
+            let points = self
+                .get_hotspot_points(hotspot.hotspot_id)
+                .await?
+                .iter()
+                .map(|p| Point::new(p.pos_h as i16, p.pos_v as i16))
+                .collect();
+            hotspots.push(RoomHotspot { hotspot, points });
+        }
+        Ok(hotspots)
+    }
+
+    /// Get the next unused hotspot ID in a room
+    ///
+    /// Allocated by [`room::next_hotspot_id`], so this fails only once every
+    /// positive ID in the room is taken.
+    pub async fn next_hotspot_id(&self, room_id: i16) -> Result<i16> {
+        let used: Vec<i64> = sqlx::query_scalar("SELECT id FROM hotspots WHERE room_id = ?")
+            .bind(room_id as i64)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query hotspot IDs")?;
+        let used: Vec<i16> = used.into_iter().map(|id| id as i16).collect();
+        room::next_hotspot_id(&used).context("No free hotspot IDs in room")
+    }
+
+    /// Create a hotspot with its polygon, returning the hotspot's row id
+    ///
+    /// Points are absolute room coordinates and the hotspot's location is the
+    /// first of them, as for imported rooms.
+    pub async fn create_hotspot(
+        &self,
+        room_id: i16,
+        id: i16,
+        hotspot_type: HotspotType,
+        points: &[Point],
+    ) -> Result<i64> {
+        let loc = points.first().copied().unwrap_or(Point::origin());
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            "INSERT INTO hotspots (room_id, id, type, loc_h, loc_v, state)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(room_id as i64)
+        .bind(id as i64)
+        .bind(hotspot_type.as_i16() as i64)
+        .bind(loc.h as i64)
+        .bind(loc.v as i64)
+        .bind(HotspotState::Unlocked.as_i16() as i64)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to create hotspot")?;
+        let hotspot_id = result.last_insert_rowid();
+        insert_hotspot_points(&mut tx, hotspot_id, points).await?;
+
+        tx.commit().await?;
+        Ok(hotspot_id)
+    }
+
+    /// Insert or replace a room from a room script declaration
+    ///
+    /// The declaration's doors and spots become the room's hotspots. A room
+    /// that is already in the database is left alone, so edits made while
+    /// the server runs (moved or locked hotspots, say) survive a restart,
+    /// unless `overwrite` is set: then the room takes the declaration's
+    /// name, artist, picture and flags, and its hotspots are replaced.
+    /// Loose props aren't declared and stay either way. Returns whether the
+    /// room was imported.
+    pub async fn import_room_decl(&self, decl: &RoomDecl, overwrite: bool) -> Result<bool> {
+        // Convert first so rooms that can't be sent to clients are rejected
+        let template =
+            convert_room(decl).with_context(|| format!("Failed to convert room {}", decl.id))?;
+        let name = decl
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Room {}", decl.id));
+
+        let mut tx = self.pool.begin().await?;
+
+        let on_conflict = if overwrite {
+            "DO UPDATE SET name = excluded.name, artist = excluded.artist,
+                 background_image = excluded.background_image, flags = excluded.flags"
+        } else {
+            "DO NOTHING"
+        };
+        let result = sqlx::query(&format!(
+            "INSERT INTO rooms (room_id, name, artist, background_image, flags)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (room_id) {}",
+            on_conflict
+        ))
+        .bind(decl.id as i64)
+        .bind(&name)
+        .bind(&decl.artist)
+        .bind(&decl.pict)
+        .bind(template.room_flags.bits() as i64)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to insert room")?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        // Points go with their hotspots
+        sqlx::query("DELETE FROM hotspots WHERE room_id = ?")
+            .bind(decl.id as i64)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to clear hotspots")?;
+
+        let doors = decl.doors.iter().map(|door| {
+            (
+                door.id,
+                door.name.as_deref(),
+                HotspotType::Door,
+                Some(door.dest),
+                &door.outline,
+                door.picts.len(),
+                door.script.as_ref(),
+            )
+        });
+        let spots = decl.spots.iter().map(|spot| {
+            (
+                spot.id,
+                spot.name.as_deref(),
+                HotspotType::Normal,
+                None,
+                &spot.outline,
+                spot.picts.len(),
+                spot.script.as_ref(),
+            )
+        });
+
+        for (id, name, hotspot_type, dest, outline, nbr_states, script) in doors.chain(spots) {
+            // Same location rule as the converter: the first outline point
+            let loc = outline.first().copied().unwrap_or(Point::origin());
+            let event_mask = script.map_or(EventMask::empty(), |script| {
+                script
+                    .handlers
+                    .iter()
+                    .fold(EventMask::empty(), |mask, handler| {
+                        mask | handler.event.to_mask()
+                    })
+            });
+
+            let result = sqlx::query(
+                "INSERT INTO hotspots
+                     (room_id, id, name, type, dest_room_id, loc_h, loc_v,
+                      script_event_mask, script_text, state, nbr_states)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(decl.id as i64)
+            .bind(id as i64)
+            .bind(name)
+            .bind(hotspot_type.as_i16() as i64)
+            .bind(dest.map(|dest| dest as i64))
+            .bind(loc.h as i64)
+            .bind(loc.v as i64)
+            .bind(i32::from(event_mask) as i64)
+            .bind(script.map(|script| script.to_source()))
+            .bind(HotspotState::Unlocked.as_i16() as i64)
+            .bind(nbr_states as i64)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to create hotspot")?;
+            insert_hotspot_points(&mut tx, result.last_insert_rowid(), outline).await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Delete a hotspot (and its points), returning whether it existed
+    pub async fn delete_hotspot(&self, room_id: i16, id: i16) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM hotspots WHERE room_id = ? AND id = ?")
+            .bind(room_id as i64)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete hotspot")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Move a hotspot, returning whether it exists
+    ///
+    /// The outline is shifted along with the location so the location stays
+    /// on its first point.
+    pub async fn move_hotspot(&self, room_id: i16, id: i16, loc: Point) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let current: Option<(i64, i64, i64)> = sqlx::query_as(
+            "SELECT hotspot_id, loc_h, loc_v FROM hotspots WHERE room_id = ? AND id = ?",
+        )
+        .bind(room_id as i64)
+        .bind(id as i64)
+        .fetch_optional(&mut *tx)
+        .await
+        .context("Failed to query hotspot")?;
+        let Some((hotspot_id, loc_h, loc_v)) = current else {
+            return Ok(false);
+        };
+
+        sqlx::query("UPDATE hotspots SET loc_h = ?, loc_v = ? WHERE hotspot_id = ?")
+            .bind(loc.h as i64)
+            .bind(loc.v as i64)
+            .bind(hotspot_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to move hotspot")?;
+        sqlx::query(
+            "UPDATE hotspot_points SET pos_h = pos_h + ?, pos_v = pos_v + ?
+             WHERE hotspot_id = ?",
+        )
+        .bind(loc.h as i64 - loc_h)
+        .bind(loc.v as i64 - loc_v)
+        .bind(hotspot_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to move hotspot points")?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Set a hotspot's state (e.g. locked), returning whether it exists
+    pub async fn set_hotspot_state(&self, room_id: i16, id: i16, state: i16) -> Result<bool> {
+        let result = sqlx::query("UPDATE hotspots SET state = ? WHERE room_id = ? AND id = ?")
+            .bind(state as i64)
+            .bind(room_id as i64)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update hotspot state")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get the loose props in a room, in the order they were added
+    ///
+    /// The position in this list is the `prop_num` used by PropMove/PropDel.
+    pub async fn loose_props_for_room(&self, room_id: i16) -> Result<Vec<LooseProp>> {
+        let props = sqlx::query_as::<_, LooseProp>(
+            "SELECT * FROM room_loose_props WHERE room_id = ? ORDER BY id",
+        )
+        .bind(room_id as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to query loose props")?;
+        Ok(props)
+    }
+
+    /// Add a loose prop to a room, returning its row id
+    ///
+    /// Returns `None` without adding anything if the room already has `max`
+    /// loose props. The count and insert are one statement, so concurrent
+    /// drops can't push a room past `max`.
+    pub async fn add_loose_prop(
+        &self,
+        room_id: i16,
+        spec: AssetSpec,
+        pos: Point,
+        max: usize,
+    ) -> Result<Option<i64>> {
+        let result = sqlx::query(
+            "INSERT INTO room_loose_props (room_id, prop_id, prop_crc, pos_h, pos_v)
+             SELECT ?1, ?2, ?3, ?4, ?5
+             WHERE (SELECT COUNT(*) FROM room_loose_props WHERE room_id = ?1) < ?6",
+        )
+        .bind(room_id as i64)
+        .bind(spec.id as i64)
+        .bind(spec.crc as i64)
+        .bind(pos.h as i64)
+        .bind(pos.v as i64)
+        .bind(max.min(i64::MAX as usize) as i64)
+        .execute(&self.pool)
+        .await
+        .context("Failed to add loose prop")?;
+        Ok((result.rows_affected() > 0).then_some(result.last_insert_rowid()))
+    }
+
+    /// Remove a loose prop by its `prop_num` (index in insertion order)
+    ///
+    /// A `prop_num` of -1 clears every loose prop in the room, matching
+    /// PropDel. Returns whether anything was removed.
+    pub async fn remove_loose_prop(&self, room_id: i16, prop_num: i32) -> Result<bool> {
+        if prop_num == -1 {
+            return Ok(self.clear_loose_props(room_id).await? > 0);
+        }
+        if prop_num < 0 {
+            return Ok(false);
+        }
+
+        let result = sqlx::query(
+            "DELETE FROM room_loose_props WHERE id = (
+                 SELECT id FROM room_loose_props WHERE room_id = ? ORDER BY id LIMIT 1 OFFSET ?
+             )",
+        )
+        .bind(room_id as i64)
+        .bind(prop_num as i64)
+        .execute(&self.pool)
+        .await
+        .context("Failed to remove loose prop")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Remove every loose prop from a room, returning how many were removed
+    pub async fn clear_loose_props(&self, room_id: i16) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM room_loose_props WHERE room_id = ?")
+            .bind(room_id as i64)
+            .execute(&self.pool)
+            .await
+            .context("Failed to clear loose props")?;
+        Ok(result.rows_affected())
+    }
+
     /// Count users currently in a room (from in-memory state, not DB)
     /// Note: This should be called from the state manager, not the database
     /// Keeping this as a placeholder for future implementation
@@ -57,3 +417,212 @@ impl Database {
         Ok(0)
     }
 }
+
+/// Insert a hotspot's polygon points in order
+async fn insert_hotspot_points(
+    conn: &mut SqliteConnection,
+    hotspot_id: i64,
+    points: &[Point],
+) -> Result<()> {
+    for (order, point) in points.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO hotspot_points (hotspot_id, point_order, pos_h, pos_v)
+             VALUES (?, ?, ?, ?)",
+        )
+        .bind(hotspot_id)
+        .bind(order as i64)
+        .bind(point.h as i64)
+        .bind(point.v as i64)
+        .execute(&mut *conn)
+        .await
+        .context("Failed to create hotspot point")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDatabase;
+    use thepalace::iptscrae::{EventMask, RoomScriptParser};
+    use thepalace::messages::flags::RoomFlags;
+    use thepalace::room::{HotspotState, HotspotType};
+    use thepalace::{AssetSpec, Point};
+
+    #[tokio::test]
+    async fn test_loose_props() {
+        let test_db = TestDatabase::new("loose-props").await;
+        let db = &test_db.db;
+
+        for (id, h) in [(100, 10), (200, 20), (300, 30)] {
+            db.add_loose_prop(1, AssetSpec::new(id, 0xABCD), Point::new(h, 50), 3)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        db.add_loose_prop(2, AssetSpec::new(400, 0), Point::new(0, 0), 3)
+            .await
+            .unwrap()
+            .unwrap();
+        // Room 1 is full
+        let extra = db.add_loose_prop(1, AssetSpec::new(500, 0), Point::new(0, 0), 3);
+        assert_eq!(extra.await.unwrap(), None);
+
+        let props = db.loose_props_for_room(1).await.unwrap();
+        let specs: Vec<_> = props.iter().map(|p| p.spec()).collect();
+        assert_eq!(
+            specs,
+            [
+                AssetSpec::new(100, 0xABCD),
+                AssetSpec::new(200, 0xABCD),
+                AssetSpec::new(300, 0xABCD)
+            ]
+        );
+        assert_eq!(props[1].pos(), Point::new(20, 50));
+
+        // prop_num indexes the props in the order they were added
+        assert!(db.remove_loose_prop(1, 1).await.unwrap());
+        assert!(!db.remove_loose_prop(1, 5).await.unwrap());
+        let ids: Vec<_> = db
+            .loose_props_for_room(1)
+            .await
+            .unwrap()
+            .iter()
+            .map(|p| p.prop_id)
+            .collect();
+        assert_eq!(ids, [100, 300]);
+
+        // -1 clears the room but leaves other rooms alone
+        assert!(db.remove_loose_prop(1, -1).await.unwrap());
+        assert!(db.loose_props_for_room(1).await.unwrap().is_empty());
+        assert_eq!(db.loose_props_for_room(2).await.unwrap().len(), 1);
+
+        assert_eq!(db.clear_loose_props(2).await.unwrap(), 1);
+        assert_eq!(db.clear_loose_props(2).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_hotspot_persistence() {
+        let test_db = TestDatabase::new("hotspots").await;
+        let db = &test_db.db;
+
+        let id = db.next_hotspot_id(1).await.unwrap();
+        assert_eq!(id, 1);
+        let outline = [Point::new(0, 0), Point::new(50, 0), Point::new(25, 40)];
+        db.create_hotspot(1, id, HotspotType::LockableDoor, &outline)
+            .await
+            .unwrap();
+        assert_eq!(db.next_hotspot_id(1).await.unwrap(), 2);
+
+        assert!(db.move_hotspot(1, id, Point::new(100, 120)).await.unwrap());
+        assert!(
+            db.set_hotspot_state(1, id, HotspotState::Locked.as_i16())
+                .await
+                .unwrap()
+        );
+        assert!(!db.move_hotspot(1, 99, Point::new(0, 0)).await.unwrap());
+
+        let hotspots = db.load_room_hotspots(1).await.unwrap();
+        assert_eq!(hotspots.len(), 1);
+        let spot = &hotspots[0];
+        assert_eq!(spot.hotspot.id, id as i64);
+        assert_eq!(
+            spot.hotspot.r#type,
+            HotspotType::LockableDoor.as_i16() as i64
+        );
+        assert_eq!((spot.hotspot.loc_h, spot.hotspot.loc_v), (100, 120));
+        assert_eq!(spot.hotspot.state, HotspotState::Locked.as_i16() as i64);
+        assert_eq!(
+            spot.points,
+            [
+                Point::new(100, 120),
+                Point::new(150, 120),
+                Point::new(125, 160)
+            ]
+        );
+
+        assert!(db.delete_hotspot(1, id).await.unwrap());
+        assert!(db.load_room_hotspots(1).await.unwrap().is_empty());
+        let points: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM hotspot_points")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(points, 0);
+    }
+
+    #[tokio::test]
+    async fn test_import_room_decl() {
+        let test_db = TestDatabase::new("import-room").await;
+        let db = &test_db.db;
+
+        let source = r#"
+            ROOM
+              ID 10
+              NAME "Library"
+              PICT "library.png"
+              PRIVATE
+              DOOR
+                ID 1
+                DEST 2
+                NAME "Exit"
+                OUTLINE 10,10 50,10 50,200 10,200
+                PICTS 5,0,0 6,0,0 ENDPICTS
+                SCRIPT
+                  ON SELECT { "Leaving" SAY }
+                ENDSCRIPT
+              ENDDOOR
+            ENDROOM
+        "#;
+        let mut decls = RoomScriptParser::new(source).unwrap().parse().unwrap();
+        assert!(db.import_room_decl(&decls[0], false).await.unwrap());
+
+        let room = db.get_room(10).await.unwrap().unwrap();
+        assert_eq!(room.name, "Library");
+        assert_eq!(room.background_image.as_deref(), Some("library.png"));
+        assert_eq!(room.flags, RoomFlags::PRIVATE.bits() as i64);
+
+        let hotspots = db.load_room_hotspots(10).await.unwrap();
+        assert_eq!(hotspots.len(), 1);
+        let door = &hotspots[0];
+        assert_eq!(door.hotspot.id, 1);
+        assert_eq!(door.hotspot.name.as_deref(), Some("Exit"));
+        assert_eq!(door.hotspot.r#type, HotspotType::Door.as_i16() as i64);
+        assert_eq!(door.hotspot.dest_room_id, Some(2));
+        assert_eq!((door.hotspot.loc_h, door.hotspot.loc_v), (10, 10));
+        assert_eq!(
+            door.hotspot.script_event_mask,
+            i32::from(EventMask::SELECT) as i64
+        );
+        assert!(door.hotspot.script_text.as_deref().unwrap().contains("SAY"));
+        assert_eq!(door.points.len(), 4);
+        assert_eq!(door.hotspot.nbr_states, 2);
+
+        // Re-importing leaves the room and its runtime edits alone
+        assert!(db.move_hotspot(10, 1, Point::new(20, 30)).await.unwrap());
+        decls[0].name = Some("Reading Room".to_string());
+        assert!(!db.import_room_decl(&decls[0], false).await.unwrap());
+        let rooms = db.get_all_rooms().await.unwrap();
+        assert_eq!(rooms.iter().filter(|r| r.room_id == 10).count(), 1);
+        assert_eq!(db.get_room(10).await.unwrap().unwrap().name, "Library");
+        let hotspots = db.load_room_hotspots(10).await.unwrap();
+        assert_eq!(hotspots.len(), 1);
+        assert_eq!(hotspots[0].points[0], Point::new(20, 30));
+        let points: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM hotspot_points")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(points, 4);
+
+        // Unless told to overwrite it with the declaration
+        decls[0].doors[0].outline.truncate(3);
+        assert!(db.import_room_decl(&decls[0], true).await.unwrap());
+        assert_eq!(db.get_room(10).await.unwrap().unwrap().name, "Reading Room");
+        let hotspots = db.load_room_hotspots(10).await.unwrap();
+        assert_eq!(hotspots.len(), 1);
+        assert_eq!(hotspots[0].points, decls[0].doors[0].outline);
+        let points: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM hotspot_points")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(points, 3);
+    }
+}
@@ -0,0 +1,40 @@
+//! Shared fixtures for database tests
+
+use std::path::PathBuf;
+
+use super::{Database, DatabaseOptions};
+
+/// Database with the default schema in a throwaway directory, removed on drop
+pub struct TestDatabase {
+    pub db: Database,
+    pub dir: PathBuf,
+}
+
+impl TestDatabase {
+    /// Create a database with the default schema and rooms
+    pub async fn new(name: &str) -> Self {
+        let test_db = Self::empty(name).await;
+        test_db.db.init_schema().await.unwrap();
+        test_db
+    }
+
+    /// Create a database with no schema at all
+    pub async fn empty(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("palace-{}-test-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = format!("sqlite:{}", dir.join("palace.db").display());
+        let db = Database::with_options(&path, DatabaseOptions::default())
+            .await
+            .unwrap();
+
+        Self { db, dir }
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
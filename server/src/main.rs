@@ -2,43 +2,51 @@
 
 mod config;
 mod db;
+mod logging;
 mod net;
 mod state;
 
 use anyhow::{Context, Result};
 use config::Config;
 use db::Database;
-use net::handler::ConnectionHandler;
+use net::dispatch::DefaultMessageHandler;
+use net::handler::{ConnectionHandler, ConnectionLimits};
 use state::ServerState;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use thepalace::decode_chat_text;
+use thepalace::iptscrae::RoomScriptParser;
 use tokio::net::TcpListener;
 use tracing::{error, info};
-use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Load configuration from file if it exists, otherwise use defaults.
+    // This comes first since it decides how to log.
+    let config_file = Path::new("palace.json");
+    let config = if config_file.exists() {
+        Config::from_file(config_file)?
+    } else {
+        Config::default()
+    };
+
     // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .init();
+    tracing::subscriber::set_global_default(logging::subscriber(&config.logging, std::io::stdout))
+        .context("Failed to initialize logging")?;
 
     info!("Palace Server starting...");
-
-    // Load configuration from file if it exists, otherwise use defaults
-    let config = if std::path::Path::new("palace.json").exists() {
-        info!("Loading configuration from palace.json");
-        Config::from_file("palace.json")?
+    if config_file.exists() {
+        info!("Loaded configuration from palace.json");
     } else {
         info!("Using default configuration (palace.json not found)");
-        Config::default()
-    };
+    }
     
     info!("Server configuration: {:?}", config);
 
     // Connect to database
     let db_url = format!("sqlite:{}", config.database.path);
-    let db = Database::new(&db_url)
+    let db = Database::with_options(&db_url, config.database_options())
         .await
         .context("Failed to connect to database")?;
 
@@ -47,19 +55,50 @@ async fn main() -> Result<()> {
         .await
         .context("Failed to initialize database schema")?;
 
-    // Initialize server state
-    let state = ServerState::new(db);
+    // Seed rooms from server scripts, if any
+    import_room_scripts(&db, Path::new("rooms"), config.database.overwrite_rooms).await?;
+
+    // Open asset storage, creating the directory if needed
+    let assets = config.asset_store()?;
+    info!("Asset store at {}", assets.root().display());
+
+    // Initialize server state. To override how specific messages are
+    // handled, pass your own MessageHandler to with_message_handler.
+    let state = ServerState::new(db, assets)
+        .with_guest_name_prefix(&config.security.guest_name_prefix)
+        .with_max_loose_props(config.security.max_loose_props)
+        .with_message_handler(Arc::new(DefaultMessageHandler));
     info!("Server state initialized");
 
-    // Bind TCP listener
-    let bind_addr = config.bind_addr()?;
-    let listener = TcpListener::bind(&bind_addr)
-        .await
-        .context("Failed to bind TCP listener")?;
+    // Bind every TCP listener before accepting on any of them
+    let mut listeners = Vec::new();
+    for bind_addr in config.bind_addrs()? {
+        let listener = TcpListener::bind(&bind_addr)
+            .await
+            .with_context(|| format!("Failed to bind TCP listener on {}", bind_addr))?;
+        info!("Listening on {}", bind_addr);
+        listeners.push(listener);
+    }
 
-    info!("Listening on {}", bind_addr);
+    // Bind WebSocket listener for browser clients
+    let limits = config.connection_limits();
+    if let Some(ws_addr) = config.ws_bind_addr()? {
+        serve_ws(ws_addr, state.clone(), limits).await?;
+    }
+
+    // Accept connections on every listener, all sharing one server state
+    let accept_loops: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| tokio::spawn(accept_loop(listener, state.clone(), limits)))
+        .collect();
+    for accept_loop in accept_loops {
+        accept_loop.await.context("Accept loop panicked")?;
+    }
+    Ok(())
+}
 
-    // Accept connections
+/// Accept TCP connections forever, spawning a handler task for each
+async fn accept_loop(listener: TcpListener, state: ServerState, limits: ConnectionLimits) {
     loop {
         match listener.accept().await {
             Ok((socket, addr)) => {
@@ -68,7 +107,8 @@ async fn main() -> Result<()> {
 
                 // Spawn a task for this connection
                 tokio::spawn(async move {
-                    let handler = ConnectionHandler::new(socket, addr, state);
+                    let handler = ConnectionHandler::new(socket, addr, state)
+                        .with_limits(limits);
                     if let Err(e) = handler.handle().await {
                         error!("Connection error from {}: {}", addr, e);
                     }
@@ -81,3 +121,82 @@ async fn main() -> Result<()> {
         }
     }
 }
+
+/// Import every `*.ipt` room script in `dir` into the database
+///
+/// A missing directory is skipped. Rooms already in the database are kept as
+/// they are, so edits made while the server ran survive a restart, unless
+/// `overwrite` is set (`database.overwrite_rooms`), in which case the scripts
+/// replace them.
+async fn import_room_scripts(db: &Database, dir: &Path, overwrite: bool) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read room script directory {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("ipt"))
+        })
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        // Classic server scripts are often Mac Roman, so decode like chat text
+        let bytes =
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let source = decode_chat_text(&bytes);
+        let decls = RoomScriptParser::new(&source)
+            .and_then(|mut parser| parser.parse())
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        let mut imported = 0;
+        for decl in &decls {
+            if db
+                .import_room_decl(decl, overwrite)
+                .await
+                .with_context(|| format!("Failed to import {}", path.display()))?
+            {
+                imported += 1;
+            }
+        }
+        info!(
+            "Imported {} of {} room(s) from {}",
+            imported,
+            decls.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Bind the WebSocket listener and accept connections on a background task
+#[cfg(feature = "ws")]
+async fn serve_ws(
+    ws_addr: std::net::SocketAddr,
+    state: ServerState,
+    limits: ConnectionLimits,
+) -> Result<()> {
+    let listener = TcpListener::bind(&ws_addr)
+        .await
+        .context("Failed to bind WebSocket listener")?;
+    info!("Listening for WebSocket clients on {}", ws_addr);
+
+    tokio::spawn(net::ws::serve(listener, state, limits));
+    Ok(())
+}
+
+#[cfg(not(feature = "ws"))]
+async fn serve_ws(
+    ws_addr: std::net::SocketAddr,
+    _state: ServerState,
+    _limits: ConnectionLimits,
+) -> Result<()> {
+    tracing::warn!(
+        "ws_port is set ({}) but the server was built without the `ws` feature",
+        ws_addr
+    );
+    Ok(())
+}
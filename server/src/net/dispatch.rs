@@ -0,0 +1,41 @@
+//! Pluggable message handling
+//!
+//! [`ConnectionHandler`](super::handler::ConnectionHandler) offers every
+//! incoming message to a [`MessageHandler`] before running its built-in
+//! behavior, so server operators can override individual messages without
+//! touching the connection logic.
+
+use std::net::SocketAddr;
+use thepalace::messages::Message;
+
+use crate::state::{RoomId, UserId};
+
+/// Connection details passed to a [`MessageHandler`]
+#[derive(Debug, Clone, Copy)]
+pub struct HandlerContext {
+    /// Client address
+    pub addr: SocketAddr,
+    /// Session UserID, once logged on
+    pub user_id: Option<UserId>,
+    /// Room the client is currently in
+    pub current_room: RoomId,
+}
+
+/// Hook for overriding how incoming messages are handled
+pub trait MessageHandler: Send + Sync {
+    /// Handle an incoming message
+    ///
+    /// Return `Some(replies)` to send `replies` to the client and skip the
+    /// built-in handling, or `None` to fall through to it.
+    fn handle(&self, message: &Message, ctx: &HandlerContext) -> Option<Vec<Message>>;
+}
+
+/// Handler that keeps the built-in behavior for every message
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultMessageHandler;
+
+impl MessageHandler for DefaultMessageHandler {
+    fn handle(&self, _message: &Message, _ctx: &HandlerContext) -> Option<Vec<Message>> {
+        None
+    }
+}
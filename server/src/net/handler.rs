@@ -1,61 +1,151 @@
 //! Connection handler for individual client sessions
 
 use anyhow::{Context, Result};
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, BufMut, BytesMut};
 use std::net::SocketAddr;
+use std::sync::Arc;
+use thepalace::iptscrae::{EventType, ScriptContext, SecurityLevel, Vm};
 use thepalace::messages::auth::{LogonMsg, TiyidMsg};
 use thepalace::messages::chat::{TalkMsg, XTalkMsg, XWhisperMsg};
-use thepalace::messages::flags::RoomFlags;
 use thepalace::messages::{
-    ListOfAllRoomsMsg, Message, MessageId, MessagePayload, RoomDescMsg, RoomGotoMsg, RoomListRec,
-    ServerInfoMsg, UserListMsg, UserNewMsg,
+    AssetQueryMsg, AssetSendMsg, DoorLockMsg, DoorUnlockMsg, Hotspot, LPropRec, ListOfAllRoomsMsg,
+    Message, MessageHeader, MessageId, MessagePayload, PropDelMsg, PropNewMsg, RoomDescMsg,
+    RoomGotoMsg, ServerInfoMsg, ServerStatusMsg, SpotDelMsg, SpotMoveMsg, SpotStateMsg,
+    UserListMsg, UserMoveMsg, UserNewMsg, UserPropMsg, UserRec, UserStatusMsg,
 };
-use thepalace::{AssetSpec, Point};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use thepalace::room::{default_spot_outline, HotspotState, HotspotType};
+use thepalace::{AssetSpec, AssetType, BufMutExt, EventMask, Point};
 use tokio::sync::mpsc;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, field, info, info_span, warn, Instrument, Span};
+
+use crate::db::models::{Hotspot as DbHotspot, RoomHotspot};
+use crate::net::dispatch::{HandlerContext, MessageHandler};
+use crate::net::send_queue::{run_writer, SendPolicy, SendQueue, DEFAULT_SEND_QUEUE_SIZE};
+use crate::net::transport::{Transport, TransportRead};
+use crate::state::{
+    should_broadcast, RoomId, ServerMessage, ServerState, SessionSender, UserId, MIN_MOVE_DELTA,
+};
+
+/// Per-connection limits, usually taken from the server config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionLimits {
+    /// Largest payload accepted from the client
+    pub max_message_size: usize,
+    /// Messages, and broadcasts, queued for the client before `send_policy`
+    /// applies
+    pub send_queue_size: usize,
+    /// What to do with a message for a client whose queue is full
+    pub send_policy: SendPolicy,
+}
 
-use crate::state::{RoomId, ServerMessage, ServerState, UserId};
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_message_size: MessageHeader::MAX_LENGTH,
+            send_queue_size: DEFAULT_SEND_QUEUE_SIZE,
+            send_policy: SendPolicy::default(),
+        }
+    }
+}
 
 /// Connection handler for a single client
-pub struct ConnectionHandler {
-    socket: TcpStream,
+///
+/// Generic over the [`Transport`] so TCP and WebSocket clients share the same
+/// message handling. Outgoing messages go through a bounded [`SendQueue`]
+/// drained by a separate writer task.
+pub struct ConnectionHandler<T: Transport> {
+    reader: T::Reader,
+    send_queue: SendQueue,
+    /// Writer half and queue receiver, until `run` hands them to the writer task
+    pending_writer: Option<(T::Writer, mpsc::Receiver<bytes::Bytes>)>,
     addr: SocketAddr,
     state: ServerState,
     user_id: Option<UserId>,
     username: Option<String>,
     current_room: RoomId,
+    /// Position last relayed to the room, if any since entering it
+    last_move: Option<Point>,
     read_buffer: BytesMut,
-    message_rx: mpsc::UnboundedReceiver<ServerMessage>,
-    message_tx: mpsc::UnboundedSender<ServerMessage>,
+    limits: ConnectionLimits,
+    message_rx: mpsc::Receiver<ServerMessage>,
+    /// Sending end of `message_rx`, until logon hands it to the session
+    message_tx: Option<SessionSender>,
+    message_handler: Arc<dyn MessageHandler>,
+    /// Runs spot scripts for this session; globals they set last until logoff
+    script_vm: Vm,
 }
 
-impl ConnectionHandler {
+impl<T: Transport> ConnectionHandler<T> {
     /// Create a new connection handler
-    pub fn new(socket: TcpStream, addr: SocketAddr, state: ServerState) -> Self {
-        let (message_tx, message_rx) = mpsc::unbounded_channel();
+    pub fn new(transport: T, addr: SocketAddr, state: ServerState) -> Self {
+        let (reader, writer) = transport.split();
+        let limits = ConnectionLimits::default();
+        let (send_queue, queue_rx) = SendQueue::new(limits.send_queue_size, limits.send_policy);
+        let (message_tx, message_rx) =
+            SessionSender::channel(limits.send_queue_size, limits.send_policy);
+        let message_handler = state.message_handler();
 
         Self {
-            socket,
+            reader,
+            send_queue,
+            pending_writer: Some((writer, queue_rx)),
             addr,
             state,
             user_id: None,
             username: None,
             current_room: 0, // Start in Gate
+            last_move: None,
             read_buffer: BytesMut::with_capacity(8192),
+            limits,
             message_rx,
-            message_tx,
+            message_tx: Some(message_tx),
+            message_handler,
+            script_vm: Vm::new(),
         }
     }
 
+    /// Apply `limits` to this connection
+    pub fn with_limits(mut self, limits: ConnectionLimits) -> Self {
+        if let Some((writer, _)) = self.pending_writer.take() {
+            let (send_queue, queue_rx) = SendQueue::new(limits.send_queue_size, limits.send_policy);
+            self.send_queue = send_queue;
+            self.pending_writer = Some((writer, queue_rx));
+        }
+        if self.message_tx.is_some() {
+            let (message_tx, message_rx) =
+                SessionSender::channel(limits.send_queue_size, limits.send_policy);
+            self.message_tx = Some(message_tx);
+            self.message_rx = message_rx;
+        }
+        self.limits = limits;
+        self
+    }
+
     /// Handle the connection (public entry point)
+    ///
+    /// Everything the connection logs is inside a `connection` span with the
+    /// peer address, and the session's `user_id` once the user has logged on.
     pub async fn handle(self) -> Result<()> {
-        self.run().await
+        let span = info_span!("connection", peer = %self.addr, user_id = field::Empty);
+        self.run().instrument(span).await
     }
 
     /// Run the connection handler
     async fn run(mut self) -> Result<()> {
+        let (writer, queue_rx) = self
+            .pending_writer
+            .take()
+            .context("Connection handler already ran")?;
+        let addr = self.addr;
+        let writer_task = tokio::spawn(
+            async move {
+                if let Err(e) = run_writer(writer, queue_rx).await {
+                    error!("Write error to {}: {}", addr, e);
+                }
+            }
+            .in_current_span(),
+        );
+
         // Send initial TIYID message for endianness detection
         self.send_tiyid().await?;
 
@@ -63,7 +153,7 @@ impl ConnectionHandler {
         loop {
             tokio::select! {
                 // Read from socket
-                result = self.socket.read_buf(&mut self.read_buffer) => {
+                result = self.reader.read_into(&mut self.read_buffer) => {
                     match result {
                         Ok(0) => {
                             info!("Client {} disconnected", self.addr);
@@ -81,17 +171,34 @@ impl ConnectionHandler {
                 }
 
                 // Receive broadcast messages
-                Some(msg) = self.message_rx.recv() => {
-                    self.handle_server_message(msg).await?;
-                }
+                msg = self.message_rx.recv() => match msg {
+                    Some(msg) => self.handle_server_message(msg).await?,
+                    // The session was dropped for falling behind
+                    None => {
+                        warn!("Session for {} closed by the server", self.addr);
+                        break;
+                    }
+                },
             }
         }
 
         // Cleanup on disconnect
         if let Some(user_id) = self.user_id {
+            // Spot scripts see the user leave the room, then the session end
+            for event in [EventType::Leave, EventType::SignOff] {
+                if let Err(e) = self.fire_room_event(event).await {
+                    warn!(
+                        "Failed to run {:?} scripts for user {}: {:#}",
+                        event, user_id, e
+                    );
+                }
+            }
             self.state.unregister_session(user_id).await;
         }
 
+        // Closing the queue lets the writer flush what's left and stop
+        drop(self);
+        let _ = writer_task.await;
         Ok(())
     }
 
@@ -111,7 +218,8 @@ impl ConnectionHandler {
 
             // Try to parse a message (peek without consuming)
             let mut peek_buf = &self.read_buffer[..];
-            let message = match Message::parse(&mut peek_buf) {
+            let max_size = self.limits.max_message_size;
+            let message = match Message::parse_with_limit(&mut peek_buf, max_size) {
                 Ok(msg) => {
                     // Successfully parsed, now consume from read_buffer
                     let total_size = Message::HEADER_SIZE + msg.payload.len();
@@ -129,6 +237,11 @@ impl ConnectionHandler {
 
             debug!("Received message: {:?}", message.msg_id);
             self.handle_message(message).await?;
+
+            // Keep up with broadcasts, including our own, through a burst
+            while let Ok(msg) = self.message_rx.try_recv() {
+                self.handle_server_message(msg).await?;
+            }
         }
 
         Ok(())
@@ -136,13 +249,43 @@ impl ConnectionHandler {
 
     /// Handle a single incoming message
     async fn handle_message(&mut self, message: Message) -> Result<()> {
+        let ctx = HandlerContext {
+            addr: self.addr,
+            user_id: self.user_id,
+            current_room: self.current_room,
+        };
+        if let Some(replies) = self.message_handler.handle(&message, &ctx) {
+            debug!(
+                "Message {:?} from {} (user {:?}, room {}) handled by override",
+                message.msg_id, ctx.addr, ctx.user_id, ctx.current_room
+            );
+            for reply in &replies {
+                self.send_message(reply).await?;
+            }
+            return Ok(());
+        }
+
         match message.msg_id {
             MessageId::Logon => self.handle_logon(message).await?,
             MessageId::Talk => self.handle_talk(message).await?,
             MessageId::XTalk => self.handle_xtalk(message).await?,
             MessageId::XWhisper => self.handle_whisper(message).await?,
+            MessageId::UserMove => self.handle_user_move(message).await?,
             MessageId::RoomGoto => self.handle_room_goto(message).await?,
             MessageId::ListOfAllRooms => self.handle_list_rooms(message).await?,
+            MessageId::PropNew => self.handle_prop_new(message).await?,
+            MessageId::PropDel => self.handle_prop_del(message).await?,
+            MessageId::AssetQuery => self.handle_asset_query(message).await?,
+            MessageId::AssetRegi => self.handle_asset_regi(message).await?,
+            MessageId::SpotNew => self.handle_spot_new().await?,
+            MessageId::SpotDel => self.handle_spot_del(message).await?,
+            MessageId::SpotMove => self.handle_spot_move(message).await?,
+            MessageId::SpotState => self.handle_spot_state(message).await?,
+            MessageId::DoorLock => self.handle_door_lock(message).await?,
+            MessageId::DoorUnlock => self.handle_door_unlock(message).await?,
+            MessageId::UserStatus => self.handle_user_status(message).await?,
+            MessageId::UserProp => self.handle_user_prop(message).await?,
+            MessageId::ServerStatus => self.handle_server_status().await?,
             MessageId::Ping => self.handle_ping(message).await?,
             MessageId::Pong => { /* Ignore pong */ }
             _ => {
@@ -155,11 +298,18 @@ impl ConnectionHandler {
 
     /// Handle logon message
     async fn handle_logon(&mut self, message: Message) -> Result<()> {
+        if self.user_id.is_some() {
+            warn!("Ignoring repeated logon from {}", self.addr);
+            return Ok(());
+        }
         let logon = message
             .parse_payload::<LogonMsg>()
             .context("Failed to parse logon message")?;
 
-        let username = logon.rec.user_name.clone();
+        let mut username = logon.rec.user_name.trim().to_string();
+        if username.is_empty() {
+            username = self.state.next_guest_name().await;
+        }
         info!("User '{}' logging in from {}", username, self.addr);
 
         // Check if IP is banned
@@ -189,20 +339,40 @@ impl ConnectionHandler {
             }
         };
 
-        let user_id = user.user_id;
+        // Session UserIDs are allocated separately from database IDs so a
+        // reconnecting user never reuses an ID clients may still display
+        let user_id = self.state.allocate_user_id().await?;
+        let flags = user.logon_flags(&logon.rec.wiz_password);
+        if user.user_flags().is_wizard() && !flags.is_wizard() {
+            warn!(
+                "User '{}' logged on without a valid wizard password",
+                username
+            );
+        }
+        debug!(
+            "User {} (db id {}) flags: {:?}",
+            user_id, user.user_id, flags
+        );
         self.user_id = Some(user_id);
         self.username = Some(username.clone());
-
-        // Register session in state
+        Span::current().record("user_id", user_id);
+
+        // Register session in state; it holds the only sender from here on,
+        // so the session being dropped ends the connection
+        let message_tx = self
+            .message_tx
+            .take()
+            .context("Session channel already registered")?;
         self.state
             .register_session(
                 user_id,
                 username.clone(),
                 self.current_room,
                 self.addr,
-                self.message_tx.clone(),
+                message_tx,
             )
             .await;
+        self.state.set_user_flags(user_id, flags).await;
 
         // Send server info
         self.send_server_info(user_id).await?;
@@ -216,9 +386,68 @@ impl ConnectionHandler {
         // Notify other users
         self.broadcast_user_joined().await?;
 
+        // SIGNON runs once per session, before the first ENTER
+        self.fire_room_event(EventType::SignOn).await?;
+        self.fire_room_event(EventType::Enter).await?;
+
         Ok(())
     }
 
+    /// Run the current room's spot scripts for `event` and carry out their actions
+    ///
+    /// Only spots whose event mask includes `event` are run, in this
+    /// session's VM. A failing script is logged and doesn't stop the others.
+    async fn fire_room_event(&mut self, event: EventType) -> Result<()> {
+        let Some(user_id) = self.user_id else {
+            return Ok(());
+        };
+
+        let spots = self
+            .state
+            .db()
+            .load_room_hotspots(self.current_room)
+            .await?;
+        let scripts: Vec<_> = spots
+            .iter()
+            .filter(|spot| {
+                EventMask::from(spot.hotspot.script_event_mask as i32).contains(event.to_mask())
+            })
+            .filter_map(|spot| {
+                let text = spot.hotspot.script_text.as_deref()?;
+                self.state
+                    .scripts()
+                    .get_or_parse(text)
+                    .inspect_err(|e| warn!("Spot {} script doesn't parse: {}", spot.hotspot.id, e))
+                    .ok()
+            })
+            .collect();
+        if scripts.is_empty() {
+            return Ok(());
+        }
+
+        let user_props = self.state.user_props(user_id).await.unwrap_or_default();
+        let mut actions = Vec::new();
+        {
+            let mut context = ScriptContext::builder(SecurityLevel::Server, &mut actions)
+                .user_id(user_id as i32)
+                .user_name(self.username.clone().unwrap_or_default())
+                .user_props(user_props)
+                .room_id(self.current_room)
+                .build();
+            let errors = self.script_vm.fire_events(
+                scripts.iter().map(|script| &**script),
+                event,
+                &mut context,
+            );
+            for error in errors {
+                warn!("{:?} script failed for user {}: {}", event, user_id, error);
+            }
+        }
+        self.state
+            .apply_script_actions(user_id, self.current_room, actions)
+            .await
+    }
+
     /// Handle talk (chat) message
     async fn handle_talk(&mut self, message: Message) -> Result<()> {
         let talk = message
@@ -226,6 +455,11 @@ impl ConnectionHandler {
             .context("Failed to parse talk message")?;
 
         if let Some(user_id) = self.user_id {
+            if self.is_gagged(user_id).await {
+                debug!("Dropping chat from gagged user {}", user_id);
+                return Ok(());
+            }
+
             info!("User {} says: {}", user_id, talk.text);
 
             // Broadcast to room
@@ -237,7 +471,7 @@ impl ConnectionHandler {
             };
 
             self.state
-                .broadcast_to_room(self.current_room, broadcast_msg)
+                .broadcast_to_room(self.current_room, broadcast_msg, None)
                 .await;
         }
 
@@ -256,6 +490,11 @@ impl ConnectionHandler {
             .context("Failed to decrypt xtalk message")?;
 
         if let Some(user_id) = self.user_id {
+            if self.is_gagged(user_id).await {
+                debug!("Dropping chat from gagged user {}", user_id);
+                return Ok(());
+            }
+
             info!("User {} says (extended): {}", user_id, text);
 
             // Broadcast to room (send encrypted bytes)
@@ -267,7 +506,7 @@ impl ConnectionHandler {
             };
 
             self.state
-                .broadcast_to_room(self.current_room, broadcast_msg)
+                .broadcast_to_room(self.current_room, broadcast_msg, None)
                 .await;
         }
 
@@ -286,6 +525,11 @@ impl ConnectionHandler {
             .context("Failed to decrypt whisper message")?;
 
         if let Some(from_user_id) = self.user_id {
+            if self.is_gagged(from_user_id).await {
+                debug!("Dropping whisper from gagged user {}", from_user_id);
+                return Ok(());
+            }
+
             let target_user_id = whisper.target as UserId;
             info!(
                 "User {} whispers to {}: {}",
@@ -300,6 +544,95 @@ impl ConnectionHandler {
         Ok(())
     }
 
+    /// Whether chat from `user_id` should be dropped
+    async fn is_gagged(&self, user_id: UserId) -> bool {
+        self.state
+            .user_flags(user_id)
+            .await
+            .is_some_and(|flags| flags.is_gagged())
+    }
+
+    /// Handle a wizard gagging or ungagging another user
+    ///
+    /// The protocol has no dedicated gag message, so wizards send UserStatus
+    /// with the target in refNum. Only the GAG bit of the payload is applied;
+    /// requests from non-wizards are ignored.
+    async fn handle_user_status(&mut self, message: Message) -> Result<()> {
+        let status = message
+            .parse_payload::<UserStatusMsg>()
+            .context("Failed to parse user status message")?;
+
+        if let Some(user_id) = self.user_id {
+            let is_wizard = self
+                .state
+                .user_flags(user_id)
+                .await
+                .is_some_and(|flags| flags.is_wizard());
+            if !is_wizard {
+                warn!("Non-wizard user {} tried to change user flags", user_id);
+                return Ok(());
+            }
+
+            let target_user_id = message.ref_num as UserId;
+            let gagged = status.flags.is_gagged();
+            if let Some(flags) = self.state.set_gagged(target_user_id, gagged).await {
+                info!(
+                    "User {} {} user {}",
+                    user_id,
+                    if gagged { "gagged" } else { "ungagged" },
+                    target_user_id
+                );
+                self.state
+                    .send_to_user(target_user_id, ServerMessage::UserStatus { flags })
+                    .await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle the client changing its props and relay it to the room
+    async fn handle_user_prop(&mut self, message: Message) -> Result<()> {
+        let user_prop = message
+            .parse_payload::<UserPropMsg>()
+            .context("Failed to parse user prop message")?;
+
+        if let Some(user_id) = self.user_id {
+            self.state
+                .set_user_props(user_id, user_prop.props, Some(user_id))
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Handle a user moving within the current room
+    async fn handle_user_move(&mut self, message: Message) -> Result<()> {
+        let user_move = message
+            .parse_payload::<UserMoveMsg>()
+            .context("Failed to parse user move message")?;
+
+        if let Some(user_id) = self.user_id {
+            // Anything counts as a move until the first one in this room
+            let relay = self
+                .last_move
+                .is_none_or(|last| should_broadcast(last, user_move.pos, MIN_MOVE_DELTA));
+            if relay {
+                self.last_move = Some(user_move.pos);
+                let broadcast_msg = ServerMessage::UserMoved {
+                    user_id,
+                    room_id: self.current_room,
+                    pos: user_move.pos,
+                };
+                self.state
+                    .broadcast_to_room(self.current_room, broadcast_msg, None)
+                    .await;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Handle room goto message
     async fn handle_room_goto(&mut self, message: Message) -> Result<()> {
         let goto = message
@@ -308,19 +641,27 @@ impl ConnectionHandler {
 
         if let Some(user_id) = self.user_id {
             let new_room = goto.dest;
+            if !self.state.room_exists(new_room).await {
+                warn!("Room {} not found", new_room);
+                return Ok(());
+            }
             info!("User {} moving to room {}", user_id, new_room);
 
             // Move user to new room
             if self.state.move_user_to_room(user_id, new_room).await {
+                // LEAVE runs in the old room once the move is committed
+                self.fire_room_event(EventType::Leave).await?;
+
                 let old_room = self.current_room;
                 self.current_room = new_room;
+                self.last_move = None;
 
                 // Notify users in old room
                 let left_msg = ServerMessage::UserLeft {
                     user_id,
                     room_id: old_room,
                 };
-                self.state.broadcast_to_room(old_room, left_msg).await;
+                self.state.broadcast_to_room(old_room, left_msg, None).await;
 
                 // Send new room description
                 self.send_room_description().await?;
@@ -330,8 +671,8 @@ impl ConnectionHandler {
 
                 // Notify users in new room
                 self.broadcast_user_joined().await?;
-            } else {
-                warn!("Room {} not found", new_room);
+
+                self.fire_room_event(EventType::Enter).await?;
             }
         }
 
@@ -339,24 +680,13 @@ impl ConnectionHandler {
     }
 
     /// Handle list rooms request
+    ///
+    /// Hidden rooms are only listed for wizards.
     async fn handle_list_rooms(&mut self, _message: Message) -> Result<()> {
-        // Get rooms from database
-        let rooms = self.state.db().get_all_rooms().await?;
-
-        // Create room list message with current user counts
-        let mut room_list_recs = Vec::new();
-        for room in rooms {
-            let user_count = self.state.get_room_user_count(room.room_id as i16).await;
-            room_list_recs.push(RoomListRec {
-                room_id: room.room_id as i32,
-                flags: RoomFlags::from_bits_truncate(room.flags as u16),
-                nbr_users: user_count,
-                name: room.name,
-            });
-        }
+        let is_wizard = self.is_wizard().await;
 
         let room_list = ListOfAllRoomsMsg {
-            rooms: room_list_recs,
+            rooms: self.state.room_list(is_wizard).await?,
         };
 
         let msg = room_list.to_message_default();
@@ -365,6 +695,384 @@ impl ConnectionHandler {
         Ok(())
     }
 
+    /// Handle a server status request
+    ///
+    /// Only wizards get an answer; other requests are ignored.
+    async fn handle_server_status(&mut self) -> Result<()> {
+        if !self.is_wizard().await {
+            warn!(
+                "Non-wizard connection {} requested server status",
+                self.addr
+            );
+            return Ok(());
+        }
+
+        let status = ServerStatusMsg {
+            user_count: self.state.get_total_users().await as u32,
+            room_count: self.state.get_populated_rooms().await as u32,
+            uptime_secs: self.state.uptime().as_secs() as u32,
+        };
+        let msg = status.to_message_default();
+        self.send_message(&msg).await?;
+
+        Ok(())
+    }
+
+    /// Handle a new loose prop dropped in the current room
+    async fn handle_prop_new(&mut self, message: Message) -> Result<()> {
+        let prop_new = message
+            .parse_payload::<PropNewMsg>()
+            .context("Failed to parse prop new message")?;
+
+        if self.user_id.is_some() {
+            let added = self
+                .state
+                .add_loose_prop(self.current_room, prop_new.prop_spec, prop_new.pos)
+                .await?;
+            if added {
+                debug!(
+                    "Added loose prop {} to room {}",
+                    prop_new.prop_spec.id, self.current_room
+                );
+            } else {
+                warn!(
+                    "Room {} is full of loose props, dropping prop {}",
+                    self.current_room, prop_new.prop_spec.id
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle deletion of a loose prop (or all of them) in the current room
+    async fn handle_prop_del(&mut self, message: Message) -> Result<()> {
+        let prop_del = message
+            .parse_payload::<PropDelMsg>()
+            .context("Failed to parse prop delete message")?;
+
+        if self.user_id.is_some() {
+            let removed = self
+                .state
+                .remove_loose_prop(self.current_room, prop_del.prop_num)
+                .await?;
+            if !removed {
+                warn!(
+                    "No loose prop {} in room {}",
+                    prop_del.prop_num, self.current_room
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle a client's request for an asset
+    ///
+    /// Props in the asset store are sent back in a single block. A query
+    /// with a don't-care CRC of 0 is looked up by asset ID instead. Anything
+    /// the server can't send, other asset types included, is answered with
+    /// FileNotFnd so the client doesn't wait for it.
+    async fn handle_asset_query(&mut self, message: Message) -> Result<()> {
+        let query = message
+            .parse_payload::<AssetQueryMsg>()
+            .context("Failed to parse asset query")?;
+
+        if self.user_id.is_none() {
+            return Ok(());
+        }
+
+        // Named as the AssetSend would be, or by ID if the CRC isn't known
+        let spec = query.spec;
+        let missing = if spec.crc == 0 {
+            format!("{:08X}", spec.id)
+        } else {
+            format!("{:08X}", spec.crc)
+        };
+        if query.asset_type != AssetType::Prop {
+            debug!("No {:?} assets to send", query.asset_type);
+            return self.send_file_not_found(&missing).await;
+        }
+
+        let crc = if spec.crc == 0 {
+            match self.state.db().get_prop_by_asset_id(spec.id).await? {
+                Some(prop) => prop.crc32 as u32,
+                None => {
+                    debug!("No prop with asset ID {}", spec.id);
+                    return self.send_file_not_found(&missing).await;
+                }
+            }
+        } else {
+            spec.crc
+        };
+
+        let data = match self.state.assets().load(AssetType::Prop, crc) {
+            Ok(data) => data,
+            Err(e) => {
+                debug!("No prop with crc 0x{:08X}: {}", crc, e);
+                return self.send_file_not_found(&missing).await;
+            }
+        };
+
+        let spec = AssetSpec::new(spec.id, crc);
+        let name = format!("{:08X}", crc);
+        let send = AssetSendMsg::single_block(AssetType::Prop, spec, name, data.into());
+        self.send_message(&send.to_message_default()).await
+    }
+
+    /// Handle a prop uploaded by the client
+    ///
+    /// Only single-block prop uploads are accepted. Props that don't decode
+    /// are answered with FileNotFnd instead of being stored.
+    async fn handle_asset_regi(&mut self, message: Message) -> Result<()> {
+        let upload = message
+            .parse_payload::<AssetSendMsg>()
+            .context("Failed to parse asset upload")?;
+
+        if self.user_id.is_none() {
+            return Ok(());
+        }
+
+        let name = upload
+            .desc
+            .as_ref()
+            .map(|desc| desc.name.clone())
+            .unwrap_or_else(|| format!("{:08X}", upload.spec.crc));
+        if upload.asset_type != AssetType::Prop || upload.nbr_blocks != 1 {
+            warn!(
+                "Ignoring {:?} upload '{}' in {} blocks",
+                upload.asset_type, name, upload.nbr_blocks
+            );
+            return Ok(());
+        }
+
+        let stored = self
+            .state
+            .store_uploaded_prop(&name, upload.spec.id, &upload.data)
+            .await;
+        if let Err(e) = stored {
+            warn!(
+                "Rejected prop upload '{}' from {}: {:#}",
+                name, self.addr, e
+            );
+            self.send_file_not_found(&name).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Tell the client the server has no asset or file called `name`
+    async fn send_file_not_found(&mut self, name: &str) -> Result<()> {
+        let mut payload = BytesMut::new();
+        payload.try_put_str63(name)?;
+        let not_found = Message::new(MessageId::FileNotFnd, 0, payload.to_vec());
+        self.send_message(&not_found).await
+    }
+
+    /// Whether the logged-on user is a wizard (or god)
+    async fn is_wizard(&self) -> bool {
+        match self.user_id {
+            Some(user_id) => self
+                .state
+                .user_flags(user_id)
+                .await
+                .is_some_and(|flags| flags.is_wizard()),
+            None => false,
+        }
+    }
+
+    /// Handle creation of a new hotspot in the current room
+    ///
+    /// Wizards only. The room's users get the updated room description.
+    async fn handle_spot_new(&mut self) -> Result<()> {
+        if !self.is_wizard().await {
+            warn!(
+                "Non-wizard connection {} tried to create a hotspot",
+                self.addr
+            );
+            return Ok(());
+        }
+
+        let db = self.state.db();
+        let id = db.next_hotspot_id(self.current_room).await?;
+
+        let outline = default_spot_outline();
+        db.create_hotspot(self.current_room, id, HotspotType::Normal, &outline)
+            .await?;
+        info!("Created hotspot {} in room {}", id, self.current_room);
+        self.broadcast_room_description().await
+    }
+
+    /// Handle deletion of a hotspot in the current room
+    ///
+    /// Wizards only. The room's users get the updated room description.
+    async fn handle_spot_del(&mut self, message: Message) -> Result<()> {
+        let spot_del = message
+            .parse_payload::<SpotDelMsg>()
+            .context("Failed to parse spot delete message")?;
+
+        if !self.is_wizard().await {
+            warn!(
+                "Non-wizard connection {} tried to delete a hotspot",
+                self.addr
+            );
+            return Ok(());
+        }
+
+        let deleted = self
+            .state
+            .db()
+            .delete_hotspot(self.current_room, spot_del.spot_id as i16)
+            .await?;
+        if !deleted {
+            warn!(
+                "No hotspot {} in room {}",
+                spot_del.spot_id, self.current_room
+            );
+            return Ok(());
+        }
+        self.broadcast_room_description().await
+    }
+
+    /// Handle a hotspot in the current room being moved
+    ///
+    /// Wizards only. The move is relayed to the room.
+    async fn handle_spot_move(&mut self, message: Message) -> Result<()> {
+        let spot_move = message
+            .parse_payload::<SpotMoveMsg>()
+            .context("Failed to parse spot move message")?;
+
+        if !self.is_wizard().await {
+            warn!(
+                "Non-wizard connection {} tried to move a hotspot",
+                self.addr
+            );
+            return Ok(());
+        }
+
+        let moved = self
+            .state
+            .db()
+            .move_hotspot(self.current_room, spot_move.spot_id as i16, spot_move.pos)
+            .await?;
+        if !moved {
+            warn!(
+                "No hotspot {} in room {}",
+                spot_move.spot_id, self.current_room
+            );
+            return Ok(());
+        }
+
+        let relay = SpotMoveMsg {
+            room_id: self.current_room,
+            ..spot_move
+        };
+        self.state
+            .broadcast_to_room(self.current_room, relay.to_message(0), None)
+            .await;
+        Ok(())
+    }
+
+    /// Handle a hotspot state change
+    async fn handle_spot_state(&mut self, message: Message) -> Result<()> {
+        let spot_state = message
+            .parse_payload::<SpotStateMsg>()
+            .context("Failed to parse spot state message")?;
+
+        // States the hotspot doesn't have are rejected, not clamped
+        let relay = SpotStateMsg {
+            room_id: self.current_room,
+            ..spot_state
+        };
+        self.set_spot_state(
+            spot_state.room_id,
+            spot_state.spot_id,
+            spot_state.state,
+            DbHotspot::accepts_state,
+            relay.to_message(0),
+        )
+        .await
+    }
+
+    /// Handle a door being locked
+    async fn handle_door_lock(&mut self, message: Message) -> Result<()> {
+        let lock = message
+            .parse_payload::<DoorLockMsg>()
+            .context("Failed to parse door lock message")?;
+
+        let relay = DoorLockMsg::new(self.current_room, lock.door_id).to_message(0);
+        self.set_spot_state(
+            lock.room_id,
+            lock.door_id,
+            HotspotState::Locked.as_i16(),
+            is_lockable_door,
+            relay,
+        )
+        .await
+    }
+
+    /// Handle a door being unlocked
+    async fn handle_door_unlock(&mut self, message: Message) -> Result<()> {
+        let unlock = message
+            .parse_payload::<DoorUnlockMsg>()
+            .context("Failed to parse door unlock message")?;
+
+        let relay = DoorUnlockMsg::new(self.current_room, unlock.door_id).to_message(0);
+        self.set_spot_state(
+            unlock.room_id,
+            unlock.door_id,
+            HotspotState::Unlocked.as_i16(),
+            is_lockable_door,
+            relay,
+        )
+        .await
+    }
+
+    /// Persist a hotspot's state and send `relay` to the room
+    ///
+    /// Only hotspots in the user's current room can be changed, and only
+    /// when `accepts` allows the new state; other requests are ignored.
+    async fn set_spot_state(
+        &mut self,
+        room_id: RoomId,
+        spot_id: i32,
+        state: i16,
+        accepts: fn(&DbHotspot, i16) -> bool,
+        relay: Message,
+    ) -> Result<()> {
+        if self.user_id.is_none() {
+            return Ok(());
+        }
+        if room_id != self.current_room {
+            warn!(
+                "Ignoring state change for hotspot {} in room {} from a user in room {}",
+                spot_id, room_id, self.current_room
+            );
+            return Ok(());
+        }
+
+        let db = self.state.db();
+        let Some(hotspot) = db.get_hotspot(room_id, spot_id as i16).await? else {
+            warn!("No hotspot {} in room {}", spot_id, room_id);
+            return Ok(());
+        };
+        if !accepts(&hotspot, state) {
+            warn!(
+                "Ignoring invalid state {} for hotspot {} in room {}",
+                state, spot_id, room_id
+            );
+            return Ok(());
+        }
+
+        db.set_hotspot_state(room_id, spot_id as i16, state).await?;
+        debug!(
+            "Hotspot {} in room {} now in state {}",
+            spot_id, room_id, state
+        );
+        self.state.broadcast_to_room(room_id, relay, None).await;
+        Ok(())
+    }
+
     /// Handle ping message
     async fn handle_ping(&mut self, _message: Message) -> Result<()> {
         // Send pong response
@@ -415,10 +1123,29 @@ impl ConnectionHandler {
                     }
                 }
             }
+            ServerMessage::UserMoved {
+                user_id,
+                room_id,
+                pos,
+            } => {
+                if room_id == self.current_room && Some(user_id) != self.user_id {
+                    let msg = UserMoveMsg { pos }.to_message(user_id as i32);
+                    self.send_message(&msg).await?;
+                }
+            }
+            ServerMessage::UserStatus { flags } => {
+                if let Some(user_id) = self.user_id {
+                    let msg = UserStatusMsg::new(flags).to_message(user_id as i32);
+                    self.send_message(&msg).await?;
+                }
+            }
             ServerMessage::UserDisconnected { user_id: _ } => {
                 // Handle user disconnect
                 // TODO: Send user status update
             }
+            ServerMessage::Relay(msg) => {
+                self.send_message(&msg).await?;
+            }
         }
 
         Ok(())
@@ -444,23 +1171,13 @@ impl ConnectionHandler {
     async fn send_user_list(&mut self) -> Result<()> {
         let users = self.state.get_room_users(self.current_room).await;
 
-        let user_list = UserListMsg {
-            users: users
-                .into_iter()
-                .map(|(user_id, username)| thepalace::messages::UserRec {
-                    user_id: user_id as i32,
-                    room_pos: Point::new(128, 128), // Default position
-                    prop_spec: [AssetSpec { id: 0, crc: 0 }; 9],
-                    room_id: self.current_room,
-                    face_nbr: 0,
-                    color_nbr: 0,
-                    away_flag: 0,
-                    open_to_msgs: 1,
-                    nbr_props: 0,
-                    name: username,
-                })
-                .collect(),
-        };
+        let mut user_list = UserListMsg { users: Vec::new() };
+        for (user_id, username) in users {
+            let props = self.state.user_props(user_id).await.unwrap_or_default();
+            user_list
+                .users
+                .push(user_rec(user_id, username, self.current_room, &props));
+        }
 
         let msg = user_list.to_message_default();
         self.send_message(&msg).await
@@ -468,6 +1185,25 @@ impl ConnectionHandler {
 
     /// Send room description
     async fn send_room_description(&mut self) -> Result<()> {
+        if let Some(room_desc) = self.room_description().await? {
+            self.send_message(&room_desc.to_message_default()).await?;
+        }
+        Ok(())
+    }
+
+    /// Send the current room's description to everyone in it, after an edit
+    async fn broadcast_room_description(&self) -> Result<()> {
+        if let Some(room_desc) = self.room_description().await? {
+            let message = room_desc.to_message_default();
+            self.state
+                .broadcast_to_room(self.current_room, message, None)
+                .await;
+        }
+        Ok(())
+    }
+
+    /// Describe the current room as persisted, or `None` if it doesn't exist
+    async fn room_description(&self) -> Result<Option<RoomDescMsg>> {
         use bytes::BufMut;
         use thepalace::messages::flags::RoomFlags;
         use thepalace::messages::RoomRec;
@@ -498,6 +1234,23 @@ impl ConnectionHandler {
             let password_ofst = var_buf.len() as i16;
             var_buf.put_u8(0);
 
+            // Loose props persisted for this room
+            let loose_props = self.state.db().loose_props_for_room(self.current_room).await?;
+            let first_lprop = var_buf.len() as i16;
+            for prop in &loose_props {
+                LPropRec {
+                    prop_spec: prop.spec(),
+                    flags: 0,
+                    ref_con: 0,
+                    loc: prop.pos(),
+                }
+                .to_bytes(&mut var_buf);
+            }
+
+            // Hotspots persisted for this room
+            let hotspots = self.state.db().load_room_hotspots(self.current_room).await?;
+            let hotspot_ofst = put_hotspots(&mut var_buf, &hotspots);
+
             let len_vars = var_buf.len() as i16;
 
             // Get current user count from in-memory state
@@ -511,26 +1264,23 @@ impl ConnectionHandler {
                 pict_name_ofst,
                 artist_name_ofst,
                 password_ofst,
-                nbr_hotspots: 0, // TODO: Query hotspots from DB
-                hotspot_ofst: 0,
+                nbr_hotspots: hotspots.len() as i16,
+                hotspot_ofst,
                 nbr_pictures: 0,
                 picture_ofst: 0,
                 nbr_draw_cmds: 0,
                 first_draw_cmd: 0,
                 nbr_people,
-                nbr_lprops: 0, // TODO: Query loose props from DB
-                first_lprop: 0,
+                nbr_lprops: loose_props.len() as i16,
+                first_lprop,
                 len_vars,
                 var_buf: var_buf.freeze(),
             };
 
-            let room_desc = RoomDescMsg { room: room_rec };
-
-            let msg = room_desc.to_message_default();
-            self.send_message(&msg).await?;
+            return Ok(Some(RoomDescMsg { room: room_rec }));
         }
 
-        Ok(())
+        Ok(None)
     }
 
     /// Broadcast user joined to room
@@ -543,7 +1293,7 @@ impl ConnectionHandler {
             };
 
             self.state
-                .broadcast_to_room(self.current_room, broadcast_msg)
+                .broadcast_to_room(self.current_room, broadcast_msg, None)
                 .await;
         }
 
@@ -552,19 +1302,9 @@ impl ConnectionHandler {
 
     /// Send UserNew message for a specific user
     async fn send_user_new(&mut self, user_id: UserId, username: &str) -> Result<()> {
+        let props = self.state.user_props(user_id).await.unwrap_or_default();
         let user_new = UserNewMsg {
-            new_user: thepalace::messages::UserRec {
-                user_id: user_id as i32,
-                room_pos: Point::new(128, 128),
-                prop_spec: [AssetSpec { id: 0, crc: 0 }; 9],
-                room_id: self.current_room,
-                face_nbr: 0,
-                color_nbr: 0,
-                away_flag: 0,
-                open_to_msgs: 1,
-                nbr_props: 0,
-                name: username.to_string(),
-            },
+            new_user: user_rec(user_id, username.to_string(), self.current_room, &props),
         };
 
         let msg = user_new.to_message_default();
@@ -573,13 +1313,1239 @@ impl ConnectionHandler {
 
     /// Send a message to the client
     async fn send_message(&mut self, message: &Message) -> Result<()> {
-        let bytes = message.to_bytes();
-        self.socket
-            .write_all(&bytes)
+        let bytes = message.encode();
+        let len = bytes.len();
+        let queued = self
+            .send_queue
+            .send(bytes)
             .await
             .context("Failed to send message")?;
 
-        debug!("Sent message: {:?} ({} bytes)", message.msg_id, bytes.len());
+        if queued {
+            debug!("Queued message: {:?} ({} bytes)", message.msg_id, len);
+        } else {
+            warn!(
+                "Send queue for {} full, dropped {:?} ({} bytes)",
+                self.addr, message.msg_id, len
+            );
+        }
         Ok(())
     }
 }
+
+/// DoorLock/DoorUnlock only apply to lockable doors
+fn is_lockable_door(hotspot: &DbHotspot, _state: i16) -> bool {
+    hotspot.hotspot_type() == HotspotType::LockableDoor
+}
+
+/// Build the roster entry for a user standing at the default position
+fn user_rec(user_id: UserId, name: String, room_id: RoomId, props: &[AssetSpec]) -> UserRec {
+    let props = &props[..props.len().min(UserRec::MAX_PROPS)];
+    let mut prop_spec = [AssetSpec { id: 0, crc: 0 }; 9];
+    prop_spec[..props.len()].copy_from_slice(props);
+
+    UserRec {
+        user_id: user_id as i32,
+        room_pos: Point::new(128, 128),
+        prop_spec,
+        room_id,
+        face_nbr: 0,
+        color_nbr: 0,
+        away_flag: 0,
+        open_to_msgs: 1,
+        nbr_props: props.len() as i16,
+        name,
+    }
+}
+
+/// Pad a varBuf to a 4-byte boundary
+fn align_var_buf(var_buf: &mut BytesMut) {
+    while !var_buf.len().is_multiple_of(4) {
+        var_buf.put_u8(0);
+    }
+}
+
+/// Write hotspot names, outlines and records into a room's varBuf
+///
+/// Returns the offset of the hotspot record array.
+fn put_hotspots(var_buf: &mut BytesMut, hotspots: &[RoomHotspot]) -> i16 {
+    let mut records = Vec::with_capacity(hotspots.len());
+    for spot in hotspots {
+        let name_ofst = match &spot.hotspot.name {
+            Some(name) => {
+                let ofst = var_buf.len() as i16;
+                let name = &name.as_bytes()[..name.len().min(255)];
+                var_buf.put_u8(name.len() as u8);
+                var_buf.put_slice(name);
+                ofst
+            }
+            None => -1,
+        };
+
+        let script_text_ofst = match &spot.hotspot.script_text {
+            Some(script) if !script.is_empty() => {
+                let ofst = var_buf.len() as i16;
+                var_buf.put_slice(script.as_bytes());
+                var_buf.put_u8(0);
+                ofst
+            }
+            _ => 0,
+        };
+
+        align_var_buf(var_buf);
+        let pts_ofst = var_buf.len() as i16;
+        for point in &spot.points {
+            point.to_bytes(var_buf);
+        }
+
+        let hotspot = &spot.hotspot;
+        records.push(Hotspot {
+            script_event_mask: EventMask::from(hotspot.script_event_mask as i32),
+            flags: 0,
+            secure_info: 0,
+            ref_con: 0,
+            loc: Point::new(hotspot.loc_h as i16, hotspot.loc_v as i16),
+            id: hotspot.id as i16,
+            dest: hotspot.dest_room_id.unwrap_or(0) as i16,
+            nbr_pts: spot.points.len() as i16,
+            pts_ofst,
+            hotspot_type: HotspotType::from_i16(hotspot.r#type as i16)
+                .unwrap_or(HotspotType::Normal),
+            group_id: 0,
+            nbr_scripts: 0,
+            script_rec_ofst: 0,
+            state: hotspot.state as i16,
+            nbr_states: hotspot.nbr_states as i16,
+            state_rec_ofst: 0,
+            name_ofst,
+            script_text_ofst,
+        });
+    }
+
+    align_var_buf(var_buf);
+    let hotspot_ofst = var_buf.len() as i16;
+    for record in &records {
+        record.to_bytes(var_buf);
+    }
+    hotspot_ofst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::test_support::TestServer;
+    use std::time::Duration;
+    use thepalace::messages::auth::AuxRegistrationRec;
+    use thepalace::messages::flags::UserFlags;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    /// Read one complete message's bytes from the client end of the pipe
+    async fn read_message_bytes(client: &mut DuplexStream) -> Vec<u8> {
+        let mut bytes = vec![0u8; Message::HEADER_SIZE];
+        tokio::time::timeout(Duration::from_secs(5), client.read_exact(&mut bytes))
+            .await
+            .expect("timed out waiting for handler")
+            .unwrap();
+
+        let len = u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        bytes.resize(Message::HEADER_SIZE + len, 0);
+        client
+            .read_exact(&mut bytes[Message::HEADER_SIZE..])
+            .await
+            .unwrap();
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_room_goto_over_duplex() {
+        let server = TestServer::new("handler").await;
+        let (mut client, transport) = tokio::io::duplex(64 * 1024);
+        let addr = "127.0.0.1:9998".parse().unwrap();
+        tokio::spawn(ConnectionHandler::new(transport, addr, server.state.clone()).handle());
+
+        let tiyid = read_message_bytes(&mut client).await;
+        assert_eq!(&tiyid[0..4], &MessageId::Tiyid.as_u32().to_be_bytes());
+
+        let logon = LogonMsg::new(AuxRegistrationRec::new_guest("Piper", 0));
+        client
+            .write_all(&logon.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        client
+            .write_all(&RoomGotoMsg { dest: 2 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+
+        // Logon replies come first; the goto ends with the new room's description
+        loop {
+            let bytes = read_message_bytes(&mut client).await;
+            let message = Message::parse(&mut &bytes[..]).unwrap();
+            assert_eq!(&bytes[4..8], &(message.payload_size() as u32).to_be_bytes());
+
+            if message.msg_id == MessageId::RoomDesc {
+                let desc = message.parse_payload::<RoomDescMsg>().unwrap();
+                if desc.room.room_id == 2 {
+                    assert_eq!(&bytes[0..4], &MessageId::RoomDesc.as_u32().to_be_bytes());
+                    break;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oversized_message_rejected() {
+        let server = TestServer::new("max-message").await;
+        let mut config = crate::config::Config::default();
+        config.server.max_message_size = 64;
+
+        let (mut client, transport) = tokio::io::duplex(64 * 1024);
+        let addr = "127.0.0.1:9998".parse().unwrap();
+        let handler = ConnectionHandler::new(transport, addr, server.state.clone())
+            .with_limits(config.connection_limits());
+        let task = tokio::spawn(handler.handle());
+        read_message_bytes(&mut client).await; // TIYID
+
+        // A message at the cap is fine
+        let ping = Message::new(MessageId::Ping, 0, vec![0; 64]);
+        client.write_all(&ping.to_bytes()).await.unwrap();
+        assert_eq!(
+            read_until(&mut client, MessageId::Pong).await.msg_id,
+            MessageId::Pong
+        );
+
+        // One byte over is refused from the header alone
+        let header = MessageHeader {
+            msg_id: MessageId::Talk,
+            length: 65,
+            ref_num: 0,
+        };
+        let mut bytes = Vec::new();
+        header.write_to(&mut bytes);
+        client.write_all(&bytes).await.unwrap();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("handler kept the connection open")
+            .unwrap();
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_flood_to_stalled_reader() {
+        for (policy, keeps_session) in [(SendPolicy::Drop, true), (SendPolicy::Block, false)] {
+            let server = TestServer::new(&format!("flood-{:?}", policy)).await;
+            let limits = ConnectionLimits {
+                send_queue_size: 4,
+                send_policy: policy,
+                ..Default::default()
+            };
+
+            // Logs on, then stops reading with little room left in the pipe
+            let (mut stalled, transport) = tokio::io::duplex(1024);
+            let addr = "127.0.0.1:9998".parse().unwrap();
+            let handler =
+                ConnectionHandler::new(transport, addr, server.state.clone()).with_limits(limits);
+            tokio::spawn(handler.handle());
+            let logon = LogonMsg::new(AuxRegistrationRec::new_guest("Snail", 0));
+            stalled
+                .write_all(&logon.to_message(0).to_bytes())
+                .await
+                .unwrap();
+            let stalled_id = read_until(&mut stalled, MessageId::ServerInfo)
+                .await
+                .ref_num as UserId;
+            sync(&mut stalled).await;
+
+            // Talking to the room isn't held up by the stalled reader
+            let (mut talker, _) = connect(&server, "Piper").await;
+            let flood: Vec<u8> = (0..200)
+                .flat_map(|n| {
+                    let talk = TalkMsg {
+                        text: format!("flood {}", n),
+                    };
+                    talk.to_message(0).to_bytes()
+                })
+                .collect();
+            talker.write_all(&flood).await.unwrap();
+            sync(&mut talker).await;
+
+            // Its queue overflowed: Drop loses messages, Block the session
+            assert_eq!(
+                server.state.user_room(stalled_id).await.is_some(),
+                keeps_session,
+                "{:?}",
+                policy
+            );
+            drop(stalled);
+        }
+    }
+
+    /// Answers RoomGoto with a chat line instead of moving the user
+    struct GotoOverride {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    impl MessageHandler for GotoOverride {
+        fn handle(&self, message: &Message, ctx: &HandlerContext) -> Option<Vec<Message>> {
+            if message.msg_id != MessageId::RoomGoto {
+                return None;
+            }
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let talk = TalkMsg {
+                text: "No exits here".to_string(),
+            };
+            Some(vec![talk.to_message(ctx.user_id.unwrap_or(0) as i32)])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_message_handler_override() {
+        let server = TestServer::new("override").await;
+        let (mut client, transport) = tokio::io::duplex(64 * 1024);
+        let addr = "127.0.0.1:9998".parse().unwrap();
+        let hook = Arc::new(GotoOverride {
+            calls: Default::default(),
+        });
+        let state = server.state.clone().with_message_handler(hook.clone());
+        tokio::spawn(ConnectionHandler::new(transport, addr, state).handle());
+
+        read_message_bytes(&mut client).await; // TIYID
+        let logon = LogonMsg::new(AuxRegistrationRec::new_guest("Piper", 0));
+        client
+            .write_all(&logon.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        client
+            .write_all(&RoomGotoMsg { dest: 2 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+
+        loop {
+            let bytes = read_message_bytes(&mut client).await;
+            let message = Message::parse(&mut &bytes[..]).unwrap();
+            if message.msg_id == MessageId::RoomDesc {
+                let desc = message.parse_payload::<RoomDescMsg>().unwrap();
+                assert_eq!(desc.room.room_id, 0, "default RoomGoto handling ran");
+            }
+            if message.msg_id == MessageId::Talk {
+                let talk = message.parse_payload::<TalkMsg>().unwrap();
+                assert_eq!(talk.text, "No exits here");
+                break;
+            }
+        }
+
+        assert_eq!(hook.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(server.state.get_room_users(2).await.is_empty());
+    }
+
+    /// Send a ping and wait for the pong, so earlier messages are processed
+    async fn sync(client: &mut DuplexStream) {
+        client
+            .write_all(&Message::new_empty(MessageId::Ping, 0).to_bytes())
+            .await
+            .unwrap();
+        loop {
+            let bytes = read_message_bytes(client).await;
+            if bytes[0..4] == MessageId::Pong.as_u32().to_be_bytes() {
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_loose_props_persisted() {
+        let server = TestServer::new("handler-props").await;
+        let (mut client, transport) = tokio::io::duplex(64 * 1024);
+        let addr = "127.0.0.1:9998".parse().unwrap();
+        tokio::spawn(ConnectionHandler::new(transport, addr, server.state.clone()).handle());
+
+        let logon = LogonMsg::new(AuxRegistrationRec::new_guest("Piper", 0));
+        client
+            .write_all(&logon.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        let prop_new = PropNewMsg {
+            prop_spec: AssetSpec::new(1234, 0x5678),
+            pos: Point::new(40, 60),
+        };
+        client
+            .write_all(&prop_new.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        sync(&mut client).await;
+
+        let props = server.state.db().loose_props_for_room(0).await.unwrap();
+        assert_eq!(props.len(), 1);
+        assert_eq!(props[0].spec(), prop_new.prop_spec);
+
+        // Re-entering the room describes the persisted prop
+        client
+            .write_all(&RoomGotoMsg { dest: 0 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        let desc = loop {
+            let bytes = read_message_bytes(&mut client).await;
+            let message = Message::parse(&mut &bytes[..]).unwrap();
+            if message.msg_id == MessageId::RoomDesc {
+                break message.parse_payload::<RoomDescMsg>().unwrap();
+            }
+        };
+        assert_eq!(desc.room.nbr_lprops, 1);
+        let mut lprop = &desc.room.var_buf[desc.room.first_lprop as usize..];
+        let lprop = LPropRec::from_bytes(&mut lprop).unwrap();
+        assert_eq!(lprop.prop_spec, prop_new.prop_spec);
+        assert_eq!(lprop.loc, prop_new.pos);
+
+        client
+            .write_all(&PropDelMsg::delete_all().to_message(0).to_bytes())
+            .await
+            .unwrap();
+        sync(&mut client).await;
+        let props = server.state.db().loose_props_for_room(0).await.unwrap();
+        assert!(props.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_room_description_sends_hotspot_scripts() {
+        let server = TestServer::new("handler-hotspot-scripts").await;
+        let source = r#"
+            ROOM
+              ID 10
+              NAME "Library"
+              DOOR
+                ID 1
+                DEST 0
+                OUTLINE 10,10 50,10 50,200 10,200
+                SCRIPT
+                  ON SELECT { "Leaving" SAY }
+                ENDSCRIPT
+              ENDDOOR
+              SPOT
+                ID 2
+                OUTLINE 100,100 150,100 150,150
+              ENDSPOT
+            ENDROOM
+        "#;
+        let decls = thepalace::iptscrae::RoomScriptParser::new(source)
+            .unwrap()
+            .parse()
+            .unwrap();
+        let db = server.state.db();
+        assert!(db.import_room_decl(&decls[0], false).await.unwrap());
+
+        let (mut client, _) = connect(&server, "Piper").await;
+        client
+            .write_all(&RoomGotoMsg { dest: 10 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        let desc = read_until(&mut client, MessageId::RoomDesc).await;
+        let desc = desc.parse_payload::<RoomDescMsg>().unwrap();
+        let hotspots = desc.room.hotspots().unwrap();
+        assert_eq!(hotspots.len(), 2);
+
+        let door = hotspots.iter().find(|spot| spot.id == 1).unwrap();
+        assert_ne!(door.script_text_ofst, 0);
+        let text = &desc.room.var_buf[door.script_text_ofst as usize..];
+        let text = &text[..text.iter().position(|&b| b == 0).unwrap()];
+        let script = decls[0].doors[0].script.as_ref().unwrap().to_source();
+        assert_eq!(text, script.as_bytes());
+
+        let spot = hotspots.iter().find(|spot| spot.id == 2).unwrap();
+        assert_eq!(spot.script_text_ofst, 0);
+    }
+
+    #[tokio::test]
+    async fn test_loose_props_relayed_and_capped() {
+        let mut server = TestServer::new("handler-props-relay").await;
+        server.state = server.state.clone().with_max_loose_props(1);
+        let (mut dropper, _) = connect(&server, "Piper").await;
+        let (mut watcher, _) = connect(&server, "Merlin").await;
+
+        let hat = PropNewMsg::new(AssetSpec::new(1234, 0x5678), Point::new(40, 60));
+        let cane = PropNewMsg::new(AssetSpec::new(99, 0), Point::new(10, 10));
+        for prop_new in [&hat, &cane] {
+            dropper
+                .write_all(&prop_new.to_message(0).to_bytes())
+                .await
+                .unwrap();
+        }
+
+        // Everyone, the dropper included, sees the first prop
+        for client in [&mut dropper, &mut watcher] {
+            let relayed = read_until(client, MessageId::PropNew).await;
+            assert_eq!(relayed.parse_payload::<PropNewMsg>().unwrap(), hat);
+        }
+        // The room was full for the second
+        sync(&mut dropper).await;
+        watcher
+            .write_all(&Message::new_empty(MessageId::Ping, 0).to_bytes())
+            .await
+            .unwrap();
+        loop {
+            let message = Message::parse(&mut &read_message_bytes(&mut watcher).await[..]).unwrap();
+            assert_ne!(message.msg_id, MessageId::PropNew);
+            if message.msg_id == MessageId::Pong {
+                break;
+            }
+        }
+        let props = server.state.db().loose_props_for_room(0).await.unwrap();
+        assert_eq!(props.len(), 1);
+
+        dropper
+            .write_all(&PropDelMsg::delete_all().to_message(0).to_bytes())
+            .await
+            .unwrap();
+        let relayed = read_until(&mut watcher, MessageId::PropDel).await;
+        assert_eq!(relayed.parse_payload::<PropDelMsg>().unwrap().prop_num, -1);
+    }
+
+    #[tokio::test]
+    async fn test_door_lock_persisted() {
+        let server = TestServer::new("handler-spots").await;
+        let (mut wizard, wizard_id) = connect(&server, "Merlin").await;
+        server
+            .state
+            .set_user_flags(wizard_id, UserFlags::SUPERUSER)
+            .await;
+        let (mut client, _) = connect(&server, "Piper").await;
+
+        // Guests can't create hotspots
+        client
+            .write_all(&Message::new_empty(MessageId::SpotNew, 0).to_bytes())
+            .await
+            .unwrap();
+        sync(&mut client).await;
+        let hotspots = server.state.db().load_room_hotspots(0).await.unwrap();
+        assert!(hotspots.is_empty());
+
+        // A wizard's new hotspot is described to the whole room
+        wizard
+            .write_all(&Message::new_empty(MessageId::SpotNew, 0).to_bytes())
+            .await
+            .unwrap();
+        for user in [&mut wizard, &mut client] {
+            let desc = read_until(user, MessageId::RoomDesc).await;
+            let desc = desc.parse_payload::<RoomDescMsg>().unwrap();
+            assert_eq!(desc.room.nbr_hotspots, 1);
+        }
+        sqlx::query("UPDATE hotspots SET type = ? WHERE room_id = 0 AND id = 1")
+            .bind(HotspotType::LockableDoor.as_i16() as i64)
+            .execute(server.state.db().pool())
+            .await
+            .unwrap();
+
+        // Anyone in the room can lock its doors; the room hears about it
+        let lock = DoorLockMsg {
+            room_id: 0,
+            door_id: 1,
+        };
+        client
+            .write_all(&lock.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        for user in [&mut wizard, &mut client] {
+            let relayed = read_until(user, MessageId::DoorLock).await;
+            assert_eq!(relayed.parse_payload::<DoorLockMsg>().unwrap(), lock);
+        }
+
+        // But not doors in other rooms
+        let elsewhere = DoorUnlockMsg {
+            room_id: 1,
+            door_id: 1,
+        };
+        client
+            .write_all(&elsewhere.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        sync(&mut client).await;
+
+        let hotspots = server.state.db().load_room_hotspots(0).await.unwrap();
+        assert_eq!(hotspots.len(), 1);
+        assert_eq!(hotspots[0].points.len(), 4);
+        assert_eq!(
+            hotspots[0].hotspot.state,
+            HotspotState::Locked.as_i16() as i64
+        );
+
+        // Re-entering the room describes the locked spot
+        client
+            .write_all(&RoomGotoMsg { dest: 0 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        let desc = loop {
+            let bytes = read_message_bytes(&mut client).await;
+            let message = Message::parse(&mut &bytes[..]).unwrap();
+            if message.msg_id == MessageId::RoomDesc {
+                break message.parse_payload::<RoomDescMsg>().unwrap();
+            }
+        };
+        assert_eq!(desc.room.nbr_hotspots, 1);
+        let mut record = &desc.room.var_buf[desc.room.hotspot_ofst as usize..];
+        let hotspot = Hotspot::from_bytes(&mut record).unwrap();
+        assert_eq!(hotspot.id, 1);
+        assert_eq!(hotspot.nbr_pts, 4);
+        assert_eq!(hotspot.state, HotspotState::Locked.as_i16());
+    }
+
+    #[tokio::test]
+    async fn test_spot_state_validated() {
+        let server = TestServer::new("handler-spot-state").await;
+        let (mut client, user_id) = connect(&server, "Piper").await;
+        server
+            .state
+            .set_user_flags(user_id, UserFlags::SUPERUSER)
+            .await;
+        client
+            .write_all(&Message::new_empty(MessageId::SpotNew, 0).to_bytes())
+            .await
+            .unwrap();
+
+        sync(&mut client).await;
+
+        // Make the new hotspot a `hotspot_type` with `nbr_states` pictures
+        async fn reshape(server: &TestServer, hotspot_type: HotspotType, nbr_states: i64) {
+            sqlx::query("UPDATE hotspots SET type = ?, nbr_states = ?, state = 0 WHERE id = 1")
+                .bind(hotspot_type.as_i16() as i64)
+                .bind(nbr_states)
+                .execute(server.state.db().pool())
+                .await
+                .unwrap();
+        }
+
+        // Send a message for the new hotspot and return its stored state
+        async fn send(server: &TestServer, client: &mut DuplexStream, message: Message) -> i64 {
+            client.write_all(&message.to_bytes()).await.unwrap();
+            sync(client).await;
+            server.state.db().load_room_hotspots(0).await.unwrap()[0]
+                .hotspot
+                .state
+        }
+        let spot_state = |state| SpotStateMsg::new(0, 1, state).to_message(0);
+
+        // Spots with pictures take any picture index
+        reshape(&server, HotspotType::Normal, 3).await;
+        assert_eq!(send(&server, &mut client, spot_state(2)).await, 2);
+        assert_eq!(send(&server, &mut client, spot_state(3)).await, 2);
+        assert_eq!(send(&server, &mut client, spot_state(-1)).await, 2);
+
+        // ...and the room description reports the picture state
+        client
+            .write_all(&RoomGotoMsg { dest: 0 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        let desc = read_until(&mut client, MessageId::RoomDesc).await;
+        let desc = desc.parse_payload::<RoomDescMsg>().unwrap();
+        let mut record = &desc.room.var_buf[desc.room.hotspot_ofst as usize..];
+        let hotspot = Hotspot::from_bytes(&mut record).unwrap();
+        assert_eq!((hotspot.state, hotspot.nbr_states), (2, 3));
+        assert_eq!(send(&server, &mut client, spot_state(0)).await, 0);
+
+        // ...but aren't doors, so can't be locked
+        let lock = DoorLockMsg::new(0, 1).to_message(0);
+        assert_eq!(send(&server, &mut client, lock).await, 0);
+
+        // Lockable doors only take locked/unlocked
+        reshape(&server, HotspotType::LockableDoor, 3).await;
+        let locked = HotspotState::Locked.as_i16();
+        assert_eq!(send(&server, &mut client, spot_state(locked)).await, 1);
+        assert_eq!(send(&server, &mut client, spot_state(2)).await, 1);
+        assert_eq!(send(&server, &mut client, spot_state(-1)).await, 1);
+        let unlock = DoorUnlockMsg::new(0, 1).to_message(0);
+        assert_eq!(send(&server, &mut client, unlock).await, 0);
+
+        // A plain spot only has state 0
+        reshape(&server, HotspotType::Normal, 0).await;
+        assert_eq!(send(&server, &mut client, spot_state(locked)).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_hotspot_editing_requires_wizard() {
+        let server = TestServer::new("handler-spot-edit").await;
+        let (mut wizard, wizard_id) = connect(&server, "Merlin").await;
+        server
+            .state
+            .set_user_flags(wizard_id, UserFlags::SUPERUSER)
+            .await;
+        let (mut guest, _) = connect(&server, "Piper").await;
+        wizard
+            .write_all(&Message::new_empty(MessageId::SpotNew, 0).to_bytes())
+            .await
+            .unwrap();
+        sync(&mut wizard).await;
+
+        // A guest's move and delete are ignored
+        let spot_move = SpotMoveMsg {
+            room_id: 0,
+            spot_id: 1,
+            pos: Point::new(10, 20),
+        };
+        for message in [spot_move.to_message(0), SpotDelMsg::new(1).to_message(0)] {
+            guest.write_all(&message.to_bytes()).await.unwrap();
+        }
+        sync(&mut guest).await;
+        let hotspots = server.state.db().load_room_hotspots(0).await.unwrap();
+        assert_eq!(hotspots.len(), 1);
+        assert_eq!(hotspots[0].points, default_spot_outline());
+
+        // A wizard's move goes to the room they're in, whatever the message says
+        let spot_move = SpotMoveMsg {
+            room_id: 2,
+            ..spot_move
+        };
+        wizard
+            .write_all(&spot_move.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        let relayed = read_until(&mut guest, MessageId::SpotMove).await;
+        let relayed = relayed.parse_payload::<SpotMoveMsg>().unwrap();
+        assert_eq!((relayed.room_id, relayed.pos), (0, Point::new(10, 20)));
+        let hotspots = server.state.db().load_room_hotspots(0).await.unwrap();
+        let spot = &hotspots[0].hotspot;
+        assert_eq!((spot.loc_h, spot.loc_v), (10, 20));
+        assert_eq!(hotspots[0].points[0], Point::new(10, 20));
+
+        wizard
+            .write_all(&SpotDelMsg::new(1).to_message(0).to_bytes())
+            .await
+            .unwrap();
+        let desc = read_until(&mut guest, MessageId::RoomDesc).await;
+        let desc = desc.parse_payload::<RoomDescMsg>().unwrap();
+        assert_eq!(desc.room.nbr_hotspots, 0);
+    }
+
+    /// Log a guest on over a fresh pipe and return the client end and its UserID
+    async fn connect(server: &TestServer, name: &str) -> (DuplexStream, UserId) {
+        connect_as(server, AuxRegistrationRec::new_guest(name, 0)).await
+    }
+
+    /// Log on with `rec` over a fresh pipe and return the client end and its UserID
+    async fn connect_as(server: &TestServer, rec: AuxRegistrationRec) -> (DuplexStream, UserId) {
+        let (mut client, transport) = tokio::io::duplex(64 * 1024);
+        let addr = "127.0.0.1:9998".parse().unwrap();
+        tokio::spawn(ConnectionHandler::new(transport, addr, server.state.clone()).handle());
+
+        let logon = LogonMsg::new(rec);
+        client
+            .write_all(&logon.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        // ServerInfo carries the session's UserID
+        let user_id = read_until(&mut client, MessageId::ServerInfo).await.ref_num as UserId;
+        sync(&mut client).await;
+        (client, user_id)
+    }
+
+    /// Read messages until one with `msg_id` arrives
+    async fn read_until(client: &mut DuplexStream, msg_id: MessageId) -> Message {
+        loop {
+            let bytes = read_message_bytes(client).await;
+            let message = Message::parse(&mut &bytes[..]).unwrap();
+            if message.msg_id == msg_id {
+                break message;
+            }
+        }
+    }
+
+    /// Give `room_id` a new spot running `source`
+    async fn add_spot_script(server: &TestServer, room_id: RoomId, source: &str) {
+        use thepalace::iptscrae::{Lexer, Parser};
+
+        let db = server.state.db();
+        let id = db.next_hotspot_id(room_id).await.unwrap();
+        let outline = default_spot_outline();
+        let row = db
+            .create_hotspot(room_id, id, HotspotType::Normal, &outline)
+            .await
+            .unwrap();
+
+        let script = Parser::new(Lexer::new(source).tokenize().unwrap())
+            .parse()
+            .unwrap();
+        let event_mask = script
+            .handlers
+            .iter()
+            .fold(EventMask::empty(), |mask, handler| {
+                mask | handler.event.to_mask()
+            });
+        sqlx::query(
+            "UPDATE hotspots SET script_text = ?, script_event_mask = ? WHERE hotspot_id = ?",
+        )
+        .bind(source)
+        .bind(i32::from(event_mask) as i64)
+        .bind(row)
+        .execute(db.pool())
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_naked_spot_script_clears_props() {
+        let server = TestServer::new("handler-naked").await;
+        add_spot_script(&server, 1, "ON ENTER { NAKED }").await;
+
+        let (mut watcher, _) = connect(&server, "Merlin").await;
+        watcher
+            .write_all(&RoomGotoMsg { dest: 1 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        sync(&mut watcher).await;
+
+        let (mut wearer, wearer_id) = connect(&server, "Piper").await;
+        let hat = AssetSpec::new(7, 0xCAFE);
+        let dress = UserPropMsg {
+            props: vec![hat, hat],
+        };
+        wearer
+            .write_all(&dress.to_message(wearer_id as i32).to_bytes())
+            .await
+            .unwrap();
+        sync(&mut wearer).await;
+        assert_eq!(
+            server.state.user_props(wearer_id).await.unwrap(),
+            [hat, hat]
+        );
+
+        // Walking onto the spot's room runs its ENTER script
+        wearer
+            .write_all(&RoomGotoMsg { dest: 1 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+
+        // Everyone in the room, the wearer included, sees the change
+        for client in [&mut wearer, &mut watcher] {
+            let message = loop {
+                let message = read_until(client, MessageId::UserProp).await;
+                if message.ref_num == wearer_id as i32 {
+                    break message;
+                }
+            };
+            let user_prop = message.parse_payload::<UserPropMsg>().unwrap();
+            assert!(user_prop.props.is_empty());
+        }
+        assert_eq!(server.state.user_props(wearer_id).await.unwrap(), []);
+    }
+
+    #[tokio::test]
+    async fn test_goto_missing_room_stays_put() {
+        let server = TestServer::new("handler-goto-missing").await;
+        add_spot_script(&server, 0, "ON LEAVE { 7 10 10 ADDLOOSEPROP }").await;
+        let loose_props = || async { server.state.db().loose_props_for_room(0).await.unwrap() };
+
+        let (mut client, user_id) = connect(&server, "Piper").await;
+        client
+            .write_all(&RoomGotoMsg { dest: 99 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        sync(&mut client).await;
+
+        // Nowhere to go, so the user hasn't left
+        assert_eq!(server.state.user_room(user_id).await, Some(0));
+        assert!(loose_props().await.is_empty());
+
+        client
+            .write_all(&RoomGotoMsg { dest: 1 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        sync(&mut client).await;
+        assert_eq!(server.state.user_room(user_id).await, Some(1));
+        assert_eq!(loose_props().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_signon_runs_once_per_session() {
+        let server = TestServer::new("handler-signon").await;
+        // SIGNON sets up a global that every ENTER reads
+        add_spot_script(
+            &server,
+            0,
+            "ON SIGNON { 7 prop = prop 10 10 ADDLOOSEPROP }
+             ON ENTER { prop 20 20 ADDLOOSEPROP }
+             ON SIGNOFF { CLEARLOOSEPROPS }",
+        )
+        .await;
+        let loose_props = || async { server.state.db().loose_props_for_room(0).await.unwrap() };
+
+        let (mut client, _) = connect(&server, "Piper").await;
+        assert_eq!(loose_props().await.len(), 2);
+
+        // Coming back to the room is another ENTER but not another SIGNON
+        for dest in [1, 0] {
+            client
+                .write_all(&RoomGotoMsg { dest }.to_message(0).to_bytes())
+                .await
+                .unwrap();
+        }
+        sync(&mut client).await;
+        let props = loose_props().await;
+        assert_eq!(props.len(), 3);
+        assert!(props.iter().all(|prop| prop.prop_id == 7));
+
+        // Hanging up signs the session off
+        drop(client);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !loose_props().await.is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("SIGNOFF script didn't run");
+    }
+
+    #[tokio::test]
+    async fn test_wizard_logon_needs_password() {
+        use argon2::password_hash::SaltString;
+        use argon2::{Argon2, PasswordHasher};
+
+        let server = TestServer::new("handler-wizard-logon").await;
+        let db = server.state.db();
+        let db_id = db.create_user("Merlin", None).await.unwrap();
+        let salt = SaltString::from_b64("c2FsdHlzYWx0c2FsdA").unwrap();
+        let hash = Argon2::default()
+            .hash_password(b"abracadabra", &salt)
+            .unwrap()
+            .to_string();
+        sqlx::query("UPDATE users SET flags = ?, wizard_password = ? WHERE user_id = ?")
+            .bind((UserFlags::SUPERUSER | UserFlags::GAG).bits() as i64)
+            .bind(hash)
+            .bind(db_id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        // The name alone, or a wrong password, gives no wizard rights
+        for password in ["", "hocus pocus"] {
+            let mut rec = AuxRegistrationRec::new_guest("Merlin", 0);
+            rec.wiz_password = password.to_string();
+            let (_client, user_id) = connect_as(&server, rec).await;
+            let flags = server.state.user_flags(user_id).await.unwrap();
+            assert!(!flags.is_wizard(), "wizard with password {:?}", password);
+            assert!(flags.is_gagged());
+        }
+
+        let mut rec = AuxRegistrationRec::new_guest("Merlin", 0);
+        rec.wiz_password = "abracadabra".to_string();
+        let (_client, user_id) = connect_as(&server, rec).await;
+        let flags = server.state.user_flags(user_id).await.unwrap();
+        assert!(flags.is_wizard());
+        assert!(flags.is_gagged());
+    }
+
+    #[tokio::test]
+    async fn test_gagged_chat_suppressed() {
+        let server = TestServer::new("handler-gag").await;
+        let (mut wizard, wizard_id) = connect(&server, "Merlin").await;
+        let (mut guest, guest_id) = connect(&server, "Piper").await;
+        assert!(
+            server
+                .state
+                .set_user_flags(wizard_id, UserFlags::SUPERUSER)
+                .await
+        );
+
+        let gag = UserStatusMsg::new(UserFlags::GAG);
+        wizard
+            .write_all(&gag.to_message(guest_id as i32).to_bytes())
+            .await
+            .unwrap();
+        let status = read_until(&mut guest, MessageId::UserStatus).await;
+        let status = status.parse_payload::<UserStatusMsg>().unwrap();
+        assert!(status.flags.is_gagged());
+
+        // Non-wizards can't lift their own gag, and their chat goes nowhere
+        let ungag = UserStatusMsg::new(UserFlags::empty());
+        guest
+            .write_all(&ungag.to_message(guest_id as i32).to_bytes())
+            .await
+            .unwrap();
+        let muffled = TalkMsg {
+            text: "muffled".to_string(),
+        };
+        guest
+            .write_all(&muffled.to_message(guest_id as i32).to_bytes())
+            .await
+            .unwrap();
+        // Gagged users can still navigate
+        guest
+            .write_all(&RoomGotoMsg { dest: 0 }.to_message(0).to_bytes())
+            .await
+            .unwrap();
+        read_until(&mut guest, MessageId::RoomDesc).await;
+        sync(&mut guest).await;
+        assert!(server.state.user_flags(guest_id).await.unwrap().is_gagged());
+
+        wizard
+            .write_all(&ungag.to_message(guest_id as i32).to_bytes())
+            .await
+            .unwrap();
+        read_until(&mut guest, MessageId::UserStatus).await;
+        let audible = TalkMsg {
+            text: "audible".to_string(),
+        };
+        guest
+            .write_all(&audible.to_message(guest_id as i32).to_bytes())
+            .await
+            .unwrap();
+
+        let talk = read_until(&mut wizard, MessageId::Talk).await;
+        assert_eq!(talk.ref_num, guest_id as i32);
+        assert_eq!(talk.parse_payload::<TalkMsg>().unwrap().text, "audible");
+    }
+
+    #[tokio::test]
+    async fn test_room_list_hides_hidden_rooms() {
+        use thepalace::messages::flags::RoomFlags;
+
+        let server = TestServer::new("handler-room-list").await;
+        // The default schema seeds Gate, Main Hall and Ballroom
+        sqlx::query("UPDATE rooms SET flags = ? WHERE room_id = 2")
+            .bind(RoomFlags::HIDDEN.bits() as i64)
+            .execute(server.state.db().pool())
+            .await
+            .unwrap();
+
+        let (mut guest, _) = connect(&server, "Piper").await;
+        let (mut wizard, wizard_id) = connect(&server, "Merlin").await;
+        server
+            .state
+            .set_user_flags(wizard_id, UserFlags::SUPERUSER)
+            .await;
+
+        let request = ListOfAllRoomsMsg::request().to_message(0).to_bytes();
+        guest.write_all(&request).await.unwrap();
+        let list = read_until(&mut guest, MessageId::ListOfAllRooms).await;
+        let rooms = list.parse_payload::<ListOfAllRoomsMsg>().unwrap().rooms;
+        let ids: Vec<i32> = rooms.iter().map(|room| room.room_id).collect();
+        assert_eq!(ids, [0, 1]);
+        let occupancy = server.state.get_room_user_count(0).await;
+        assert_eq!(occupancy, 2);
+        assert_eq!(rooms[0].nbr_users, occupancy);
+        assert_eq!(rooms[1].nbr_users, 0);
+
+        wizard.write_all(&request).await.unwrap();
+        let list = read_until(&mut wizard, MessageId::ListOfAllRooms).await;
+        let rooms = list.parse_payload::<ListOfAllRoomsMsg>().unwrap().rooms;
+        let ids: Vec<i32> = rooms.iter().map(|room| room.room_id).collect();
+        assert_eq!(ids, [0, 1, 2]);
+        assert!(rooms[2].flags.contains(RoomFlags::HIDDEN));
+    }
+
+    #[tokio::test]
+    async fn test_server_status_for_wizards() {
+        let server = TestServer::new("handler-status").await;
+        let (mut guest, guest_id) = connect(&server, "Piper").await;
+        let (mut wizard, wizard_id) = connect(&server, "Merlin").await;
+        let _wanderer = connect(&server, "Wanderer").await;
+        server
+            .state
+            .set_user_flags(wizard_id, UserFlags::SUPERUSER)
+            .await;
+        assert!(server.state.move_user_to_room(guest_id, 1).await);
+
+        let request = Message::new_empty(MessageId::ServerStatus, 0).to_bytes();
+        wizard.write_all(&request).await.unwrap();
+        let reply = read_until(&mut wizard, MessageId::ServerStatus).await;
+        let status = reply.parse_payload::<ServerStatusMsg>().unwrap();
+        assert_eq!(status.user_count, 3);
+        assert_eq!(status.room_count, 2);
+        assert!(status.uptime_secs < 60);
+
+        // Non-wizards get nothing back
+        guest.write_all(&request).await.unwrap();
+        guest
+            .write_all(&Message::new_empty(MessageId::Ping, 0).to_bytes())
+            .await
+            .unwrap();
+        loop {
+            let bytes = read_message_bytes(&mut guest).await;
+            let message = Message::parse(&mut &bytes[..]).unwrap();
+            assert_ne!(message.msg_id, MessageId::ServerStatus);
+            if message.msg_id == MessageId::Pong {
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prop_upload_validated() {
+        use bytes::Bytes;
+        use thepalace::messages::flags::PropFlags;
+        use thepalace::prop::{prop_crc, Color, PropRec, PROP_PIXELS};
+
+        let server = TestServer::new("handler-prop-upload").await;
+        let (mut client, _) = connect(&server, "Piper").await;
+
+        let pixels = vec![Color::new(255, 255, 0, 0); PROP_PIXELS];
+        let prop = PropRec::encode(&pixels, 44, 44, 0, 0, PropFlags::FORMAT_S20BIT).unwrap();
+        let mut valid = Vec::new();
+        prop.to_bytes(&mut valid);
+        let valid_crc = prop_crc(&valid);
+        // Header claims S20-bit but the image data isn't zlib
+        let mut invalid = valid[..12].to_vec();
+        invalid.extend_from_slice(b"not a prop");
+        let invalid_crc = prop_crc(&invalid);
+        // Header claims 65535x65535, which must not be allocated
+        let mut huge = valid.clone();
+        huge[..4].copy_from_slice(&[0xFF; 4]);
+        let huge_crc = prop_crc(&huge);
+
+        for (name, data) in [("Red", valid), ("Junk", invalid), ("Huge", huge)] {
+            let spec = AssetSpec {
+                id: 1,
+                crc: prop_crc(&data),
+            };
+            let upload = AssetSendMsg::single_block(
+                AssetType::Prop,
+                spec,
+                name.to_string(),
+                Bytes::from(data),
+            );
+            let mut message = Message::from_payload(&upload, 0);
+            message.msg_id = MessageId::AssetRegi;
+            client.write_all(&message.to_bytes()).await.unwrap();
+        }
+
+        let not_found = read_until(&mut client, MessageId::FileNotFnd).await;
+        assert_eq!(&not_found.payload[..5], b"\x04Junk");
+        let not_found = read_until(&mut client, MessageId::FileNotFnd).await;
+        assert_eq!(&not_found.payload[..5], b"\x04Huge");
+
+        let db = server.state.db();
+        let stored = db.get_prop_by_crc(valid_crc).await.unwrap().unwrap();
+        assert_eq!(stored.name, "Red");
+        assert_eq!(stored.width, 44);
+        assert!(db.get_prop_by_crc(invalid_crc).await.unwrap().is_none());
+        assert!(db.get_prop_by_crc(huge_crc).await.unwrap().is_none());
+
+        let assets = server.state.assets();
+        let valid = assets.load(AssetType::Prop, valid_crc).unwrap();
+        assert!(!assets.asset_path(AssetType::Prop, invalid_crc).exists());
+
+        // A known prop uploaded again keeps its registration
+        let crc = server
+            .state
+            .store_uploaded_prop("Crimson", 1, &valid)
+            .await
+            .unwrap();
+        assert_eq!(crc, valid_crc);
+        let again = db.get_prop_by_crc(valid_crc).await.unwrap().unwrap();
+        assert_eq!(again.prop_id, stored.prop_id);
+        assert_eq!(again.name, "Red");
+    }
+
+    #[tokio::test]
+    async fn test_asset_query_served_from_store() {
+        use thepalace::messages::flags::PropFlags;
+        use thepalace::prop::{Color, PROP_PIXELS, PropRec, prop_crc};
+
+        let server = TestServer::new("handler-asset-query").await;
+        let (mut client, _) = connect(&server, "Piper").await;
+
+        let pixels = vec![Color::new(255, 0, 0, 255); PROP_PIXELS];
+        let prop = PropRec::encode(&pixels, 44, 44, 0, 0, PropFlags::FORMAT_S20BIT).unwrap();
+        let mut data = Vec::new();
+        prop.to_bytes(&mut data);
+        let crc = server
+            .state
+            .store_uploaded_prop("Blue", 1, &data)
+            .await
+            .unwrap();
+        assert_eq!(crc, prop_crc(&data));
+
+        // Send a query and return the reply
+        async fn ask(client: &mut DuplexStream, asset_type: AssetType, spec: AssetSpec) -> Message {
+            let query = AssetQueryMsg { asset_type, spec };
+            client
+                .write_all(&query.to_message(0).to_bytes())
+                .await
+                .unwrap();
+            let bytes = read_message_bytes(client).await;
+            Message::parse(&mut &bytes[..]).unwrap()
+        }
+        let not_found = |reply: Message, name: String| {
+            assert_eq!(reply.msg_id, MessageId::FileNotFnd);
+            assert_eq!(reply.payload[0] as usize, name.len());
+            assert_eq!(&reply.payload[1..=name.len()], name.as_bytes());
+        };
+
+        let reply = ask(&mut client, AssetType::Prop, AssetSpec::new(1, crc)).await;
+        let send = reply.parse_payload::<AssetSendMsg>().unwrap();
+        assert_eq!(send.spec, AssetSpec::new(1, crc));
+        assert_eq!(send.nbr_blocks, 1);
+        assert_eq!(send.data, data);
+
+        // A don't-care CRC is looked up by asset ID
+        let reply = ask(&mut client, AssetType::Prop, AssetSpec::new(1, 0)).await;
+        let send = reply.parse_payload::<AssetSendMsg>().unwrap();
+        assert_eq!(send.spec, AssetSpec::new(1, crc));
+        assert_eq!(send.data, data);
+
+        // Anything else is answered with FileNotFnd
+        let reply = ask(&mut client, AssetType::Prop, AssetSpec::new(2, crc ^ 1)).await;
+        not_found(reply, format!("{:08X}", crc ^ 1));
+        let reply = ask(&mut client, AssetType::Prop, AssetSpec::new(2, 0)).await;
+        not_found(reply, "00000002".to_string());
+        let reply = ask(&mut client, AssetType::Userbase, AssetSpec::new(1, crc)).await;
+        not_found(reply, format!("{:08X}", crc));
+    }
+
+    #[tokio::test]
+    async fn test_connection_logs_carry_span_fields() {
+        use crate::config::{LogFormat, LoggingConfig};
+        use std::io::{self, Write};
+        use std::sync::Mutex;
+
+        #[derive(Clone, Default)]
+        struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for LogBuffer {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = LogBuffer::default();
+        let config = LoggingConfig {
+            level: "info".to_string(),
+            format: LogFormat::Json,
+        };
+        let writer = buffer.clone();
+        let _guard =
+            tracing::subscriber::set_default(crate::logging::subscriber(&config, move || {
+                writer.clone()
+            }));
+
+        let server = TestServer::new("handler-log-spans").await;
+        let (_client, user_id) = connect(&server, "Piper").await;
+
+        let logs = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<serde_json::Value> = logs
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let find = |text: &str| {
+            events
+                .iter()
+                .find(|event| {
+                    event["fields"]["message"]
+                        .as_str()
+                        .is_some_and(|message| message.contains(text))
+                })
+                .unwrap()
+        };
+
+        // The user ID joins the span once it's known
+        let logon = &find("logging in")["span"];
+        assert_eq!(logon["name"], "connection");
+        assert_eq!(logon["peer"], "127.0.0.1:9998");
+        assert!(logon.get("user_id").is_none());
+
+        let registered = &find("Registered session")["span"];
+        assert_eq!(registered["peer"], "127.0.0.1:9998");
+        assert_eq!(registered["user_id"], user_id);
+    }
+}
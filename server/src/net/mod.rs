@@ -1,3 +1,11 @@
 //! Network connection handling module
 
+pub mod dispatch;
 pub mod handler;
+pub mod send_queue;
+pub mod transport;
+#[cfg(feature = "ws")]
+pub mod ws;
+
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -0,0 +1,160 @@
+//! Bounded outgoing message queue
+//!
+//! Each connection hands its encoded messages to a [`SendQueue`]; a writer
+//! task drains the queue into the transport. A client that reads slowly only
+//! fills its own queue, and what happens then is set by [`SendPolicy`].
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::io;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::net::transport::TransportWrite;
+
+/// Default number of messages queued per connection
+pub const DEFAULT_SEND_QUEUE_SIZE: usize = 256;
+
+/// What to do with a message when the queue is full
+///
+/// Also applied to the channel carrying broadcasts to a connection, where a
+/// sender can't wait: see [`SessionSender::try_send`](crate::state::SessionSender::try_send).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SendPolicy {
+    /// Wait for room in the queue
+    #[default]
+    Block,
+    /// Discard the message and log a warning
+    Drop,
+}
+
+/// Sending end of a connection's outgoing queue
+#[derive(Debug)]
+pub struct SendQueue {
+    tx: mpsc::Sender<Bytes>,
+    policy: SendPolicy,
+}
+
+impl SendQueue {
+    /// Create a queue holding up to `capacity` messages
+    ///
+    /// The receiver is passed to [`run_writer`].
+    pub fn new(capacity: usize, policy: SendPolicy) -> (Self, mpsc::Receiver<Bytes>) {
+        // mpsc panics on a zero bound
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        (Self { tx, policy }, rx)
+    }
+
+    /// Queue an encoded message
+    ///
+    /// Returns `Ok(false)` if the message was dropped because the queue was
+    /// full under [`SendPolicy::Drop`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `BrokenPipe` once the writer has stopped.
+    pub async fn send(&self, bytes: Bytes) -> io::Result<bool> {
+        let closed = || io::Error::new(io::ErrorKind::BrokenPipe, "connection writer stopped");
+        match self.policy {
+            SendPolicy::Block => self.tx.send(bytes).await.map_err(|_| closed())?,
+            SendPolicy::Drop => match self.tx.try_send(bytes) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => return Ok(false),
+                Err(TrySendError::Closed(_)) => return Err(closed()),
+            },
+        }
+        Ok(true)
+    }
+}
+
+/// Write queued messages to `writer` until every [`SendQueue`] is dropped
+///
+/// # Errors
+///
+/// Stops at the first failed write and returns its error.
+pub async fn run_writer(
+    mut writer: impl TransportWrite,
+    mut rx: mpsc::Receiver<Bytes>,
+) -> io::Result<()> {
+    while let Some(bytes) = rx.recv().await {
+        writer.write_message(&bytes).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::Semaphore;
+
+    /// Writer that only completes a write when the test adds a permit
+    struct GatedWriter {
+        gate: Arc<Semaphore>,
+        written: mpsc::UnboundedSender<Bytes>,
+    }
+
+    impl TransportWrite for GatedWriter {
+        async fn write_message(&mut self, data: &[u8]) -> io::Result<()> {
+            self.gate.acquire().await.unwrap().forget();
+            let _ = self.written.send(Bytes::copy_from_slice(data));
+            Ok(())
+        }
+    }
+
+    fn gated_writer() -> (GatedWriter, Arc<Semaphore>, mpsc::UnboundedReceiver<Bytes>) {
+        let gate = Arc::new(Semaphore::new(0));
+        let (written, written_rx) = mpsc::unbounded_channel();
+        let writer = GatedWriter {
+            gate: gate.clone(),
+            written,
+        };
+        (writer, gate, written_rx)
+    }
+
+    fn message(n: u8) -> Bytes {
+        Bytes::from(vec![n])
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_blocks() {
+        let (writer, gate, mut written) = gated_writer();
+        let (queue, rx) = SendQueue::new(2, SendPolicy::Block);
+        tokio::spawn(run_writer(writer, rx));
+
+        // The writer takes the first message and waits on the gate
+        assert!(queue.send(message(0)).await.unwrap());
+        tokio::task::yield_now().await;
+        assert!(queue.send(message(1)).await.unwrap());
+        assert!(queue.send(message(2)).await.unwrap());
+
+        let blocked = tokio::time::timeout(Duration::from_millis(50), queue.send(message(3)));
+        assert!(blocked.await.is_err());
+
+        gate.add_permits(4);
+        assert!(queue.send(message(3)).await.unwrap());
+        for n in 0..4 {
+            assert_eq!(written.recv().await.unwrap(), message(n));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops() {
+        let (writer, gate, mut written) = gated_writer();
+        let (queue, rx) = SendQueue::new(2, SendPolicy::Drop);
+        tokio::spawn(run_writer(writer, rx));
+
+        // Nothing is drained until this task yields
+        assert!(queue.send(message(0)).await.unwrap());
+        assert!(queue.send(message(1)).await.unwrap());
+        assert!(!queue.send(message(2)).await.unwrap());
+
+        gate.add_permits(2);
+        assert_eq!(written.recv().await.unwrap(), message(0));
+        assert_eq!(written.recv().await.unwrap(), message(1));
+        drop(queue);
+        assert!(written.recv().await.is_none());
+    }
+}
@@ -0,0 +1,22 @@
+//! Shared fixtures for connection tests
+
+use thepalace::assets::AssetStore;
+
+use crate::db::test_support::TestDatabase;
+use crate::state::ServerState;
+
+/// Server state backed by a throwaway on-disk database, removed on drop
+pub struct TestServer {
+    pub state: ServerState,
+    _db: TestDatabase,
+}
+
+impl TestServer {
+    /// Create a server state with the default schema and rooms
+    pub async fn new(name: &str) -> Self {
+        let db = TestDatabase::new(name).await;
+        let state = ServerState::new(db.db.clone(), AssetStore::new(db.dir.join("assets")));
+
+        Self { state, _db: db }
+    }
+}
@@ -0,0 +1,63 @@
+//! Byte transports for client connections
+//!
+//! The connection handler only needs to read raw Palace protocol bytes and
+//! write whole encoded messages, so the socket type is abstracted behind
+//! [`Transport`]. Raw TCP (and any other `AsyncRead + AsyncWrite` stream) gets
+//! a blanket implementation, which also lets tests drive a handler through
+//! in-memory `tokio::io::duplex` pipes. Other transports such as WebSocket
+//! provide their own framing.
+//!
+//! A transport is split into a [`TransportRead`] half, polled by the handler,
+//! and a [`TransportWrite`] half, owned by the connection's writer task so a
+//! slow client never stalls message handling.
+
+use bytes::BytesMut;
+use std::future::Future;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+
+/// Transport carrying Palace protocol bytes to and from a client
+pub trait Transport: Send {
+    /// Receiving half
+    type Reader: TransportRead + 'static;
+    /// Sending half
+    type Writer: TransportWrite + 'static;
+
+    /// Split into halves that can be used from different tasks
+    fn split(self) -> (Self::Reader, Self::Writer);
+}
+
+/// Receiving half of a [`Transport`]
+pub trait TransportRead: Send {
+    /// Read available bytes into `buf`, returning how many were read
+    ///
+    /// Returns `Ok(0)` once the client has closed the connection.
+    fn read_into(&mut self, buf: &mut BytesMut) -> impl Future<Output = io::Result<usize>> + Send;
+}
+
+/// Sending half of a [`Transport`]
+pub trait TransportWrite: Send {
+    /// Write one complete encoded message
+    fn write_message(&mut self, data: &[u8]) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send + 'static> Transport for S {
+    type Reader = ReadHalf<S>;
+    type Writer = WriteHalf<S>;
+
+    fn split(self) -> (Self::Reader, Self::Writer) {
+        tokio::io::split(self)
+    }
+}
+
+impl<R: AsyncRead + Unpin + Send> TransportRead for R {
+    async fn read_into(&mut self, buf: &mut BytesMut) -> io::Result<usize> {
+        self.read_buf(buf).await
+    }
+}
+
+impl<W: AsyncWrite + Unpin + Send> TransportWrite for W {
+    async fn write_message(&mut self, data: &[u8]) -> io::Result<()> {
+        self.write_all(data).await
+    }
+}
@@ -0,0 +1,191 @@
+//! WebSocket transport for browser clients
+//!
+//! Each binary WebSocket message carries Palace protocol bytes. Incoming
+//! messages are appended to the handler's read buffer, so a Palace message may
+//! span several WebSocket messages or share one; outgoing Palace messages are
+//! sent as one binary WebSocket message each.
+
+use bytes::BytesMut;
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use std::io;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+use tracing::{error, info};
+
+use super::handler::{ConnectionHandler, ConnectionLimits};
+use super::transport::{Transport, TransportRead, TransportWrite};
+use crate::state::ServerState;
+
+/// Accept WebSocket clients on `listener` forever
+///
+/// Each connection is upgraded and then handled exactly like a TCP client,
+/// with the same `limits`.
+pub async fn serve(listener: TcpListener, state: ServerState, limits: ConnectionLimits) {
+    loop {
+        match listener.accept().await {
+            Ok((socket, addr)) => {
+                info!("New WebSocket connection from {}", addr);
+                let state = state.clone();
+
+                tokio::spawn(async move {
+                    let transport = match WsTransport::accept(socket).await {
+                        Ok(transport) => transport,
+                        Err(e) => {
+                            error!("WebSocket handshake with {} failed: {}", addr, e);
+                            return;
+                        }
+                    };
+                    let handler = ConnectionHandler::new(transport, addr, state)
+                        .with_limits(limits);
+                    if let Err(e) = handler.handle().await {
+                        error!("Connection error from {}: {}", addr, e);
+                    }
+                    info!("WebSocket connection closed: {}", addr);
+                });
+            }
+            Err(e) => {
+                error!("Failed to accept WebSocket connection: {}", e);
+            }
+        }
+    }
+}
+
+/// WebSocket connection carrying Palace protocol bytes
+pub struct WsTransport {
+    stream: WebSocketStream<TcpStream>,
+}
+
+impl WsTransport {
+    /// Perform the server side of the WebSocket handshake
+    pub async fn accept(socket: TcpStream) -> io::Result<Self> {
+        let stream = tokio_tungstenite::accept_async(socket)
+            .await
+            .map_err(io::Error::other)?;
+        Ok(Self { stream })
+    }
+}
+
+impl Transport for WsTransport {
+    type Reader = WsReader;
+    type Writer = WsWriter;
+
+    fn split(self) -> (WsReader, WsWriter) {
+        let (sink, stream) = self.stream.split();
+        (WsReader { stream }, WsWriter { sink })
+    }
+}
+
+/// Receiving half of a [`WsTransport`]
+pub struct WsReader {
+    stream: SplitStream<WebSocketStream<TcpStream>>,
+}
+
+impl TransportRead for WsReader {
+    async fn read_into(&mut self, buf: &mut BytesMut) -> io::Result<usize> {
+        loop {
+            match self.stream.next().await {
+                Some(Ok(WsMessage::Binary(data))) => {
+                    buf.extend_from_slice(&data);
+                    // An empty frame isn't a close, so keep waiting for data
+                    if !data.is_empty() {
+                        return Ok(data.len());
+                    }
+                }
+                Some(Ok(WsMessage::Text(_))) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "text WebSocket messages aren't Palace protocol data",
+                    ));
+                }
+                // Pings are answered by tungstenite itself
+                Some(Ok(WsMessage::Ping(_) | WsMessage::Pong(_) | WsMessage::Frame(_))) => {}
+                Some(Ok(WsMessage::Close(_))) | None => return Ok(0),
+                Some(Err(e)) => return Err(io::Error::other(e)),
+            }
+        }
+    }
+}
+
+/// Sending half of a [`WsTransport`]
+pub struct WsWriter {
+    sink: SplitSink<WebSocketStream<TcpStream>, WsMessage>,
+}
+
+impl TransportWrite for WsWriter {
+    async fn write_message(&mut self, data: &[u8]) -> io::Result<()> {
+        self.sink
+            .send(WsMessage::binary(data.to_vec()))
+            .await
+            .map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::test_support::TestServer;
+    use std::time::Duration;
+    use thepalace::messages::auth::{AuxRegistrationRec, LogonMsg};
+    use thepalace::messages::{Message, MessageId, MessagePayload, RoomDescMsg, RoomGotoMsg};
+
+    type Client = WebSocketStream<TcpStream>;
+
+    /// Receive the next Palace message sent to the client
+    async fn recv(client: &mut Client) -> Message {
+        loop {
+            let frame = tokio::time::timeout(Duration::from_secs(5), client.next())
+                .await
+                .expect("timed out waiting for server")
+                .expect("connection closed")
+                .unwrap();
+            if let WsMessage::Binary(data) = frame {
+                return Message::parse(&mut &data[..]).unwrap();
+            }
+        }
+    }
+
+    async fn send(client: &mut Client, message: Message) {
+        client
+            .send(WsMessage::binary(message.to_bytes()))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ws_room_goto() {
+        let server = TestServer::new("ws").await;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(
+            listener,
+            server.state.clone(),
+            ConnectionLimits::default(),
+        ));
+
+        let socket = TcpStream::connect(addr).await.unwrap();
+        let (mut client, _) = tokio_tungstenite::client_async(format!("ws://{}/", addr), socket)
+            .await
+            .unwrap();
+
+        assert_eq!(recv(&mut client).await.msg_id, MessageId::Tiyid);
+
+        let logon = LogonMsg::new(AuxRegistrationRec::new_guest("Surfer", 0));
+        send(&mut client, logon.to_message(0)).await;
+        send(&mut client, RoomGotoMsg { dest: 1 }.to_message(0)).await;
+
+        // Skip the logon replies until the new room is described
+        loop {
+            let message = recv(&mut client).await;
+            if message.msg_id == MessageId::RoomDesc {
+                let desc = message.parse_payload::<RoomDescMsg>().unwrap();
+                if desc.room.room_id == 1 {
+                    break;
+                }
+            }
+        }
+    }
+}
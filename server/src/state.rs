@@ -3,13 +3,27 @@
 //! Manages in-memory state for connected users and active sessions
 //! while using database for persistent data.
 
+use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thepalace::assets::AssetStore;
+use thepalace::iptscrae::{ScriptAction, ScriptCache};
+use thepalace::messages::flags::{RoomFlags, UserFlags};
+use thepalace::messages::{
+    Message, MessagePayload, PropDelMsg, PropNewMsg, RoomListRec, UserPropMsg, UserRec,
+};
+use thepalace::prop::{decode_prop, prop_crc};
+use thepalace::{AssetSpec, AssetType, Point};
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::{mpsc, RwLock};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::db::Database;
+use crate::net::dispatch::{DefaultMessageHandler, MessageHandler};
+use crate::net::send_queue::SendPolicy;
 
 /// User ID type
 pub type UserId = i64;
@@ -38,10 +52,55 @@ pub enum ServerMessage {
         message: String,
         encrypted: bool,
     },
+    /// User moved within a room
+    UserMoved {
+        user_id: UserId,
+        room_id: RoomId,
+        pos: Point,
+    },
+    /// The receiving user's flags changed (e.g. a wizard gagged them)
+    UserStatus { flags: UserFlags },
     /// User disconnected
     UserDisconnected { user_id: UserId },
+    /// Protocol message passed to the client as-is
+    Relay(Message),
 }
 
+impl From<Message> for ServerMessage {
+    fn from(message: Message) -> Self {
+        ServerMessage::Relay(message)
+    }
+}
+
+/// Smallest move, in pixels along either axis, that is relayed to the room
+///
+/// Dragging an avatar sends a UserMove for nearly every mouse event. Smaller
+/// moves are dropped so a drag doesn't flood the room. The delta is measured
+/// from the last relayed position, so a slow drag is still relayed once its
+/// steps add up.
+///
+/// Clients interpolating between relayed positions look smooth at 10-20
+/// updates per second, which is the rate clients should aim to send at.
+pub const MIN_MOVE_DELTA: i16 = 2;
+
+/// Decide whether a move from `last` to `new` is worth relaying to the room
+///
+/// A move is relayed once it covers at least `min_delta` pixels along either
+/// axis. Moving to the same spot is never relayed, even with a `min_delta`
+/// of 0.
+pub fn should_broadcast(last: Point, new: Point, min_delta: i16) -> bool {
+    let dh = (new.h as i32 - last.h as i32).abs();
+    let dv = (new.v as i32 - last.v as i32).abs();
+    let delta = dh.max(dv);
+    delta > 0 && delta >= min_delta as i32
+}
+
+/// Name prefix for guests who log on without a name
+pub const DEFAULT_GUEST_NAME_PREFIX: &str = "Guest";
+
+/// Most loose props a room holds unless configured otherwise
+pub const DEFAULT_MAX_LOOSE_PROPS: usize = 50;
+
 /// Connected user session
 #[derive(Debug)]
 pub struct UserSession {
@@ -49,8 +108,64 @@ pub struct UserSession {
     pub username: String,
     pub room_id: RoomId,
     pub addr: SocketAddr,
+    /// Protocol flags for this session; GAG is checked before relaying chat
+    pub flags: UserFlags,
+    /// Props the user is wearing, at most [`UserRec::MAX_PROPS`]
+    pub props: Vec<AssetSpec>,
     /// Channel to send messages to this user's connection
-    pub tx: mpsc::UnboundedSender<ServerMessage>,
+    pub tx: SessionSender,
+}
+
+/// Bounded channel carrying [`ServerMessage`]s to a user's connection
+///
+/// Other sessions send on it while the connection is busy with its own
+/// client, so nothing waits for room: see [`SessionSender::try_send`].
+#[derive(Debug, Clone)]
+pub struct SessionSender {
+    tx: mpsc::Sender<ServerMessage>,
+    policy: SendPolicy,
+}
+
+/// What became of a message offered to a [`SessionSender`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Queued for the connection
+    Queued,
+    /// The connection is closing
+    Closed,
+    /// The queue was full under [`SendPolicy::Drop`]
+    Dropped,
+    /// The queue was full under [`SendPolicy::Block`]; the session should
+    /// be disconnected rather than miss the message
+    Lagging,
+}
+
+impl SessionSender {
+    /// Create a channel holding up to `capacity` messages
+    pub fn channel(capacity: usize, policy: SendPolicy) -> (Self, mpsc::Receiver<ServerMessage>) {
+        // mpsc panics on a zero bound
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        (Self { tx, policy }, rx)
+    }
+
+    /// Queue `message` without waiting
+    ///
+    /// A full queue means the connection isn't keeping up. Waiting for it
+    /// could deadlock two connections broadcasting to each other (or one
+    /// broadcasting to its own room), so instead the message is dropped
+    /// under [`SendPolicy::Drop`] and the session reported as
+    /// [`Delivery::Lagging`] under [`SendPolicy::Block`], which never loses
+    /// messages silently.
+    pub fn try_send(&self, message: ServerMessage) -> Delivery {
+        match self.tx.try_send(message) {
+            Ok(()) => Delivery::Queued,
+            Err(TrySendError::Closed(_)) => Delivery::Closed,
+            Err(TrySendError::Full(_)) => match self.policy {
+                SendPolicy::Drop => Delivery::Dropped,
+                SendPolicy::Block => Delivery::Lagging,
+            },
+        }
+    }
 }
 
 /// Active room state (in-memory)
@@ -60,10 +175,57 @@ pub struct ActiveRoom {
     pub user_ids: Vec<UserId>,
 }
 
+/// Hands out session UserIDs
+///
+/// IDs increase monotonically so a just-disconnected user's ID isn't reissued
+/// while clients may still be processing their exit. After `i32::MAX` (the
+/// largest UserID the protocol carries) allocation wraps back to 1, skipping
+/// any ID still in use.
+#[derive(Debug)]
+pub struct UserIdAllocator {
+    next: UserId,
+}
+
+impl UserIdAllocator {
+    /// Largest UserID the protocol can carry
+    pub const MAX_ID: UserId = i32::MAX as UserId;
+
+    /// Create an allocator starting at 1
+    pub fn new() -> Self {
+        Self { next: 1 }
+    }
+
+    /// Allocate the next ID for which `in_use` returns false
+    ///
+    /// Returns `None` only if every ID in the protocol range is in use.
+    pub fn allocate(&mut self, in_use: impl Fn(UserId) -> bool) -> Option<UserId> {
+        for _ in 0..Self::MAX_ID {
+            let id = self.next;
+            self.next = if id >= Self::MAX_ID { 1 } else { id + 1 };
+            if !in_use(id) {
+                return Some(id);
+            }
+        }
+        None
+    }
+}
+
+impl Default for UserIdAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Shared server state
 #[derive(Clone)]
 pub struct ServerState {
     db: Database,
+    assets: AssetStore,
+    guest_name_prefix: Arc<str>,
+    max_loose_props: usize,
+    started: Instant,
+    scripts: Arc<ScriptCache>,
+    message_handler: Arc<dyn MessageHandler>,
     inner: Arc<RwLock<ServerStateInner>>,
 }
 
@@ -72,25 +234,160 @@ struct ServerStateInner {
     sessions: HashMap<UserId, UserSession>,
     /// Active rooms with their current users
     active_rooms: HashMap<RoomId, ActiveRoom>,
+    /// Session UserID allocator
+    user_ids: UserIdAllocator,
+    /// Number for the next generated guest name
+    next_guest: u64,
 }
 
 impl ServerState {
     /// Create new server state
-    pub fn new(db: Database) -> Self {
+    pub fn new(db: Database, assets: AssetStore) -> Self {
         Self {
             db,
+            assets,
+            guest_name_prefix: DEFAULT_GUEST_NAME_PREFIX.into(),
+            max_loose_props: DEFAULT_MAX_LOOSE_PROPS,
+            started: Instant::now(),
+            scripts: Arc::new(ScriptCache::new()),
+            message_handler: Arc::new(DefaultMessageHandler),
             inner: Arc::new(RwLock::new(ServerStateInner {
                 sessions: HashMap::new(),
                 active_rooms: HashMap::new(),
+                user_ids: UserIdAllocator::new(),
+                next_guest: 1,
             })),
         }
     }
 
+    /// Use `prefix` for generated guest names instead of "Guest"
+    pub fn with_guest_name_prefix(mut self, prefix: &str) -> Self {
+        self.guest_name_prefix = prefix.into();
+        self
+    }
+
+    /// Allow at most `max` loose props per room instead of the default 50
+    pub fn with_max_loose_props(mut self, max: usize) -> Self {
+        self.max_loose_props = max;
+        self
+    }
+
+    /// Offer every connection's incoming messages to `handler` first
+    ///
+    /// Lets the server override how individual messages are handled; see
+    /// [`MessageHandler`]. The default keeps the built-in behavior.
+    pub fn with_message_handler(mut self, handler: Arc<dyn MessageHandler>) -> Self {
+        self.message_handler = handler;
+        self
+    }
+
+    /// Get the handler new connections offer incoming messages to first
+    pub fn message_handler(&self) -> Arc<dyn MessageHandler> {
+        Arc::clone(&self.message_handler)
+    }
+
     /// Get database handle
     pub fn db(&self) -> &Database {
         &self.db
     }
 
+    /// Get asset store for props and other uploaded files
+    pub fn assets(&self) -> &AssetStore {
+        &self.assets
+    }
+
+    /// Get the parsed spot scripts shared by every session
+    pub fn scripts(&self) -> &ScriptCache {
+        &self.scripts
+    }
+
+    /// Validate, store and register a prop uploaded as asset `asset_id`,
+    /// returning its CRC
+    ///
+    /// The blob must decode as a prop so garbage never reaches the asset
+    /// store; undecodable blobs are an error and nothing is written. A prop
+    /// whose CRC is already registered, with its file still in place, is
+    /// left as it is.
+    pub async fn store_uploaded_prop(&self, name: &str, asset_id: i32, data: &[u8]) -> Result<u32> {
+        let decoded = decode_prop(data).context("Uploaded prop is not decodable")?;
+        let crc = prop_crc(data);
+        if let Some(known) = self.db.get_prop_by_crc(crc).await?
+            && Path::new(&known.file_path).is_file()
+        {
+            debug!(
+                "Prop '{}' (crc 0x{:08X}) already registered as '{}'",
+                name, crc, known.name
+            );
+            return Ok(crc);
+        }
+        let path = self
+            .assets
+            .store(AssetType::Prop, crc, data)
+            .context("Failed to store uploaded prop")?;
+
+        let prop = &decoded.prop;
+        self.db
+            .register_prop(
+                AssetSpec::new(asset_id, crc),
+                name,
+                prop.flags.bits(),
+                prop.width,
+                prop.height,
+                &path.to_string_lossy(),
+            )
+            .await?;
+
+        info!("Stored uploaded prop '{}' (crc 0x{:08X})", name, crc);
+        Ok(crc)
+    }
+
+    /// Allocate a UserID for a new session
+    ///
+    /// The ID is never one that still has a session or is in a room roster.
+    pub async fn allocate_user_id(&self) -> Result<UserId> {
+        let mut inner = self.inner.write().await;
+        let inner = &mut *inner;
+        let sessions = &inner.sessions;
+        let active_rooms = &inner.active_rooms;
+        inner
+            .user_ids
+            .allocate(|id| {
+                sessions.contains_key(&id)
+                    || active_rooms.values().any(|room| room.user_ids.contains(&id))
+            })
+            .context("No free user IDs")
+    }
+
+    /// Generate a name for a guest who logged on without one
+    ///
+    /// Names are the configured prefix and a number, e.g. "Guest 12", and
+    /// never match (ignoring case) a connected user's name. A long prefix is
+    /// shortened so the name still fits the protocol's 31 bytes.
+    pub async fn next_guest_name(&self) -> String {
+        let mut inner = self.inner.write().await;
+        loop {
+            let number = format!(" {}", inner.next_guest);
+            inner.next_guest += 1;
+
+            let mut prefix_len = self
+                .guest_name_prefix
+                .len()
+                .min(UserRec::MAX_NAME_LEN - number.len());
+            while !self.guest_name_prefix.is_char_boundary(prefix_len) {
+                prefix_len -= 1;
+            }
+            let name = format!("{}{}", &self.guest_name_prefix[..prefix_len], number);
+
+            let taken = inner
+                .sessions
+                .values()
+                .any(|session| session.username.eq_ignore_ascii_case(&name));
+            if !taken {
+                return name;
+            }
+        }
+    }
+
     /// Register a new user session
     pub async fn register_session(
         &self,
@@ -98,7 +395,7 @@ impl ServerState {
         username: String,
         room_id: RoomId,
         addr: SocketAddr,
-        tx: mpsc::UnboundedSender<ServerMessage>,
+        tx: SessionSender,
     ) {
         let mut inner = self.inner.write().await;
         
@@ -107,6 +404,8 @@ impl ServerState {
             username: username.clone(),
             room_id,
             addr,
+            flags: UserFlags::empty(),
+            props: Vec::new(),
             tx,
         };
 
@@ -150,6 +449,139 @@ impl ServerState {
         }
     }
 
+    /// Get a connected user's flags
+    pub async fn user_flags(&self, user_id: UserId) -> Option<UserFlags> {
+        let inner = self.inner.read().await;
+        inner.sessions.get(&user_id).map(|s| s.flags)
+    }
+
+    /// Get the room a connected user is in
+    #[cfg(test)]
+    pub async fn user_room(&self, user_id: UserId) -> Option<RoomId> {
+        let inner = self.inner.read().await;
+        inner.sessions.get(&user_id).map(|s| s.room_id)
+    }
+
+    /// Replace a connected user's flags, returning whether they are connected
+    pub async fn set_user_flags(&self, user_id: UserId, flags: UserFlags) -> bool {
+        let mut inner = self.inner.write().await;
+        match inner.sessions.get_mut(&user_id) {
+            Some(session) => {
+                session.flags = flags;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Gag or ungag a connected user, returning their new flags
+    ///
+    /// Gagged users can still move and navigate; only their chat is dropped.
+    pub async fn set_gagged(&self, user_id: UserId, gagged: bool) -> Option<UserFlags> {
+        let mut inner = self.inner.write().await;
+        let session = inner.sessions.get_mut(&user_id)?;
+        session.flags.set(UserFlags::GAG, gagged);
+        Some(session.flags)
+    }
+
+    /// Get the props a connected user is wearing
+    pub async fn user_props(&self, user_id: UserId) -> Option<Vec<AssetSpec>> {
+        let inner = self.inner.read().await;
+        inner.sessions.get(&user_id).map(|s| s.props.clone())
+    }
+
+    /// Change a connected user's props and tell their room
+    ///
+    /// Props past [`UserRec::MAX_PROPS`] are dropped. The room gets a
+    /// UserProp message (skipping `except`, usually the user who sent the
+    /// change). Returns how many users it was queued for, or `None` if the
+    /// user isn't connected.
+    pub async fn set_user_props(
+        &self,
+        user_id: UserId,
+        mut props: Vec<AssetSpec>,
+        except: Option<UserId>,
+    ) -> Option<usize> {
+        props.truncate(UserRec::MAX_PROPS);
+        let room_id = {
+            let mut inner = self.inner.write().await;
+            let session = inner.sessions.get_mut(&user_id)?;
+            session.props = props.clone();
+            session.room_id
+        };
+
+        let message = UserPropMsg { props }.to_message(user_id as i32);
+        Some(self.broadcast_to_room(room_id, message, except).await)
+    }
+
+    /// Drop a loose prop in a room and tell everyone there
+    ///
+    /// The whole room gets the PropNew, the user who dropped it included,
+    /// since the server's list is the one clients should show. Returns
+    /// `false`, changing nothing, if the room is already at its loose prop
+    /// limit.
+    pub async fn add_loose_prop(
+        &self,
+        room_id: RoomId,
+        spec: AssetSpec,
+        pos: Point,
+    ) -> Result<bool> {
+        let added = self
+            .db
+            .add_loose_prop(room_id, spec, pos, self.max_loose_props)
+            .await?;
+        if added.is_none() {
+            return Ok(false);
+        }
+        let message = PropNewMsg::new(spec, pos).to_message(0);
+        self.broadcast_to_room(room_id, message, None).await;
+        Ok(true)
+    }
+
+    /// Remove a loose prop by index, or all of them for -1, and tell the room
+    ///
+    /// Returns whether anything was removed; nothing is sent if not.
+    pub async fn remove_loose_prop(&self, room_id: RoomId, prop_num: i32) -> Result<bool> {
+        let removed = self.db.remove_loose_prop(room_id, prop_num).await?;
+        if removed {
+            let message = PropDelMsg::new(prop_num).to_message(0);
+            self.broadcast_to_room(room_id, message, None).await;
+        }
+        Ok(removed)
+    }
+
+    /// Carry out the actions of a script run on behalf of `user_id` in `room_id`
+    ///
+    /// Prop changes (NAKED, DONPROP, DOFFPROP, ...) update the user's props
+    /// and are broadcast to the whole room, the user included. Loose props
+    /// are added or cleared in `room_id`, which a LEAVE script's user has
+    /// already left. Other actions aren't performed by the server yet and
+    /// are skipped.
+    pub async fn apply_script_actions(
+        &self,
+        user_id: UserId,
+        room_id: RoomId,
+        actions: Vec<ScriptAction>,
+    ) -> Result<()> {
+        for action in actions {
+            match action {
+                ScriptAction::SetProps(props) => {
+                    self.set_user_props(user_id, props, None).await;
+                }
+                ScriptAction::AddLooseProp { prop_id, x, y } => {
+                    // Scripts name props by ID alone
+                    let spec = AssetSpec::new(prop_id, 0);
+                    self.add_loose_prop(room_id, spec, Point::new(x, y)).await?;
+                }
+                ScriptAction::ClearLooseProps => {
+                    self.remove_loose_prop(room_id, -1).await?;
+                }
+                other => debug!("Skipping script action for user {}: {:?}", user_id, other),
+            }
+        }
+        Ok(())
+    }
+
     /// Move a user to a different room
     pub async fn move_user_to_room(&self, user_id: UserId, new_room_id: RoomId) -> bool {
         let mut inner = self.inner.write().await;
@@ -205,30 +637,58 @@ impl ServerState {
         }
     }
 
-    /// Broadcast a message to all users in a room
-    pub async fn broadcast_to_room(&self, room_id: RoomId, message: ServerMessage) {
-        let inner = self.inner.read().await;
-        
-        if let Some(room) = inner.active_rooms.get(&room_id) {
-            let mut sent_count = 0;
-            for &user_id in &room.user_ids {
-                if let Some(session) = inner.sessions.get(&user_id) {
-                    // Ignore send errors (user might be disconnecting)
-                    if session.tx.send(message.clone()).is_ok() {
-                        sent_count += 1;
+    /// Broadcast a message to all users in a room, except `except` if given
+    ///
+    /// A protocol [`Message`] is relayed to clients as-is. Users whose
+    /// connection is closing are skipped, and a full queue is handled as
+    /// [`SessionSender::try_send`] describes, disconnecting lagging users.
+    /// Returns how many users the message was queued for.
+    pub async fn broadcast_to_room(
+        &self,
+        room_id: RoomId,
+        message: impl Into<ServerMessage>,
+        except: Option<UserId>,
+    ) -> usize {
+        let message = message.into();
+        let mut lagging = Vec::new();
+        let mut sent_count = 0;
+        {
+            let inner = self.inner.read().await;
+            if let Some(room) = inner.active_rooms.get(&room_id) {
+                for &user_id in &room.user_ids {
+                    if Some(user_id) == except {
+                        continue;
+                    }
+                    if let Some(session) = inner.sessions.get(&user_id) {
+                        match deliver(session, message.clone()) {
+                            Delivery::Queued => sent_count += 1,
+                            Delivery::Closed | Delivery::Dropped => {}
+                            Delivery::Lagging => lagging.push(user_id),
+                        }
                     }
                 }
+                debug!("Broadcast to room {}: {} recipients", room_id, sent_count);
             }
-            debug!("Broadcast to room {}: {} recipients", room_id, sent_count);
         }
+        for user_id in lagging {
+            self.unregister_session(user_id).await;
+        }
+        sent_count
     }
 
     /// Send a message to a specific user
+    ///
+    /// A full queue is handled as in [`broadcast_to_room`](Self::broadcast_to_room).
     pub async fn send_to_user(&self, user_id: UserId, message: ServerMessage) {
-        let inner = self.inner.read().await;
-        
-        if let Some(session) = inner.sessions.get(&user_id) {
-            let _ = session.tx.send(message);
+        let delivery = {
+            let inner = self.inner.read().await;
+            match inner.sessions.get(&user_id) {
+                Some(session) => deliver(session, message),
+                None => return,
+            }
+        };
+        if delivery == Delivery::Lagging {
+            self.unregister_session(user_id).await;
         }
     }
 
@@ -243,14 +703,360 @@ impl ServerState {
             .unwrap_or(0)
     }
 
+    /// Room list with live occupancy counts
+    ///
+    /// Rooms flagged [`RoomFlags::HIDDEN`] are left out unless
+    /// `include_hidden` is set, which is for wizards.
+    pub async fn room_list(&self, include_hidden: bool) -> Result<Vec<RoomListRec>> {
+        let mut rooms = self.db.room_list().await?;
+        rooms.retain(|room| include_hidden || !room.flags.contains(RoomFlags::HIDDEN));
+
+        let inner = self.inner.read().await;
+        for room in &mut rooms {
+            if let Some(active) = inner.active_rooms.get(&(room.room_id as RoomId)) {
+                room.nbr_users = active.user_ids.len() as i16;
+            }
+        }
+        Ok(rooms)
+    }
+
     /// Get total number of connected users
     pub async fn get_total_users(&self) -> usize {
         let inner = self.inner.read().await;
         inner.sessions.len()
     }
 
+    /// Get number of rooms with at least one user in them
+    pub async fn get_populated_rooms(&self) -> usize {
+        let inner = self.inner.read().await;
+        inner.active_rooms.len()
+    }
+
+    /// Time since this state was created, i.e. server uptime
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
     /// Check if a room exists in the database
     pub async fn room_exists(&self, room_id: RoomId) -> bool {
         self.db.get_room(room_id).await.ok().flatten().is_some()
     }
 }
+
+/// Offer `message` to `session`, warning if its queue was full
+fn deliver(session: &UserSession, message: ServerMessage) -> Delivery {
+    let delivery = session.tx.try_send(message);
+    match delivery {
+        Delivery::Queued | Delivery::Closed => {}
+        Delivery::Dropped => warn!(
+            "Message queue for user {} full, dropped a message",
+            session.user_id
+        ),
+        Delivery::Lagging => warn!(
+            "Message queue for user {} full, disconnecting them",
+            session.user_id
+        ),
+    }
+    delivery
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DatabaseOptions;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    async fn test_state() -> ServerState {
+        let db = Database::with_options("sqlite::memory:", DatabaseOptions::default())
+            .await
+            .unwrap();
+        ServerState::new(db, AssetStore::new(std::env::temp_dir()))
+    }
+
+    fn test_addr() -> SocketAddr {
+        "127.0.0.1:9998".parse().unwrap()
+    }
+
+    fn session_channel() -> (SessionSender, mpsc::Receiver<ServerMessage>) {
+        SessionSender::channel(16, SendPolicy::Drop)
+    }
+
+    #[test]
+    fn test_should_broadcast_threshold() {
+        let last = Point::new(100, 100);
+
+        // Below the threshold on both axes
+        assert!(!should_broadcast(last, Point::new(101, 99), MIN_MOVE_DELTA));
+        // At or above it on either axis
+        assert!(should_broadcast(last, Point::new(102, 100), MIN_MOVE_DELTA));
+        assert!(should_broadcast(last, Point::new(100, 40), MIN_MOVE_DELTA));
+        // Extremes don't overflow
+        assert!(should_broadcast(
+            Point::new(i16::MIN, i16::MIN),
+            Point::new(i16::MAX, i16::MAX),
+            i16::MAX
+        ));
+    }
+
+    #[test]
+    fn test_should_broadcast_zero_delta() {
+        let pos = Point::new(128, 128);
+        assert!(!should_broadcast(pos, pos, MIN_MOVE_DELTA));
+        assert!(!should_broadcast(pos, pos, 0));
+        assert!(should_broadcast(pos, Point::new(129, 128), 0));
+    }
+
+    #[test]
+    fn test_allocator_wraps_and_skips_live_ids() {
+        let mut allocator = UserIdAllocator {
+            next: UserIdAllocator::MAX_ID,
+        };
+        let live = [1, 2];
+
+        assert_eq!(
+            allocator.allocate(|id| live.contains(&id)),
+            Some(UserIdAllocator::MAX_ID)
+        );
+        assert_eq!(allocator.allocate(|id| live.contains(&id)), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_allocate_skips_room_roster() {
+        let state = test_state().await;
+        state.inner.write().await.user_ids = UserIdAllocator {
+            next: UserIdAllocator::MAX_ID,
+        };
+
+        let (tx, _rx) = session_channel();
+        state
+            .register_session(1, "Ghost".to_string(), 86, test_addr(), tx)
+            .await;
+
+        assert_eq!(
+            state.allocate_user_id().await.unwrap(),
+            UserIdAllocator::MAX_ID
+        );
+        assert_eq!(state.allocate_user_id().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_set_gagged() {
+        let state = test_state().await;
+        let (tx, _rx) = session_channel();
+        state
+            .register_session(1, "Echo".to_string(), 0, test_addr(), tx)
+            .await;
+        assert!(state.set_user_flags(1, UserFlags::GUEST).await);
+
+        let flags = state.set_gagged(1, true).await.unwrap();
+        assert_eq!(flags, UserFlags::GUEST | UserFlags::GAG);
+        assert!(state.user_flags(1).await.unwrap().is_gagged());
+
+        assert_eq!(state.set_gagged(1, false).await, Some(UserFlags::GUEST));
+        assert_eq!(state.set_gagged(2, true).await, None);
+        assert!(!state.set_user_flags(2, UserFlags::GAG).await);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_allocate_from_many_tasks() {
+        let state = test_state().await;
+        let live = Arc::new(Mutex::new(HashSet::new()));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|task| {
+                let state = state.clone();
+                let live = live.clone();
+                tokio::spawn(async move {
+                    for round in 0..50 {
+                        let user_id = state.allocate_user_id().await.unwrap();
+                        assert!(
+                            live.lock().unwrap().insert(user_id),
+                            "user ID {} issued while live",
+                            user_id
+                        );
+
+                        let (tx, _rx) = session_channel();
+                        state
+                            .register_session(user_id, format!("u{}", task), 86, test_addr(), tx)
+                            .await;
+
+                        // Free every other ID
+                        if round % 2 == 0 {
+                            live.lock().unwrap().remove(&user_id);
+                            state.unregister_session(user_id).await;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(state.get_total_users().await, 8 * 25);
+        assert_eq!(state.get_room_users(86).await.len(), 8 * 25);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_except_sender() {
+        let state = test_state().await;
+        let mut receivers = Vec::new();
+        for user_id in 1..=3 {
+            let (tx, rx) = session_channel();
+            state
+                .register_session(user_id, format!("u{}", user_id), 0, test_addr(), tx)
+                .await;
+            receivers.push(rx);
+        }
+        // Someone in another room hears nothing
+        let (tx, mut elsewhere) = session_channel();
+        state
+            .register_session(4, "u4".to_string(), 1, test_addr(), tx)
+            .await;
+
+        let message = Message::new_empty(thepalace::messages::MessageId::DoorLock, 0);
+        let reached = state.broadcast_to_room(0, message.clone(), Some(2)).await;
+        assert_eq!(reached, 2);
+
+        for (user_id, rx) in (1..=3).zip(&mut receivers) {
+            match rx.try_recv() {
+                Ok(ServerMessage::Relay(relayed)) => {
+                    assert_ne!(user_id, 2);
+                    assert_eq!(relayed, message);
+                }
+                Ok(other) => panic!("unexpected {:?}", other),
+                Err(_) => assert_eq!(user_id, 2),
+            }
+        }
+        assert!(elsewhere.try_recv().is_err());
+
+        // A user whose connection has gone is skipped
+        drop(receivers.remove(0));
+        assert_eq!(state.broadcast_to_room(0, message, None).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_to_full_queue() {
+        let state = test_state().await;
+        let message = Message::new_empty(thepalace::messages::MessageId::DoorLock, 0);
+
+        // Under Drop, a full queue loses the message but keeps the session
+        let (tx, mut rx) = SessionSender::channel(2, SendPolicy::Drop);
+        state
+            .register_session(1, "Slow".to_string(), 0, test_addr(), tx)
+            .await;
+        for expected in [1, 1, 0] {
+            assert_eq!(
+                state.broadcast_to_room(0, message.clone(), None).await,
+                expected
+            );
+        }
+        assert!(state.user_room(1).await.is_some());
+        assert!(rx.try_recv().is_ok());
+        assert_eq!(state.broadcast_to_room(0, message.clone(), None).await, 1);
+
+        // Under Block, the session is disconnected instead
+        let (tx, mut rx) = SessionSender::channel(2, SendPolicy::Block);
+        state
+            .register_session(2, "Stuck".to_string(), 1, test_addr(), tx)
+            .await;
+        for expected in [1, 1, 0] {
+            assert_eq!(
+                state.broadcast_to_room(1, message.clone(), None).await,
+                expected
+            );
+        }
+        assert!(state.user_room(2).await.is_none());
+        let status = ServerMessage::UserStatus {
+            flags: UserFlags::GAG,
+        };
+        state.send_to_user(2, status).await;
+        let queued = std::iter::from_fn(|| rx.try_recv().ok()).count();
+        assert_eq!(queued, 2);
+        // With the session gone, its connection sees the channel close
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_script_loose_props() {
+        use thepalace::messages::MessageId;
+
+        let test_db = crate::db::test_support::TestDatabase::new("state-loose-props").await;
+        let state = ServerState::new(test_db.db.clone(), AssetStore::new(&test_db.dir))
+            .with_max_loose_props(2);
+        let (tx, mut rx) = session_channel();
+        state
+            .register_session(1, "Piper".to_string(), 1, test_addr(), tx)
+            .await;
+
+        let add = |prop_id| ScriptAction::AddLooseProp {
+            prop_id,
+            x: 5,
+            y: 6,
+        };
+        state
+            .apply_script_actions(1, 1, vec![add(10), add(11), add(12)])
+            .await
+            .unwrap();
+        let props = state.db().loose_props_for_room(1).await.unwrap();
+        let ids: Vec<_> = props.iter().map(|p| p.prop_id).collect();
+        assert_eq!(ids, [10, 11]);
+        for _ in 0..2 {
+            match rx.try_recv() {
+                Ok(ServerMessage::Relay(message)) => assert_eq!(message.msg_id, MessageId::PropNew),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert!(rx.try_recv().is_err());
+
+        state
+            .apply_script_actions(1, 1, vec![ScriptAction::ClearLooseProps])
+            .await
+            .unwrap();
+        assert!(state.db().loose_props_for_room(1).await.unwrap().is_empty());
+        match rx.try_recv() {
+            Ok(ServerMessage::Relay(message)) => assert_eq!(message.msg_id, MessageId::PropDel),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_next_guest_name_unique() {
+        let state = test_state().await.with_guest_name_prefix("Visitor");
+        // Someone already picked the first name
+        let (tx, _rx) = session_channel();
+        state
+            .register_session(1, "visitor 1".to_string(), 0, test_addr(), tx)
+            .await;
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let mut names = Vec::new();
+                    for _ in 0..10 {
+                        names.push(state.next_guest_name().await);
+                    }
+                    names
+                })
+            })
+            .collect();
+        let mut names = HashSet::new();
+        for task in tasks {
+            for name in task.await.unwrap() {
+                assert!(name.starts_with("Visitor "), "{}", name);
+                assert_ne!(name, "Visitor 1");
+                assert!(names.insert(name));
+            }
+        }
+        assert_eq!(names.len(), 80);
+
+        // A long prefix is cut to keep names within 31 bytes
+        let state = test_state().await.with_guest_name_prefix(&"x".repeat(40));
+        let name = state.next_guest_name().await;
+        assert_eq!(name.len(), UserRec::MAX_NAME_LEN);
+        assert!(name.ends_with(" 1"));
+    }
+}